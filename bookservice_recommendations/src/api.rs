@@ -1,19 +1,293 @@
+use std::collections::HashSet;
+
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-use bookservice_reservations::api::BookId;
+use bookservice_reservations::api::{TagWeight, UnixSeconds};
+use bookservice_types::BookId;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
 /// A set of book recommendations for user, contains only book ids that were never reserved by user before
+/// Empty lists are omitted in compact responses, so all of them default to empty
 pub struct Recommendations {
     /// Up to 4 most popular books that were not yet reserved by user
+    #[serde(default)]
     pub most_popular: Vec<BookId>,
     /// Up to 4 most popular books of the author that the user already reserved a book from
     /// The priority is to take books of different authors
+    #[serde(default)]
     pub author_match: Vec<BookId>,
     /// Up to 4 most popular book of the authors with the highest comparison score and never reserved before by the user
+    #[serde(default)]
     pub new_author_match: Vec<BookId>,
-    // TODO: Add tag match
-    // /// Among the books with similar tag matching score, up to 4 will be randomly selected
-    // pub wild_tags_matches: Vec<BookId>,
+    /// Up to 4 books with the highest sum of weights of user favourite tags, never reserved before by the user
+    #[serde(default)]
+    pub tag_match: Vec<BookId>,
+    /// Up to 4 books with the highest ids (assumed to be the most recently added), never reserved before by the user
+    #[serde(default)]
+    pub new_releases: Vec<BookId>,
+}
+
+impl Recommendations {
+    /// Returns which of the recommendation lists are non empty
+    pub fn categories(&self) -> RecommendationCategories {
+        RecommendationCategories {
+            most_popular: !self.most_popular.is_empty(),
+            author_match: !self.author_match.is_empty(),
+            new_author_match: !self.new_author_match.is_empty(),
+            tag_match: !self.tag_match.is_empty(),
+            new_releases: !self.new_releases.is_empty(),
+        }
+    }
+
+    /// Returns all recommended books as a single deduplicated list, books of the preferred category go first,
+    /// the other categories follow in the order of fields
+    pub fn flattened(&self, prefer: Option<RecommendationCategory>) -> Vec<BookId> {
+        let categories = [
+            RecommendationCategory::MostPopular,
+            RecommendationCategory::AuthorMatch,
+            RecommendationCategory::NewAuthorMatch,
+            RecommendationCategory::TagMatch,
+            RecommendationCategory::NewReleases,
+        ];
+        let mut seen_books = HashSet::new();
+        prefer
+            .into_iter()
+            .chain(
+                categories
+                    .into_iter()
+                    .filter(|category| Some(*category) != prefer),
+            )
+            .flat_map(|category| self.category(category).iter().cloned())
+            .filter(|book_id| seen_books.insert(*book_id))
+            .collect()
+    }
+
+    /// Returns books added to and removed from each of the lists compared to the baseline
+    pub fn diff(&self, baseline: &Recommendations) -> RecommendationsDiff {
+        let diff = |current: &[BookId], baseline: &[BookId]| BookIdsDiff {
+            added: current
+                .iter()
+                .filter(|book_id| !baseline.contains(book_id))
+                .cloned()
+                .collect(),
+            removed: baseline
+                .iter()
+                .filter(|book_id| !current.contains(book_id))
+                .cloned()
+                .collect(),
+        };
+        RecommendationsDiff {
+            most_popular: diff(&self.most_popular, &baseline.most_popular),
+            author_match: diff(&self.author_match, &baseline.author_match),
+            new_author_match: diff(&self.new_author_match, &baseline.new_author_match),
+            tag_match: diff(&self.tag_match, &baseline.tag_match),
+            new_releases: diff(&self.new_releases, &baseline.new_releases),
+        }
+    }
+
+    fn category(&self, category: RecommendationCategory) -> &[BookId] {
+        match category {
+            RecommendationCategory::MostPopular => &self.most_popular,
+            RecommendationCategory::AuthorMatch => &self.author_match,
+            RecommendationCategory::NewAuthorMatch => &self.new_author_match,
+            RecommendationCategory::TagMatch => &self.tag_match,
+            RecommendationCategory::NewReleases => &self.new_releases,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+/// One of the recommendation lists
+pub enum RecommendationCategory {
+    MostPopular,
+    AuthorMatch,
+    NewAuthorMatch,
+    TagMatch,
+    NewReleases,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Books that appeared in and disappeared from a recommendation list
+pub struct BookIdsDiff {
+    pub added: Vec<BookId>,
+    pub removed: Vec<BookId>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Changes of each recommendation list of the user compared to the stored baseline
+pub struct RecommendationsDiff {
+    pub most_popular: BookIdsDiff,
+    pub author_match: BookIdsDiff,
+    pub new_author_match: BookIdsDiff,
+    pub tag_match: BookIdsDiff,
+    pub new_releases: BookIdsDiff,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct RecommendationsQuery {
+    /// Comma separated list of book ids that are removed from all recommendation lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    /// Only books carrying this tag are kept in all recommendation lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// If true, empty recommendation lists are omitted from the response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compact: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct FlatRecommendationsQuery {
+    /// Category which books are put at the beginning of the list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefer: Option<RecommendationCategory>,
+}
+
+/// Number of serendipity books returned when no limit is given
+pub const DEFAULT_SERENDIPITY_LIMIT: usize = 5;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct SerendipityQuery {
+    /// Maximal number of returned books, default 5
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct PopularBooksQuery {
+    /// Minimal popularity (number of users that reserved the book), default 0 returns all known books
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Recommended book accompanied by its title
+pub struct RecommendationWithTitle {
+    pub book_id: BookId,
+    pub title: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Recommendations of the user with titles of the books, books missing in the repository are dropped
+pub struct TitledRecommendations {
+    pub most_popular: Vec<RecommendationWithTitle>,
+    pub author_match: Vec<RecommendationWithTitle>,
+    pub new_author_match: Vec<RecommendationWithTitle>,
+    pub tag_match: Vec<RecommendationWithTitle>,
+    pub new_releases: Vec<RecommendationWithTitle>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Flags telling which recommendation lists of the user are populated, so that empty ones can be hidden
+pub struct RecommendationCategories {
+    pub most_popular: bool,
+    pub author_match: bool,
+    pub new_author_match: bool,
+    pub tag_match: bool,
+    #[serde(default)]
+    pub new_releases: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Apiv2Schema)]
+/// Synthetic user data to calculate recommendations for
+pub struct SimulatedUser {
+    /// Books currently reserved by the user
+    pub reservations: Vec<BookId>,
+    /// Books reserved by the user in the past
+    pub history: Vec<BookId>,
+    /// Favourite tags of the user
+    #[serde(default)]
+    pub favourite_tags: Vec<TagWeight>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Favourite tags of a user that is not registered yet, e.g. during signup
+pub struct RecommendationsPreviewRequest {
+    pub favourite_tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Status of a service that recommendations are calculated from
+pub struct DownstreamStatus {
+    /// True if the last request to the service succeeded
+    pub reachable: bool,
+    /// Time of the last successful request to the service
+    pub last_success_at: Option<UnixSeconds>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Summary of recommendations service health
+pub struct RecommendationsStatus {
+    pub repository: DownstreamStatus,
+    pub reservations: DownstreamStatus,
+    /// Time of the last completed recommendations update
+    pub last_tick_completed_at: Option<UnixSeconds>,
+    /// Number of users that have recommendations calculated
+    pub users_in_engine: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Apiv2Schema)]
+/// Aggregate metrics of stored recommendations, used to evaluate recommendations quality
+pub struct RecommendationsCoverage {
+    pub users_with_recommendations: i64,
+    /// Average number of books in author match of users with recommendations
+    pub avg_author_match_size: f64,
+    /// Average number of books in most popular of users with recommendations
+    pub avg_popular_size: f64,
+    /// Number of users without any personalized (author, new author or tag match) recommendations
+    pub users_on_default_only: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Progress of the running recommendations update, counted in books with fetched details
+pub struct UpdateProgress {
+    pub processed: u64,
+    pub total: u64,
+}
+
+#[cfg(test)]
+mod api_tests {
+    use std::marker::PhantomData;
+
+    use super::*;
+
+    #[test]
+    /// Tests if id types of repository and reservations apis are the shared ones (does not compile otherwise)
+    fn test_id_types_shared_between_crates() {
+        fn same_type<T>(_: PhantomData<T>, _: PhantomData<T>) {}
+        same_type(
+            PhantomData::<bookservice_repository::api::BookId>,
+            PhantomData::<BookId>,
+        );
+        same_type(
+            PhantomData::<bookservice_reservations::api::BookId>,
+            PhantomData::<BookId>,
+        );
+        same_type(
+            PhantomData::<bookservice_reservations::api::UserId>,
+            PhantomData::<bookservice_types::UserId>,
+        );
+    }
+
+    #[test]
+    /// Tests if preferred category is put first in flat recommendations and books are not repeated
+    /// 1.Creates recommendations with a book present in both most popular and new author match lists
+    /// 2.Checks default order and order with new author match preferred
+    fn test_flattened_recommendations_prefer_category() {
+        let recommendations = Recommendations {
+            most_popular: vec![1, 2],
+            author_match: vec![3],
+            new_author_match: vec![4, 2],
+            tag_match: vec![5, 1],
+            new_releases: vec![6],
+        };
+
+        assert_eq!(recommendations.flattened(None), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            recommendations.flattened(Some(RecommendationCategory::NewAuthorMatch)),
+            vec![4, 2, 1, 3, 5, 6]
+        );
+    }
 }
@@ -5,9 +5,74 @@ use crate::handlers;
 pub fn config_app(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/health").route(web::get().to(handlers::health)))
         .service(
-            web::scope("/api").service(
-                web::resource("/recommendations/{user_id}")
-                    .route(web::get().to(handlers::get_recommendations_for_user)),
-            ),
+            web::scope("/api")
+                .service(web::resource("/status").route(web::get().to(handlers::get_status)))
+                .service(
+                    web::resource("/books/popular")
+                        .route(web::get().to(handlers::get_popular_books)),
+                )
+                .service(
+                    web::resource("/recommendations/preview")
+                        .route(web::post().to(handlers::preview_recommendations)),
+                )
+                .service(
+                    web::resource("/recommendations/simulate")
+                        .route(web::post().to(handlers::simulate_recommendations)),
+                )
+                .service(
+                    web::resource("/admin/recommendations/progress")
+                        .route(web::get().to(handlers::get_update_progress)),
+                )
+                .service(
+                    web::resource("/admin/recommendations/coverage")
+                        .route(web::get().to(handlers::get_coverage)),
+                )
+                .service(
+                    web::resource("/admin/recommendations/unrecommended")
+                        .route(web::get().to(handlers::get_unrecommended_books)),
+                )
+                .service(
+                    web::resource("/admin/recommendations/refresh_book/{book_id}")
+                        .route(web::post().to(handlers::refresh_book)),
+                )
+                .service(
+                    web::resource("/admin/recommendations/retry_user/{user_id}")
+                        .route(web::post().to(handlers::retry_user)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/categories")
+                        .route(web::get().to(handlers::get_recommendation_categories_for_user)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/baseline")
+                        .route(web::post().to(handlers::set_recommendations_baseline)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/diff")
+                        .route(web::get().to(handlers::get_recommendations_diff)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/serendipity")
+                        .route(web::get().to(handlers::get_serendipity_for_user)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/titled")
+                        .route(web::get().to(handlers::get_titled_recommendations_for_user)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}/flat")
+                        .route(web::get().to(handlers::get_flat_recommendations_for_user)),
+                )
+                .service(
+                    web::resource("/recommendations/{user_id}")
+                        .route(web::get().to(handlers::get_recommendations_for_user)),
+                ),
         );
 }
+
+/// Serves the given OpenAPI spec converted to YAML under the given path
+pub fn config_yaml_spec(cfg: &mut web::ServiceConfig, path: &str, spec: serde_json::Value) {
+    let spec_yaml = serde_yaml::to_string(&spec).expect("Failed to convert spec to yaml");
+    cfg.app_data(web::Data::new(handlers::YamlSpec(spec_yaml)))
+        .service(web::resource(path).route(web::get().to(handlers::get_yaml_spec)));
+}
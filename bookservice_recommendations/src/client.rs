@@ -0,0 +1,280 @@
+use anyhow::{bail, Context};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_tracing::TracingMiddleware;
+
+use bookservice_types::api_version::ApiVersionRecorder;
+use bookservice_types::{BookId, UserId};
+
+use crate::api::{RecommendationsPreviewRequest, TitledRecommendations};
+
+pub struct BookServiceRecommendationsClient {
+    url: String,
+    client: ClientWithMiddleware,
+    api_version: ApiVersionRecorder,
+}
+
+impl BookServiceRecommendationsClient {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let reqwest_client = reqwest::Client::builder()
+            .build()
+            .context("Failed to build reqwest client")?;
+        let api_version = ApiVersionRecorder::default();
+        let client = ClientBuilder::new(reqwest_client)
+            // Insert the tracing middleware
+            .with(TracingMiddleware::default())
+            .with(api_version.clone())
+            .build();
+
+        Ok(Self {
+            url: url.to_string(),
+            client,
+            api_version,
+        })
+    }
+
+    /// Returns api version of the server from the last response, None if no response was received yet
+    pub fn server_api_version(&self) -> Option<String> {
+        self.api_version.server_api_version()
+    }
+
+    /// Calls POST /api/recommendations/preview endpoint
+    /// Returns books recommended for a new user with given favourite tags
+    pub async fn preview_recommendations(
+        &self,
+        favourite_tags: &[String],
+    ) -> anyhow::Result<Vec<BookId>> {
+        let response = self
+            .client
+            .post(format!("{}/api/recommendations/preview", self.url))
+            .json(&RecommendationsPreviewRequest {
+                favourite_tags: favourite_tags.to_vec(),
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error = response.text().await.unwrap_or_default();
+            bail!("Failed to preview recommendations {}", error)
+        }
+    }
+
+    /// Calls GET /api/recommendations/{user_id}/titled endpoint
+    /// Returns recommendations of the user with titles of the books
+    pub async fn get_titled_recommendations(
+        &self,
+        user_id: UserId,
+    ) -> anyhow::Result<TitledRecommendations> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/api/recommendations/{}/titled",
+                self.url, user_id
+            ))
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error = response.text().await.unwrap_or_default();
+            bail!("Failed to get titled recommendations {}", error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod client_tests {
+    use std::collections::HashMap;
+
+    use std::sync::Arc;
+
+    use actix_web::{web, App, HttpServer};
+    use bookservice_repository::api::BookDetails;
+    use bookservice_repository::books_repository::{BookRepository, InMemoryBookRepository};
+    use bookservice_repository::client::BookServiceRepositoryClient;
+    use bookservice_repository::tags_validator::TagsValidator;
+    use bookservice_reservations::api::{ReservationHistoryRecord, UnixSeconds};
+    use paperclip::actix::OpenApiExt;
+
+    use super::*;
+    use crate::api::RecommendationWithTitle;
+    use crate::app_config::config_app;
+    use crate::recommendations::{CoefficientsStorage, RecommendationsEngine};
+    use crate::recommendations_updater::RecommendationsProvider;
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Seeds storage with scifi books of different popularity and a more popular book without the tag
+    /// 2. Previews recommendations for scifi - expect only scifi books, the most popular first
+    /// 3. Previews recommendations for unknown tag - expect no books
+    async fn test_preview_recommendations() {
+        let book = |tags: &[&str]| BookDetails {
+            title: "title".to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            isbn: None,
+        };
+        let history = |book_ids: &[BookId]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let book_details = HashMap::from([
+            (1, book(&["scifi"])),
+            (2, book(&["SciFi", "fantasy"])),
+            (3, book(&["romance"])),
+            (4, book(&[])),
+        ]);
+        let user_to_history = HashMap::from([
+            (1, history(&[3, 2, 1])),
+            (2, history(&[3, 2])),
+            (3, history(&[3])),
+        ]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let provider =
+            RecommendationsProvider::from_parts(storage, RecommendationsEngine::default());
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(provider.clone()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind recommendations server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRecommendationsClient::new(&url).unwrap();
+        assert_eq!(
+            client
+                .preview_recommendations(&["scifi".to_string()])
+                .await
+                .unwrap(),
+            vec![2, 1]
+        );
+        assert!(client
+            .preview_recommendations(&["unknown".to_string()])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository service with two books, seeds storage with them and a book missing in the repository
+    /// 2. Gets titled recommendations of a user without history
+    /// 3. Expect the most popular books with their titles, the missing book dropped
+    async fn test_get_titled_recommendations() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let book = |title: &str| BookDetails {
+            title: title.to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let first_book_id = repository_client.add_book(book("First")).await.unwrap();
+        let second_book_id = repository_client.add_book(book("Second")).await.unwrap();
+        let missing_book_id = first_book_id.max(second_book_id) + 100;
+
+        let history = |book_ids: &[BookId]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let book_details = HashMap::from([
+            (first_book_id, book("First")),
+            (second_book_id, book("Second")),
+            (missing_book_id, book("Missing")),
+        ]);
+        let user_to_history = HashMap::from([
+            (
+                1,
+                history(&[missing_book_id, first_book_id, second_book_id]),
+            ),
+            (2, history(&[missing_book_id, first_book_id])),
+            (3, history(&[missing_book_id])),
+        ]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::new(),
+                &user_to_history,
+                &HashMap::new(),
+            )
+            .unwrap();
+        let provider = RecommendationsProvider::from_parts(storage, engine);
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(provider.clone()))
+                .app_data(web::Data::new(
+                    BookServiceRepositoryClient::new(&repository_url).unwrap(),
+                ))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind recommendations server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let recommendations = BookServiceRecommendationsClient::new(&url)
+            .unwrap()
+            .get_titled_recommendations(4)
+            .await
+            .unwrap();
+        assert_eq!(
+            recommendations.most_popular,
+            vec![
+                RecommendationWithTitle {
+                    book_id: first_book_id,
+                    title: "First".to_string(),
+                },
+                RecommendationWithTitle {
+                    book_id: second_book_id,
+                    title: "Second".to_string(),
+                },
+            ]
+        );
+    }
+}
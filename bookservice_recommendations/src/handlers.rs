@@ -1,14 +1,138 @@
-use actix_web::Error;
-use actix_web::HttpResponse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use actix_web::body::BoxBody;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::header::{CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch, VARY};
+use actix_web::web::Bytes;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse, Responder};
 use paperclip::actix::{
     api_v2_operation,
-    web::{self, Json},
+    web::{self, Data, Json},
+    OperationModifier,
+};
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw};
+use paperclip::v2::schema::Apiv2Schema;
+use serde::ser::Error as _;
+use serde::{Serialize, Serializer};
+use tokio::sync::broadcast::error::RecvError;
+
+use bookservice_repository::client::BookServiceRepositoryClient;
+use bookservice_reservations::api::TagWeight;
+use bookservice_types::msgpack::{accepts_msgpack, negotiated_response};
+use bookservice_types::{BookId, UserId};
+
+use crate::api::{
+    FlatRecommendationsQuery, PopularBooksQuery, RecommendationCategories, RecommendationWithTitle,
+    Recommendations, RecommendationsCoverage, RecommendationsDiff, RecommendationsPreviewRequest,
+    RecommendationsQuery, RecommendationsStatus, SerendipityQuery, SimulatedUser,
+    TitledRecommendations, DEFAULT_SERENDIPITY_LIMIT,
+};
+use crate::recommendations::versioned_etag;
+use crate::recommendations_updater::{
+    RecommendationsCacheControl, RecommendationsProvider, RecommendationsUpdater,
 };
 
-use bookservice_reservations::api::UserId;
+/// OpenAPI spec converted to YAML once at startup
+pub struct YamlSpec(pub String);
+
+#[api_v2_operation(skip)]
+pub async fn get_yaml_spec(spec: Data<YamlSpec>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok()
+        .content_type("application/yaml")
+        .body(spec.0.clone()))
+}
+
+/// Json (or MessagePack if client accepts it) response with an ETag and `Cache-Control: max-age`, responds with
+/// 304 Not Modified if client sent matching `If-None-Match`
+/// MessagePack responses get their own ETag, as their body differs from the json one
+/// Documented in api spec the same way as `Json<T>`
+pub struct ETaggedJson<T> {
+    pub value: T,
+    pub etag: String,
+    pub max_age_seconds: u64,
+}
+
+impl<T: Serialize> Responder for ETaggedJson<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let etag = if accepts_msgpack(req) {
+            EntityTag::new_strong(format!("{}-msgpack", self.etag))
+        } else {
+            EntityTag::new_strong(self.etag)
+        };
+        let cache_control = CacheControl(vec![CacheDirective::MaxAge(self.max_age_seconds as u32)]);
+        let not_modified = match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            None => false,
+        };
+
+        if not_modified {
+            HttpResponse::NotModified()
+                .insert_header(ETag(etag))
+                .insert_header(cache_control)
+                .insert_header((VARY, "Accept"))
+                .finish()
+        } else {
+            let mut response = HttpResponse::Ok();
+            response
+                .insert_header(ETag(etag))
+                .insert_header(cache_control);
+            negotiated_response(req, response, &self.value)
+        }
+    }
+}
+
+impl<T: Apiv2Schema> Apiv2Schema for ETaggedJson<T> {
+    fn name() -> Option<String> {
+        T::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        T::raw_schema()
+    }
+}
+
+impl<T: Apiv2Schema> OperationModifier for ETaggedJson<T> {
+    fn update_response(op: &mut DefaultOperationRaw) {
+        Json::<T>::update_response(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<T>::update_definitions(map)
+    }
+}
+
+/// Recommendations serialized without empty lists if `compact` is set
+/// Documented in api spec the same way as `Recommendations`
+pub struct MaybeCompactRecommendations {
+    pub recommendations: Recommendations,
+    pub compact: bool,
+}
+
+impl Serialize for MaybeCompactRecommendations {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.compact {
+            return self.recommendations.serialize(serializer);
+        }
+        let mut value = serde_json::to_value(&self.recommendations).map_err(S::Error::custom)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.retain(|_, field| field.as_array().is_none_or(|book_ids| !book_ids.is_empty()));
+        }
+        value.serialize(serializer)
+    }
+}
 
-use crate::api::Recommendations;
-use crate::recommendations_updater::RecommendationsProvider;
+impl Apiv2Schema for MaybeCompactRecommendations {
+    fn name() -> Option<String> {
+        Recommendations::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        Recommendations::raw_schema()
+    }
+}
 
 #[api_v2_operation]
 pub async fn health() -> Result<HttpResponse, Error> {
@@ -17,16 +141,831 @@ pub async fn health() -> Result<HttpResponse, Error> {
 
 #[api_v2_operation]
 pub async fn get_recommendations_for_user(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    cache_control: web::Data<RecommendationsCacheControl>,
+    user_id: web::Path<UserId>,
+    query: web::Query<RecommendationsQuery>,
+) -> Result<ETaggedJson<MaybeCompactRecommendations>, Error> {
+    let excluded_book_ids = query
+        .exclude
+        .iter()
+        .flat_map(|exclude| exclude.split(','))
+        .map(str::trim)
+        .filter(|book_id| !book_id.is_empty())
+        .map(|book_id| book_id.parse::<BookId>())
+        .collect::<Result<HashSet<BookId>, _>>()
+        .map_err(ErrorBadRequest)?;
+    let tagged_book_ids = query
+        .tag
+        .as_deref()
+        .map(|tag| recommendations_provider.books_with_tag(tag));
+
+    let versioned_recommendations =
+        recommendations_provider.get_versioned_recommendations_for_user(user_id.into_inner());
+    let mut recommendations = versioned_recommendations.recommendations;
+    for book_ids in [
+        &mut recommendations.most_popular,
+        &mut recommendations.author_match,
+        &mut recommendations.new_author_match,
+        &mut recommendations.tag_match,
+        &mut recommendations.new_releases,
+    ] {
+        book_ids.retain(|book_id| {
+            !excluded_book_ids.contains(book_id)
+                && tagged_book_ids
+                    .as_ref()
+                    .is_none_or(|tagged_book_ids| tagged_book_ids.contains(book_id))
+        });
+    }
+    let value = MaybeCompactRecommendations {
+        recommendations,
+        compact: query.compact.unwrap_or_default(),
+    };
+    // Filters and compact form change the body, so the ETag is calculated from the served one
+    Ok(ETaggedJson {
+        etag: versioned_etag(versioned_recommendations.version, &value),
+        value,
+        max_age_seconds: cache_control.max_age_seconds,
+    })
+}
+
+/// Recommendations of the user with titles fetched from the repository, books missing there are dropped
+#[api_v2_operation]
+pub async fn get_titled_recommendations_for_user(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    repository_client: web::Data<BookServiceRepositoryClient>,
+    user_id: web::Path<UserId>,
+) -> Result<Json<TitledRecommendations>, Error> {
+    let recommendations =
+        recommendations_provider.get_recommendations_for_user(user_id.into_inner());
+    let book_id_to_details = repository_client
+        .get_books(&recommendations.flattened(None))
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let book_id_to_title: HashMap<BookId, String> = book_id_to_details
+        .into_iter()
+        .map(|(book_id, details)| (book_id, details.title))
+        .collect();
+    let titled = |book_ids: &[BookId]| {
+        book_ids
+            .iter()
+            .filter_map(|book_id| {
+                book_id_to_title
+                    .get(book_id)
+                    .map(|title| RecommendationWithTitle {
+                        book_id: *book_id,
+                        title: title.clone(),
+                    })
+            })
+            .collect()
+    };
+    Ok(Json(TitledRecommendations {
+        most_popular: titled(&recommendations.most_popular),
+        author_match: titled(&recommendations.author_match),
+        new_author_match: titled(&recommendations.new_author_match),
+        tag_match: titled(&recommendations.tag_match),
+        new_releases: titled(&recommendations.new_releases),
+    }))
+}
+
+#[api_v2_operation]
+pub async fn get_recommendation_categories_for_user(
     recommendations_provider: web::Data<RecommendationsProvider>,
     user_id: web::Path<UserId>,
+) -> Result<Json<RecommendationCategories>, Error> {
+    Ok(Json(
+        recommendations_provider.get_recommendation_categories_for_user(user_id.into_inner()),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_flat_recommendations_for_user(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    user_id: web::Path<UserId>,
+    query: web::Query<FlatRecommendationsQuery>,
+) -> Result<Json<Vec<BookId>>, Error> {
+    Ok(Json(
+        recommendations_provider
+            .get_flat_recommendations_for_user(user_id.into_inner(), query.prefer),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_serendipity_for_user(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    user_id: web::Path<UserId>,
+    query: web::Query<SerendipityQuery>,
+) -> Result<Json<Vec<BookId>>, Error> {
+    Ok(Json(recommendations_provider.get_serendipity_for_user(
+        user_id.into_inner(),
+        query.limit.unwrap_or(DEFAULT_SERENDIPITY_LIMIT),
+    )))
+}
+
+#[api_v2_operation]
+pub async fn get_popular_books(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    query: web::Query<PopularBooksQuery>,
+) -> Result<Json<Vec<BookId>>, Error> {
+    Ok(Json(recommendations_provider.books_above_popularity(
+        query.min_score.unwrap_or_default(),
+    )))
+}
+
+#[api_v2_operation]
+pub async fn set_recommendations_baseline(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    user_id: web::Path<UserId>,
+) -> Result<Json<Recommendations>, Error> {
+    Ok(Json(
+        recommendations_provider.set_baseline(user_id.into_inner()),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_recommendations_diff(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    user_id: web::Path<UserId>,
+) -> Result<Json<RecommendationsDiff>, Error> {
+    let user_id = user_id.into_inner();
+    recommendations_provider
+        .diff_from_baseline(user_id)
+        .map(Json)
+        .ok_or_else(|| ErrorNotFound(format!("No baseline set for user {}", user_id)))
+}
+
+#[api_v2_operation]
+pub async fn simulate_recommendations(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    user: Json<SimulatedUser>,
 ) -> Result<Json<Recommendations>, Error> {
     Ok(Json(
-        recommendations_provider.get_recommendations_for_user(user_id.into_inner()),
+        recommendations_provider.simulate_recommendations(&user),
     ))
 }
 
+#[api_v2_operation]
+pub async fn preview_recommendations(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+    request: Json<RecommendationsPreviewRequest>,
+) -> Result<Json<Vec<BookId>>, Error> {
+    let favourite_tags: Vec<TagWeight> = request
+        .favourite_tags
+        .iter()
+        .map(|tag| tag.as_str().into())
+        .collect();
+    Ok(Json(
+        recommendations_provider.preview_recommendations(&favourite_tags),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn refresh_book(
+    recommendations_updater: web::Data<RecommendationsUpdater>,
+    book_id: web::Path<BookId>,
+) -> Result<HttpResponse, Error> {
+    let book_id = book_id.into_inner();
+    if recommendations_updater
+        .refresh_book(book_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::NotFound().body(format!("Book not found {}", book_id)))
+    }
+}
+
+/// Makes the next recommendations update process the user, responds with true if the user was skipped before
+#[api_v2_operation]
+pub async fn retry_user(
+    recommendations_updater: web::Data<RecommendationsUpdater>,
+    user_id: web::Path<UserId>,
+) -> Result<Json<bool>, Error> {
+    Ok(Json(
+        recommendations_updater.requeue_user(user_id.into_inner()),
+    ))
+}
+
+/// Streams progress of recommendations updates as server-sent events, one `UpdateProgress` json per event
+#[api_v2_operation]
+pub async fn get_update_progress(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+) -> Result<HttpResponse, Error> {
+    let events = futures_util::stream::unfold(
+        recommendations_provider.subscribe_progress(),
+        |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(progress) => {
+                        let event = format!("data: {}\n\n", serde_json::to_string(&progress).ok()?);
+                        return Some((Ok::<_, Error>(Bytes::from(event)), receiver));
+                    }
+                    // Slow subscriber missed some events, newer ones are still worth sending
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(events))
+}
+
+#[api_v2_operation]
+pub async fn get_status(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+) -> Result<Json<RecommendationsStatus>, Error> {
+    Ok(Json(recommendations_provider.get_status()))
+}
+
+#[api_v2_operation]
+pub async fn get_coverage(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+) -> Result<Json<RecommendationsCoverage>, Error> {
+    Ok(Json(recommendations_provider.coverage()))
+}
+
+#[api_v2_operation]
+pub async fn get_unrecommended_books(
+    recommendations_provider: web::Data<RecommendationsProvider>,
+) -> Result<Json<Vec<BookId>>, Error> {
+    Ok(Json(recommendations_provider.unrecommended_books()))
+}
+
 #[cfg(test)]
 mod handler_tests {
-    // TODO: Add client for api
-    // TODO: Add tests for handler
+    use std::collections::HashMap;
+
+    use actix_web::http::header::{ACCEPT, CACHE_CONTROL, ETAG, IF_NONE_MATCH, VARY};
+    use actix_web::http::StatusCode;
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use paperclip::actix::OpenApiExt;
+
+    use bookservice_repository::api::BookDetails;
+    use bookservice_reservations::api::{ReservationHistoryRecord, UnixSeconds};
+    use bookservice_types::msgpack::MSGPACK_CONTENT_TYPE;
+
+    use crate::api::{Recommendations, SimulatedUser};
+    use crate::app_config::{config_app, config_yaml_spec};
+    use crate::recommendations::{CoefficientsStorage, RecommendationsEngine};
+    use crate::recommendations_updater::{RecommendationsCacheControl, RecommendationsProvider};
+
+    #[actix_web::test]
+    /// Tests if the OpenAPI spec is served as YAML and contains the recommendations endpoint
+    async fn test_get_yaml_spec() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .configure(config_app)
+                .with_raw_json_spec(|app, spec| {
+                    app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+                })
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/apispec/v2.yaml")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+
+        let body = test::read_body(response).await;
+        let spec: serde_yaml::Value =
+            serde_yaml::from_slice(&body).expect("Failed to parse yaml spec");
+        let paths = spec["paths"].as_mapping().expect("No paths in spec");
+        assert!(paths.contains_key("/api/recommendations/{user_id}"));
+        assert!(spec["definitions"]["Recommendations"].is_mapping());
+    }
+
+    #[actix_web::test]
+    /// Tests if second poll with ETag from the first one returns 304 as recommendations did not change
+    async fn test_get_recommendations_not_modified() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .expect("No ETag header")
+            .clone();
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(ETAG), Some(&etag));
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((IF_NONE_MATCH, "\"other\""))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    /// Tests if recommendations responses (also the not modified ones) carry configured Cache-Control max-age
+    async fn test_get_recommendations_cache_control() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .app_data(Data::new(RecommendationsCacheControl {
+                    max_age_seconds: 3,
+                }))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "max-age=3");
+        let etag = response.headers().get(ETAG).unwrap().clone();
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((IF_NONE_MATCH, etag))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "max-age=3");
+    }
+
+    #[actix_web::test]
+    /// Tests if json and MessagePack recommendations are cached separately
+    /// 1. Get json recommendations - expect `Vary: Accept`
+    /// 2. Get MessagePack recommendations - expect `Vary: Accept` and ETag different from the json one
+    /// 3. Get MessagePack recommendations with json ETag - expect 200 as representation differs
+    /// 4. Get MessagePack recommendations with MessagePack ETag - expect 304 with `Vary: Accept`
+    async fn test_get_recommendations_etag_per_media_type() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+        let json_etag = response.headers().get(ETAG).unwrap().clone();
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((ACCEPT, MSGPACK_CONTENT_TYPE))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+        let msgpack_etag = response.headers().get(ETAG).unwrap().clone();
+        assert_ne!(msgpack_etag, json_etag);
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((ACCEPT, MSGPACK_CONTENT_TYPE))
+            .insert_header((IF_NONE_MATCH, json_etag))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .insert_header((ACCEPT, MSGPACK_CONTENT_TYPE))
+            .insert_header((IF_NONE_MATCH, msgpack_etag.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(ETAG), Some(&msgpack_etag));
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+    }
+
+    #[actix_web::test]
+    /// Tests if simulate endpoint returns recommendations for synthetic user (empty, as there are no coefficients yet)
+    async fn test_simulate_recommendations() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/recommendations/simulate")
+            .set_json(SimulatedUser {
+                reservations: vec![1],
+                history: vec![2],
+                favourite_tags: vec!["tag".into()],
+            })
+            .to_request();
+        let recommendations: Recommendations = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(recommendations, Recommendations::default());
+    }
+
+    #[actix_web::test]
+    /// Tests if flat recommendations accept preferred category and reject unknown one
+    async fn test_get_flat_recommendations_prefer() {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1/flat?prefer=new_author_match")
+            .to_request();
+        let book_ids: Vec<i32> = test::call_and_read_body_json(&app, request).await;
+        assert!(book_ids.is_empty());
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1/flat?prefer=unknown")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Calculate recommendations for a user that read a book of the first author, while other user read books
+    ///    of both authors - all of most popular, author match and new author match lists are populated
+    /// 2. Get recommendations excluding one book of each of these lists
+    /// 3. Check that the excluded books are absent from all lists and other books remain
+    /// 4. Check that invalid excluded id is rejected
+    async fn test_get_recommendations_with_exclusions() {
+        let book = |author: &str| BookDetails {
+            title: "title".to_string(),
+            authors: vec![author.to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let history = |book_ids: &[i32]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let book_details = HashMap::from([
+            (1, book("a1")),
+            (2, book("a1")),
+            (3, book("a1")),
+            (4, book("a2")),
+            (5, book("a2")),
+        ]);
+        let user_to_history = HashMap::from([(1, history(&[1])), (2, history(&[1, 2, 4]))]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::from_parts(
+                    storage, engine,
+                )))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let all: Recommendations = test::call_and_read_body_json(&app, request).await;
+        let excluded = [
+            all.most_popular[0],
+            all.author_match[0],
+            all.new_author_match[0],
+        ];
+
+        let request = test::TestRequest::get()
+            .uri(&format!(
+                "/api/recommendations/1?exclude={},{},{}",
+                excluded[0], excluded[1], excluded[2]
+            ))
+            .to_request();
+        let filtered: Recommendations = test::call_and_read_body_json(&app, request).await;
+        for (all_books, filtered_books) in [
+            (&all.most_popular, &filtered.most_popular),
+            (&all.author_match, &filtered.author_match),
+            (&all.new_author_match, &filtered.new_author_match),
+        ] {
+            let expected: Vec<i32> = all_books
+                .iter()
+                .filter(|book_id| !excluded.contains(book_id))
+                .cloned()
+                .collect();
+            assert_eq!(filtered_books, &expected);
+        }
+        assert!(!filtered.most_popular.is_empty());
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1?exclude=1,x")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Calculate recommendations for a user that read a book of the first author, while other user read books
+    ///    of both authors, part of the books is tagged with scifi
+    /// 2. Get recommendations restricted to scifi tag with ETag of the unrestricted ones - expect 200 and other ETag
+    /// 3. Check that books without the tag are removed from all lists and scifi books remain
+    async fn test_get_recommendations_with_tag() {
+        let book = |author: &str, tags: &[&str]| BookDetails {
+            title: "title".to_string(),
+            authors: vec![author.to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            isbn: None,
+        };
+        let history = |book_ids: &[i32]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let book_details = HashMap::from([
+            (1, book("a1", &["scifi"])),
+            (2, book("a1", &["fantasy"])),
+            (3, book("a1", &["SciFi"])),
+            (4, book("a2", &[])),
+            (5, book("a2", &["scifi"])),
+        ]);
+        let scifi_books = [1, 3, 5];
+        let user_to_history = HashMap::from([(1, history(&[1])), (2, history(&[1, 2, 4]))]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::from_parts(
+                    storage, engine,
+                )))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        let all_etag = response.headers().get(ETAG).unwrap().clone();
+        let all: Recommendations = test::read_body_json(response).await;
+        assert!(all
+            .most_popular
+            .iter()
+            .any(|book_id| !scifi_books.contains(book_id)));
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1?tag=scifi")
+            .insert_header((IF_NONE_MATCH, all_etag.clone()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers().get(ETAG), Some(&all_etag));
+        let filtered: Recommendations = test::read_body_json(response).await;
+        for (all_books, filtered_books) in [
+            (&all.most_popular, &filtered.most_popular),
+            (&all.author_match, &filtered.author_match),
+            (&all.new_author_match, &filtered.new_author_match),
+        ] {
+            let expected: Vec<i32> = all_books
+                .iter()
+                .filter(|book_id| scifi_books.contains(book_id))
+                .cloned()
+                .collect();
+            assert_eq!(filtered_books, &expected);
+        }
+        assert!(!filtered.most_popular.is_empty());
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Calculate recommendations for a user that read one of 10 books with fallback most popular size of 8
+    /// 2. Get recommendations of the user - expect 5 most popular books
+    /// 3. Get recommendations of unknown user - expect 8 most popular books
+    async fn test_fallback_most_popular_size() {
+        let book_details: HashMap<i32, BookDetails> = (1..=10)
+            .map(|book_id| {
+                (
+                    book_id,
+                    BookDetails {
+                        title: "title".to_string(),
+                        authors: vec![format!("author{}", book_id)],
+                        publisher: "".to_string(),
+                        description: "".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    },
+                )
+            })
+            .collect();
+        let user_to_history = HashMap::from([(
+            1,
+            vec![ReservationHistoryRecord {
+                book_id: 1,
+                reserved_at: None,
+                unreserved_at: UnixSeconds(10),
+            }],
+        )]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine.set_fallback_most_popular_size(8);
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::from_parts(
+                    storage, engine,
+                )))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1")
+            .to_request();
+        let user_recommendations: Recommendations =
+            test::call_and_read_body_json(&app, request).await;
+        assert_eq!(user_recommendations.most_popular.len(), 5);
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/2")
+            .to_request();
+        let fallback_recommendations: Recommendations =
+            test::call_and_read_body_json(&app, request).await;
+        assert_eq!(fallback_recommendations.most_popular.len(), 8);
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Calculate recommendations for a user that read a book of the first author, while other user read books
+    ///    of both authors
+    /// 2. Get compact recommendations of a cold-start user - expect empty lists are omitted
+    /// 3. Get not compact recommendations of the cold-start user - expect empty lists are present
+    /// 4. Get compact recommendations of the user - expect populated lists are present
+    async fn test_get_compact_recommendations() {
+        let book = |author: &str| BookDetails {
+            title: "title".to_string(),
+            authors: vec![author.to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let history = |book_ids: &[i32]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let book_details = HashMap::from([
+            (1, book("a1")),
+            (2, book("a1")),
+            (3, book("a1")),
+            (4, book("a2")),
+            (5, book("a2")),
+        ]);
+        let user_to_history = HashMap::from([(1, history(&[1])), (2, history(&[1, 2, 4]))]);
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(RecommendationsProvider::from_parts(
+                    storage, engine,
+                )))
+                .app_data(Data::new(RecommendationsCacheControl::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/100?compact=true")
+            .to_request();
+        let cold_start: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert!(cold_start["most_popular"].is_array());
+        for field in ["author_match", "new_author_match", "tag_match"] {
+            assert!(cold_start.get(field).is_none(), "{} is present", field);
+        }
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/100?compact=true")
+            .to_request();
+        let cold_start: Recommendations = test::call_and_read_body_json(&app, request).await;
+        assert!(cold_start.author_match.is_empty());
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/100")
+            .to_request();
+        let cold_start: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(cold_start["author_match"], serde_json::json!([]));
+
+        let request = test::TestRequest::get()
+            .uri("/api/recommendations/1?compact=true")
+            .to_request();
+        let populated: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert!(!populated["author_match"].as_array().unwrap().is_empty());
+        assert!(!populated["new_author_match"].as_array().unwrap().is_empty());
+    }
 }
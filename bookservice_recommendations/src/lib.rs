@@ -1,7 +1,7 @@
 pub mod api;
 
-// #[cfg(any(feature = "client", test))]
-// pub mod client;
+#[cfg(any(feature = "client", test))]
+pub mod client;
 
 #[cfg(any(feature = "server", test))]
 pub mod app_config;
@@ -41,8 +41,16 @@ fn init_telemetry() {
 async fn main() -> anyhow::Result<()> {
     use actix_web::{App, HttpServer};
     use anyhow::Context;
-    use bookservice_recommendations::app_config::config_app;
-    use bookservice_recommendations::recommendations_updater::RecommendationsUpdater;
+    use bookservice_recommendations::app_config::{config_app, config_yaml_spec};
+    use bookservice_recommendations::recommendations_updater::{
+        AuthorMatchConfig, RecommendationsCacheControl, RecommendationsUpdater,
+        DEFAULT_AUTHOR_MATCH_BOOKS_PER_AUTHOR, DEFAULT_FALLBACK_MOST_POPULAR_SIZE,
+        DEFAULT_MAX_CONCURRENT_BOOK_REQUESTS, DEFAULT_MAX_CONSECUTIVE_TICK_FAILURES,
+        DEFAULT_RECOMMENDATIONS_MAX_AGE_SECONDS,
+    };
+    use bookservice_repository::client::BookServiceRepositoryClient;
+    use bookservice_types::api_error::not_found;
+    use bookservice_types::api_version::api_version_headers;
     use paperclip::actix::web;
     use paperclip::actix::OpenApiExt;
     use std::env;
@@ -56,27 +64,88 @@ async fn main() -> anyhow::Result<()> {
     let bookservice_reservations_url =
         env::var("BOOKSERVICE_RESERVATIONS_URL").unwrap_or("http://localhost:8081".to_string());
 
-    let recommendations_updater =
-        RecommendationsUpdater::new(&bookservice_repository_url, &bookservice_reservations_url)?;
+    let cache_control = web::Data::new(RecommendationsCacheControl {
+        max_age_seconds: env::var("RECOMMENDATIONS_MAX_AGE_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RECOMMENDATIONS_MAX_AGE_SECONDS),
+    });
+
+    let max_consecutive_tick_failures = env::var("RECOMMENDATIONS_MAX_TICK_FAILURES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONSECUTIVE_TICK_FAILURES);
+
+    let author_match_config = AuthorMatchConfig {
+        max_books_per_author: env::var("AUTHOR_MATCH_BOOKS_PER_AUTHOR")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_AUTHOR_MATCH_BOOKS_PER_AUTHOR),
+    };
+
+    let max_concurrent_book_requests = env::var("RECOMMENDATIONS_MAX_CONCURRENT_BOOK_REQUESTS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_BOOK_REQUESTS);
+
+    let fallback_most_popular_size = env::var("FALLBACK_MOST_POPULAR_SIZE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_FALLBACK_MOST_POPULAR_SIZE);
+
+    let recommendations_capacity: Option<usize> = env::var("RECOMMENDATIONS_CAPACITY")
+        .ok()
+        .and_then(|value| value.parse().ok());
+
+    let eager_book_load = env::var("EAGER_BOOK_LOAD")
+        .map(|value| value.to_lowercase() == "true")
+        .unwrap_or_default();
+
+    let recommendations_updater = web::Data::new(
+        RecommendationsUpdater::new(&bookservice_repository_url, &bookservice_reservations_url)?
+            .with_max_consecutive_tick_failures(max_consecutive_tick_failures)
+            .with_author_match_config(author_match_config)
+            .with_eager_book_load(eager_book_load)
+            .with_max_concurrent_book_requests(max_concurrent_book_requests)
+            .with_fallback_most_popular_size(fallback_most_popular_size)
+            .with_recommendations_capacity(recommendations_capacity),
+    );
+
+    let repository_client = web::Data::new(
+        BookServiceRepositoryClient::new(&bookservice_repository_url)
+            .context("Failed to create repository client")?,
+    );
 
     let provider = recommendations_updater.provider();
+    let server_recommendations_updater = recommendations_updater.clone();
 
+    let updater_shutdown = recommendations_updater.shutdown_handle();
     let updater_handle = recommendations_updater.start();
 
     let start_server = async {
         HttpServer::new(move || {
             App::new()
                 .wrap_api()
+                .wrap(api_version_headers())
                 .wrap(TracingLogger::default())
                 .app_data(web::Data::new(provider.clone()))
+                .app_data(server_recommendations_updater.clone())
+                .app_data(cache_control.clone())
+                .app_data(repository_client.clone())
                 .configure(config_app)
+                .default_service(web::to(not_found))
                 .with_json_spec_at("/apispec/v2")
+                .with_raw_json_spec(|app, spec| {
+                    app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+                })
                 .build()
         })
         .bind(("0.0.0.0", 8080))?
         .run()
         .await
         .context("Http server failure")?;
+        // Server stops on termination signal, the updater is stopped after finishing its running tick
+        updater_shutdown.shutdown();
         Ok(())
     };
 
@@ -1,17 +1,120 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use itertools::Itertools;
+use serde::Serialize;
 
 use bookservice_repository::api::BookDetails;
-use bookservice_reservations::api::{BookId, ReservationHistoryRecord, UserId};
+use bookservice_reservations::api::{ReservationHistoryRecord, TagWeight, UnixSeconds};
+use bookservice_types::{BookId, UserId};
 
-use crate::api::Recommendations;
+use crate::api::{Recommendations, RecommendationsCoverage};
 
 const NO_OF_RECOMMENDATIONS: usize = 5;
-#[derive(Default)]
+/// Users without recommendations get as many most popular books as users with recommendations by default
+pub const DEFAULT_FALLBACK_MOST_POPULAR_SIZE: usize = NO_OF_RECOMMENDATIONS;
+/// Authors contribute one book to author match by default
+pub const DEFAULT_AUTHOR_MATCH_BOOKS_PER_AUTHOR: usize = 1;
+
+/// Configuration of author match recommendations
+#[derive(Debug, Clone)]
+pub struct AuthorMatchConfig {
+    /// Maximal number of books of a single author, an author contributes at most as many books
+    /// as the user has read from them
+    pub max_books_per_author: usize,
+}
+
+impl Default for AuthorMatchConfig {
+    fn default() -> Self {
+        Self {
+            max_books_per_author: DEFAULT_AUTHOR_MATCH_BOOKS_PER_AUTHOR,
+        }
+    }
+}
+
 pub struct RecommendationsEngine {
-    user_to_recommendations: HashMap<UserId, Recommendations>,
-    default_recommendations: Recommendations,
+    user_to_recommendations: HashMap<UserId, VersionedRecommendations>,
+    default_recommendations: VersionedRecommendations,
+    /// Number of most popular books in recommendations of users without calculated recommendations
+    fallback_most_popular_size: usize,
+    /// All books from history of each user, history is fetched incrementally so it is accumulated here
+    user_to_history_books: HashMap<UserId, HashSet<BookId>>,
+    /// Maximal number of users with stored recommendations, least recently requested users are evicted above it
+    capacity: Option<usize>,
+    /// Value of `request_clock` when recommendations of each user were last requested (or first stored)
+    user_to_last_request: HashMap<UserId, AtomicU64>,
+    request_clock: AtomicU64,
+    /// Books that were ever in recommendations of any user or in the default recommendations
+    recommended_books: HashSet<BookId>,
+}
+
+impl Default for RecommendationsEngine {
+    fn default() -> Self {
+        Self {
+            user_to_recommendations: Default::default(),
+            default_recommendations: Default::default(),
+            fallback_most_popular_size: DEFAULT_FALLBACK_MOST_POPULAR_SIZE,
+            user_to_history_books: Default::default(),
+            capacity: None,
+            user_to_last_request: Default::default(),
+            request_clock: Default::default(),
+            recommended_books: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Recommendations with a version that is bumped only when the recommendations change
+pub struct VersionedRecommendations {
+    pub recommendations: Recommendations,
+    pub version: u64,
+    /// Version combined with hash of serialized recommendations, can be used as an ETag
+    pub etag: String,
+}
+
+/// Returns version combined with hash of serialized value, can be used as an ETag
+pub fn versioned_etag(version: u64, value: &impl Serialize) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(value)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{}-{:x}", version, hasher.finish())
+}
+
+impl VersionedRecommendations {
+    fn new(version: u64, recommendations: Recommendations) -> Self {
+        Self {
+            etag: versioned_etag(version, &recommendations),
+            recommendations,
+            version,
+        }
+    }
+
+    /// Returns the same version if recommendations did not change, otherwise the next one
+    fn updated(&self, recommendations: Recommendations) -> Self {
+        if self.recommendations == recommendations {
+            self.clone()
+        } else {
+            Self::new(self.version + 1, recommendations)
+        }
+    }
+}
+
+impl Default for VersionedRecommendations {
+    fn default() -> Self {
+        Self::new(0, Recommendations::default())
+    }
+}
+
+/// Weights of favourite tags by lowercased tag
+fn tag_to_weight(favourite_tags: &[TagWeight]) -> HashMap<String, f32> {
+    favourite_tags
+        .iter()
+        .map(|tag_weight| (tag_weight.tag.trim().to_lowercase(), tag_weight.weight))
+        .collect()
 }
 
 #[derive(Default)]
@@ -22,7 +125,17 @@ pub struct CoefficientsStorage {
     popularity_score: HashMap<BookId, i64>,
     author_to_books: BTreeMap<String, HashSet<BookId>>,
     book_id_to_authors: HashMap<BookId, Vec<String>>,
-    last_processed_timestamp_per_user: HashMap<UserId, i64>,
+    /// Lowercased, unique tags of each book
+    book_id_to_tags: HashMap<BookId, Vec<String>>,
+    /// Books carrying each of the lowercased tags
+    tag_to_books: HashMap<String, HashSet<BookId>>,
+    /// All known books ordered by id, new releases are the books with the highest ids
+    /// (creation time of books is not known here, ids are only assumed to grow with it)
+    books_by_id: BTreeSet<BookId>,
+    last_processed_timestamp_per_user: HashMap<UserId, UnixSeconds>,
+    /// Time of the latest reservation end of each book, more recently reserved books win popularity ties
+    last_unreserved_at_per_book: HashMap<BookId, UnixSeconds>,
+    author_match_config: AuthorMatchConfig,
 }
 
 impl CoefficientsStorage {
@@ -32,15 +145,7 @@ impl CoefficientsStorage {
         book_details: &HashMap<BookId, BookDetails>,
     ) -> anyhow::Result<()> {
         for (book_id, details) in book_details.iter() {
-            self.book_id_to_authors
-                .insert(*book_id, details.authors.clone());
-            for author in details.authors.iter() {
-                self.author_to_books
-                    .entry(author.clone())
-                    .or_default()
-                    .insert(*book_id);
-            }
-            self.popularity_score.entry(*book_id).or_default();
+            self.update_book_details(*book_id, details);
         }
 
         for (user_id, history_records) in user_to_history.iter() {
@@ -51,14 +156,22 @@ impl CoefficientsStorage {
                 .last_processed_timestamp_per_user
                 .get(user_id)
                 .cloned()
-                .unwrap_or(-1);
+                .unwrap_or(UnixSeconds(-1));
 
-            for book_id in history_records
+            let new_history_records = history_records
                 .iter()
                 .filter(|r| r.unreserved_at > last_processed_timestamp_for_user)
-                .map(|r| r.book_id)
-                .unique()
-            {
+                .collect_vec();
+
+            for record in new_history_records.iter() {
+                let last_unreserved_at = self
+                    .last_unreserved_at_per_book
+                    .entry(record.book_id)
+                    .or_insert(record.unreserved_at);
+                *last_unreserved_at = (*last_unreserved_at).max(record.unreserved_at);
+            }
+
+            for book_id in new_history_records.iter().map(|r| r.book_id).unique() {
                 *self.popularity_score.entry(book_id).or_default() += 1;
                 if let Some(details) = book_details.get(&book_id) {
                     for author in details.authors.iter() {
@@ -75,17 +188,106 @@ impl CoefficientsStorage {
                     .or_default() += 1;
             }
 
-            self.last_processed_timestamp_per_user.insert(
-                *user_id,
-                history_records
-                    .iter()
-                    .map(|r| r.unreserved_at)
-                    .max()
-                    .unwrap_or_default(),
-            );
+            if let Some(last_unreserved_at) = history_records.iter().map(|r| r.unreserved_at).max()
+            {
+                self.last_processed_timestamp_per_user
+                    .insert(*user_id, last_unreserved_at);
+            }
         }
 
-        // Sort books per author by popularity
+        self.sort_books_by_popularity();
+
+        Ok(())
+    }
+
+    pub fn set_author_match_config(&mut self, config: AuthorMatchConfig) {
+        self.author_match_config = config;
+    }
+
+    /// Returns books with popularity score of at least `min_score`, the most popular first
+    pub fn books_above_popularity(&self, min_score: i64) -> Vec<BookId> {
+        self.books_sorted_by_popularity
+            .iter()
+            .filter(|book_id| {
+                self.popularity_score
+                    .get(book_id)
+                    .cloned()
+                    .unwrap_or_default()
+                    >= min_score
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// All known books, ordered by id
+    pub fn all_books(&self) -> &BTreeSet<BookId> {
+        &self.books_by_id
+    }
+
+    /// Number of users that reserved each known book
+    pub fn popularity_scores(&self) -> &HashMap<BookId, i64> {
+        &self.popularity_score
+    }
+
+    /// Replaces details of a single book (e.g. after its authors were edited) without processing any history
+    pub fn refresh_book(&mut self, book_id: BookId, details: &BookDetails) {
+        self.update_book_details(book_id, details);
+        self.sort_books_by_popularity();
+    }
+
+    fn update_book_details(&mut self, book_id: BookId, details: &BookDetails) {
+        // Remove the book from its previous authors, as they could have changed
+        if let Some(previous_authors) = self
+            .book_id_to_authors
+            .insert(book_id, details.authors.clone())
+        {
+            for author in previous_authors.iter() {
+                if let Some(books) = self.author_to_books.get_mut(author) {
+                    books.remove(&book_id);
+                    if books.is_empty() {
+                        self.author_to_books.remove(author);
+                    }
+                }
+            }
+        }
+        let tags: Vec<String> = details
+            .tags
+            .iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .unique()
+            .collect();
+        for tag in tags.iter() {
+            self.tag_to_books
+                .entry(tag.clone())
+                .or_default()
+                .insert(book_id);
+        }
+        // Remove the book from its previous tags that are no longer present
+        if let Some(previous_tags) = self.book_id_to_tags.insert(book_id, tags) {
+            for tag in previous_tags.iter() {
+                if self.book_id_to_tags[&book_id].contains(tag) {
+                    continue;
+                }
+                if let Some(books) = self.tag_to_books.get_mut(tag) {
+                    books.remove(&book_id);
+                    if books.is_empty() {
+                        self.tag_to_books.remove(tag);
+                    }
+                }
+            }
+        }
+        for author in details.authors.iter() {
+            self.author_to_books
+                .entry(author.clone())
+                .or_default()
+                .insert(book_id);
+        }
+        self.popularity_score.entry(book_id).or_default();
+        self.books_by_id.insert(book_id);
+    }
+
+    fn sort_books_by_popularity(&mut self) {
+        // Sort books per author by popularity, ties are broken by book id
         self.author_to_books_sorted_by_popularity = self
             .author_to_books
             .iter()
@@ -95,10 +297,13 @@ impl CoefficientsStorage {
                     books
                         .iter()
                         .sorted_by_key(|book_id| {
-                            self.popularity_score
-                                .get(book_id)
-                                .cloned()
-                                .unwrap_or_default()
+                            (
+                                self.popularity_score
+                                    .get(book_id)
+                                    .cloned()
+                                    .unwrap_or_default(),
+                                **book_id,
+                            )
                         })
                         .cloned()
                         .collect(),
@@ -106,25 +311,239 @@ impl CoefficientsStorage {
             })
             .collect();
 
-        // Sort books by popularity
+        // Sort books by popularity, ties are broken by the most recent reservation and then by book id
         self.books_sorted_by_popularity = self
             .popularity_score
             .iter()
-            .sorted_by_key(|(_, score)| -**score)
+            .sorted_by_key(|(book_id, score)| {
+                (
+                    -**score,
+                    Reverse(self.last_unreserved_at_per_book.get(book_id).cloned()),
+                    **book_id,
+                )
+            })
             .map(|(book_id, _)| *book_id)
             .collect_vec();
+    }
 
-        Ok(())
+    /// Returns all known books carrying given tag (compared case insensitively)
+    pub fn books_with_tag(&self, tag: &str) -> HashSet<BookId> {
+        self.tag_to_books
+            .get(&tag.trim().to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns up to `limit` most popular books that share no authors and no tags with the given books
+    pub fn books_without_overlap(&self, books: &HashSet<BookId>, limit: usize) -> Vec<BookId> {
+        let authors: HashSet<&String> = books
+            .iter()
+            .filter_map(|book_id| self.book_id_to_authors.get(book_id))
+            .flatten()
+            .collect();
+        let tags: HashSet<&String> = books
+            .iter()
+            .filter_map(|book_id| self.book_id_to_tags.get(book_id))
+            .flatten()
+            .collect();
+        self.books_sorted_by_popularity
+            .iter()
+            .filter(|book_id| !books.contains(book_id))
+            .filter(|book_id| {
+                self.book_id_to_authors
+                    .get(book_id)
+                    .into_iter()
+                    .flatten()
+                    .all(|author| !authors.contains(author))
+                    && self
+                        .book_id_to_tags
+                        .get(book_id)
+                        .into_iter()
+                        .flatten()
+                        .all(|tag| !tags.contains(tag))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns books with the highest sum of weights of matching tags, equally scored books in order of popularity,
+    /// used for users without any reservations yet
+    pub fn preview_recommendations(&self, favourite_tags: &[TagWeight]) -> Vec<BookId> {
+        let tag_to_weight = tag_to_weight(favourite_tags);
+        self.books_sorted_by_popularity
+            .iter()
+            .map(|book_id| (*book_id, self.tag_score(*book_id, &tag_to_weight)))
+            .filter(|(_, score)| *score > 0.0)
+            // Stable sort keeps popularity order of books with the same score
+            .sorted_by(|(_, score_a), (_, score_b)| score_b.total_cmp(score_a))
+            .map(|(book_id, _)| book_id)
+            .take(NO_OF_RECOMMENDATIONS)
+            .collect()
+    }
+
+    /// Sum of weights of the book tags present in given weights
+    fn tag_score(&self, book_id: BookId, tag_to_weight: &HashMap<String, f32>) -> f32 {
+        self.book_id_to_tags
+            .get(&book_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|tag| tag_to_weight.get(tag))
+            .sum()
+    }
+
+    /// Calculates recommendations for a user that reserved given books (currently or in the past)
+    /// and has given favourite tags
+    pub fn calculate_recommendations(
+        &self,
+        all_books_reserved_by_user: &HashSet<BookId>,
+        favourite_tags: &[TagWeight],
+    ) -> Recommendations {
+        let all_user_authors_with_number_of_books_reserved: HashMap<&String, i64> =
+            all_books_reserved_by_user
+                .iter()
+                .filter_map(|book_id| self.book_id_to_authors.get(book_id))
+                .fold(HashMap::default(), |mut map, authors| {
+                    for author in authors.iter() {
+                        *map.entry(author).or_default() += 1;
+                    }
+                    map
+                });
+
+        // Authors the user read more books from contribute more books, up to the configured limit
+        let author_match: Vec<BookId> = all_user_authors_with_number_of_books_reserved
+            .iter()
+            .sorted_by_key(|(author, score)| (-**score, **author))
+            .flat_map(|(author, score)| {
+                self.author_to_books_sorted_by_popularity
+                    .get(*author)
+                    .into_iter()
+                    .flatten()
+                    .filter(|book_id| !all_books_reserved_by_user.contains(book_id))
+                    .take((*score as usize).min(self.author_match_config.max_books_per_author))
+            })
+            .unique()
+            .take(NO_OF_RECOMMENDATIONS)
+            .cloned()
+            .collect();
+
+        // Tak books of 4 authors with best score
+        let new_author_match: Vec<BookId> = self
+            .author_to_books_sorted_by_popularity
+            .keys()
+            .filter(|a| !all_user_authors_with_number_of_books_reserved.contains_key(a))
+            .map(|new_author| {
+                (
+                    new_author,
+                    all_user_authors_with_number_of_books_reserved
+                        .keys()
+                        .map(|user_author| {
+                            self.author_match_score
+                                .get(&(new_author.clone(), (*user_author).clone()))
+                                .cloned()
+                                .unwrap_or_default()
+                        })
+                        .sum::<i64>(),
+                )
+            })
+            .sorted_by_key(|(author, score)| (-*score, *author))
+            .filter_map(|(author, _)| {
+                self.author_to_books_sorted_by_popularity
+                    .get(author)
+                    .and_then(|author_books| author_books.first().cloned())
+            })
+            .take(NO_OF_RECOMMENDATIONS)
+            .collect();
+
+        // Take books with the highest sum of weights of matching user tags
+        let tag_to_weight = tag_to_weight(favourite_tags);
+        let tag_match: Vec<BookId> = self
+            .book_id_to_tags
+            .keys()
+            .filter(|book_id| !all_books_reserved_by_user.contains(book_id))
+            .map(|book_id| (*book_id, self.tag_score(*book_id, &tag_to_weight)))
+            .filter(|(_, score)| *score > 0.0)
+            .sorted_by(|(book_a, score_a), (book_b, score_b)| {
+                score_b.total_cmp(score_a).then(book_a.cmp(book_b))
+            })
+            .map(|(book_id, _)| book_id)
+            .take(NO_OF_RECOMMENDATIONS)
+            .collect();
+
+        let new_releases: Vec<BookId> = self
+            .books_by_id
+            .iter()
+            .rev()
+            .filter(|book_id| !all_books_reserved_by_user.contains(book_id))
+            .take(NO_OF_RECOMMENDATIONS)
+            .cloned()
+            .collect();
+
+        Recommendations {
+            most_popular: self
+                .books_sorted_by_popularity
+                .iter()
+                .filter(|book_id| !all_books_reserved_by_user.contains(book_id))
+                .take(NO_OF_RECOMMENDATIONS)
+                .cloned()
+                .collect(),
+            author_match,
+            new_author_match,
+            tag_match,
+            new_releases,
+        }
     }
 }
 
 impl RecommendationsEngine {
+    /// Sets number of most popular books recommended to users without calculated recommendations (e.g. anonymous),
+    /// independent of the number of books in per user recommendations
+    pub fn set_fallback_most_popular_size(&mut self, size: usize) {
+        self.fallback_most_popular_size = size;
+    }
+
+    /// Sets maximal number of users with stored recommendations (unlimited if None), when exceeded recommendations
+    /// of the least recently requested users are removed and they get default recommendations until recalculated
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        self.evict_least_recently_requested();
+    }
+
+    /// Removes recommendations of the least recently requested users until there are at most `capacity` of them
+    fn evict_least_recently_requested(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        let no_of_evicted = self.user_to_recommendations.len().saturating_sub(capacity);
+        let evicted_users = self
+            .user_to_last_request
+            .iter()
+            .map(|(user_id, last_request)| (last_request.load(Ordering::Relaxed), *user_id))
+            .sorted()
+            .take(no_of_evicted)
+            .map(|(_, user_id)| user_id)
+            .collect_vec();
+        for user_id in evicted_users {
+            tracing::info!("Evicting recommendations of user {}", user_id);
+            self.user_to_recommendations.remove(&user_id);
+            self.user_to_last_request.remove(&user_id);
+        }
+    }
+
     pub fn update_recommendations_for_users(
         &mut self,
         coefficients_storage: &CoefficientsStorage,
         user_to_reservations: &HashMap<UserId, Vec<BookId>>,
         user_to_history: &HashMap<UserId, Vec<ReservationHistoryRecord>>,
+        user_to_favourite_tags: &HashMap<UserId, Vec<TagWeight>>,
     ) -> anyhow::Result<()> {
+        for (user_id, history_records) in user_to_history.iter() {
+            self.user_to_history_books
+                .entry(*user_id)
+                .or_default()
+                .extend(history_records.iter().map(|r| r.book_id));
+        }
+
         // Generate recommendations for each user
         user_to_reservations
             .iter()
@@ -133,111 +552,645 @@ impl RecommendationsEngine {
                     .iter()
                     .cloned()
                     .chain(
-                        user_to_history
+                        self.user_to_history_books
                             .get(user_id)
-                            .iter()
-                            .flat_map(|history_records| history_records.iter().map(|r| r.book_id)),
+                            .into_iter()
+                            .flatten()
+                            .cloned(),
                     )
                     .collect();
 
-                let all_user_authors_with_number_of_books_reserved: HashMap<&String, i64> =
-                    all_books_reserved_by_user
-                        .iter()
-                        .filter_map(|book_id| coefficients_storage.book_id_to_authors.get(book_id))
-                        .fold(HashMap::default(), |mut map, authors| {
-                            for author in authors.iter() {
-                                *map.entry(author).or_default() += 1;
-                            }
-                            map
-                        });
-
-                let author_match: Vec<BookId> = all_user_authors_with_number_of_books_reserved
-                    .iter()
-                    .sorted_by_key(|(_, score)| -**score)
-                    .filter_map(|(author, _)| {
-                        coefficients_storage
-                            .author_to_books_sorted_by_popularity
-                            .get(*author)
-                            .and_then(|author_books| {
-                                author_books
-                                    .iter()
-                                    .find(|book_id| !all_books_reserved_by_user.contains(book_id))
-                            })
-                    })
-                    .take(NO_OF_RECOMMENDATIONS)
-                    .cloned()
-                    .collect();
-
-                // Tak books of 4 authors with best score
-                let new_author_match: Vec<BookId> = coefficients_storage
-                    .author_to_books_sorted_by_popularity
-                    .keys()
-                    .filter(|a| !all_user_authors_with_number_of_books_reserved.contains_key(a))
-                    .map(|new_author| {
-                        (
-                            new_author,
-                            all_user_authors_with_number_of_books_reserved
-                                .keys()
-                                .map(|user_author| {
-                                    coefficients_storage
-                                        .author_match_score
-                                        .get(&(new_author.clone(), (*user_author).clone()))
-                                        .cloned()
-                                        .unwrap_or_default()
-                                })
-                                .sum::<i64>(),
-                        )
-                    })
-                    .sorted_by_key(|(_, score)| -*score)
-                    .filter_map(|(author, _)| {
-                        coefficients_storage
-                            .author_to_books_sorted_by_popularity
-                            .get(author)
-                            .and_then(|author_books| author_books.first().cloned())
-                    })
-                    .take(NO_OF_RECOMMENDATIONS)
-                    .collect();
-
-                let recommendations = Recommendations {
-                    most_popular: coefficients_storage
-                        .books_sorted_by_popularity
-                        .iter()
-                        .filter(|book_id| !all_books_reserved_by_user.contains(book_id))
-                        .take(NO_OF_RECOMMENDATIONS)
-                        .cloned()
-                        .collect(),
-                    author_match,
-                    new_author_match,
-                };
+                let recommendations = coefficients_storage.calculate_recommendations(
+                    &all_books_reserved_by_user,
+                    user_to_favourite_tags
+                        .get(user_id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                );
 
                 tracing::info!(
                     "Adding recommendations for user {} : {:?}",
                     user_id,
                     recommendations
                 );
+                self.recommended_books
+                    .extend(recommendations.flattened(None));
 
+                let versioned_recommendations = match self.user_to_recommendations.get(user_id) {
+                    Some(current) => current.updated(recommendations),
+                    None => {
+                        let tick = self.request_clock.fetch_add(1, Ordering::Relaxed) + 1;
+                        self.user_to_last_request
+                            .insert(*user_id, AtomicU64::new(tick));
+                        VersionedRecommendations::new(0, recommendations)
+                    }
+                };
                 self.user_to_recommendations
-                    .insert(*user_id, recommendations);
+                    .insert(*user_id, versioned_recommendations);
             });
 
-        self.default_recommendations = Recommendations {
+        self.default_recommendations = self.default_recommendations.updated(Recommendations {
             most_popular: coefficients_storage
                 .books_sorted_by_popularity
                 .iter()
-                .take(NO_OF_RECOMMENDATIONS)
+                .take(self.fallback_most_popular_size)
                 .cloned()
                 .collect(),
             author_match: vec![],
             new_author_match: vec![],
-        };
+            tag_match: vec![],
+            new_releases: coefficients_storage
+                .books_by_id
+                .iter()
+                .rev()
+                .take(NO_OF_RECOMMENDATIONS)
+                .cloned()
+                .collect(),
+        });
+        self.recommended_books
+            .extend(self.default_recommendations.recommendations.flattened(None));
+        self.evict_least_recently_requested();
 
         Ok(())
     }
 
+    /// Returns all books from the accumulated history of the user
+    pub fn history_books_of_user(&self, user_id: UserId) -> HashSet<BookId> {
+        self.user_to_history_books
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns number of users with calculated recommendations
+    pub fn number_of_users(&self) -> usize {
+        self.user_to_recommendations.len()
+    }
+
+    /// Returns known books (ordered by id) that were never recommended to any user nor in default recommendations
+    pub fn unrecommended_books(&self, coefficients_storage: &CoefficientsStorage) -> Vec<BookId> {
+        coefficients_storage
+            .all_books()
+            .iter()
+            .filter(|book_id| !self.recommended_books.contains(book_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns aggregate metrics of recommendations of all users with calculated recommendations
+    pub fn coverage(&self) -> RecommendationsCoverage {
+        let users = self.user_to_recommendations.len();
+        if users == 0 {
+            return RecommendationsCoverage::default();
+        }
+        let average = |size: fn(&Recommendations) -> usize| {
+            self.user_to_recommendations
+                .values()
+                .map(|versioned| size(&versioned.recommendations))
+                .sum::<usize>() as f64
+                / users as f64
+        };
+        RecommendationsCoverage {
+            users_with_recommendations: users as i64,
+            avg_author_match_size: average(|recommendations| recommendations.author_match.len()),
+            avg_popular_size: average(|recommendations| recommendations.most_popular.len()),
+            users_on_default_only: self
+                .user_to_recommendations
+                .values()
+                .filter(|versioned| {
+                    let recommendations = &versioned.recommendations;
+                    recommendations.author_match.is_empty()
+                        && recommendations.new_author_match.is_empty()
+                        && recommendations.tag_match.is_empty()
+                })
+                .count() as i64,
+        }
+    }
+
     pub fn get_recommendations_for_user(&self, user_id: UserId) -> Recommendations {
+        self.get_versioned_recommendations_for_user(user_id)
+            .recommendations
+    }
+
+    /// Returns recommendations of the user (default ones if not stored) and records the time of the request
+    pub fn get_versioned_recommendations_for_user(
+        &self,
+        user_id: UserId,
+    ) -> VersionedRecommendations {
+        if let Some(last_request) = self.user_to_last_request.get(&user_id) {
+            let tick = self.request_clock.fetch_add(1, Ordering::Relaxed) + 1;
+            last_request.store(tick, Ordering::Relaxed);
+        }
         self.user_to_recommendations
             .get(&user_id)
             .cloned()
             .unwrap_or_else(|| self.default_recommendations.clone())
     }
 }
+
+#[cfg(test)]
+mod recommendations_tests {
+    use super::*;
+    use crate::recommendations_updater::RecommendationsProvider;
+
+    fn book(title: &str, authors: &[&str]) -> BookDetails {
+        BookDetails {
+            title: title.to_string(),
+            authors: authors.iter().map(|a| a.to_string()).collect(),
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        }
+    }
+
+    #[test]
+    /// Tests if version of user recommendations is bumped only when recommendations change
+    fn test_recommendations_version_changes_only_on_update() {
+        let mut storage = CoefficientsStorage::default();
+        let mut engine = RecommendationsEngine::default();
+        let user_id: UserId = 1;
+
+        let book_details = HashMap::from([(1, book("b1", &["a1"])), (2, book("b2", &["a1"]))]);
+        let user_to_history = HashMap::from([(
+            user_id,
+            vec![ReservationHistoryRecord {
+                book_id: 1,
+                reserved_at: None,
+                unreserved_at: UnixSeconds(10),
+            }],
+        )]);
+        let user_to_reservations = HashMap::from([(user_id, vec![])]);
+
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &user_to_reservations,
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+        let first = engine.get_versioned_recommendations_for_user(user_id);
+
+        // Same input data, so recommendations stay the same
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &user_to_reservations,
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+        let second = engine.get_versioned_recommendations_for_user(user_id);
+        assert_eq!(first.version, second.version);
+        assert_eq!(first.etag, second.etag);
+
+        // User reserved recommended book, so recommendations change
+        let user_to_reservations = HashMap::from([(user_id, vec![2])]);
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &user_to_reservations,
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+        let third = engine.get_versioned_recommendations_for_user(user_id);
+        assert_eq!(third.version, first.version + 1);
+        assert_ne!(third.etag, first.etag);
+    }
+
+    #[test]
+    /// Tests if recommendations calculated for a simulated user match authors from its history
+    /// 1. Seeds storage with books of two authors and history of other users
+    /// 2. Calculates recommendations for user that read one book of the first author
+    /// 3. Checks that the other book of the first author is in author match
+    fn test_calculate_recommendations_author_match() {
+        let mut storage = CoefficientsStorage::default();
+        let book_details = HashMap::from([
+            (1, book("b1", &["a1"])),
+            (2, book("b2", &["a1"])),
+            (3, book("b3", &["a2"])),
+        ]);
+        let user_to_history = HashMap::from([(
+            1,
+            vec![
+                ReservationHistoryRecord {
+                    book_id: 1,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                },
+                ReservationHistoryRecord {
+                    book_id: 3,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                },
+            ],
+        )]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+
+        let recommendations = storage.calculate_recommendations(&HashSet::from([1]), &[]);
+        assert_eq!(recommendations.author_match, vec![2]);
+        assert_eq!(recommendations.new_author_match, vec![3]);
+        assert!(!recommendations.most_popular.contains(&1));
+    }
+
+    #[test]
+    /// Tests if newly added book is recommended in new releases to user that has not reserved it
+    /// 1. Seeds storage with two books, user reserved both of them
+    /// 2. Adds a new book in the next update
+    /// 3. Checks that only the new book is in new releases of the user
+    fn test_calculate_recommendations_new_releases() {
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(
+                &HashMap::new(),
+                &HashMap::from([(1, book("b1", &["a1"])), (2, book("b2", &["a2"]))]),
+            )
+            .unwrap();
+        let reserved_books = HashSet::from([1, 2]);
+        assert!(storage
+            .calculate_recommendations(&reserved_books, &[])
+            .new_releases
+            .is_empty());
+
+        storage
+            .update_storage(&HashMap::new(), &HashMap::from([(3, book("b3", &["a1"]))]))
+            .unwrap();
+
+        let recommendations = storage.calculate_recommendations(&reserved_books, &[]);
+        assert_eq!(recommendations.new_releases, vec![3]);
+        assert_eq!(
+            storage
+                .calculate_recommendations(&HashSet::new(), &[])
+                .new_releases,
+            vec![3, 2, 1]
+        );
+    }
+
+    #[test]
+    /// Tests if book with the higher weighted tag is recommended first in tag match
+    /// 1. Seeds storage with books tagged with two different tags
+    /// 2. Calculates recommendations for user with one tag weighted higher than the other
+    /// 3. Checks that books are ordered by weight of the matching tag and not reserved books are skipped
+    fn test_calculate_recommendations_weighted_tag_match() {
+        let mut storage = CoefficientsStorage::default();
+        let tagged_book = |title: &str, tags: &[&str]| BookDetails {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..book(title, &["a1"])
+        };
+        let book_details = HashMap::from([
+            (1, tagged_book("b1", &["fantasy"])),
+            (2, tagged_book("b2", &["SciFi"])),
+            (3, tagged_book("b3", &["scifi"])),
+            (4, tagged_book("b4", &["romance"])),
+        ]);
+        storage
+            .update_storage(&HashMap::new(), &book_details)
+            .unwrap();
+
+        let favourite_tags = vec![
+            TagWeight {
+                tag: "fantasy".to_string(),
+                weight: 0.5,
+            },
+            TagWeight {
+                tag: "scifi".to_string(),
+                weight: 2.0,
+            },
+        ];
+        let recommendations =
+            storage.calculate_recommendations(&HashSet::from([3]), &favourite_tags);
+        assert_eq!(recommendations.tag_match, vec![2, 1]);
+    }
+
+    #[test]
+    /// Tests if of two books with equal popularity the more recently reserved one ranks first
+    /// 1. Two users reserved one book each, the second book later
+    /// 2. Checks that the second book is the first in most popular
+    fn test_popularity_ties_broken_by_most_recent_reservation() {
+        let mut storage = CoefficientsStorage::default();
+        let book_details = HashMap::from([(1, book("b1", &["a1"])), (2, book("b2", &["a2"]))]);
+        let user_to_history = HashMap::from([
+            (
+                10,
+                vec![ReservationHistoryRecord {
+                    book_id: 1,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(100),
+                }],
+            ),
+            (
+                11,
+                vec![ReservationHistoryRecord {
+                    book_id: 2,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(200),
+                }],
+            ),
+        ]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+
+        assert_eq!(storage.books_sorted_by_popularity, vec![2, 1]);
+    }
+
+    #[test]
+    /// Tests if books sharing an author or a tag with user history are not returned for serendipity
+    /// 1. Seeds storage with books, user history covers one author and one tag
+    /// 2. Checks that only books of other authors and without the user tags are returned, most popular first
+    fn test_serendipity_excludes_overlapping_books() {
+        let mut storage = CoefficientsStorage::default();
+        let mut engine = RecommendationsEngine::default();
+        let tagged_book = |title: &str, authors: &[&str], tags: &[&str]| BookDetails {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..book(title, authors)
+        };
+        let book_details = HashMap::from([
+            (1, tagged_book("b1", &["a1"], &["fantasy"])),
+            (2, tagged_book("b2", &["a1"], &["romance"])),
+            (3, tagged_book("b3", &["a2", "a3"], &["Fantasy"])),
+            (4, tagged_book("b4", &["a4"], &["scifi"])),
+            (5, tagged_book("b5", &["a5"], &[])),
+        ]);
+        let record = |book_id: BookId| ReservationHistoryRecord {
+            book_id,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(10),
+        };
+        let user_to_history =
+            HashMap::from([(1, vec![record(1)]), (2, vec![record(5)]), (3, vec![])]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &user_to_history,
+                &Default::default(),
+            )
+            .unwrap();
+
+        let provider = RecommendationsProvider::from_parts(storage, engine);
+        assert_eq!(provider.get_serendipity_for_user(1, 5), vec![5, 4]);
+        assert_eq!(provider.get_serendipity_for_user(1, 1), vec![5]);
+    }
+
+    #[test]
+    /// Tests if author the user read more books from contributes more books to author match
+    /// 1. Seeds storage with 7 books of author A and 3 books of author B
+    /// 2. Calculates recommendations for user that read 3 books of A and 1 of B with the default config
+    ///    - expect one book of each author
+    /// 3. Allows up to 3 books per author - expect 3 books of A and 1 book of B
+    fn test_calculate_recommendations_weighted_author_match() {
+        let mut storage = CoefficientsStorage::default();
+        let book_details: HashMap<BookId, BookDetails> = (1..=7)
+            .map(|book_id| (book_id, book("a", &["A"])))
+            .chain((8..=10).map(|book_id| (book_id, book("b", &["B"]))))
+            .collect();
+        storage
+            .update_storage(&HashMap::new(), &book_details)
+            .unwrap();
+        let read_books = HashSet::from([1, 2, 3, 8]);
+        let books_of = |recommendations: &Recommendations, author: &str| {
+            recommendations
+                .author_match
+                .iter()
+                .filter(|book_id| book_details[book_id].authors == vec![author.to_string()])
+                .count()
+        };
+
+        let recommendations = storage.calculate_recommendations(&read_books, &[]);
+        assert_eq!(books_of(&recommendations, "A"), 1);
+        assert_eq!(books_of(&recommendations, "B"), 1);
+
+        storage.set_author_match_config(AuthorMatchConfig {
+            max_books_per_author: 3,
+        });
+        let recommendations = storage.calculate_recommendations(&read_books, &[]);
+        assert_eq!(books_of(&recommendations, "A"), 3);
+        assert_eq!(books_of(&recommendations, "B"), 1);
+        assert_eq!(recommendations.author_match.len(), 4);
+        assert!(recommendations
+            .author_match
+            .iter()
+            .all(|book_id| !read_books.contains(book_id)));
+    }
+
+    #[test]
+    /// Tests if books with tag follow tag changes of the refreshed book
+    /// 1. Seeds storage with two books tagged with scifi (in different case) and one without tags
+    /// 2. Refreshes one of the books with a different tag and checks that it is no longer returned for scifi
+    fn test_books_with_tag() {
+        let mut storage = CoefficientsStorage::default();
+        let tagged_book = |tags: &[&str]| BookDetails {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..book("title", &["a"])
+        };
+        let book_details = HashMap::from([
+            (1, tagged_book(&["scifi", "fantasy"])),
+            (2, tagged_book(&["SciFi"])),
+            (3, tagged_book(&[])),
+        ]);
+        storage
+            .update_storage(&Default::default(), &book_details)
+            .unwrap();
+        assert_eq!(storage.books_with_tag("scifi"), HashSet::from([1, 2]));
+        assert_eq!(storage.books_with_tag(" Fantasy"), HashSet::from([1]));
+
+        storage.refresh_book(2, &tagged_book(&["romance"]));
+        assert_eq!(storage.books_with_tag("scifi"), HashSet::from([1]));
+        assert_eq!(storage.books_with_tag("romance"), HashSet::from([2]));
+        assert!(storage.books_with_tag("unknown").is_empty());
+    }
+
+    #[test]
+    /// Tests if coverage metrics are computed from recommendations stored in the engine
+    /// 1. Checks that empty engine has zero metrics
+    /// 2. Stores recommendations of three users, one of them without personalized recommendations
+    /// 3. Checks number of users, average sizes and number of users on default recommendations only
+    fn test_coverage() {
+        let mut engine = RecommendationsEngine::default();
+        assert_eq!(engine.coverage(), RecommendationsCoverage::default());
+
+        let recommendations = [
+            Recommendations {
+                most_popular: vec![1, 2, 3, 4],
+                author_match: vec![5, 6],
+                ..Default::default()
+            },
+            Recommendations {
+                most_popular: vec![1, 2],
+                author_match: vec![5, 6, 7, 8],
+                ..Default::default()
+            },
+            Recommendations {
+                most_popular: vec![1, 2, 3],
+                new_releases: vec![9],
+                ..Default::default()
+            },
+        ];
+        for (user_id, recommendations) in recommendations.into_iter().enumerate() {
+            engine.user_to_recommendations.insert(
+                user_id as UserId,
+                VersionedRecommendations::new(0, recommendations),
+            );
+        }
+
+        assert_eq!(
+            engine.coverage(),
+            RecommendationsCoverage {
+                users_with_recommendations: 3,
+                avg_author_match_size: 2.0,
+                avg_popular_size: 3.0,
+                users_on_default_only: 1,
+            }
+        );
+    }
+
+    #[test]
+    /// Tests if recommendations of the least recently requested user are evicted above capacity
+    /// 1. Stores recommendations of users 1 and 2 in engine with capacity 2
+    /// 2. Requests recommendations of user 1, so user 2 is the least recently requested
+    /// 3. Stores recommendations of user 3 - expect user 2 is evicted and gets default recommendations
+    /// 4. Checks that users 1 and 3 keep their personalized recommendations
+    fn test_least_recently_requested_user_evicted() {
+        let mut storage = CoefficientsStorage::default();
+        let mut engine = RecommendationsEngine::default();
+        engine.set_capacity(Some(2));
+
+        let book_details = HashMap::from([(1, book("b1", &["a1"])), (2, book("b2", &["a1"]))]);
+        let user_to_history = HashMap::from([(
+            10,
+            vec![ReservationHistoryRecord {
+                book_id: 1,
+                reserved_at: None,
+                unreserved_at: UnixSeconds(10),
+            }],
+        )]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+
+        let update_user = |engine: &mut RecommendationsEngine, user_id: UserId| {
+            engine
+                .update_recommendations_for_users(
+                    &storage,
+                    &HashMap::from([(user_id, vec![1])]),
+                    &Default::default(),
+                    &Default::default(),
+                )
+                .unwrap();
+        };
+        update_user(&mut engine, 1);
+        update_user(&mut engine, 2);
+        let personalized = engine.get_recommendations_for_user(1);
+        assert_eq!(personalized.author_match, vec![2]);
+
+        update_user(&mut engine, 3);
+        assert_eq!(engine.number_of_users(), 2);
+        assert_eq!(
+            engine.get_recommendations_for_user(2),
+            engine.default_recommendations.recommendations
+        );
+        assert_eq!(engine.get_recommendations_for_user(1), personalized);
+        assert_eq!(engine.get_recommendations_for_user(3), personalized);
+    }
+
+    #[test]
+    /// Tests if books that never surface in recommendations are reported
+    /// 1. Seeds storage with 12 books, books 2-8 are reserved by two other users, book 1 by one
+    ///    of them, so it is neither among the most popular nor among the least popular
+    /// 2. Calculates recommendations of a user without history
+    /// 3. Checks that book 1 is reported and every reported book is in no recommendations
+    fn test_unrecommended_books() {
+        let mut storage = CoefficientsStorage::default();
+        let mut engine = RecommendationsEngine::default();
+
+        let book_details: HashMap<BookId, BookDetails> = (1..=12)
+            .map(|book_id| (book_id, book(&format!("b{}", book_id), &["a1"])))
+            .collect();
+        let history: Vec<ReservationHistoryRecord> = (2..=8)
+            .map(|book_id| ReservationHistoryRecord {
+                book_id,
+                reserved_at: None,
+                unreserved_at: UnixSeconds(10),
+            })
+            .collect();
+        let mut history_with_book_1 = history.clone();
+        history_with_book_1.push(ReservationHistoryRecord {
+            book_id: 1,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(10),
+        });
+        let user_to_history = HashMap::from([(100, history_with_book_1), (101, history)]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(1, vec![])]),
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap();
+
+        let unrecommended = engine.unrecommended_books(&storage);
+        assert!(unrecommended.contains(&1));
+        let recommended: HashSet<BookId> = engine
+            .get_recommendations_for_user(1)
+            .flattened(None)
+            .into_iter()
+            .chain(
+                engine
+                    .default_recommendations
+                    .recommendations
+                    .flattened(None),
+            )
+            .collect();
+        assert!(unrecommended
+            .iter()
+            .all(|book_id| !recommended.contains(book_id)));
+        assert_eq!(unrecommended.len() + recommended.len(), 12);
+    }
+
+    #[test]
+    /// Tests if only books with popularity meeting the threshold are returned, the most popular first
+    fn test_books_above_popularity() {
+        let mut storage = CoefficientsStorage::default();
+        let book_details: HashMap<BookId, BookDetails> = (1..=4)
+            .map(|book_id| (book_id, book(&format!("b{}", book_id), &["a1"])))
+            .collect();
+        let history = |book_ids: &[BookId]| {
+            book_ids
+                .iter()
+                .map(|book_id| ReservationHistoryRecord {
+                    book_id: *book_id,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(10),
+                })
+                .collect::<Vec<_>>()
+        };
+        let user_to_history = HashMap::from([
+            (1, history(&[1, 2, 3])),
+            (2, history(&[2, 3])),
+            (3, history(&[3])),
+        ]);
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+
+        assert_eq!(storage.books_above_popularity(2), vec![3, 2]);
+        assert_eq!(storage.books_above_popularity(3), vec![3]);
+        assert_eq!(storage.books_above_popularity(0), vec![3, 2, 1, 4]);
+        assert!(storage.books_above_popularity(4).is_empty());
+    }
+}
@@ -1,39 +1,247 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use futures_util::stream::StreamExt;
 use itertools::Itertools;
 use opentelemetry_sdk::util::tokio_interval_stream;
 use parking_lot::{Mutex, RwLock};
+use tokio::sync::{broadcast, watch};
 
 use bookservice_repository::api::BookDetails;
 use bookservice_repository::client::BookServiceRepositoryClient;
-use bookservice_reservations::api::{BookId, ReservationHistoryRecord, UserId};
+use bookservice_reservations::api::{ReservationHistoryRecord, TagWeight, UnixSeconds};
 use bookservice_reservations::client::BookServiceReservationsClient;
+use bookservice_types::{BookId, UserId};
 
-use crate::api::Recommendations;
-use crate::recommendations::{CoefficientsStorage, RecommendationsEngine};
+use crate::api::{
+    DownstreamStatus, RecommendationCategories, RecommendationCategory, Recommendations,
+    RecommendationsCoverage, RecommendationsDiff, RecommendationsStatus, SimulatedUser,
+    UpdateProgress,
+};
+pub use crate::recommendations::{
+    AuthorMatchConfig, DEFAULT_AUTHOR_MATCH_BOOKS_PER_AUTHOR, DEFAULT_FALLBACK_MOST_POPULAR_SIZE,
+};
+use crate::recommendations::{
+    CoefficientsStorage, RecommendationsEngine, VersionedRecommendations,
+};
 
 const INTERVAL_SECONDS: u64 = 10;
+/// Recommendations are recalculated every interval, so cached responses should expire before that
+pub const DEFAULT_RECOMMENDATIONS_MAX_AGE_SECONDS: u64 = INTERVAL_SECONDS / 2;
 const UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL: i32 = 200;
 const USERS_SPLIT: i32 = 10;
+/// Number of failed ticks in a row after which the updater gives up
+pub const DEFAULT_MAX_CONSECUTIVE_TICK_FAILURES: u32 = 30;
+/// Number of book details requests sent to the repository at the same time
+pub const DEFAULT_MAX_CONCURRENT_BOOK_REQUESTS: usize = 8;
+/// Number of progress events kept for subscribers that did not receive them yet
+const PROGRESS_CHANNEL_CAPACITY: usize = 64;
 
+/// Configuration of `Cache-Control` header sent with user recommendations
+pub struct RecommendationsCacheControl {
+    pub max_age_seconds: u64,
+}
+
+impl Default for RecommendationsCacheControl {
+    fn default() -> Self {
+        Self {
+            max_age_seconds: DEFAULT_RECOMMENDATIONS_MAX_AGE_SECONDS,
+        }
+    }
+}
+
+/// Broadcasts progress of the running update to all subscribers, events are dropped if nobody listens
 #[derive(Clone)]
+struct ProgressBroadcast(broadcast::Sender<UpdateProgress>);
+
+impl Default for ProgressBroadcast {
+    fn default() -> Self {
+        Self(broadcast::channel(PROGRESS_CHANNEL_CAPACITY).0)
+    }
+}
+
+/// Newest processed history records of a user, history is fetched again from their unreserved_at
+/// (it has one second resolution, so later records of the same second may still come) and records
+/// already processed are dropped
+#[derive(Debug, Clone, PartialEq)]
+struct HistoryCursor {
+    unreserved_at: UnixSeconds,
+    processed_records: Vec<ReservationHistoryRecord>,
+}
+
+/// Stops the updater started with `start`, the running tick is finished before the updater stops
+#[derive(Clone, Default)]
+pub struct UpdaterShutdown(Arc<watch::Sender<bool>>);
+
+impl UpdaterShutdown {
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct RecommendationsProvider {
+    coefficients_storage: Arc<Mutex<CoefficientsStorage>>,
     recommendations_engine: Arc<RwLock<RecommendationsEngine>>,
+    status: Arc<RwLock<RecommendationsStatus>>,
+    progress: ProgressBroadcast,
+    /// Recommendations snapshots of users that current recommendations are compared to
+    baselines: Arc<RwLock<HashMap<UserId, Recommendations>>>,
 }
 
 impl RecommendationsProvider {
+    #[cfg(test)]
+    pub(crate) fn from_parts(
+        coefficients_storage: CoefficientsStorage,
+        recommendations_engine: RecommendationsEngine,
+    ) -> Self {
+        Self {
+            coefficients_storage: Arc::new(Mutex::new(coefficients_storage)),
+            recommendations_engine: Arc::new(RwLock::new(recommendations_engine)),
+            status: Default::default(),
+            progress: Default::default(),
+            baselines: Default::default(),
+        }
+    }
+
     pub fn get_recommendations_for_user(&self, user_id: UserId) -> Recommendations {
         self.recommendations_engine
             .read()
             .get_recommendations_for_user(user_id)
     }
+
+    pub fn get_versioned_recommendations_for_user(
+        &self,
+        user_id: UserId,
+    ) -> VersionedRecommendations {
+        self.recommendations_engine
+            .read()
+            .get_versioned_recommendations_for_user(user_id)
+    }
+
+    /// Returns which of the stored recommendation lists of the user are populated
+    pub fn get_recommendation_categories_for_user(
+        &self,
+        user_id: UserId,
+    ) -> RecommendationCategories {
+        self.get_recommendations_for_user(user_id).categories()
+    }
+
+    /// Returns stored recommendations of the user as a single list with the preferred category first
+    pub fn get_flat_recommendations_for_user(
+        &self,
+        user_id: UserId,
+        prefer: Option<RecommendationCategory>,
+    ) -> Vec<BookId> {
+        self.get_recommendations_for_user(user_id).flattened(prefer)
+    }
+
+    /// Calculates recommendations for a synthetic user against current coefficients, without storing them
+    pub fn simulate_recommendations(&self, user: &SimulatedUser) -> Recommendations {
+        let all_books_reserved_by_user: HashSet<BookId> = user
+            .reservations
+            .iter()
+            .chain(user.history.iter())
+            .cloned()
+            .collect();
+        self.coefficients_storage
+            .lock()
+            .calculate_recommendations(&all_books_reserved_by_user, &user.favourite_tags)
+    }
+
+    /// Calculates recommendations for a new user from favourite tags and current popularity, without storing them
+    pub fn preview_recommendations(&self, favourite_tags: &[TagWeight]) -> Vec<BookId> {
+        self.coefficients_storage
+            .lock()
+            .preview_recommendations(favourite_tags)
+    }
+
+    /// Returns books with popularity score of at least `min_score`, the most popular first
+    pub fn books_above_popularity(&self, min_score: i64) -> Vec<BookId> {
+        self.coefficients_storage
+            .lock()
+            .books_above_popularity(min_score)
+    }
+
+    /// Returns all known books carrying given tag
+    pub fn books_with_tag(&self, tag: &str) -> HashSet<BookId> {
+        self.coefficients_storage.lock().books_with_tag(tag)
+    }
+
+    /// Returns up to `limit` most popular books sharing no authors and no tags with the user history
+    pub fn get_serendipity_for_user(&self, user_id: UserId, limit: usize) -> Vec<BookId> {
+        let history_books = self
+            .recommendations_engine
+            .read()
+            .history_books_of_user(user_id);
+        self.coefficients_storage
+            .lock()
+            .books_without_overlap(&history_books, limit)
+    }
+
+    /// Returns current recommendations of the user
+    pub fn snapshot_for(&self, user_id: UserId) -> Recommendations {
+        self.get_recommendations_for_user(user_id)
+    }
+
+    /// Stores current recommendations of the user as the baseline for later diffs, returns the stored snapshot
+    pub fn set_baseline(&self, user_id: UserId) -> Recommendations {
+        let snapshot = self.snapshot_for(user_id);
+        self.baselines.write().insert(user_id, snapshot.clone());
+        snapshot
+    }
+
+    /// Compares current recommendations of the user to the stored baseline, None if no baseline was set
+    pub fn diff_from_baseline(&self, user_id: UserId) -> Option<RecommendationsDiff> {
+        let baseline = self.baselines.read().get(&user_id).cloned()?;
+        Some(self.snapshot_for(user_id).diff(&baseline))
+    }
+
+    /// Returns receiver of progress events of updates started after subscribing
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<UpdateProgress> {
+        self.progress.0.subscribe()
+    }
+
+    /// Returns known books that were never recommended to any user
+    pub fn unrecommended_books(&self) -> Vec<BookId> {
+        let coefficients_storage = self.coefficients_storage.lock();
+        self.recommendations_engine
+            .read()
+            .unrecommended_books(&coefficients_storage)
+    }
+
+    /// Returns aggregate metrics of recommendations of all users in the engine
+    pub fn coverage(&self) -> RecommendationsCoverage {
+        self.recommendations_engine.read().coverage()
+    }
+
+    /// Returns status of the updater with number of users currently in the engine
+    pub fn get_status(&self) -> RecommendationsStatus {
+        RecommendationsStatus {
+            users_in_engine: self.recommendations_engine.read().number_of_users(),
+            ..self.status.read().clone()
+        }
+    }
 }
 
 pub struct RecommendationsUpdater {
     coefficients_storage: Arc<Mutex<CoefficientsStorage>>,
     recommendations_engine: Arc<RwLock<RecommendationsEngine>>,
+    status: Arc<RwLock<RecommendationsStatus>>,
+    progress: ProgressBroadcast,
+    /// Recommendations snapshots of users that current recommendations are compared to
+    baselines: Arc<RwLock<HashMap<UserId, Recommendations>>>,
+    /// Last processed history records per user, only not yet processed records are used
+    history_cursor_per_user: Mutex<HashMap<UserId, HistoryCursor>>,
+    max_consecutive_tick_failures: u32,
+    /// Details of all books are fetched every tick instead of every UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL ticks
+    eager_book_load: bool,
+    max_concurrent_book_requests: usize,
+    /// Users missing from the last reservations response, they are not updated until processed again
+    skipped_users: Mutex<HashSet<UserId>>,
+    /// Users processed in the next tick regardless of their group
+    requeued_users: Mutex<HashSet<UserId>>,
+    shutdown: UpdaterShutdown,
     book_service_repository_client: BookServiceRepositoryClient,
     book_service_reservations_client: BookServiceReservationsClient,
 }
@@ -46,6 +254,16 @@ impl RecommendationsUpdater {
         Ok(Self {
             coefficients_storage: Arc::new(Default::default()),
             recommendations_engine: Arc::new(Default::default()),
+            status: Arc::new(Default::default()),
+            progress: Default::default(),
+            baselines: Default::default(),
+            history_cursor_per_user: Default::default(),
+            max_consecutive_tick_failures: DEFAULT_MAX_CONSECUTIVE_TICK_FAILURES,
+            eager_book_load: false,
+            max_concurrent_book_requests: DEFAULT_MAX_CONCURRENT_BOOK_REQUESTS,
+            skipped_users: Default::default(),
+            requeued_users: Default::default(),
+            shutdown: Default::default(),
             book_service_repository_client: BookServiceRepositoryClient::new(
                 book_service_repository_url,
             )?,
@@ -54,124 +272,419 @@ impl RecommendationsUpdater {
             )?,
         })
     }
+
+    /// Sets number of failed ticks in a row after which `start` returns the error, failed ticks before that
+    /// are logged and the next tick is run as usual
+    pub fn with_max_consecutive_tick_failures(self, max_consecutive_tick_failures: u32) -> Self {
+        Self {
+            max_consecutive_tick_failures,
+            ..self
+        }
+    }
+
+    /// Makes every tick fetch details of all books, simpler than incremental updates for small catalogs
+    pub fn with_eager_book_load(self, eager_book_load: bool) -> Self {
+        Self {
+            eager_book_load,
+            ..self
+        }
+    }
+
+    /// Sets how many book details requests can be sent to the repository at the same time (at least one)
+    pub fn with_max_concurrent_book_requests(self, max_concurrent_book_requests: usize) -> Self {
+        Self {
+            max_concurrent_book_requests: max_concurrent_book_requests.max(1),
+            ..self
+        }
+    }
+
+    /// Sets how many books of a single author can be recommended in author match
+    pub fn with_author_match_config(self, config: AuthorMatchConfig) -> Self {
+        self.coefficients_storage
+            .lock()
+            .set_author_match_config(config);
+        self
+    }
+
+    /// Sets how many most popular books are recommended to users without recommendations (e.g. for a homepage)
+    pub fn with_fallback_most_popular_size(self, size: usize) -> Self {
+        self.recommendations_engine
+            .write()
+            .set_fallback_most_popular_size(size);
+        self
+    }
+
+    /// Sets maximal number of users with stored recommendations (unlimited if None), least recently requested users
+    /// are evicted above it
+    pub fn with_recommendations_capacity(self, capacity: Option<usize>) -> Self {
+        self.recommendations_engine.write().set_capacity(capacity);
+        self
+    }
+
     pub fn provider(&self) -> RecommendationsProvider {
         RecommendationsProvider {
+            coefficients_storage: self.coefficients_storage.clone(),
             recommendations_engine: self.recommendations_engine.clone(),
+            status: self.status.clone(),
+            progress: self.progress.clone(),
+            baselines: self.baselines.clone(),
         }
     }
 
-    pub async fn start(self) -> anyhow::Result<()> {
-        let mut periodic_updater =
-            tokio_interval_stream(std::time::Duration::from_secs(INTERVAL_SECONDS));
+    /// Returns handle stopping the updater, `start` returns `Ok` once the running tick is finished
+    pub fn shutdown_handle(&self) -> UpdaterShutdown {
+        self.shutdown.clone()
+    }
+
+    pub async fn start(&self) -> anyhow::Result<()> {
+        self.run_ticks(std::time::Duration::from_secs(INTERVAL_SECONDS))
+            .await
+    }
+
+    async fn run_ticks(&self, interval: std::time::Duration) -> anyhow::Result<()> {
+        let mut periodic_updater = tokio_interval_stream(interval);
         let mut interval_no = 0;
         let mut processed_users_to_last_updated: HashMap<UserId, std::time::Instant> =
             Default::default();
+        let mut consecutive_failures = 0;
+        let mut shutdown = self.shutdown.0.subscribe();
 
-        while periodic_updater.next().await.is_some() {
-            tracing::info!("Recommendations tick no {}", interval_no);
-
-            // Every tick get all users
-            let user_ids = self.book_service_reservations_client.list_users().await?;
-
-            // Process the users that were never processed every tick
-            let mut users_to_process = user_ids
-                .iter()
-                .filter(|uid| !processed_users_to_last_updated.contains_key(uid))
-                .cloned()
-                .collect_vec();
-
-            // In constant intervals, take a group of users and update them
-            if interval_no % (UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL / USERS_SPLIT) == 0 {
-                let user_modulo =
-                    interval_no / (UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL / USERS_SPLIT);
-                tracing::info!("Processing users modulo {}", user_modulo);
-                for user_id in processed_users_to_last_updated.keys() {
-                    if user_id & user_modulo == 0 {
-                        users_to_process.push(*user_id);
+        loop {
+            // Shutdown is checked only between ticks, so the running tick is never interrupted
+            tokio::select! {
+                biased;
+                _ = shutdown.wait_for(|shutdown| *shutdown) => break,
+                tick = periodic_updater.next() => {
+                    if tick.is_none() {
+                        break;
+                    }
+                }
+            }
+            match self
+                .run_tick(interval_no, &mut processed_users_to_last_updated)
+                .await
+            {
+                Ok(()) => {
+                    consecutive_failures = 0;
+                    interval_no = (interval_no + 1) % UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL;
+                }
+                // Failed tick is repeated in the next interval, so e.g. the full books details update is not skipped
+                Err(err) => {
+                    consecutive_failures += 1;
+                    tracing::error!(
+                        "Recommendations tick no {} failed ({} in a row): {:?}",
+                        interval_no,
+                        consecutive_failures,
+                        err
+                    );
+                    if consecutive_failures >= self.max_consecutive_tick_failures {
+                        return Err(err.context(format!(
+                            "{} recommendations ticks failed in a row",
+                            consecutive_failures
+                        )));
                     }
                 }
             }
-            let (user_id_to_reservations, user_id_to_history) =
-                self.fetch_user_reservations_data(users_to_process).await?;
+        }
+        Ok(())
+    }
 
-            // Every UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL ticks process all books
-            let book_ids_to_process = if interval_no == 0 {
-                self.book_service_repository_client
-                    .list_books()
-                    .await?
-                    .into_iter()
-                    .map(|id_and_title| id_and_title.book_id)
-                    .collect_vec()
-            } else {
-                // Otherwise process only books from user reservations and history
-                user_id_to_reservations
-                    .values()
-                    .flatten()
-                    .cloned()
-                    .chain(user_id_to_history.values().flatten().map(|r| r.book_id))
-                    .unique()
-                    .collect_vec()
-            };
+    /// Runs a single recommendations update, see README for details of what is updated in which tick
+    async fn run_tick(
+        &self,
+        interval_no: i32,
+        processed_users_to_last_updated: &mut HashMap<UserId, std::time::Instant>,
+    ) -> anyhow::Result<()> {
+        tracing::info!("Recommendations tick no {}", interval_no);
+
+        // Every tick get all users
+        let user_ids = self
+            .record_reservations_call(self.book_service_reservations_client.list_users().await)?;
 
-            let mut book_id_to_details: HashMap<BookId, BookDetails> = Default::default();
-            for book_id in book_ids_to_process {
-                if let Some(details) = self
-                    .book_service_repository_client
-                    .get_book(book_id)
-                    .await?
-                {
-                    book_id_to_details.insert(book_id, details);
-                } else {
-                    tracing::warn!("Failed to get details for book {}", book_id);
+        // Process the users that were never processed every tick
+        let mut users_to_process = user_ids
+            .iter()
+            .filter(|uid| !processed_users_to_last_updated.contains_key(uid))
+            .cloned()
+            .collect_vec();
+
+        // In constant intervals, take a group of users and update them
+        if interval_no % (UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL / USERS_SPLIT) == 0 {
+            let user_modulo = interval_no / (UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL / USERS_SPLIT);
+            tracing::info!("Processing users modulo {}", user_modulo);
+            for user_id in processed_users_to_last_updated.keys() {
+                if user_id & user_modulo == 0 {
+                    users_to_process.push(*user_id);
                 }
             }
+        }
 
-            self.update(
-                &user_id_to_reservations,
-                &user_id_to_history,
-                &book_id_to_details,
-            )
-            .await?;
+        // Requeued users are processed in this tick, they stay queued until the tick succeeds
+        let requeued_users = self.requeued_users.lock().iter().cloned().collect_vec();
+        let users_to_process = users_to_process
+            .into_iter()
+            .chain(requeued_users.iter().cloned())
+            .unique()
+            .collect_vec();
+        let (mut user_id_to_reservations, mut user_id_to_history) =
+            self.fetch_user_reservations_data(&users_to_process).await?;
+        let mut user_id_to_favourite_tags =
+            self.fetch_user_favourite_tags(&users_to_process).await?;
+        self.skip_users_with_missing_data(
+            &users_to_process,
+            &mut user_id_to_reservations,
+            &mut user_id_to_history,
+            &mut user_id_to_favourite_tags,
+        );
+
+        // Every UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL ticks (or every tick if eager) process all books
+        let book_ids_to_process = if interval_no == 0 || self.eager_book_load {
+            self.record_repository_call(self.book_service_repository_client.list_books().await)?
+                .into_iter()
+                .map(|id_and_title| id_and_title.book_id)
+                .collect_vec()
+        } else {
+            // Otherwise process only books from user reservations and history
+            user_id_to_reservations
+                .values()
+                .flatten()
+                .cloned()
+                .chain(user_id_to_history.values().flatten().map(|r| r.book_id))
+                .unique()
+                .collect_vec()
+        };
+
+        let total = book_ids_to_process.len() as u64;
+        let mut book_id_to_details: HashMap<BookId, BookDetails> = Default::default();
+        let mut book_details_responses = futures_util::stream::iter(book_ids_to_process)
+            .map(|book_id| async move {
+                (
+                    book_id,
+                    self.book_service_repository_client.get_book(book_id).await,
+                )
+            })
+            .buffer_unordered(self.max_concurrent_book_requests);
+        let mut processed = 0;
+        while let Some((book_id, response)) = book_details_responses.next().await {
+            if let Some(details) = self.record_repository_call(response)? {
+                book_id_to_details.insert(book_id, details);
+            } else {
+                tracing::warn!("Failed to get details for book {}", book_id);
+            }
+            processed += 1;
+            // Sending fails only if there are no subscribers
+            let _ = self.progress.0.send(UpdateProgress { processed, total });
+        }
+
+        self.update(
+            &user_id_to_reservations,
+            &user_id_to_history,
+            &user_id_to_favourite_tags,
+            &book_id_to_details,
+        )
+        .await?;
+
+        self.advance_history_cursors(&user_id_to_history);
+
+        if interval_no == 0 {
+            self.push_popularity().await?;
+        }
 
-            let now = std::time::Instant::now();
-            for (user_id, _) in user_id_to_reservations.iter() {
-                processed_users_to_last_updated.insert(*user_id, now);
+        let now = std::time::Instant::now();
+        for (user_id, _) in user_id_to_reservations.iter() {
+            processed_users_to_last_updated.insert(*user_id, now);
+        }
+        {
+            let mut queued_users = self.requeued_users.lock();
+            for user_id in requeued_users.iter() {
+                queued_users.remove(user_id);
             }
+        }
+
+        self.status.write().last_tick_completed_at = Some(UnixSeconds::now());
+        Ok(())
+    }
+
+    /// Fetches details of a single book and updates them in coefficients storage
+    /// Returns false if the book was not found in the repository
+    pub async fn refresh_book(&self, book_id: BookId) -> anyhow::Result<bool> {
+        if let Some(details) = self
+            .record_repository_call(self.book_service_repository_client.get_book(book_id).await)?
+        {
+            self.coefficients_storage
+                .lock()
+                .refresh_book(book_id, &details);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Makes the next tick process given user regardless of its group (e.g. after it was skipped)
+    /// Returns true if the user was skipped the last time it was processed
+    pub fn requeue_user(&self, user_id: UserId) -> bool {
+        self.requeued_users.lock().insert(user_id);
+        self.skipped_users.lock().contains(&user_id)
+    }
 
-            interval_no = (interval_no + 1) % UPDATE_ALL_BOOK_DETAILS_EVERY_INTERVAL;
+    /// Sends popularity score of every known book to the repository
+    async fn push_popularity(&self) -> anyhow::Result<()> {
+        let popularity_scores = self.coefficients_storage.lock().popularity_scores().clone();
+        for (book_id, popularity) in popularity_scores {
+            if !self.record_repository_call(
+                self.book_service_repository_client
+                    .set_book_popularity(book_id, popularity)
+                    .await,
+            )? {
+                tracing::warn!("Failed to set popularity of book {}", book_id);
+            }
         }
         Ok(())
     }
 
+    fn record_repository_call<T>(&self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        record_downstream_call(&mut self.status.write().repository, &result);
+        result
+    }
+
+    fn record_reservations_call<T>(&self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        record_downstream_call(&mut self.status.write().reservations, &result);
+        result
+    }
+
     async fn fetch_user_reservations_data(
         &self,
-        user_ids: Vec<UserId>,
+        user_ids: &[UserId],
     ) -> anyhow::Result<(
         HashMap<UserId, Vec<BookId>>,
         HashMap<UserId, Vec<ReservationHistoryRecord>>,
     )> {
-        let mut user_id_to_reservations: HashMap<UserId, Vec<BookId>> = Default::default();
-        let mut user_id_to_history: HashMap<UserId, Vec<ReservationHistoryRecord>> =
-            Default::default();
-        for user_id in user_ids {
-            let history = self
-                .book_service_reservations_client
-                .history(user_id)
-                .await?;
-            user_id_to_history.insert(user_id, history);
-            let reservations = self
-                .book_service_reservations_client
-                .list_reservations(user_id)
-                .await?;
-            user_id_to_reservations.insert(user_id, reservations);
+        let user_id_to_reservations = self.record_reservations_call(
+            self.book_service_reservations_client
+                .list_reservations_for_users(user_ids)
+                .await,
+        )?;
+        let user_id_to_since: HashMap<UserId, UnixSeconds> = {
+            let history_cursor_per_user = self.history_cursor_per_user.lock();
+            user_ids
+                .iter()
+                .map(|user_id| {
+                    (
+                        *user_id,
+                        history_cursor_per_user
+                            .get(user_id)
+                            .map(|cursor| cursor.unreserved_at)
+                            .unwrap_or(UnixSeconds(-1)),
+                    )
+                })
+                .collect()
+        };
+        let mut user_id_to_history = self.record_reservations_call(
+            self.book_service_reservations_client
+                .history_since_for_users(&user_id_to_since)
+                .await,
+        )?;
+        {
+            let history_cursor_per_user = self.history_cursor_per_user.lock();
+            for (user_id, history_records) in user_id_to_history.iter_mut() {
+                if let Some(cursor) = history_cursor_per_user.get(user_id) {
+                    let mut processed_records = cursor.processed_records.clone();
+                    history_records.retain(|record| {
+                        match processed_records
+                            .iter()
+                            .position(|processed| processed == record)
+                        {
+                            Some(position) => {
+                                processed_records.swap_remove(position);
+                                false
+                            }
+                            None => true,
+                        }
+                    });
+                }
+            }
         }
         Ok((user_id_to_reservations, user_id_to_history))
     }
 
+    async fn fetch_user_favourite_tags(
+        &self,
+        user_ids: &[UserId],
+    ) -> anyhow::Result<HashMap<UserId, Vec<TagWeight>>> {
+        let user_id_to_details = self.record_reservations_call(
+            self.book_service_reservations_client
+                .users_details(user_ids)
+                .await,
+        )?;
+        Ok(user_id_to_details
+            .into_iter()
+            .map(|(user_id, details)| (user_id, details.favourite_tags))
+            .collect())
+    }
+
+    /// Leaves out of the update users without reservations, history or details (e.g. deleted in the meantime)
+    /// and marks them as skipped, users with all of them are no longer skipped
+    fn skip_users_with_missing_data(
+        &self,
+        user_ids: &[UserId],
+        user_id_to_reservations: &mut HashMap<UserId, Vec<BookId>>,
+        user_id_to_history: &mut HashMap<UserId, Vec<ReservationHistoryRecord>>,
+        user_id_to_favourite_tags: &mut HashMap<UserId, Vec<TagWeight>>,
+    ) {
+        let mut skipped_users = self.skipped_users.lock();
+        for user_id in user_ids.iter() {
+            if user_id_to_reservations.contains_key(user_id)
+                && user_id_to_history.contains_key(user_id)
+                && user_id_to_favourite_tags.contains_key(user_id)
+            {
+                skipped_users.remove(user_id);
+            } else {
+                tracing::warn!("Failed to get data of user {}, skipping", user_id);
+                user_id_to_reservations.remove(user_id);
+                user_id_to_history.remove(user_id);
+                user_id_to_favourite_tags.remove(user_id);
+                skipped_users.insert(*user_id);
+            }
+        }
+    }
+
+    /// Moves history cursor of each user to its newest processed records
+    fn advance_history_cursors(
+        &self,
+        user_id_to_history: &HashMap<UserId, Vec<ReservationHistoryRecord>>,
+    ) {
+        let mut history_cursor_per_user = self.history_cursor_per_user.lock();
+        for (user_id, history_records) in user_id_to_history.iter() {
+            let Some(last_unreserved_at) = history_records.iter().map(|r| r.unreserved_at).max()
+            else {
+                continue;
+            };
+            let last_records = history_records
+                .iter()
+                .filter(|record| record.unreserved_at == last_unreserved_at)
+                .cloned();
+            match history_cursor_per_user.get_mut(user_id) {
+                Some(cursor) if cursor.unreserved_at == last_unreserved_at => {
+                    cursor.processed_records.extend(last_records)
+                }
+                _ => {
+                    history_cursor_per_user.insert(
+                        *user_id,
+                        HistoryCursor {
+                            unreserved_at: last_unreserved_at,
+                            processed_records: last_records.collect(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
     async fn update(
         &self,
         user_id_to_reservations: &HashMap<UserId, Vec<BookId>>,
         user_id_to_history: &HashMap<UserId, Vec<ReservationHistoryRecord>>,
+        user_id_to_favourite_tags: &HashMap<UserId, Vec<TagWeight>>,
         book_id_to_details: &HashMap<BookId, BookDetails>,
     ) -> anyhow::Result<()> {
         let mut storage = self.coefficients_storage.lock();
@@ -183,7 +696,934 @@ impl RecommendationsUpdater {
                 &storage,
                 user_id_to_reservations,
                 user_id_to_history,
+                user_id_to_favourite_tags,
             )?;
         Ok(())
     }
 }
+
+fn record_downstream_call<T>(status: &mut DownstreamStatus, result: &anyhow::Result<T>) {
+    status.reachable = result.is_ok();
+    if result.is_ok() {
+        status.last_success_at = Some(UnixSeconds::now());
+    }
+}
+
+#[cfg(test)]
+mod recommendations_updater_tests {
+    use actix_web::{App, HttpResponse, HttpServer};
+    use bookservice_repository::api::{BookDetails, BookDetailsPatch, BookTitleAndId};
+    use bookservice_repository::books_repository::{BookRepository, InMemoryBookRepository};
+    use bookservice_repository::tags_validator::TagsValidator;
+    use bookservice_reservations::api::{HistoryRetention, UserDetails};
+    use bookservice_reservations::book_existance_checker::BookExistanceChecker;
+    use bookservice_reservations::reservation_cooldown::ReservationCooldown;
+    use bookservice_reservations::reservations_repository::{
+        InMemoryReservationsRepository, ReservationsRepository,
+    };
+    use paperclip::actix::{web, OpenApiExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::api::BookIdsDiff;
+
+    /// Starts in memory repository and reservations services, returns their urls
+    fn start_downstream_services() -> (String, String) {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        tokio::spawn(repository_server.run());
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let checker_url = repository_url.clone();
+        let reservation_cooldown = web::Data::new(ReservationCooldown::default());
+        let reservations_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(reservations_repository.clone()))
+                .app_data(reservation_cooldown.clone())
+                .app_data(web::Data::new(BookExistanceChecker::new(
+                    checker_url.clone(),
+                )))
+                .configure(bookservice_reservations::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind reservations server");
+        let reservations_url = format!("http://{}", reservations_server.addrs()[0]);
+        tokio::spawn(reservations_server.run());
+
+        (repository_url, reservations_url)
+    }
+
+    /// Test scenario:
+    /// 1. Start repository and reservations services
+    /// 2. Add a book and a user that reserves it
+    /// 3. Check that status is empty before any update
+    /// 4. Run a single update tick
+    /// 5. Check that all status fields are populated
+    #[actix_web::test]
+    async fn test_status_populated_after_update() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let reservations_client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        let book_id = repository_client
+            .add_book(BookDetails {
+                title: "Book".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec!["tag".to_string()],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+        let user_id = reservations_client
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec!["tag".into()],
+            })
+            .await
+            .unwrap();
+        assert!(reservations_client
+            .reserve_book(book_id, user_id)
+            .await
+            .unwrap()
+            .is_some());
+
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        let provider = updater.provider();
+        assert_eq!(provider.get_status(), RecommendationsStatus::default());
+
+        updater.run_tick(0, &mut Default::default()).await.unwrap();
+
+        let status = provider.get_status();
+        assert!(status.repository.reachable);
+        assert!(status.repository.last_success_at.is_some());
+        assert!(status.reservations.reachable);
+        assert!(status.reservations.last_success_at.is_some());
+        assert!(status.last_tick_completed_at.is_some());
+        assert_eq!(status.users_in_engine, 1);
+    }
+
+    /// Test scenario:
+    /// 1. Start repository and reservations services and add two books
+    /// 2. Start recommendations service and subscribe to update progress events
+    /// 3. Run a single update tick
+    /// 4. Check that progress events are received, the first one after details of the first book are fetched
+    #[actix_web::test]
+    async fn test_update_progress_streamed() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        for title in ["Book1", "Book2"] {
+            repository_client
+                .add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: vec!["Author".to_string()],
+                    publisher: "Publisher".to_string(),
+                    description: "Description".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        let provider = updater.provider();
+        let recommendations_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(provider.clone()))
+                .configure(crate::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind recommendations server");
+        let recommendations_url = format!("http://{}", recommendations_server.addrs()[0]);
+        tokio::spawn(recommendations_server.run());
+
+        let mut response = reqwest::get(format!(
+            "{}/api/admin/recommendations/progress",
+            recommendations_url
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        updater.run_tick(0, &mut Default::default()).await.unwrap();
+
+        let chunk =
+            actix_web::rt::time::timeout(std::time::Duration::from_secs(5), response.chunk())
+                .await
+                .expect("No progress event received")
+                .unwrap()
+                .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        let first_event = chunk
+            .split("\n\n")
+            .next()
+            .and_then(|event| event.strip_prefix("data: "))
+            .expect("Invalid event");
+        assert_eq!(
+            serde_json::from_str::<UpdateProgress>(first_event).unwrap(),
+            UpdateProgress {
+                processed: 1,
+                total: 2
+            }
+        );
+    }
+
+    /// Test scenario:
+    /// 1. Start repository and reservations services
+    /// 2. Add two books and a user that reserved and unreserved the first one
+    /// 3. Run a single update tick - the history record is processed and cursor is stored
+    /// 4. Fetch user data as in the next tick - expect no history records, as the only one is already processed
+    /// 5. Recalculate the user and check that the first book is still not recommended to them
+    #[actix_web::test]
+    async fn test_next_tick_fetches_only_newer_history() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let reservations_client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        let mut book_ids = vec![];
+        for title in ["Book1", "Book2"] {
+            book_ids.push(
+                repository_client
+                    .add_book(BookDetails {
+                        title: title.to_string(),
+                        authors: vec!["Author".to_string()],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let user_id = reservations_client
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        assert!(reservations_client
+            .reserve_book(book_ids[0], user_id)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(reservations_client
+            .unreserve_book(book_ids[0], user_id)
+            .await
+            .unwrap());
+        let unreserved_at = reservations_client.history(user_id).await.unwrap()[0].unreserved_at;
+
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        let mut processed_users_to_last_updated = Default::default();
+        updater
+            .run_tick(0, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+        assert_eq!(
+            updater
+                .history_cursor_per_user
+                .lock()
+                .get(&user_id)
+                .map(|cursor| cursor.unreserved_at),
+            Some(unreserved_at)
+        );
+
+        let (_, user_id_to_history) = updater
+            .fetch_user_reservations_data(&[user_id])
+            .await
+            .unwrap();
+        assert_eq!(user_id_to_history[&user_id], vec![]);
+
+        // Tick 0 again recalculates all already processed users
+        updater
+            .run_tick(0, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+        let recommendations = updater.provider().get_recommendations_for_user(user_id);
+        assert_eq!(recommendations.most_popular, vec![book_ids[1]]);
+    }
+
+    /// Starts downstream services with a single user 2 without reservations, its history is given by the test
+    /// and served inclusively from the requested timestamp, returns url
+    fn start_history_downstream(history: Arc<Mutex<Vec<ReservationHistoryRecord>>>) -> String {
+        let server = HttpServer::new(move || {
+            let history = history.clone();
+            App::new()
+                    .route(
+                        "/api/users",
+                        actix_web::web::get().to(|| async { HttpResponse::Ok().json(vec![2]) }),
+                    )
+                    .route(
+                        "/api/users/reservations/batch",
+                        actix_web::web::post().to(|| async {
+                            HttpResponse::Ok().json(HashMap::from([(2, Vec::<BookId>::new())]))
+                        }),
+                    )
+                    .route(
+                        "/api/users/history/since/batch",
+                        actix_web::web::post().to(
+                            move |user_id_to_since: actix_web::web::Json<
+                                HashMap<UserId, UnixSeconds>,
+                            >| {
+                                let since = user_id_to_since[&2];
+                                let history_records = history
+                                    .lock()
+                                    .iter()
+                                    .filter(|record| record.unreserved_at >= since)
+                                    .cloned()
+                                    .collect_vec();
+                                async move {
+                                    HttpResponse::Ok().json(HashMap::from([(2, history_records)]))
+                                }
+                            },
+                        ),
+                    )
+                    .route(
+                        "/api/users/details/batch",
+                        actix_web::web::post().to(|| async {
+                            HttpResponse::Ok().json(HashMap::from([(
+                                2,
+                                UserDetails {
+                                    username: "user".to_string(),
+                                    favourite_tags: vec![],
+                                },
+                            )]))
+                        }),
+                    )
+                    .route(
+                        "/api/books",
+                        actix_web::web::get()
+                            .to(|| async { HttpResponse::Ok().json(Vec::<BookId>::new()) }),
+                    )
+                    .default_service(actix_web::web::to(HttpResponse::NotFound))
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind history downstream server");
+        let url = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+        url
+    }
+
+    /// Test scenario:
+    /// 1. Start downstream with user 2 that unreserved the first book
+    /// 2. Run a tick - the history record is processed
+    /// 3. The user unreserves the second book in the same second
+    /// 4. Fetch user data as in the next tick - expect only the second book record
+    /// 5. Run the next tick and fetch user data again - expect no records, both are processed
+    #[actix_web::test]
+    async fn test_history_unreserved_in_the_same_second_processed_across_ticks() {
+        let unreserved_at = UnixSeconds::now();
+        let record = |book_id: BookId| ReservationHistoryRecord {
+            book_id,
+            reserved_at: None,
+            unreserved_at,
+        };
+        let history = Arc::new(Mutex::new(vec![record(1)]));
+        let url = start_history_downstream(history.clone());
+        let updater = RecommendationsUpdater::new(&url, &url).unwrap();
+        let mut processed_users_to_last_updated = HashMap::new();
+
+        updater
+            .run_tick(1, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+
+        history.lock().push(record(2));
+        let (_, user_id_to_history) = updater.fetch_user_reservations_data(&[2]).await.unwrap();
+        assert_eq!(user_id_to_history[&2], vec![record(2)]);
+
+        updater
+            .run_tick(20, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+        let (_, user_id_to_history) = updater.fetch_user_reservations_data(&[2]).await.unwrap();
+        assert_eq!(user_id_to_history[&2], vec![]);
+    }
+
+    /// Test scenario:
+    /// 1. Start repository and reservations services, add books of two authors
+    /// 2. Run a single update tick - all books are loaded
+    /// 3. Change authors of the first book to the author of the second one
+    /// 4. Check that recommendations do not reflect the change before refresh and do after refreshing the book
+    #[actix_web::test]
+    async fn test_refresh_book_updates_authors() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+
+        let mut book_ids = vec![];
+        for (title, author) in [("Book1", "Author1"), ("Book2", "Author2")] {
+            book_ids.push(
+                repository_client
+                    .add_book(BookDetails {
+                        title: title.to_string(),
+                        authors: vec![author.to_string()],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        updater.run_tick(0, &mut Default::default()).await.unwrap();
+
+        repository_client
+            .update_book(
+                book_ids[0],
+                BookDetailsPatch {
+                    authors: Some(vec!["Author2".to_string()]),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // User that read the book of the second author
+        let user = SimulatedUser {
+            history: vec![book_ids[1]],
+            ..Default::default()
+        };
+        let provider = updater.provider();
+        assert!(provider
+            .simulate_recommendations(&user)
+            .author_match
+            .is_empty());
+
+        assert!(updater.refresh_book(book_ids[0]).await.unwrap());
+        assert_eq!(
+            provider.simulate_recommendations(&user).author_match,
+            vec![book_ids[0]]
+        );
+
+        assert!(!updater.refresh_book(20000).await.unwrap());
+    }
+
+    /// Test scenario:
+    /// 1. Start repository and reservations services, add two books without authors and two users
+    /// 2. First user reserves and unreserves the first book, second user has no history nor favourite tags,
+    ///    without authors there is nothing to match by author
+    /// 3. Run a single update tick
+    /// 4. Check that only most popular and new releases categories are populated for the second user
+    #[actix_web::test]
+    async fn test_recommendation_categories_without_matches() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let reservations_client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        let mut book_ids = vec![];
+        for title in ["Book1", "Book2"] {
+            book_ids.push(
+                repository_client
+                    .add_book(BookDetails {
+                        title: title.to_string(),
+                        authors: vec![],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let mut user_ids = vec![];
+        for username in ["reader", "newcomer"] {
+            user_ids.push(
+                reservations_client
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert!(reservations_client
+            .reserve_book(book_ids[0], user_ids[0])
+            .await
+            .unwrap()
+            .is_some());
+        assert!(reservations_client
+            .unreserve_book(book_ids[0], user_ids[0])
+            .await
+            .unwrap());
+
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        updater.run_tick(0, &mut Default::default()).await.unwrap();
+
+        assert_eq!(
+            updater
+                .provider()
+                .get_recommendation_categories_for_user(user_ids[1]),
+            RecommendationCategories {
+                most_popular: true,
+                author_match: false,
+                new_author_match: false,
+                tag_match: false,
+                new_releases: true,
+            }
+        );
+    }
+
+    /// 1. Seed coefficients with history of other user and calculate recommendations of a user without history
+    /// 2. Store the recommendations of the user as the baseline - expect empty diff
+    /// 3. Recalculate recommendations after the user reserved the most popular book
+    /// 4. Check that the diff lists the reserved book as removed and the author match as added
+    #[test]
+    fn test_recommendations_diff_from_baseline() {
+        let book = |title: &str, author: &str| BookDetails {
+            title: title.to_string(),
+            authors: vec![author.to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let record = |book_id: BookId| ReservationHistoryRecord {
+            book_id,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(10),
+        };
+        let book_details = HashMap::from([
+            (1, book("b1", "a1")),
+            (2, book("b2", "a1")),
+            (3, book("b3", "a2")),
+        ]);
+        let user_to_history = HashMap::from([(10, vec![record(1), record(3)])]);
+        let user_id: UserId = 1;
+
+        let mut storage = CoefficientsStorage::default();
+        storage
+            .update_storage(&user_to_history, &book_details)
+            .unwrap();
+        let mut engine = RecommendationsEngine::default();
+        engine
+            .update_recommendations_for_users(
+                &storage,
+                &HashMap::from([(user_id, vec![])]),
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap();
+        let provider = RecommendationsProvider::from_parts(storage, engine);
+
+        assert!(provider.diff_from_baseline(user_id).is_none());
+        let baseline = provider.set_baseline(user_id);
+        assert_eq!(baseline.most_popular, vec![1, 3, 2]);
+        assert_eq!(
+            provider.diff_from_baseline(user_id),
+            Some(RecommendationsDiff::default())
+        );
+
+        provider
+            .recommendations_engine
+            .write()
+            .update_recommendations_for_users(
+                &provider.coefficients_storage.lock(),
+                &HashMap::from([(user_id, vec![1])]),
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap();
+
+        let diff = provider.diff_from_baseline(user_id).unwrap();
+        assert_eq!(
+            diff.most_popular,
+            BookIdsDiff {
+                added: vec![],
+                removed: vec![1],
+            }
+        );
+        assert_eq!(
+            diff.author_match,
+            BookIdsDiff {
+                added: vec![2],
+                removed: vec![],
+            }
+        );
+        assert_eq!(
+            diff.new_releases,
+            BookIdsDiff {
+                added: vec![],
+                removed: vec![1],
+            }
+        );
+        assert_eq!(provider.snapshot_for(user_id).most_popular, vec![3, 2]);
+    }
+
+    /// Starts mock of both downstream services, listing users fails the given number of times first,
+    /// afterwards there are no users nor books
+    fn start_flaky_downstream(failures: usize) -> String {
+        let list_users_calls = Arc::new(AtomicUsize::new(0));
+        let server = HttpServer::new(move || {
+            let list_users_calls = list_users_calls.clone();
+            App::new()
+                .route(
+                    "/api/users",
+                    actix_web::web::get().to(move || {
+                        let calls = list_users_calls.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            if calls < failures {
+                                HttpResponse::InternalServerError().finish()
+                            } else {
+                                HttpResponse::Ok().json(Vec::<UserId>::new())
+                            }
+                        }
+                    }),
+                )
+                .route(
+                    "/api/books",
+                    actix_web::web::get()
+                        .to(|| async { HttpResponse::Ok().json(Vec::<BookId>::new()) }),
+                )
+                .default_service(actix_web::web::to(|| async {
+                    HttpResponse::Ok().json(HashMap::<UserId, Vec<BookId>>::new())
+                }))
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind flaky downstream server");
+        let url = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+        url
+    }
+
+    /// 1. Start downstream services that fail the first 3 ticks and then recover
+    /// 2. Run ticks of updater allowing 5 failures in a row - expect it keeps running and completes a tick
+    /// 3. Run ticks of updater allowing only 2 failures in a row - expect it stops with an error
+    #[actix_web::test]
+    async fn test_updater_survives_transient_tick_failures() {
+        let interval = std::time::Duration::from_millis(20);
+
+        let url = start_flaky_downstream(3);
+        let updater = Arc::new(
+            RecommendationsUpdater::new(&url, &url)
+                .unwrap()
+                .with_max_consecutive_tick_failures(5),
+        );
+        let running_updater = updater.clone();
+        let updater_handle =
+            actix_web::rt::spawn(async move { running_updater.run_ticks(interval).await });
+        let mut waited = std::time::Duration::ZERO;
+        while updater
+            .provider()
+            .get_status()
+            .last_tick_completed_at
+            .is_none()
+        {
+            assert!(
+                waited < std::time::Duration::from_secs(5),
+                "No tick completed after failures"
+            );
+            tokio::time::sleep(interval).await;
+            waited += interval;
+        }
+        assert!(!updater_handle.is_finished());
+        assert!(updater.provider().get_status().reservations.reachable);
+        updater_handle.abort();
+
+        let url = start_flaky_downstream(3);
+        let updater = RecommendationsUpdater::new(&url, &url)
+            .unwrap()
+            .with_max_consecutive_tick_failures(2);
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            updater.run_ticks(interval),
+        )
+        .await
+        .expect("Updater did not give up");
+        assert!(result.is_err());
+        assert!(updater
+            .provider()
+            .get_status()
+            .last_tick_completed_at
+            .is_none());
+    }
+
+    /// 1. Start downstream services and run ticks of updater with a fast interval
+    /// 2. Wait for ticks to run and signal shutdown - expect the updater to stop without an error
+    /// 3. Wait a few intervals - expect no more ticks are run
+    #[actix_web::test]
+    async fn test_updater_stops_on_shutdown() {
+        let interval = std::time::Duration::from_millis(20);
+        let list_books_calls = Arc::new(AtomicUsize::new(0));
+        let url = start_counting_downstream(list_books_calls.clone());
+        let updater = Arc::new(
+            RecommendationsUpdater::new(&url, &url)
+                .unwrap()
+                .with_eager_book_load(true),
+        );
+        let shutdown = updater.shutdown_handle();
+        let running_updater = updater.clone();
+        let updater_handle =
+            actix_web::rt::spawn(async move { running_updater.run_ticks(interval).await });
+
+        let mut waited = std::time::Duration::ZERO;
+        while list_books_calls.load(Ordering::SeqCst) < 2 {
+            assert!(
+                waited < std::time::Duration::from_secs(5),
+                "Ticks did not run"
+            );
+            tokio::time::sleep(interval).await;
+            waited += interval;
+        }
+        shutdown.shutdown();
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), updater_handle)
+            .await
+            .expect("Updater did not stop after shutdown")
+            .unwrap();
+        assert!(result.is_ok());
+
+        let calls_after_shutdown = list_books_calls.load(Ordering::SeqCst);
+        tokio::time::sleep(interval * 3).await;
+        assert_eq!(
+            list_books_calls.load(Ordering::SeqCst),
+            calls_after_shutdown
+        );
+    }
+
+    /// Starts mock of repository service with two books counting calls listing all books,
+    /// reservations part returns no users
+    fn start_counting_downstream(list_books_calls: Arc<AtomicUsize>) -> String {
+        let server = HttpServer::new(move || {
+            let list_books_calls = list_books_calls.clone();
+            App::new()
+                .route(
+                    "/api/users",
+                    actix_web::web::get()
+                        .to(|| async { HttpResponse::Ok().json(Vec::<UserId>::new()) }),
+                )
+                .route(
+                    "/api/books",
+                    actix_web::web::get().to(move || {
+                        list_books_calls.fetch_add(1, Ordering::SeqCst);
+                        async {
+                            HttpResponse::Ok().json(
+                                [1, 2]
+                                    .map(|book_id| BookTitleAndId {
+                                        book_id,
+                                        title: "title".to_string(),
+                                    })
+                                    .to_vec(),
+                            )
+                        }
+                    }),
+                )
+                .route(
+                    "/api/book/{book_id}",
+                    actix_web::web::get().to(|| async {
+                        HttpResponse::Ok().json(BookDetails {
+                            title: "title".to_string(),
+                            authors: vec!["author".to_string()],
+                            publisher: "".to_string(),
+                            description: "".to_string(),
+                            tags: vec!["scifi".to_string()],
+                            isbn: None,
+                        })
+                    }),
+                )
+                .default_service(actix_web::web::to(|| async {
+                    HttpResponse::Ok().json(HashMap::<UserId, Vec<BookId>>::new())
+                }))
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind counting downstream server");
+        let url = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+        url
+    }
+
+    /// 1. Start mock downstream services counting calls listing all books
+    /// 2. Run two ticks other than the first one with eager book load - expect all books listed in each tick
+    ///    and their details stored
+    /// 3. Run the same ticks without eager book load - expect no books listed
+    #[actix_web::test]
+    async fn test_eager_book_load_fetches_all_books_every_tick() {
+        let list_books_calls = Arc::new(AtomicUsize::new(0));
+        let url = start_counting_downstream(list_books_calls.clone());
+        let updater = RecommendationsUpdater::new(&url, &url)
+            .unwrap()
+            .with_eager_book_load(true);
+        let mut processed_users_to_last_updated = HashMap::new();
+        for interval_no in [1, 2] {
+            updater
+                .run_tick(interval_no, &mut processed_users_to_last_updated)
+                .await
+                .unwrap();
+        }
+        assert_eq!(list_books_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            updater.provider().books_with_tag("scifi"),
+            HashSet::from([1, 2])
+        );
+
+        let list_books_calls = Arc::new(AtomicUsize::new(0));
+        let url = start_counting_downstream(list_books_calls.clone());
+        let updater = RecommendationsUpdater::new(&url, &url).unwrap();
+        for interval_no in [1, 2] {
+            updater
+                .run_tick(interval_no, &mut processed_users_to_last_updated)
+                .await
+                .unwrap();
+        }
+        assert_eq!(list_books_calls.load(Ordering::SeqCst), 0);
+        assert!(updater.provider().books_with_tag("scifi").is_empty());
+    }
+
+    /// Starts mock of repository service with given number of books, each book details request takes a while
+    /// and the highest number of requests handled at the same time is recorded, reservations part returns no users
+    fn start_slow_downstream(number_of_books: BookId, max_in_flight: Arc<AtomicUsize>) -> String {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let server = HttpServer::new(move || {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            App::new()
+                .route(
+                    "/api/users",
+                    actix_web::web::get()
+                        .to(|| async { HttpResponse::Ok().json(Vec::<UserId>::new()) }),
+                )
+                .route(
+                    "/api/books",
+                    actix_web::web::get().to(move || async move {
+                        HttpResponse::Ok().json(
+                            (1..=number_of_books)
+                                .map(|book_id| BookTitleAndId {
+                                    book_id,
+                                    title: "title".to_string(),
+                                })
+                                .collect_vec(),
+                        )
+                    }),
+                )
+                .route(
+                    "/api/book/{book_id}",
+                    actix_web::web::get().to(move || {
+                        let in_flight = in_flight.clone();
+                        let max_in_flight = max_in_flight.clone();
+                        async move {
+                            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_in_flight.fetch_max(current, Ordering::SeqCst);
+                            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                            HttpResponse::Ok().json(BookDetails {
+                                title: "title".to_string(),
+                                authors: vec!["author".to_string()],
+                                publisher: "".to_string(),
+                                description: "".to_string(),
+                                tags: vec!["tag".to_string()],
+                                isbn: None,
+                            })
+                        }
+                    }),
+                )
+                .default_service(actix_web::web::to(|| async {
+                    HttpResponse::Ok().json(HashMap::<UserId, Vec<BookId>>::new())
+                }))
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind slow downstream server");
+        let url = format!("http://{}", server.addrs()[0]);
+        tokio::spawn(server.run());
+        url
+    }
+
+    /// 1. Start mock repository with 12 books answering details requests slowly
+    /// 2. Run a tick fetching all books with at most 3 concurrent requests
+    /// 3. Check that no more than 3 requests were in flight at once and details of all books are stored
+    #[actix_web::test]
+    async fn test_book_details_fetched_with_bounded_concurrency() {
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let url = start_slow_downstream(12, max_in_flight.clone());
+        let updater = RecommendationsUpdater::new(&url, &url)
+            .unwrap()
+            .with_max_concurrent_book_requests(3);
+
+        updater.run_tick(0, &mut Default::default()).await.unwrap();
+
+        let max_in_flight = max_in_flight.load(Ordering::SeqCst);
+        assert!(max_in_flight <= 3, "{} requests in flight", max_in_flight);
+        assert!(max_in_flight > 1, "Requests were not sent concurrently");
+        assert_eq!(
+            updater.provider().books_with_tag("tag"),
+            (1..=12).collect::<HashSet<BookId>>()
+        );
+    }
+
+    /// 1. Start repository and reservations services and add two users
+    /// 2. Run a tick - expect both users are processed
+    /// 3. Delete data of the second user and requeue both users - expect none of them was skipped before
+    /// 4. Run a tick - expect the first user is processed again and the second one is not, its details are gone
+    /// 5. Requeue both users - expect only the second one was skipped
+    #[actix_web::test]
+    async fn test_requeue_skipped_user() {
+        let (repository_url, reservations_url) = start_downstream_services();
+        let reservations_client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                reservations_client
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let updater = RecommendationsUpdater::new(&repository_url, &reservations_url).unwrap();
+        let mut processed_users_to_last_updated = HashMap::new();
+
+        updater
+            .run_tick(1, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+        let first_processed_at = processed_users_to_last_updated.clone();
+        assert!(user_ids
+            .iter()
+            .all(|user_id| first_processed_at.contains_key(user_id)));
+
+        assert!(reservations_client
+            .delete_user_data(user_ids[1], HistoryRetention::Delete)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(!updater.requeue_user(user_ids[0]));
+        assert!(!updater.requeue_user(user_ids[1]));
+
+        updater
+            .run_tick(2, &mut processed_users_to_last_updated)
+            .await
+            .unwrap();
+        assert!(processed_users_to_last_updated[&user_ids[0]] > first_processed_at[&user_ids[0]]);
+        assert_eq!(
+            processed_users_to_last_updated[&user_ids[1]],
+            first_processed_at[&user_ids[1]]
+        );
+
+        assert!(!updater.requeue_user(user_ids[0]));
+        assert!(updater.requeue_user(user_ids[1]));
+    }
+}
@@ -1,7 +1,17 @@
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-pub type BookId = i32;
+use bookservice_types::text_validation::{
+    validate_all_no_control_characters, validate_no_control_characters, ControlCharactersError,
+};
+pub use bookservice_types::{BookId, UserId};
+
+/// Number of similar books returned when no limit is given
+pub const DEFAULT_SIMILAR_BOOKS_LIMIT: u32 = 10;
+/// Number of authors returned when no limit is given
+pub const DEFAULT_AUTHORS_LIMIT: u32 = 20;
+/// Maximal number of authors of books listed by author count when no maximum is given (books without authors)
+pub const DEFAULT_MAX_AUTHOR_COUNT: u32 = 0;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
 /// Struct containing book id and title
@@ -18,9 +28,59 @@ pub struct BookDetails {
     pub publisher: String,
     pub description: String,
     pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<String>,
+}
+
+impl BookDetails {
+    /// Returns error if any of the text fields contains control characters
+    pub fn validate_text(&self) -> Result<(), ControlCharactersError> {
+        validate_no_control_characters("title", &self.title)?;
+        validate_all_no_control_characters("authors", self.authors.iter().map(String::as_str))?;
+        validate_no_control_characters("publisher", &self.publisher)?;
+        validate_no_control_characters("description", &self.description)?;
+        validate_all_no_control_characters("tags", self.tags.iter().map(String::as_str))?;
+        validate_all_no_control_characters("isbn", self.isbn.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Struct containing book id and details of the book
+pub struct BookIdAndDetails {
+    pub book_id: BookId,
+    pub details: BookDetails,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Details of the book together with its reservation status, read in a single query
+/// from the database shared with the reservations service
+pub struct BookDetailsWithAvailability {
+    pub details: BookDetails,
+    pub reserved: bool,
+    /// User holding the book, None if the book is not reserved
+    pub holder: Option<UserId>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Audit timestamps of the book, missing for books stored before they were recorded
+pub struct BookMetadata {
+    /// Unix timestamp (in seconds) of adding the book
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    /// Unix timestamp (in seconds) of the last update of the book (adding it if never updated)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Details of the book with its metadata nested under `metadata` key
+pub struct BookDetailsWithMetadata {
+    #[serde(flatten)]
+    pub details: BookDetails,
+    pub metadata: BookMetadata,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, Apiv2Schema)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
 /// Struct representing a patch to book details. Allows to specify only a few fields and patch the current details
 pub struct BookDetailsPatch {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -33,4 +93,127 @@ pub struct BookDetailsPatch {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// The same patch applied to each of the listed books
+pub struct BulkBookDetailsPatch {
+    pub book_ids: Vec<BookId>,
+    pub patch: BookDetailsPatch,
+}
+
+impl BookDetailsPatch {
+    /// Returns error if any of the given text fields contains control characters
+    pub fn validate_text(&self) -> Result<(), ControlCharactersError> {
+        validate_all_no_control_characters("title", self.title.as_deref())?;
+        validate_all_no_control_characters(
+            "authors",
+            self.authors.iter().flatten().map(String::as_str),
+        )?;
+        validate_all_no_control_characters("publisher", self.publisher.as_deref())?;
+        validate_all_no_control_characters("description", self.description.as_deref())?;
+        validate_all_no_control_characters("tags", self.tags.iter().flatten().map(String::as_str))?;
+        validate_all_no_control_characters("isbn", self.isbn.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Patch applied to the book details by an update
+pub struct BookChange {
+    /// Unix timestamp (in seconds) of the update
+    pub changed_at: i64,
+    pub patch: BookDetailsPatch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Rename of a tag on every book that has it
+pub struct TagRename {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Replacement of variant spellings of an author with a single canonical name on every book
+pub struct AuthorsMerge {
+    pub from: Vec<String>,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Summary of rebuilding indexes of the books table
+pub struct ReindexSummary {
+    /// Names of rebuilt indexes, empty for repository without indexes to rebuild
+    pub reindexed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Popularity score of a book, periodically set by recommendations service
+pub struct BookPopularity {
+    pub popularity: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+/// Order of listed books
+pub enum BooksSort {
+    /// Most popular books first, books with equal popularity are ordered by id
+    Popularity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Author with the number of books written by the author
+pub struct AuthorBooksCount {
+    pub author: String,
+    pub books: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Author that co-wrote books with another author, with the number of these books
+pub struct AuthorCollaborator {
+    pub author: String,
+    pub co_authored_books: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct AuthorsQuery {
+    /// If given only authors containing this text (case insensitive) are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// Maximal number of returned authors, defaults to 20
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Page of listed books, books are ordered by id unless sorted otherwise
+pub struct BooksPageQuery {
+    /// Maximal number of returned books, all books if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    /// Number of skipped books, defaults to 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct SearchBooksQuery {
+    /// Text contained in titles of returned books (case insensitive), all books are returned if not given or empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct AuthorCountQuery {
+    /// Maximal number of authors of returned books, defaults to 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct SimilarBooksQuery {
+    /// Maximal number of returned books, defaults to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
 }
@@ -1,20 +1,90 @@
 use paperclip::actix::web;
 
+use std::sync::Arc;
+
+use crate::books_repository::{BookRepository, BookRepositoryHandle};
 use crate::handlers;
 
+/// Configures the app with the repository kept in app data as `Arc<dyn BookRepository + Send + Sync>`
 pub fn config_app(cfg: &mut web::ServiceConfig) {
+    config_app_for::<Arc<dyn BookRepository + Send + Sync>>(cfg)
+}
+
+/// Configures the app with handlers monomorphized for given repository handle kept in app data,
+/// e.g. `Arc<InMemoryBookRepository>` to avoid dynamic dispatch of repository calls
+pub fn config_app_for<R: BookRepositoryHandle>(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/health").route(web::get().to(handlers::health)))
         .service(
             web::scope("/api")
-                .service(web::resource("/books").route(web::get().to(handlers::get_all_books)))
+                .service(
+                    web::resource("/books")
+                        .route(web::get().to(handlers::get_all_books::<R>))
+                        .route(web::patch().to(handlers::update_books::<R>)),
+                )
+                .service(
+                    web::resource("/books/search")
+                        .route(web::get().to(handlers::search_books::<R>)),
+                )
+                .service(
+                    web::resource("/books/by_author_count")
+                        .route(web::get().to(handlers::get_books_by_author_count::<R>)),
+                )
+                .service(web::resource("/authors").route(web::get().to(handlers::get_authors::<R>)))
+                .service(
+                    web::resource("/author/{author}/collaborators")
+                        .route(web::get().to(handlers::get_author_collaborators::<R>)),
+                )
+                .service(
+                    web::resource("/admin/tags/rename")
+                        .route(web::post().to(handlers::rename_tag::<R>)),
+                )
+                .service(
+                    web::resource("/admin/reindex").route(web::post().to(handlers::reindex::<R>)),
+                )
+                .service(
+                    web::resource("/admin/authors/merge")
+                        .route(web::post().to(handlers::merge_authors::<R>)),
+                )
+                .service(
+                    web::resource("/admin/book/{book_id}/popularity")
+                        .route(web::post().to(handlers::set_book_popularity::<R>)),
+                )
                 .service(
                     web::scope("/book")
-                        .service(web::resource("").route(web::post().to(handlers::add_book)))
+                        .service(web::resource("").route(web::post().to(handlers::add_book::<R>)))
+                        .service(
+                            web::resource("/isbn/{isbn}")
+                                .route(web::get().to(handlers::get_book_by_isbn::<R>)),
+                        )
+                        .service(
+                            web::resource("/{book_id}/availability")
+                                .route(web::get().to(handlers::get_book_with_availability::<R>)),
+                        )
+                        .service(
+                            web::resource("/{book_id}/changes")
+                                .route(web::get().to(handlers::get_book_changes::<R>)),
+                        )
+                        .service(
+                            web::resource("/{book_id}/tags")
+                                .route(web::put().to(handlers::set_book_tags::<R>)),
+                        )
+                        .service(
+                            web::resource("/{book_id}/similar")
+                                .route(web::get().to(handlers::get_similar_books::<R>)),
+                        )
                         .service(
                             web::resource("/{book_id}")
-                                .route(web::get().to(handlers::get_book))
-                                .route(web::patch().to(handlers::update_book)),
+                                .route(web::get().to(handlers::get_book::<R>))
+                                .route(web::patch().to(handlers::update_book::<R>))
+                                .route(web::delete().to(handlers::delete_book::<R>)),
                         ),
                 ),
         );
 }
+
+/// Serves the given OpenAPI spec converted to YAML under the given path
+pub fn config_yaml_spec(cfg: &mut web::ServiceConfig, path: &str, spec: serde_json::Value) {
+    let spec_yaml = serde_yaml::to_string(&spec).expect("Failed to convert spec to yaml");
+    cfg.app_data(web::Data::new(handlers::YamlSpec(spec_yaml)))
+        .service(web::resource(path).route(web::get().to(handlers::get_yaml_spec)));
+}
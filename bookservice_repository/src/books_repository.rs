@@ -1,12 +1,91 @@
+use std::collections::HashMap;
+use std::env;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+
+#[cfg(any(feature = "fault_injection", test))]
+pub use fault_injecting_books_repository::{FaultInjectingBookRepository, FaultInjectionConfig};
 pub use in_memory_books_repository::InMemoryBookRepository;
 pub use postgres_books_repository::{PostgresBooksRepository, PostgresBooksRepositoryConfig};
 
 use crate::api;
-use crate::api::{BookDetails, BookId, BookTitleAndId};
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, BookChange, BookDetails, BookDetailsWithAvailability,
+    BookId, BookIdAndDetails, BookTitleAndId,
+};
 
+#[cfg(any(feature = "fault_injection", test))]
+mod fault_injecting_books_repository;
 mod in_memory_books_repository;
 mod postgres_books_repository;
 
+/// Selects and configures the books repository backend
+pub struct BookRepositoryConfig {
+    /// Use in memory repository instead of postgres (data is not persisted)
+    pub use_in_memory_db: bool,
+    pub postgres: PostgresBooksRepositoryConfig,
+}
+
+impl BookRepositoryConfig {
+    /// Reads config from `USE_IN_MEMORY_DB`, `DB_HOST`, `DB_USERNAME`, `DB_PASSWORD` and `SHARED_DB` env variables
+    pub fn from_env() -> Self {
+        Self {
+            use_in_memory_db: env::var("USE_IN_MEMORY_DB")
+                .map(|value| value.to_lowercase() == "true")
+                .unwrap_or(false),
+            postgres: PostgresBooksRepositoryConfig {
+                hostname: env::var("DB_HOST").unwrap_or("127.0.0.1".to_string()),
+                username: env::var("DB_USERNAME").unwrap_or("postgres".to_string()),
+                password: env::var("DB_PASSWORD").unwrap_or("postgres".to_string()),
+                shared_db: env::var("SHARED_DB")
+                    .map(|value| value.to_lowercase() == "true")
+                    .unwrap_or(false),
+            },
+        }
+    }
+}
+
+/// Creates the books repository backend selected in the config
+pub async fn build_book_repository(
+    config: BookRepositoryConfig,
+) -> anyhow::Result<Arc<dyn BookRepository + Send + Sync>> {
+    if config.use_in_memory_db {
+        Ok(Arc::new(InMemoryBookRepository::default()))
+    } else {
+        Ok(Arc::new(
+            PostgresBooksRepository::init(config.postgres).await?,
+        ))
+    }
+}
+
+/// Adds every book from a JSON file containing a list of `BookDetails`, returns number of added books
+/// Missing file is skipped with a warning, repository that already has books is not seeded again
+/// (so that restarts with persistent repository do not duplicate the books)
+pub async fn seed_from_file(
+    repo: &(dyn BookRepository + Send + Sync),
+    path: &Path,
+) -> anyhow::Result<usize> {
+    if !path.exists() {
+        tracing::warn!(path = %path.display(), "Seed file not found, skipping seeding");
+        return Ok(0);
+    }
+    if !repo.list_books(Some(1), None).await?.is_empty() {
+        tracing::info!("Repository already has books, skipping seeding");
+        return Ok(0);
+    }
+    let content = std::fs::read_to_string(path).context("Failed to read seed file")?;
+    let books: Vec<BookDetails> =
+        serde_json::from_str(&content).context("Failed to parse seed file")?;
+    let no_of_books = books.len();
+    for book in books {
+        repo.add_book(book).await?;
+    }
+    Ok(no_of_books)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum BookRepositoryError {
     // #[error("an unspecified internal error occurred: {0}")]
@@ -14,6 +93,12 @@ pub enum BookRepositoryError {
     #[error("Book {0} not found")]
     NotFound(BookId),
 
+    #[error("Book with ISBN {0} not found")]
+    IsbnNotFound(String),
+
+    #[error("Reservations can be read only from the database shared with reservations service")]
+    SharedDbDisabled,
+
     #[error("Failed to deserialize book: {0}")]
     DeserializationError(#[from] serde_json::Error),
 
@@ -28,14 +113,178 @@ pub enum BookRepositoryError {
 pub trait BookRepository {
     /// Adds book to repository, returns an id assigned to the book
     async fn add_book(&self, details: BookDetails) -> Result<BookId, BookRepositoryError>;
-    /// Updates book in the repository, returns true if book was updated and false if it was not found
+    /// Updates book in the repository and records the applied patch in book changes,
+    /// returns true if book was updated and false if it was not found
     async fn update_book(
         &self,
         book_id: BookId,
         patch: api::BookDetailsPatch,
     ) -> Result<bool, BookRepositoryError>;
+    /// Updates all given books with the same patch at once and records it in changes of each of them,
+    /// returns for each of the books true if it was updated and false if it was not found
+    async fn update_books(
+        &self,
+        book_ids: &[BookId],
+        patch: api::BookDetailsPatch,
+    ) -> Result<HashMap<BookId, bool>, BookRepositoryError>;
+    /// Lists patches applied to the book by `update_book`, oldest first
+    async fn get_book_changes(
+        &self,
+        book_id: BookId,
+    ) -> Result<Vec<BookChange>, BookRepositoryError>;
+    /// Deletes the book together with its recorded changes, returns false if book was not found
+    async fn delete_book(&self, book_id: BookId) -> Result<bool, BookRepositoryError>;
     /// Retrieves details of the book from repository
     async fn get_book(&self, book_id: BookId) -> Result<BookDetails, BookRepositoryError>;
-    /// Lists all books in the repository
-    async fn list_books(&self) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Retrieves details of the book together with times of adding and last update of the book
+    async fn get_book_with_metadata(
+        &self,
+        book_id: BookId,
+    ) -> Result<api::BookDetailsWithMetadata, BookRepositoryError>;
+    /// Lists books in the repository ordered by id, skipping first `offset` books and returning at most `limit` of them
+    /// (all books if neither is given)
+    async fn list_books(
+        &self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Lists all books written by any of given authors
+    async fn list_books_by_authors(
+        &self,
+        authors: &[String],
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Lists books with titles containing `query` (case insensitive) ordered by id, empty query lists all books
+    async fn search_books(&self, query: &str) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Lists books with at most `max_authors` authors ordered by id
+    async fn list_books_by_author_count(
+        &self,
+        max_authors: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Retrieves details of the book together with its holder in a single query,
+    /// available only in postgres repository sharing the database with reservations service
+    async fn get_book_with_availability(
+        &self,
+        book_id: BookId,
+    ) -> Result<BookDetailsWithAvailability, BookRepositoryError>;
+    /// Retrieves the book with given ISBN, if more books share the ISBN the one with the lowest id is returned
+    async fn get_book_by_isbn(&self, isbn: &str) -> Result<BookIdAndDetails, BookRepositoryError>;
+    /// Replaces all tags of the book with given ones (possibly none), returns false if book was not found
+    async fn set_tags(
+        &self,
+        book_id: BookId,
+        tags: Vec<String>,
+    ) -> Result<bool, BookRepositoryError>;
+    /// Replaces tag `from` with `to` in every book that has it (without duplicating `to`),
+    /// returns number of changed books
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<u64, BookRepositoryError>;
+    /// Replaces any of `from` authors with `to` in every book written by any of them (without duplicating `to`),
+    /// returns number of changed books
+    async fn merge_authors(&self, from: &[String], to: &str) -> Result<u64, BookRepositoryError>;
+    /// Lists up to `limit` authors with number of their books, authors with most books first (ties ordered by name),
+    /// if `query` is given only authors containing it (case insensitive) are listed
+    async fn list_authors(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<AuthorBooksCount>, BookRepositoryError>;
+    /// Lists other authors appearing on the same books as the given author with number of these books,
+    /// most co-authored books first (ties ordered by name)
+    async fn list_author_collaborators(
+        &self,
+        author: &str,
+    ) -> Result<Vec<AuthorCollaborator>, BookRepositoryError>;
+    /// Lists up to `limit` other books sharing at least one tag with the given book, ranked by Jaccard similarity
+    /// of their tags (number of common tags divided by number of all tags of both books), ties are ordered by id
+    async fn similar_books_by_tags(
+        &self,
+        book_id: BookId,
+        limit: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError>;
+    /// Sets popularity score of the book, returns false if book was not found
+    async fn set_book_popularity(
+        &self,
+        book_id: BookId,
+        popularity: i64,
+    ) -> Result<bool, BookRepositoryError>;
+    /// Retrieves popularity scores of given books, books without a set score have 0 and not existing books are skipped
+    async fn get_books_popularity(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, i64>, BookRepositoryError>;
+    /// Rebuilds indexes of the books table (e.g. after a bulk import), no-op for in memory repository
+    async fn reindex(&self) -> Result<api::ReindexSummary, BookRepositoryError>;
+}
+
+/// Repository kept in app data, handlers are generic over it so they can be monomorphized for a concrete
+/// repository (`Arc<InMemoryBookRepository>`) instead of calling it through `Arc<dyn BookRepository + Send + Sync>`
+pub trait BookRepositoryHandle: Deref<Target: BookRepository> + 'static {}
+
+impl<T> BookRepositoryHandle for T where T: Deref<Target: BookRepository> + 'static {}
+
+#[cfg(test)]
+mod books_repository_tests {
+    use super::*;
+
+    #[tokio::test]
+    /// Tests if in memory repository is built when the flag is set, without connecting to postgres
+    async fn test_build_in_memory_book_repository() {
+        let repo = build_book_repository(BookRepositoryConfig {
+            use_in_memory_db: true,
+            postgres: PostgresBooksRepositoryConfig {
+                hostname: "unreachable.invalid".to_string(),
+                username: "postgres".to_string(),
+                password: "postgres".to_string(),
+                shared_db: false,
+            },
+        })
+        .await
+        .unwrap();
+
+        assert!(repo.list_books(None, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if books from seed file are added once and missing seed file is skipped
+    /// 1. Seeds empty repository - expect all books from the file added
+    /// 2. Seeds the repository again - expect nothing added as it already has books
+    /// 3. Seeds empty repository from missing file - expect nothing added
+    async fn test_seed_from_file() {
+        let repo = InMemoryBookRepository::default();
+        let books = vec![
+            BookDetails {
+                title: "title1".to_string(),
+                authors: vec!["author1".to_string()],
+                publisher: "publisher".to_string(),
+                description: "description".to_string(),
+                tags: vec!["tag".to_string()],
+                isbn: None,
+            },
+            BookDetails {
+                title: "title2".to_string(),
+                authors: vec!["author2".to_string()],
+                publisher: "publisher".to_string(),
+                description: "description".to_string(),
+                tags: vec![],
+                isbn: Some("978-3-16-148410-0".to_string()),
+            },
+        ];
+        let path = env::temp_dir().join(format!("bookservice_seed_{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string(&books).unwrap()).unwrap();
+
+        let no_of_books = seed_from_file(&repo, &path).await;
+        let no_of_books_reseeded = seed_from_file(&repo, &path).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(no_of_books.unwrap(), 2);
+        assert_eq!(no_of_books_reseeded.unwrap(), 0);
+
+        let mut seeded_books = vec![];
+        for book in repo.list_books(None, None).await.unwrap() {
+            seeded_books.push(repo.get_book(book.book_id).await.unwrap());
+        }
+        seeded_books.sort_by(|a, b| a.title.cmp(&b.title));
+        assert_eq!(seeded_books, books);
+
+        let empty_repo = InMemoryBookRepository::default();
+        assert_eq!(seed_from_file(&empty_repo, &path).await.unwrap(), 0);
+    }
 }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::api;
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, BookChange, BookDetails, BookDetailsWithAvailability,
+    BookId, BookIdAndDetails, BookTitleAndId,
+};
+use crate::books_repository::{BookRepository, BookRepositoryError};
+
+/// Probabilities of faults injected into every call of the repository
+#[derive(Debug, Clone)]
+pub struct FaultInjectionConfig {
+    /// Probability (from 0.0 to 1.0) that a call fails without reaching the inner repository
+    pub failure_probability: f64,
+    /// Probability (from 0.0 to 1.0) that a call is delayed by `delay`
+    pub delay_probability: f64,
+    pub delay: Duration,
+    /// Seed of the random generator, the same seed gives the same sequence of faults
+    pub seed: u64,
+}
+
+/// Repository wrapper that randomly fails or delays calls to the inner repository, used for resilience testing
+pub struct FaultInjectingBookRepository {
+    inner: Arc<dyn BookRepository + Send + Sync>,
+    config: FaultInjectionConfig,
+    rng: parking_lot::Mutex<StdRng>,
+}
+
+impl FaultInjectingBookRepository {
+    pub fn new(inner: Arc<dyn BookRepository + Send + Sync>, config: FaultInjectionConfig) -> Self {
+        Self {
+            inner,
+            rng: parking_lot::Mutex::new(StdRng::seed_from_u64(config.seed)),
+            config,
+        }
+    }
+
+    /// Draws faults for a single call, sleeps if the call is delayed and returns error if it fails
+    async fn inject_faults(&self, operation: &'static str) -> Result<(), BookRepositoryError> {
+        let (fail, delay) = {
+            let mut rng = self.rng.lock();
+            (
+                rng.gen::<f64>() < self.config.failure_probability,
+                rng.gen::<f64>() < self.config.delay_probability,
+            )
+        };
+        if delay {
+            tokio::time::sleep(self.config.delay).await;
+        }
+        if fail {
+            return Err(BookRepositoryError::Other(format!(
+                "Injected failure of {}",
+                operation
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl BookRepository for FaultInjectingBookRepository {
+    async fn add_book(&self, details: BookDetails) -> Result<BookId, BookRepositoryError> {
+        self.inject_faults("add_book").await?;
+        self.inner.add_book(details).await
+    }
+
+    async fn update_book(
+        &self,
+        book_id: BookId,
+        patch: api::BookDetailsPatch,
+    ) -> Result<bool, BookRepositoryError> {
+        self.inject_faults("update_book").await?;
+        self.inner.update_book(book_id, patch).await
+    }
+
+    async fn update_books(
+        &self,
+        book_ids: &[BookId],
+        patch: api::BookDetailsPatch,
+    ) -> Result<HashMap<BookId, bool>, BookRepositoryError> {
+        self.inject_faults("update_books").await?;
+        self.inner.update_books(book_ids, patch).await
+    }
+
+    async fn get_book_changes(
+        &self,
+        book_id: BookId,
+    ) -> Result<Vec<BookChange>, BookRepositoryError> {
+        self.inject_faults("get_book_changes").await?;
+        self.inner.get_book_changes(book_id).await
+    }
+
+    async fn get_book(&self, book_id: BookId) -> Result<BookDetails, BookRepositoryError> {
+        self.inject_faults("get_book").await?;
+        self.inner.get_book(book_id).await
+    }
+
+    async fn get_book_with_metadata(
+        &self,
+        book_id: BookId,
+    ) -> Result<api::BookDetailsWithMetadata, BookRepositoryError> {
+        self.inject_faults("get_book_with_metadata").await?;
+        self.inner.get_book_with_metadata(book_id).await
+    }
+
+    async fn get_book_with_availability(
+        &self,
+        book_id: BookId,
+    ) -> Result<BookDetailsWithAvailability, BookRepositoryError> {
+        self.inject_faults("get_book_with_availability").await?;
+        self.inner.get_book_with_availability(book_id).await
+    }
+
+    async fn list_books(
+        &self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        self.inject_faults("list_books").await?;
+        self.inner.list_books(limit, offset).await
+    }
+
+    async fn list_books_by_authors(
+        &self,
+        authors: &[String],
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        self.inject_faults("list_books_by_authors").await?;
+        self.inner.list_books_by_authors(authors).await
+    }
+
+    async fn search_books(&self, query: &str) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        self.inject_faults("search_books").await?;
+        self.inner.search_books(query).await
+    }
+
+    async fn list_books_by_author_count(
+        &self,
+        max_authors: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        self.inject_faults("list_books_by_author_count").await?;
+        self.inner.list_books_by_author_count(max_authors).await
+    }
+
+    async fn get_book_by_isbn(&self, isbn: &str) -> Result<BookIdAndDetails, BookRepositoryError> {
+        self.inject_faults("get_book_by_isbn").await?;
+        self.inner.get_book_by_isbn(isbn).await
+    }
+
+    async fn delete_book(&self, book_id: BookId) -> Result<bool, BookRepositoryError> {
+        self.inject_faults("delete_book").await?;
+        self.inner.delete_book(book_id).await
+    }
+
+    async fn set_tags(
+        &self,
+        book_id: BookId,
+        tags: Vec<String>,
+    ) -> Result<bool, BookRepositoryError> {
+        self.inject_faults("set_tags").await?;
+        self.inner.set_tags(book_id, tags).await
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<u64, BookRepositoryError> {
+        self.inject_faults("rename_tag").await?;
+        self.inner.rename_tag(from, to).await
+    }
+
+    async fn merge_authors(&self, from: &[String], to: &str) -> Result<u64, BookRepositoryError> {
+        self.inject_faults("merge_authors").await?;
+        self.inner.merge_authors(from, to).await
+    }
+
+    async fn list_authors(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<AuthorBooksCount>, BookRepositoryError> {
+        self.inject_faults("list_authors").await?;
+        self.inner.list_authors(query, limit).await
+    }
+
+    async fn list_author_collaborators(
+        &self,
+        author: &str,
+    ) -> Result<Vec<AuthorCollaborator>, BookRepositoryError> {
+        self.inject_faults("list_author_collaborators").await?;
+        self.inner.list_author_collaborators(author).await
+    }
+
+    async fn similar_books_by_tags(
+        &self,
+        book_id: BookId,
+        limit: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        self.inject_faults("similar_books_by_tags").await?;
+        self.inner.similar_books_by_tags(book_id, limit).await
+    }
+
+    async fn reindex(&self) -> Result<api::ReindexSummary, BookRepositoryError> {
+        self.inject_faults("reindex").await?;
+        self.inner.reindex().await
+    }
+
+    async fn set_book_popularity(
+        &self,
+        book_id: BookId,
+        popularity: i64,
+    ) -> Result<bool, BookRepositoryError> {
+        self.inject_faults("set_book_popularity").await?;
+        self.inner.set_book_popularity(book_id, popularity).await
+    }
+
+    async fn get_books_popularity(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, i64>, BookRepositoryError> {
+        self.inject_faults("get_books_popularity").await?;
+        self.inner.get_books_popularity(book_ids).await
+    }
+}
+
+#[cfg(test)]
+mod fault_injecting_book_repository_tests {
+    use super::*;
+    use crate::books_repository::InMemoryBookRepository;
+
+    fn book() -> BookDetails {
+        BookDetails {
+            title: "title".to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec!["tag".to_string()],
+            isbn: None,
+        }
+    }
+
+    fn config(failure_probability: f64) -> FaultInjectionConfig {
+        FaultInjectionConfig {
+            failure_probability,
+            delay_probability: 0.0,
+            delay: Duration::ZERO,
+            seed: 42,
+        }
+    }
+
+    #[tokio::test]
+    /// Tests if with 100% failure probability every call errors without reaching the inner repository
+    async fn test_every_call_fails() {
+        let inner: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let book_id = inner.add_book(book()).await.unwrap();
+        let repo = FaultInjectingBookRepository::new(inner.clone(), config(1.0));
+
+        for _ in 0..10 {
+            assert!(matches!(
+                repo.add_book(book()).await,
+                Err(BookRepositoryError::Other(..))
+            ));
+            assert!(repo.get_book(book_id).await.is_err());
+            assert!(repo.list_books(None, None).await.is_err());
+            assert!(repo.rename_tag("tag", "other").await.is_err());
+        }
+        assert_eq!(inner.list_books(None, None).await.unwrap().len(), 1);
+        assert_eq!(inner.get_book(book_id).await.unwrap(), book());
+    }
+
+    #[tokio::test]
+    /// Tests if with 0% failure probability every call passes through to the inner repository
+    async fn test_every_call_passes_through() {
+        let inner: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repo = FaultInjectingBookRepository::new(inner.clone(), config(0.0));
+
+        for _ in 0..10 {
+            let book_id = repo.add_book(book()).await.unwrap();
+            assert_eq!(repo.get_book(book_id).await.unwrap(), book());
+        }
+        assert_eq!(repo.list_books(None, None).await.unwrap().len(), 10);
+        assert_eq!(repo.rename_tag("tag", "other").await.unwrap(), 10);
+        assert_eq!(inner.list_books(None, None).await.unwrap().len(), 10);
+    }
+}
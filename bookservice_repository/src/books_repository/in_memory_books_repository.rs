@@ -1,23 +1,77 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde_json::json;
 
 use crate::api;
-use crate::api::{BookDetails, BookId, BookTitleAndId};
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, BookChange, BookDetails, BookDetailsWithAvailability,
+    BookDetailsWithMetadata, BookId, BookIdAndDetails, BookMetadata, BookTitleAndId,
+};
 use crate::books_repository::{BookRepository, BookRepositoryError};
 
 #[derive(Default)]
 pub struct InMemoryBookRepository {
     book_sequence_generator: AtomicI32,
     books: parking_lot::RwLock<HashMap<BookId, BookDetails>>,
+    /// Index of books by ISBN
+    isbn_to_book_ids: parking_lot::RwLock<HashMap<String, BTreeSet<BookId>>>,
+    /// Popularity scores of books that had it set
+    popularity: parking_lot::RwLock<HashMap<BookId, i64>>,
+    /// Patches applied to each of the updated books, oldest first
+    changes: parking_lot::RwLock<HashMap<BookId, Vec<BookChange>>>,
+    /// Times of adding and last update of each of the books
+    metadata: parking_lot::RwLock<HashMap<BookId, BookMetadata>>,
+}
+
+/// Current unix timestamp in seconds, 0 if the clock is before the epoch
+fn unix_timestamp_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl InMemoryBookRepository {
+    fn update_isbn_index(
+        &self,
+        book_id: BookId,
+        old_isbn: Option<&String>,
+        new_isbn: Option<&String>,
+    ) {
+        let mut isbn_to_book_ids = self.isbn_to_book_ids.write();
+        if let Some(old_isbn) = old_isbn {
+            if let Some(book_ids) = isbn_to_book_ids.get_mut(old_isbn) {
+                book_ids.remove(&book_id);
+                if book_ids.is_empty() {
+                    isbn_to_book_ids.remove(old_isbn);
+                }
+            }
+        }
+        if let Some(new_isbn) = new_isbn {
+            isbn_to_book_ids
+                .entry(new_isbn.clone())
+                .or_default()
+                .insert(book_id);
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl BookRepository for InMemoryBookRepository {
     async fn add_book(&self, details: api::BookDetails) -> Result<BookId, BookRepositoryError> {
         let id = self.book_sequence_generator.fetch_add(1, Ordering::Relaxed);
+        self.update_isbn_index(id, None, details.isbn.as_ref());
         self.books.write().insert(id, details);
+        let created_at = unix_timestamp_now();
+        self.metadata.write().insert(
+            id,
+            BookMetadata {
+                created_at: Some(created_at),
+                updated_at: Some(created_at),
+            },
+        );
         Ok(id)
     }
 
@@ -31,13 +85,83 @@ impl BookRepository for InMemoryBookRepository {
             let mut result_book = json!(book);
             json_patch::merge(&mut result_book, &json!(patch));
             let result_book: BookDetails = serde_json::from_value(result_book)?;
+            self.update_isbn_index(book_id, book.isbn.as_ref(), result_book.isbn.as_ref());
             *book = result_book;
+            let changed_at = unix_timestamp_now();
+            self.changes
+                .write()
+                .entry(book_id)
+                .or_default()
+                .push(BookChange { changed_at, patch });
+            self.metadata.write().entry(book_id).or_default().updated_at = Some(changed_at);
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    async fn update_books(
+        &self,
+        book_ids: &[BookId],
+        patch: api::BookDetailsPatch,
+    ) -> Result<HashMap<BookId, bool>, BookRepositoryError> {
+        let mut locked_books = self.books.write();
+        // All books are patched before any of them is changed, so that either all or none are updated
+        let mut patched_books = vec![];
+        for book_id in book_ids.iter().collect::<BTreeSet<_>>() {
+            if let Some(book) = locked_books.get(book_id) {
+                let mut result_book = json!(book);
+                json_patch::merge(&mut result_book, &json!(patch));
+                let result_book: BookDetails = serde_json::from_value(result_book)?;
+                patched_books.push((*book_id, result_book));
+            }
+        }
+        let changed_at = unix_timestamp_now();
+        let mut locked_changes = self.changes.write();
+        let mut locked_metadata = self.metadata.write();
+        let mut updated: HashMap<BookId, bool> =
+            book_ids.iter().map(|book_id| (*book_id, false)).collect();
+        for (book_id, result_book) in patched_books {
+            if let Some(book) = locked_books.get_mut(&book_id) {
+                self.update_isbn_index(book_id, book.isbn.as_ref(), result_book.isbn.as_ref());
+                *book = result_book;
+            }
+            locked_changes.entry(book_id).or_default().push(BookChange {
+                changed_at,
+                patch: patch.clone(),
+            });
+            locked_metadata.entry(book_id).or_default().updated_at = Some(changed_at);
+            updated.insert(book_id, true);
+        }
+        Ok(updated)
+    }
+
+    async fn get_book_changes(
+        &self,
+        book_id: BookId,
+    ) -> Result<Vec<BookChange>, BookRepositoryError> {
+        if !self.books.read().contains_key(&book_id) {
+            return Err(BookRepositoryError::NotFound(book_id));
+        }
+        Ok(self
+            .changes
+            .read()
+            .get(&book_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn delete_book(&self, book_id: BookId) -> Result<bool, BookRepositoryError> {
+        let Some(details) = self.books.write().remove(&book_id) else {
+            return Ok(false);
+        };
+        self.update_isbn_index(book_id, details.isbn.as_ref(), None);
+        self.popularity.write().remove(&book_id);
+        self.changes.write().remove(&book_id);
+        self.metadata.write().remove(&book_id);
+        Ok(true)
+    }
+
     async fn get_book(&self, book_id: BookId) -> Result<BookDetails, BookRepositoryError> {
         self.books
             .read()
@@ -46,22 +170,326 @@ impl BookRepository for InMemoryBookRepository {
             .ok_or(BookRepositoryError::NotFound(book_id))
     }
 
-    async fn list_books(&self) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+    async fn get_book_with_metadata(
+        &self,
+        book_id: BookId,
+    ) -> Result<BookDetailsWithMetadata, BookRepositoryError> {
+        Ok(BookDetailsWithMetadata {
+            details: self.get_book(book_id).await?,
+            metadata: self
+                .metadata
+                .read()
+                .get(&book_id)
+                .copied()
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn get_book_with_availability(
+        &self,
+        _book_id: BookId,
+    ) -> Result<BookDetailsWithAvailability, BookRepositoryError> {
+        Err(BookRepositoryError::SharedDbDisabled)
+    }
+
+    async fn list_books(
+        &self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let mut books: Vec<BookTitleAndId> = self
+            .books
+            .read()
+            .iter()
+            .map(|(&book_id, details)| BookTitleAndId {
+                book_id,
+                title: details.title.clone(),
+            })
+            .collect();
+        books.sort_by_key(|book| book.book_id);
+        Ok(books
+            .into_iter()
+            .skip(offset.unwrap_or_default().max(0) as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .collect())
+    }
+
+    async fn list_books_by_authors(
+        &self,
+        authors: &[String],
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let authors: HashSet<&String> = authors.iter().collect();
         Ok(self
             .books
             .read()
             .iter()
+            .filter(|(_, details)| {
+                details
+                    .authors
+                    .iter()
+                    .any(|author| authors.contains(author))
+            })
             .map(|(&book_id, details)| BookTitleAndId {
                 book_id,
                 title: details.title.clone(),
             })
             .collect())
     }
+
+    async fn search_books(&self, query: &str) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let query = query.to_lowercase();
+        let mut books: Vec<BookTitleAndId> = self
+            .books
+            .read()
+            .iter()
+            .filter(|(_, details)| details.title.to_lowercase().contains(&query))
+            .map(|(&book_id, details)| BookTitleAndId {
+                book_id,
+                title: details.title.clone(),
+            })
+            .collect();
+        books.sort_by_key(|book| book.book_id);
+        Ok(books)
+    }
+
+    async fn list_books_by_author_count(
+        &self,
+        max_authors: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let mut books: Vec<BookTitleAndId> = self
+            .books
+            .read()
+            .iter()
+            .filter(|(_, details)| details.authors.len() <= max_authors as usize)
+            .map(|(&book_id, details)| BookTitleAndId {
+                book_id,
+                title: details.title.clone(),
+            })
+            .collect();
+        books.sort_by_key(|book| book.book_id);
+        Ok(books)
+    }
+
+    async fn get_book_by_isbn(&self, isbn: &str) -> Result<BookIdAndDetails, BookRepositoryError> {
+        let book_id = self
+            .isbn_to_book_ids
+            .read()
+            .get(isbn)
+            .and_then(|book_ids| book_ids.first().cloned())
+            .ok_or_else(|| BookRepositoryError::IsbnNotFound(isbn.to_string()))?;
+        Ok(BookIdAndDetails {
+            book_id,
+            details: self.get_book(book_id).await?,
+        })
+    }
+
+    async fn set_tags(
+        &self,
+        book_id: BookId,
+        tags: Vec<String>,
+    ) -> Result<bool, BookRepositoryError> {
+        if let Some(book) = self.books.write().get_mut(&book_id) {
+            book.tags = tags;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<u64, BookRepositoryError> {
+        let mut renamed_books = 0;
+        for book in self.books.write().values_mut() {
+            if book.tags.iter().any(|tag| tag == from) {
+                let mut seen_tags = HashSet::new();
+                book.tags = book
+                    .tags
+                    .drain(..)
+                    .map(|tag| if tag == from { to.to_string() } else { tag })
+                    .filter(|tag| seen_tags.insert(tag.clone()))
+                    .collect();
+                renamed_books += 1;
+            }
+        }
+        Ok(renamed_books)
+    }
+
+    async fn merge_authors(&self, from: &[String], to: &str) -> Result<u64, BookRepositoryError> {
+        let from: HashSet<&String> = from.iter().collect();
+        let mut merged_books = 0;
+        for book in self.books.write().values_mut() {
+            if book.authors.iter().any(|author| from.contains(author)) {
+                let mut seen_authors = HashSet::new();
+                book.authors = book
+                    .authors
+                    .drain(..)
+                    .map(|author| {
+                        if from.contains(&author) {
+                            to.to_string()
+                        } else {
+                            author
+                        }
+                    })
+                    .filter(|author| seen_authors.insert(author.clone()))
+                    .collect();
+                merged_books += 1;
+            }
+        }
+        Ok(merged_books)
+    }
+
+    async fn list_authors(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<AuthorBooksCount>, BookRepositoryError> {
+        let query = query.map(str::to_lowercase);
+        let books_lock = self.books.read();
+        let mut author_to_books: HashMap<&str, u64> = HashMap::new();
+        for author in books_lock
+            .values()
+            .flat_map(|details| details.authors.iter())
+        {
+            let matches = match &query {
+                Some(query) => author.to_lowercase().contains(query),
+                None => true,
+            };
+            if matches {
+                *author_to_books.entry(author).or_default() += 1;
+            }
+        }
+
+        let mut authors: Vec<AuthorBooksCount> = author_to_books
+            .into_iter()
+            .map(|(author, books)| AuthorBooksCount {
+                author: author.to_string(),
+                books,
+            })
+            .collect();
+        authors.sort_by(|a, b| b.books.cmp(&a.books).then_with(|| a.author.cmp(&b.author)));
+        authors.truncate(limit as usize);
+        Ok(authors)
+    }
+
+    async fn list_author_collaborators(
+        &self,
+        author: &str,
+    ) -> Result<Vec<AuthorCollaborator>, BookRepositoryError> {
+        let books_lock = self.books.read();
+        let mut collaborator_to_books: HashMap<&str, u64> = HashMap::new();
+        for details in books_lock
+            .values()
+            .filter(|details| details.authors.iter().any(|a| a == author))
+        {
+            let collaborators: HashSet<&str> = details
+                .authors
+                .iter()
+                .map(String::as_str)
+                .filter(|collaborator| *collaborator != author)
+                .collect();
+            for collaborator in collaborators {
+                *collaborator_to_books.entry(collaborator).or_default() += 1;
+            }
+        }
+
+        let mut collaborators: Vec<AuthorCollaborator> = collaborator_to_books
+            .into_iter()
+            .map(|(author, co_authored_books)| AuthorCollaborator {
+                author: author.to_string(),
+                co_authored_books,
+            })
+            .collect();
+        collaborators.sort_by(|a, b| {
+            b.co_authored_books
+                .cmp(&a.co_authored_books)
+                .then_with(|| a.author.cmp(&b.author))
+        });
+        Ok(collaborators)
+    }
+
+    async fn similar_books_by_tags(
+        &self,
+        book_id: BookId,
+        limit: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let books = self.books.read();
+        let tags: HashSet<&String> = books
+            .get(&book_id)
+            .ok_or(BookRepositoryError::NotFound(book_id))?
+            .tags
+            .iter()
+            .collect();
+
+        let mut scored_books: Vec<(f64, BookId, &String)> = books
+            .iter()
+            .filter(|(&other_book_id, _)| other_book_id != book_id)
+            .filter_map(|(&other_book_id, details)| {
+                let other_tags: HashSet<&String> = details.tags.iter().collect();
+                let common_tags = tags.intersection(&other_tags).count();
+                (common_tags > 0).then(|| {
+                    let all_tags = tags.union(&other_tags).count();
+                    (
+                        common_tags as f64 / all_tags as f64,
+                        other_book_id,
+                        &details.title,
+                    )
+                })
+            })
+            .collect();
+        scored_books.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        Ok(scored_books
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, book_id, title)| BookTitleAndId {
+                book_id,
+                title: title.clone(),
+            })
+            .collect())
+    }
+
+    async fn reindex(&self) -> Result<api::ReindexSummary, BookRepositoryError> {
+        Ok(api::ReindexSummary { reindexed: vec![] })
+    }
+
+    async fn set_book_popularity(
+        &self,
+        book_id: BookId,
+        popularity: i64,
+    ) -> Result<bool, BookRepositoryError> {
+        if !self.books.read().contains_key(&book_id) {
+            return Ok(false);
+        }
+        self.popularity.write().insert(book_id, popularity);
+        Ok(true)
+    }
+
+    async fn get_books_popularity(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, i64>, BookRepositoryError> {
+        let books = self.books.read();
+        let popularity = self.popularity.read();
+        Ok(book_ids
+            .iter()
+            .filter(|book_id| books.contains_key(book_id))
+            .map(|book_id| {
+                (
+                    *book_id,
+                    popularity.get(book_id).cloned().unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
 mod in_memory_book_repository_tests {
-    use crate::api::{BookDetails, BookDetailsPatch, BookTitleAndId};
+    use std::collections::HashMap;
+
+    use crate::api::{
+        AuthorBooksCount, AuthorCollaborator, BookDetails, BookDetailsPatch, BookIdAndDetails,
+        BookMetadata, BookTitleAndId,
+    };
     use crate::books_repository::{BookRepository, BookRepositoryError, InMemoryBookRepository};
 
     #[tokio::test]
@@ -83,6 +511,7 @@ mod in_memory_book_repository_tests {
             publisher: "".to_string(),
             description: "".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
         let id = repo
             .add_book(book_details.clone())
@@ -99,7 +528,10 @@ mod in_memory_book_repository_tests {
     async fn test_add_books_and_list_them() {
         let repo = InMemoryBookRepository::default();
 
-        let list = repo.list_books().await.expect("Failed to list books");
+        let list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
         assert_eq!(list, vec![]);
 
         let book1_details = BookDetails {
@@ -108,6 +540,7 @@ mod in_memory_book_repository_tests {
             publisher: "".to_string(),
             description: "".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
 
         let book2_details = BookDetails {
@@ -120,7 +553,10 @@ mod in_memory_book_repository_tests {
             .await
             .expect("Failed to add book");
 
-        let list = repo.list_books().await.expect("Failed to list books");
+        let list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
 
         assert_eq!(
             list,
@@ -135,7 +571,10 @@ mod in_memory_book_repository_tests {
             .await
             .expect("Failed to add book");
 
-        let mut list = repo.list_books().await.expect("Failed to list books");
+        let mut list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
 
         list.sort_by_key(|i| i.book_id);
 
@@ -154,6 +593,58 @@ mod in_memory_book_repository_tests {
         );
     }
 
+    #[tokio::test]
+    /// Tests if every applied patch is recorded in book changes
+    /// 1.Adds a book - expect no changes
+    /// 2.Applies two patches - expect both recorded in order with timestamps
+    /// 3.Gets changes of not existing book - expect not found error
+    async fn test_get_book_changes() {
+        let repo = InMemoryBookRepository::default();
+
+        let book_id = repo
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec!["author".to_string()],
+                publisher: "publisher".to_string(),
+                description: "description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        assert!(repo.get_book_changes(book_id).await.unwrap().is_empty());
+
+        let patches = vec![
+            BookDetailsPatch {
+                title: Some("new title".to_string()),
+                ..Default::default()
+            },
+            BookDetailsPatch {
+                tags: Some(vec!["tag".to_string()]),
+                isbn: Some("isbn".to_string()),
+                ..Default::default()
+            },
+        ];
+        for patch in patches.clone() {
+            assert!(repo.update_book(book_id, patch).await.unwrap());
+        }
+
+        let changes = repo.get_book_changes(book_id).await.unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.patch.clone())
+                .collect::<Vec<_>>(),
+            patches
+        );
+        assert!(changes[0].changed_at > 0);
+        assert!(changes[0].changed_at <= changes[1].changed_at);
+        assert!(matches!(
+            repo.get_book_changes(book_id + 1).await,
+            Err(BookRepositoryError::NotFound(..))
+        ));
+    }
+
     #[tokio::test]
     /// Tests if add_book and get_book work correctly
     /// for the sake of not starting container multiple times it tests everything in one testcase
@@ -173,6 +664,7 @@ mod in_memory_book_repository_tests {
             publisher: "aaad".to_string(),
             description: "ewqeweq".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
         let id = repo
             .add_book(book_details.clone())
@@ -201,6 +693,7 @@ mod in_memory_book_repository_tests {
             publisher: Some("c".to_string()),
             description: Some("d".to_string()),
             tags: Some(vec!["e".to_string(), "w".to_string()]),
+            isbn: None,
         };
         let patch_result = repo
             .update_book(id, patch_all_fields)
@@ -214,8 +707,770 @@ mod in_memory_book_repository_tests {
             publisher: "c".to_string(),
             description: "d".to_string(),
             tags: vec!["e".to_string(), "w".to_string()],
+            isbn: None,
         };
 
         assert_eq!(repo.get_book(id).await.unwrap(), expected_after_patch);
     }
+    #[tokio::test]
+    /// Tests if list_books_by_authors returns union of books of given authors without duplicates
+    /// 1.Adds books split across authors, one of them written by two of the authors
+    /// 2.Lists books by two authors and checks that each matching book is returned once
+    async fn test_list_books_by_authors() {
+        let repo = InMemoryBookRepository::default();
+
+        let mut book_ids = vec![];
+        for (title, authors) in [
+            ("book1", vec!["author1"]),
+            ("book2", vec!["author2"]),
+            ("book3", vec!["author1", "author2"]),
+            ("book4", vec!["author3"]),
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: authors.into_iter().map(|a| a.to_string()).collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        let mut books = repo
+            .list_books_by_authors(&["author1".to_string(), "author2".to_string()])
+            .await
+            .expect("Failed to list books by authors");
+        books.sort_by_key(|book| book.book_id);
+        assert_eq!(
+            books,
+            vec![
+                BookTitleAndId {
+                    book_id: book_ids[0],
+                    title: "book1".to_string(),
+                },
+                BookTitleAndId {
+                    book_id: book_ids[1],
+                    title: "book2".to_string(),
+                },
+                BookTitleAndId {
+                    book_id: book_ids[2],
+                    title: "book3".to_string(),
+                },
+            ]
+        );
+
+        let books = repo
+            .list_books_by_authors(&["not existing".to_string()])
+            .await
+            .expect("Failed to list books by authors");
+        assert!(books.is_empty());
+    }
+    #[tokio::test]
+    /// Tests if books can be found by ISBN, also after the ISBN was changed by a patch
+    /// 1.Adds a book with ISBN and a book without it
+    /// 2.Gets the book by ISBN
+    /// 3.Patches ISBN of the book and checks that only the new ISBN finds it
+    async fn test_get_book_by_isbn() {
+        let repo = InMemoryBookRepository::default();
+
+        let book_details = BookDetails {
+            title: "xx".to_string(),
+            authors: vec!["www".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: Some("978-3-16-148410-0".to_string()),
+        };
+        let id = repo
+            .add_book(book_details.clone())
+            .await
+            .expect("Failed to add book");
+        repo.add_book(BookDetails {
+            isbn: None,
+            ..book_details.clone()
+        })
+        .await
+        .expect("Failed to add book");
+
+        assert_eq!(
+            repo.get_book_by_isbn("978-3-16-148410-0").await.unwrap(),
+            BookIdAndDetails {
+                book_id: id,
+                details: book_details,
+            }
+        );
+
+        repo.update_book(
+            id,
+            BookDetailsPatch {
+                isbn: Some("978-0-00-000000-2".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            repo.get_book_by_isbn("978-3-16-148410-0").await,
+            Err(BookRepositoryError::IsbnNotFound(..))
+        ));
+        assert_eq!(
+            repo.get_book_by_isbn("978-0-00-000000-2")
+                .await
+                .unwrap()
+                .book_id,
+            id
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if tag is renamed on every book that has it
+    /// 1.Adds books with the old tag, with both old and new tag and without the old tag
+    /// 2.Renames the tag and checks the number of changed books
+    /// 3.Checks that no book has the old tag and the new tag is not duplicated
+    async fn test_rename_tag() {
+        let repo = InMemoryBookRepository::default();
+
+        let mut book_ids = vec![];
+        for tags in [vec!["old", "other"], vec!["new", "old"], vec!["other"]] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: tags.into_iter().map(|tag| tag.to_string()).collect(),
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert_eq!(
+            repo.rename_tag("old", "new")
+                .await
+                .expect("Failed to rename tag"),
+            2
+        );
+
+        let mut tags = vec![];
+        for book_id in book_ids {
+            tags.push(repo.get_book(book_id).await.unwrap().tags);
+        }
+        assert_eq!(
+            tags,
+            vec![
+                vec!["new".to_string(), "other".to_string()],
+                vec!["new".to_string()],
+                vec!["other".to_string()],
+            ]
+        );
+        assert_eq!(repo.rename_tag("old", "new").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    /// Tests if variant spellings of an author are replaced with canonical name
+    /// 1.Adds books written by two spellings of an author (one by both) and a book of other author
+    /// 2.Merges the spellings and checks the number of changed books
+    /// 3.Checks authors of all books and that listing by the canonical name returns merged books only
+    async fn test_merge_authors() {
+        let repo = InMemoryBookRepository::default();
+
+        let mut book_ids = vec![];
+        for authors in [
+            vec!["J. Tolkien", "Other"],
+            vec!["Tolkien", "J. Tolkien"],
+            vec!["Other"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: authors
+                        .into_iter()
+                        .map(|author| author.to_string())
+                        .collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert_eq!(
+            repo.merge_authors(
+                &["J. Tolkien".to_string(), "Tolkien".to_string()],
+                "J.R.R. Tolkien"
+            )
+            .await
+            .expect("Failed to merge authors"),
+            2
+        );
+
+        let mut authors = vec![];
+        for book_id in &book_ids {
+            authors.push(repo.get_book(*book_id).await.unwrap().authors);
+        }
+        assert_eq!(
+            authors,
+            vec![
+                vec!["J.R.R. Tolkien".to_string(), "Other".to_string()],
+                vec!["J.R.R. Tolkien".to_string()],
+                vec!["Other".to_string()],
+            ]
+        );
+
+        let mut merged_book_ids: Vec<_> = repo
+            .list_books_by_authors(&["J.R.R. Tolkien".to_string()])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|book| book.book_id)
+            .collect();
+        merged_book_ids.sort();
+        assert_eq!(merged_book_ids, book_ids[..2].to_vec());
+        assert!(repo
+            .list_books_by_authors(&["Tolkien".to_string()])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if similar books are ranked by Jaccard similarity of tags
+    /// 1.Adds a book and other books sharing all, two, one and none of its tags
+    /// 2.Checks that books sharing any tag are ranked by similarity and limit is applied
+    /// 3.Checks that similar books of not existing book are not found
+    async fn test_similar_books_by_tags() {
+        let repo = InMemoryBookRepository::default();
+
+        let mut book_ids = vec![];
+        for tags in [
+            vec!["a", "b", "c"],
+            vec!["a"],
+            vec!["d"],
+            vec!["a", "b", "d"],
+            vec!["c", "b", "a"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: tags.into_iter().map(|tag| tag.to_string()).collect(),
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        let similar_book_ids = |books: Vec<BookTitleAndId>| -> Vec<_> {
+            books.into_iter().map(|book| book.book_id).collect()
+        };
+        assert_eq!(
+            similar_book_ids(repo.similar_books_by_tags(book_ids[0], 10).await.unwrap()),
+            vec![book_ids[4], book_ids[3], book_ids[1]]
+        );
+        assert_eq!(
+            similar_book_ids(repo.similar_books_by_tags(book_ids[0], 2).await.unwrap()),
+            vec![book_ids[4], book_ids[3]]
+        );
+        assert!(matches!(
+            repo.similar_books_by_tags(20000, 10).await,
+            Err(BookRepositoryError::NotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    /// Tests if authors are listed with number of their books and filtered by case insensitive substring
+    /// 1.Adds books of four authors, one of them wrote two books
+    /// 2.Checks that all authors are listed without query, most books first
+    /// 3.Checks that only authors containing "ann" are listed for the query and limit is applied
+    async fn test_list_authors() {
+        let repo = InMemoryBookRepository::default();
+
+        for authors in [
+            vec!["Anna Smith"],
+            vec!["Anna Smith", "John Annan"],
+            vec!["Bob Brown"],
+            vec!["ANNE Marie"],
+        ] {
+            repo.add_book(BookDetails {
+                title: "title".to_string(),
+                authors: authors
+                    .into_iter()
+                    .map(|author| author.to_string())
+                    .collect(),
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        }
+
+        let author_count = |author: &str, books: u64| AuthorBooksCount {
+            author: author.to_string(),
+            books,
+        };
+        assert_eq!(
+            repo.list_authors(None, 10).await.unwrap(),
+            vec![
+                author_count("Anna Smith", 2),
+                author_count("ANNE Marie", 1),
+                author_count("Bob Brown", 1),
+                author_count("John Annan", 1),
+            ]
+        );
+        assert_eq!(
+            repo.list_authors(Some("ann"), 10).await.unwrap(),
+            vec![
+                author_count("Anna Smith", 2),
+                author_count("ANNE Marie", 1),
+                author_count("John Annan", 1),
+            ]
+        );
+        assert_eq!(
+            repo.list_authors(Some("ann"), 2).await.unwrap(),
+            vec![author_count("Anna Smith", 2), author_count("ANNE Marie", 1)]
+        );
+        assert!(repo.list_authors(Some("%"), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if collaborators of the author are listed with number of co-authored books
+    /// 1.Adds books with multiple authors, one of them listing the same collaborator twice
+    /// 2.Checks collaborators of two authors - expect counts of distinct books, the author itself excluded
+    /// 3.Checks collaborators of unknown author - expect empty list
+    async fn test_list_author_collaborators() {
+        let repo = InMemoryBookRepository::default();
+
+        for authors in [
+            vec!["Anna Smith", "John Annan"],
+            vec!["Anna Smith", "John Annan", "Bob Brown"],
+            vec!["Anna Smith", "Bob Brown", "Bob Brown"],
+            vec!["Anna Smith"],
+            vec!["John Annan", "Carl White"],
+        ] {
+            repo.add_book(BookDetails {
+                title: "title".to_string(),
+                authors: authors
+                    .into_iter()
+                    .map(|author| author.to_string())
+                    .collect(),
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        }
+
+        let collaborator = |author: &str, co_authored_books: u64| AuthorCollaborator {
+            author: author.to_string(),
+            co_authored_books,
+        };
+        assert_eq!(
+            repo.list_author_collaborators("Anna Smith").await.unwrap(),
+            vec![collaborator("Bob Brown", 2), collaborator("John Annan", 2)]
+        );
+        assert_eq!(
+            repo.list_author_collaborators("John Annan").await.unwrap(),
+            vec![
+                collaborator("Anna Smith", 2),
+                collaborator("Bob Brown", 1),
+                collaborator("Carl White", 1),
+            ]
+        );
+        assert!(repo
+            .list_author_collaborators("Unknown")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if tags of the book are fully replaced, including clearing them
+    /// 1.Adds a book with tags and sets new list of tags - expect only the new tags stored
+    /// 2.Sets empty list of tags - expect the book has no tags and other details are unchanged
+    /// 3.Sets tags of not existing book - expect false
+    async fn test_set_tags() {
+        let repo = InMemoryBookRepository::default();
+
+        let book_details = BookDetails {
+            title: "title".to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec!["old".to_string(), "other".to_string()],
+            isbn: None,
+        };
+        let book_id = repo
+            .add_book(book_details.clone())
+            .await
+            .expect("Failed to add book");
+
+        assert!(repo
+            .set_tags(book_id, vec!["new".to_string(), "old".to_string()])
+            .await
+            .expect("Failed to set tags"));
+        assert_eq!(
+            repo.get_book(book_id).await.unwrap().tags,
+            vec!["new".to_string(), "old".to_string()]
+        );
+
+        assert!(repo.set_tags(book_id, vec![]).await.unwrap());
+        assert_eq!(
+            repo.get_book(book_id).await.unwrap(),
+            BookDetails {
+                tags: vec![],
+                ..book_details
+            }
+        );
+
+        assert!(!repo.set_tags(book_id + 1000, vec![]).await.unwrap());
+    }
+
+    #[tokio::test]
+    /// Tests if book with availability can not be read without database shared with reservations
+    async fn test_get_book_with_availability_without_shared_db() {
+        let repo = InMemoryBookRepository::default();
+        let book_id = repo
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec![],
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            repo.get_book_with_availability(book_id).await,
+            Err(BookRepositoryError::SharedDbDisabled)
+        ));
+    }
+
+    #[tokio::test]
+    /// Tests if list_books_by_author_count returns only books with at most given number of authors
+    /// 1.Adds books with 0, 1, 2 and 3 authors
+    /// 2.Lists books by author count 0, 1 and 2 and checks that only books with at most that many authors are returned
+    async fn test_list_books_by_author_count() {
+        let repo = InMemoryBookRepository::default();
+
+        let mut book_ids = vec![];
+        for authors in [
+            vec!["author1", "author2"],
+            vec![],
+            vec!["author1"],
+            vec!["a", "b", "c"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("book with {} authors", authors.len()),
+                    authors: authors.into_iter().map(|a| a.to_string()).collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        for (max_authors, expected_books) in [
+            (0, vec![book_ids[1]]),
+            (1, vec![book_ids[1], book_ids[2]]),
+            (2, vec![book_ids[0], book_ids[1], book_ids[2]]),
+        ] {
+            let books = repo
+                .list_books_by_author_count(max_authors)
+                .await
+                .expect("Failed to list books by author count");
+            assert_eq!(
+                books.iter().map(|book| book.book_id).collect::<Vec<_>>(),
+                expected_books
+            );
+        }
+    }
+
+    #[tokio::test]
+    /// Tests if deleted book is removed from the repository
+    /// 1.Adds a book and deletes it - expects true
+    /// 2.Gets the deleted book - expects NotFound
+    /// 3.Deletes the book again - expects false
+    async fn test_delete_book() {
+        let repo = InMemoryBookRepository::default();
+        let book_id = repo
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec!["author".to_string()],
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: Some("isbn".to_string()),
+            })
+            .await
+            .expect("Failed to add book");
+
+        assert!(repo.delete_book(book_id).await.unwrap());
+        assert!(matches!(
+            repo.get_book(book_id).await,
+            Err(BookRepositoryError::NotFound(id)) if id == book_id
+        ));
+        assert!(matches!(
+            repo.get_book_by_isbn("isbn").await,
+            Err(BookRepositoryError::IsbnNotFound(_))
+        ));
+        assert!(!repo.delete_book(book_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    /// Tests if books are listed in pages ordered by id
+    /// 1.Adds 5 books
+    /// 2.Lists pages of 2 books - expects consecutive books ordered by id, the same ones on every call
+    /// 3.Lists books with offset past the end - expects no books
+    async fn test_list_books_paged() {
+        let repo = InMemoryBookRepository::default();
+        let mut book_ids = vec![];
+        for i in 0..5 {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("title{}", i),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+        book_ids.sort();
+
+        for _ in 0..3 {
+            let mut listed_ids = vec![];
+            for offset in [0, 2, 4] {
+                listed_ids.extend(
+                    repo.list_books(Some(2), Some(offset))
+                        .await
+                        .expect("Failed to list books")
+                        .into_iter()
+                        .map(|book| book.book_id),
+                );
+            }
+            assert_eq!(listed_ids, book_ids);
+        }
+        assert_eq!(
+            repo.list_books(Some(2), None)
+                .await
+                .expect("Failed to list books")
+                .into_iter()
+                .map(|book| book.book_id)
+                .collect::<Vec<_>>(),
+            book_ids[..2]
+        );
+
+        assert!(repo
+            .list_books(Some(2), Some(5))
+            .await
+            .expect("Failed to list books")
+            .is_empty());
+        assert!(repo
+            .list_books(None, Some(10))
+            .await
+            .expect("Failed to list books")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if the same patch is applied to all given books at once
+    /// 1.Adds three books and a fourth one that is not patched
+    /// 2.Patches publisher of the three books and a not existing book in one call
+    ///   - expects the three books updated and the not existing one reported as not found
+    /// 3.Gets all books - expects new publisher of the three books with the patch in their changes
+    ///   and the fourth book unchanged
+    async fn test_update_books() {
+        let repo = InMemoryBookRepository::default();
+        let mut book_ids = vec![];
+        for i in 0..4 {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("title{}", i),
+                    authors: vec![],
+                    publisher: "old publisher".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+        let not_existing_book_id = book_ids[3] + 100;
+        let patch = BookDetailsPatch {
+            publisher: Some("new publisher".to_string()),
+            ..Default::default()
+        };
+
+        let updated = repo
+            .update_books(
+                &[book_ids[0], book_ids[1], book_ids[2], not_existing_book_id],
+                patch.clone(),
+            )
+            .await
+            .expect("Failed to update books");
+        assert_eq!(
+            updated,
+            HashMap::from([
+                (book_ids[0], true),
+                (book_ids[1], true),
+                (book_ids[2], true),
+                (not_existing_book_id, false),
+            ])
+        );
+
+        for (i, book_id) in book_ids.iter().enumerate() {
+            let details = repo.get_book(*book_id).await.unwrap();
+            let changes = repo.get_book_changes(*book_id).await.unwrap();
+            if i < 3 {
+                assert_eq!(details.publisher, "new publisher");
+                assert_eq!(details.title, format!("title{}", i));
+                assert_eq!(
+                    changes
+                        .into_iter()
+                        .map(|change| change.patch)
+                        .collect::<Vec<_>>(),
+                    vec![patch.clone()]
+                );
+            } else {
+                assert_eq!(details.publisher, "old publisher");
+                assert!(changes.is_empty());
+            }
+        }
+    }
+
+    #[tokio::test]
+    /// Tests if books are found by case insensitive part of the title
+    /// 1.Adds books with different titles
+    /// 2.Searches by parts of titles in different case - expects books containing them ordered by id
+    /// 3.Searches with wildcard character - expects it matched literally
+    /// 4.Searches with empty query - expects all books
+    async fn test_search_books() {
+        let repo = InMemoryBookRepository::default();
+        let mut book_ids = vec![];
+        for title in [
+            "The Hobbit",
+            "Harry Potter",
+            "the lord of the rings",
+            "50% off",
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        for (query, expected_books) in [
+            ("hob", vec![book_ids[0]]),
+            ("THE", vec![book_ids[0], book_ids[2]]),
+            ("r", vec![book_ids[1], book_ids[2]]),
+            ("%", vec![book_ids[3]]),
+            ("not existing", vec![]),
+            ("", book_ids.clone()),
+        ] {
+            let books = repo
+                .search_books(query)
+                .await
+                .expect("Failed to search books");
+            assert_eq!(
+                books.iter().map(|book| book.book_id).collect::<Vec<_>>(),
+                expected_books,
+                "query {}",
+                query
+            );
+        }
+    }
+
+    #[tokio::test]
+    /// Tests if metadata records time of adding the book and of its last update
+    /// 1. Adds a book - expect the same creation and update times
+    /// 2. Resets the times to 0 and patches the book - expect only update time changed
+    /// 3. Resets the times to 0 and patches it together with other books - expect only update time changed
+    /// 4. Gets not existing book - expect not found
+    async fn test_get_book_with_metadata() {
+        let repo = InMemoryBookRepository::default();
+        let details = BookDetails {
+            title: "title".to_string(),
+            authors: vec![],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let book_id = repo.add_book(details.clone()).await.unwrap();
+
+        let book = repo.get_book_with_metadata(book_id).await.unwrap();
+        assert_eq!(book.details, details);
+        assert!(book.metadata.created_at.unwrap() > 0);
+        assert_eq!(book.metadata.created_at, book.metadata.updated_at);
+
+        let patch = BookDetailsPatch {
+            title: Some("new title".to_string()),
+            ..Default::default()
+        };
+        repo.metadata.write().insert(
+            book_id,
+            BookMetadata {
+                created_at: Some(0),
+                updated_at: Some(0),
+            },
+        );
+        assert!(repo.update_book(book_id, patch.clone()).await.unwrap());
+        let book = repo.get_book_with_metadata(book_id).await.unwrap();
+        assert_eq!(book.details.title, "new title");
+        assert_eq!(book.metadata.created_at, Some(0));
+        assert!(book.metadata.updated_at.unwrap() > 0);
+
+        repo.metadata.write().insert(
+            book_id,
+            BookMetadata {
+                created_at: Some(0),
+                updated_at: Some(0),
+            },
+        );
+        repo.update_books(&[book_id, book_id + 1], patch)
+            .await
+            .unwrap();
+        let metadata = repo.get_book_with_metadata(book_id).await.unwrap().metadata;
+        assert_eq!(metadata.created_at, Some(0));
+        assert!(metadata.updated_at.unwrap() > 0);
+
+        assert!(matches!(
+            repo.get_book_with_metadata(book_id + 1).await,
+            Err(BookRepositoryError::NotFound(_))
+        ));
+    }
 }
@@ -1,19 +1,28 @@
+use std::collections::HashMap;
+
 use anyhow::Context;
 use serde_json::json;
 use tokio_postgres::{Client, NoTls, Statement};
 
-use crate::api::{BookDetails, BookDetailsPatch, BookId, BookTitleAndId};
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, BookChange, BookDetails, BookDetailsPatch,
+    BookDetailsWithAvailability, BookDetailsWithMetadata, BookId, BookIdAndDetails, BookMetadata,
+    BookTitleAndId, ReindexSummary,
+};
 use crate::books_repository::{BookRepository, BookRepositoryError};
 use crate::books_repository::BookRepositoryError::Other;
 
 pub struct PostgresBooksRepository {
     client: Client,
+    shared_db: bool,
 }
 
 pub struct PostgresBooksRepositoryConfig {
     pub hostname: String,
     pub username: String,
     pub password: String,
+    /// Database is shared with reservations service, so its reservations table can be read
+    pub shared_db: bool,
 }
 
 impl PostgresBooksRepository {
@@ -39,24 +48,77 @@ impl PostgresBooksRepository {
         CREATE TABLE IF NOT EXISTS books (
             id              SERIAL PRIMARY KEY,
             params          JSONB
-            )
+            );
+        CREATE INDEX IF NOT EXISTS books_isbn_idx ON books ((params->>'isbn'));
+        ALTER TABLE books ADD COLUMN IF NOT EXISTS popularity BIGINT NOT NULL DEFAULT 0;
+        ALTER TABLE books ADD COLUMN IF NOT EXISTS created_at BIGINT;
+        ALTER TABLE books ADD COLUMN IF NOT EXISTS updated_at BIGINT;
+        CREATE TABLE IF NOT EXISTS book_history (
+            id              BIGSERIAL PRIMARY KEY,
+            book_id         INTEGER NOT NULL,
+            changed_at      BIGINT NOT NULL,
+            patch           JSONB NOT NULL
+            );
+        CREATE INDEX IF NOT EXISTS book_history_book_id_idx ON book_history (book_id);
         ",
             )
             .await
             .context("Failed to setup table")?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            shared_db: config.shared_db,
+        })
+    }
+}
+
+/// Logs failure of database operation, only the operation name and error code are logged
+/// as the query parameters may contain personal data
+fn db_failure(
+    operation: &'static str,
+) -> impl FnOnce(tokio_postgres::Error) -> BookRepositoryError {
+    move |error| {
+        tracing::error!(
+            operation,
+            code = error.code().map(|code| code.code()).unwrap_or_default(),
+            "Database operation failed"
+        );
+        BookRepositoryError::DatabaseFailure(error)
     }
 }
 
+/// Reads book id and title from the first two columns of the row
+fn row_to_book_title_and_id(
+    row: &tokio_postgres::Row,
+) -> Result<BookTitleAndId, BookRepositoryError> {
+    let book_id = row.try_get(0)?;
+    let title_json: serde_json::Value = row.try_get(1)?;
+
+    Ok(BookTitleAndId {
+        book_id,
+        title: title_json
+            .as_str()
+            .ok_or_else(|| Other("Title is not string".to_string()))?
+            .to_string(),
+    })
+}
+
 #[async_trait::async_trait]
 impl BookRepository for PostgresBooksRepository {
     async fn add_book(&self, details: BookDetails) -> Result<BookId, BookRepositoryError> {
         let stmt: Statement = self
             .client
-            .prepare("INSERT INTO books (params) VALUES ($1) RETURNING id")
-            .await?;
+            .prepare(
+                "INSERT INTO books (params, created_at, updated_at) \
+                VALUES ($1, EXTRACT(EPOCH FROM now())::BIGINT, EXTRACT(EPOCH FROM now())::BIGINT) RETURNING id",
+            )
+            .await
+            .map_err(db_failure("add_book"))?;
 
-        let rows = self.client.query(&stmt, &[&json!(details)]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&json!(details)])
+            .await
+            .map_err(db_failure("add_book"))?;
 
         let book_id: BookId = rows
             .first()
@@ -71,12 +133,107 @@ impl BookRepository for PostgresBooksRepository {
         book_id: BookId,
         patch: BookDetailsPatch,
     ) -> Result<bool, BookRepositoryError> {
+        // Patch is recorded in the same statement, so that every applied patch is in the history
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "WITH updated AS (\
+                    UPDATE books SET params = params || ($1)::JSONB, \
+                    updated_at = EXTRACT(EPOCH FROM now())::BIGINT WHERE id = ($2) RETURNING id\
+                ) INSERT INTO book_history (book_id, changed_at, patch) \
+                SELECT id, EXTRACT(EPOCH FROM now())::BIGINT, ($1)::JSONB FROM updated RETURNING book_id",
+            )
+            .await
+            .map_err(db_failure("update_book"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&json!(patch), &book_id])
+            .await
+            .map_err(db_failure("update_book"))?;
+        Ok(!rows.is_empty())
+    }
+
+    async fn update_books(
+        &self,
+        book_ids: &[BookId],
+        patch: BookDetailsPatch,
+    ) -> Result<HashMap<BookId, bool>, BookRepositoryError> {
+        // Single statement runs in its own transaction, so either all books are updated or none
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "WITH updated AS (\
+                    UPDATE books SET params = params || ($1)::JSONB, \
+                    updated_at = EXTRACT(EPOCH FROM now())::BIGINT WHERE id = ANY($2) RETURNING id\
+                ), recorded AS (\
+                    INSERT INTO book_history (book_id, changed_at, patch) \
+                    SELECT id, EXTRACT(EPOCH FROM now())::BIGINT, ($1)::JSONB FROM updated\
+                ) SELECT id FROM updated",
+            )
+            .await
+            .map_err(db_failure("update_books"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&json!(patch), &book_ids])
+            .await
+            .map_err(db_failure("update_books"))?;
+        let mut updated: HashMap<BookId, bool> =
+            book_ids.iter().map(|book_id| (*book_id, false)).collect();
+        for row in rows {
+            updated.insert(row.try_get(0)?, true);
+        }
+        Ok(updated)
+    }
+
+    async fn get_book_changes(
+        &self,
+        book_id: BookId,
+    ) -> Result<Vec<BookChange>, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT changed_at, patch FROM book_history WHERE book_id = ($1) ORDER BY id")
+            .await
+            .map_err(db_failure("get_book_changes"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("get_book_changes"))?;
+        if rows.is_empty() {
+            // Book without changes is checked to exist, so that not existing book is reported
+            self.get_book(book_id).await?;
+        }
+
+        rows.iter()
+            .map(|row| {
+                let patch: serde_json::Value = row.try_get(1)?;
+                Ok(BookChange {
+                    changed_at: row.try_get(0)?,
+                    patch: serde_json::from_value(patch)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn delete_book(&self, book_id: BookId) -> Result<bool, BookRepositoryError> {
         let stmt: Statement = self
             .client
-            .prepare("UPDATE books SET params = params || ($1)::JSONB WHERE id = ($2) RETURNING id")
-            .await?;
+            .prepare(
+                "WITH deleted AS (DELETE FROM books WHERE id = $1 RETURNING id), \
+                deleted_changes AS (DELETE FROM book_history WHERE book_id IN (SELECT id FROM deleted)) \
+                SELECT id FROM deleted",
+            )
+            .await
+            .map_err(db_failure("delete_book"))?;
 
-        let rows = self.client.query(&stmt, &[&json!(patch), &book_id]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("delete_book"))?;
         Ok(!rows.is_empty())
     }
 
@@ -84,9 +241,14 @@ impl BookRepository for PostgresBooksRepository {
         let stmt: Statement = self
             .client
             .prepare("SELECT params FROM books WHERE id = ($1)")
-            .await?;
+            .await
+            .map_err(db_failure("get_book"))?;
 
-        let rows = self.client.query(&stmt, &[&book_id]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("get_book"))?;
 
         let details: serde_json::Value = rows
             .first()
@@ -96,29 +258,430 @@ impl BookRepository for PostgresBooksRepository {
         Ok(serde_json::from_value(details)?)
     }
 
-    async fn list_books(&self) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+    async fn get_book_with_metadata(
+        &self,
+        book_id: BookId,
+    ) -> Result<BookDetailsWithMetadata, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT params, created_at, updated_at FROM books WHERE id = ($1)")
+            .await
+            .map_err(db_failure("get_book_with_metadata"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("get_book_with_metadata"))?;
+
+        let row = rows
+            .first()
+            .ok_or_else(|| BookRepositoryError::NotFound(book_id))?;
+        let details: serde_json::Value = row.try_get(0)?;
+        Ok(BookDetailsWithMetadata {
+            details: serde_json::from_value(details)?,
+            metadata: BookMetadata {
+                created_at: row.try_get(1)?,
+                updated_at: row.try_get(2)?,
+            },
+        })
+    }
+
+    async fn get_book_with_availability(
+        &self,
+        book_id: BookId,
+    ) -> Result<BookDetailsWithAvailability, BookRepositoryError> {
+        if !self.shared_db {
+            return Err(BookRepositoryError::SharedDbDisabled);
+        }
+        // Availability is reported for the default branch (0) of the reservations service
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT books.params, reservations.user_id FROM books \
+                LEFT JOIN reservations ON reservations.book_id = books.id AND reservations.branch_id = 0 \
+                WHERE books.id = ($1)",
+            )
+            .await
+            .map_err(db_failure("get_book_with_availability"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("get_book_with_availability"))?;
+
+        let row = rows
+            .first()
+            .ok_or_else(|| BookRepositoryError::NotFound(book_id))?;
+        let details: serde_json::Value = row.try_get(0)?;
+        let holder: Option<i32> = row.try_get(1)?;
+        Ok(BookDetailsWithAvailability {
+            details: serde_json::from_value(details)?,
+            reserved: holder.is_some(),
+            holder,
+        })
+    }
+
+    async fn list_books(
+        &self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        // NULL limit and offset do not limit the listed books
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT id, params->'title' FROM books ORDER BY id LIMIT $1 OFFSET $2")
+            .await
+            .map_err(db_failure("list_books"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&limit, &offset])
+            .await
+            .map_err(db_failure("list_books"))?;
+
+        rows.iter().map(row_to_book_title_and_id).collect()
+    }
+
+    async fn list_books_by_authors(
+        &self,
+        authors: &[String],
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT id, params->'title' FROM books WHERE params->'authors' ?| $1")
+            .await
+            .map_err(db_failure("list_books_by_authors"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&authors])
+            .await
+            .map_err(db_failure("list_books_by_authors"))?;
+
+        rows.iter().map(row_to_book_title_and_id).collect()
+    }
+
+    async fn search_books(&self, query: &str) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        if query.is_empty() {
+            return self.list_books(None, None).await;
+        }
+        // Wildcards in the query are matched literally
+        let query = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT id, params->'title' FROM books \
+                WHERE params->>'title' ILIKE '%' || $1 || '%' ORDER BY id",
+            )
+            .await
+            .map_err(db_failure("search_books"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&query])
+            .await
+            .map_err(db_failure("search_books"))?;
+
+        rows.iter().map(row_to_book_title_and_id).collect()
+    }
+
+    async fn list_books_by_author_count(
+        &self,
+        max_authors: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT id, params->'title' FROM books \
+                WHERE jsonb_array_length(params->'authors') <= $1::BIGINT ORDER BY id",
+            )
+            .await
+            .map_err(db_failure("list_books_by_author_count"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&i64::from(max_authors)])
+            .await
+            .map_err(db_failure("list_books_by_author_count"))?;
+
+        rows.iter().map(row_to_book_title_and_id).collect()
+    }
+
+    async fn get_book_by_isbn(&self, isbn: &str) -> Result<BookIdAndDetails, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT id, params FROM books WHERE params->>'isbn' = ($1) ORDER BY id LIMIT 1",
+            )
+            .await
+            .map_err(db_failure("get_book_by_isbn"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&isbn])
+            .await
+            .map_err(db_failure("get_book_by_isbn"))?;
+
+        let row = rows
+            .first()
+            .ok_or_else(|| BookRepositoryError::IsbnNotFound(isbn.to_string()))?;
+        let details: serde_json::Value = row.try_get(1)?;
+
+        Ok(BookIdAndDetails {
+            book_id: row.try_get(0)?,
+            details: serde_json::from_value(details)?,
+        })
+    }
+
+    async fn set_tags(
+        &self,
+        book_id: BookId,
+        tags: Vec<String>,
+    ) -> Result<bool, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("UPDATE books SET params = jsonb_set(params, '{tags}', ($1)::JSONB) WHERE id = ($2)")
+            .await
+            .map_err(db_failure("set_tags"))?;
+
+        let updated = self
+            .client
+            .execute(&stmt, &[&json!(tags), &book_id])
+            .await
+            .map_err(db_failure("set_tags"))?;
+        Ok(updated > 0)
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<u64, BookRepositoryError> {
+        // Tags are renamed in place, keeping the first position of a tag if it becomes duplicated
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "UPDATE books SET params = jsonb_set(params, '{tags}', ( \
+                    SELECT jsonb_agg(renamed.tag ORDER BY renamed.position) FROM ( \
+                        SELECT CASE WHEN tag = $1 THEN $2 ELSE tag END AS tag, \
+                        MIN(position) AS position \
+                        FROM jsonb_array_elements_text(params->'tags') WITH ORDINALITY AS t(tag, position) \
+                        GROUP BY 1 \
+                    ) renamed \
+                )) WHERE params->'tags' ? $1",
+            )
+            .await
+            .map_err(db_failure("rename_tag"))?;
+
+        self.client
+            .execute(&stmt, &[&from, &to])
+            .await
+            .map_err(db_failure("rename_tag"))
+    }
+
+    async fn merge_authors(&self, from: &[String], to: &str) -> Result<u64, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "UPDATE books SET params = jsonb_set(params, '{authors}', ( \
+                    SELECT jsonb_agg(merged.author ORDER BY merged.position) FROM ( \
+                        SELECT CASE WHEN author = ANY($1) THEN $2 ELSE author END AS author, \
+                        MIN(position) AS position \
+                        FROM jsonb_array_elements_text(params->'authors') WITH ORDINALITY AS a(author, position) \
+                        GROUP BY 1 \
+                    ) merged \
+                )) WHERE params->'authors' ?| $1",
+            )
+            .await
+            .map_err(db_failure("merge_authors"))?;
+
+        self.client
+            .execute(&stmt, &[&from, &to])
+            .await
+            .map_err(db_failure("merge_authors"))
+    }
+
+    async fn list_authors(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<AuthorBooksCount>, BookRepositoryError> {
+        // Wildcards in the query are matched literally
+        let pattern = query.map(|query| {
+            format!(
+                "%{}%",
+                query
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            )
+        });
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT author, COUNT(*) \
+                FROM books, jsonb_array_elements_text(params->'authors') AS a(author) \
+                WHERE $1::TEXT IS NULL OR author ILIKE $1 \
+                GROUP BY author ORDER BY COUNT(*) DESC, author COLLATE \"C\" LIMIT $2",
+            )
+            .await
+            .map_err(db_failure("list_authors"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&pattern, &(limit as i64)])
+            .await
+            .map_err(db_failure("list_authors"))?;
+
+        rows.iter()
+            .map(|row| {
+                let books: i64 = row.try_get(1)?;
+                Ok(AuthorBooksCount {
+                    author: row.try_get(0)?,
+                    books: books as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_author_collaborators(
+        &self,
+        author: &str,
+    ) -> Result<Vec<AuthorCollaborator>, BookRepositoryError> {
         let stmt: Statement = self
             .client
-            .prepare("SELECT id, params->'title' FROM books")
-            .await?;
+            .prepare(
+                "SELECT collaborator, COUNT(DISTINCT id) \
+                FROM books, jsonb_array_elements_text(params->'authors') AS a(collaborator) \
+                WHERE params->'authors' ? $1 AND collaborator <> $1 \
+                GROUP BY collaborator ORDER BY COUNT(DISTINCT id) DESC, collaborator COLLATE \"C\"",
+            )
+            .await
+            .map_err(db_failure("list_author_collaborators"))?;
 
-        let rows = self.client.query(&stmt, &[]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&author])
+            .await
+            .map_err(db_failure("list_author_collaborators"))?;
 
         rows.iter()
             .map(|row| {
-                let book_id = row.try_get(0)?;
-                let title_json: serde_json::Value = row.try_get(1)?;
-
-                Ok(BookTitleAndId {
-                    book_id,
-                    title: title_json
-                        .as_str()
-                        .ok_or_else(|| Other("Title is not string".to_string()))?
-                        .to_string(),
+                let co_authored_books: i64 = row.try_get(1)?;
+                Ok(AuthorCollaborator {
+                    author: row.try_get(0)?,
+                    co_authored_books: co_authored_books as u64,
                 })
             })
             .collect()
     }
+
+    async fn similar_books_by_tags(
+        &self,
+        book_id: BookId,
+        limit: u32,
+    ) -> Result<Vec<BookTitleAndId>, BookRepositoryError> {
+        let tags: Vec<String> = serde_json::from_value(
+            self.client
+                .query(
+                    "SELECT params->'tags' FROM books WHERE id = $1",
+                    &[&book_id],
+                )
+                .await
+                .map_err(db_failure("similar_books_by_tags"))?
+                .first()
+                .ok_or_else(|| BookRepositoryError::NotFound(book_id))?
+                .try_get(0)?,
+        )?;
+
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT id, title FROM ( \
+                    SELECT id, params->'title' AS title, \
+                    (SELECT COUNT(*) FROM ( \
+                        SELECT unnest($2::TEXT[]) INTERSECT SELECT jsonb_array_elements_text(params->'tags') \
+                    ) common_tags)::FLOAT8 / \
+                    (SELECT COUNT(*) FROM ( \
+                        SELECT unnest($2::TEXT[]) UNION SELECT jsonb_array_elements_text(params->'tags') \
+                    ) all_tags) AS similarity \
+                    FROM books WHERE id <> $1 AND params->'tags' ?| $2 \
+                ) scored ORDER BY similarity DESC, id LIMIT $3",
+            )
+            .await
+            .map_err(db_failure("similar_books_by_tags"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id, &tags, &(limit as i64)])
+            .await
+            .map_err(db_failure("similar_books_by_tags"))?;
+
+        rows.iter().map(row_to_book_title_and_id).collect()
+    }
+
+    async fn reindex(&self) -> Result<ReindexSummary, BookRepositoryError> {
+        self.client
+            .batch_execute("REINDEX TABLE books")
+            .await
+            .map_err(db_failure("reindex"))?;
+
+        let rows = self
+            .client
+            .query(
+                "SELECT indexname::TEXT FROM pg_indexes WHERE tablename = 'books' ORDER BY indexname",
+                &[],
+            )
+            .await
+            .map_err(db_failure("reindex"))?;
+        Ok(ReindexSummary {
+            reindexed: rows
+                .iter()
+                .map(|row| row.try_get(0))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    async fn set_book_popularity(
+        &self,
+        book_id: BookId,
+        popularity: i64,
+    ) -> Result<bool, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("UPDATE books SET popularity = $1 WHERE id = $2")
+            .await
+            .map_err(db_failure("set_book_popularity"))?;
+
+        let updated = self
+            .client
+            .execute(&stmt, &[&popularity, &book_id])
+            .await
+            .map_err(db_failure("set_book_popularity"))?;
+        Ok(updated > 0)
+    }
+
+    async fn get_books_popularity(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, i64>, BookRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT id, popularity FROM books WHERE id = ANY($1)")
+            .await
+            .map_err(db_failure("get_books_popularity"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_ids])
+            .await
+            .map_err(db_failure("get_books_popularity"))?;
+
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -128,8 +691,14 @@ mod postgres_book_repository_tests {
     use testcontainers::core::IntoContainerPort;
     use testcontainers::runners::AsyncRunner;
 
-    use crate::api::{BookDetails, BookDetailsPatch, BookTitleAndId};
+    use crate::api::{
+        AuthorBooksCount, AuthorCollaborator, BookDetails, BookDetailsPatch,
+        BookDetailsWithAvailability, BookIdAndDetails, BookTitleAndId, ReindexSummary,
+    };
     use crate::books_repository::{BookRepository, BookRepositoryError};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
 
     async fn start_postgres_container_and_init_repo() -> (
         ContainerAsync<GenericImage>,
@@ -149,6 +718,7 @@ mod postgres_book_repository_tests {
                     hostname: "127.0.0.1".to_string(),
                     username: "postgres".to_string(),
                     password: "postgres".to_string(),
+                    shared_db: true,
                 },
             )
             .await
@@ -160,6 +730,43 @@ mod postgres_book_repository_tests {
         panic!("Failed to setup postgres container")
     }
 
+    /// Layer capturing `operation` field of every logged error event
+    #[derive(Clone, Default)]
+    struct ErrorOperationsCapturingLayer {
+        operations: Arc<parking_lot::Mutex<Vec<String>>>,
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ErrorOperationsCapturingLayer {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if *event.metadata().level() == tracing::Level::ERROR {
+                let mut visitor = OperationVisitor::default();
+                event.record(&mut visitor);
+                if let Some(operation) = visitor.operation {
+                    self.operations.lock().push(operation);
+                }
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct OperationVisitor {
+        operation: Option<String>,
+    }
+
+    impl tracing::field::Visit for OperationVisitor {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            if field.name() == "operation" {
+                self.operation = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
     #[tokio::test]
     #[file_serial(key, path => "../.pgtestslock")]
     /// Tests if add_book and get_book work correctly
@@ -180,6 +787,7 @@ mod postgres_book_repository_tests {
             publisher: "".to_string(),
             description: "".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
         let id = repo
             .add_book(book_details.clone())
@@ -197,7 +805,10 @@ mod postgres_book_repository_tests {
     async fn test_add_books_and_list_them() {
         let (_container, repo) = start_postgres_container_and_init_repo().await;
 
-        let list = repo.list_books().await.expect("Failed to list books");
+        let list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
         assert_eq!(list, vec![]);
 
         let book1_details = BookDetails {
@@ -206,6 +817,7 @@ mod postgres_book_repository_tests {
             publisher: "".to_string(),
             description: "".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
 
         let book2_details = BookDetails {
@@ -218,7 +830,10 @@ mod postgres_book_repository_tests {
             .await
             .expect("Failed to add book");
 
-        let list = repo.list_books().await.expect("Failed to list books");
+        let list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
 
         assert_eq!(
             list,
@@ -233,7 +848,10 @@ mod postgres_book_repository_tests {
             .await
             .expect("Failed to add book");
 
-        let list = repo.list_books().await.expect("Failed to list books");
+        let list = repo
+            .list_books(None, None)
+            .await
+            .expect("Failed to list books");
 
         assert_eq!(
             list,
@@ -252,14 +870,67 @@ mod postgres_book_repository_tests {
 
     #[tokio::test]
     #[file_serial(key, path => "../.pgtestslock")]
-    /// Tests if add_book and get_book work correctly
-    /// for the sake of not starting container multiple times it tests everything in one testcase
-    async fn test_add_book_patch_and_get_it() {
+    /// Tests if every applied patch is recorded in book changes
+    /// 1.Adds a book - expect no changes
+    /// 2.Applies two patches - expect both recorded in order with timestamps
+    /// 3.Gets changes of not existing book - expect not found error
+    async fn test_get_book_changes() {
         let (_container, repo) = start_postgres_container_and_init_repo().await;
-        let not_existing_book = 2000;
-        let result = repo
-            .update_book(not_existing_book, BookDetailsPatch::default())
-            .await
+
+        let book_id = repo
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec!["author".to_string()],
+                publisher: "publisher".to_string(),
+                description: "description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        assert!(repo.get_book_changes(book_id).await.unwrap().is_empty());
+
+        let patches = vec![
+            BookDetailsPatch {
+                title: Some("new title".to_string()),
+                ..Default::default()
+            },
+            BookDetailsPatch {
+                tags: Some(vec!["tag".to_string()]),
+                isbn: Some("isbn".to_string()),
+                ..Default::default()
+            },
+        ];
+        for patch in patches.clone() {
+            assert!(repo.update_book(book_id, patch).await.unwrap());
+        }
+
+        let changes = repo.get_book_changes(book_id).await.unwrap();
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.patch.clone())
+                .collect::<Vec<_>>(),
+            patches
+        );
+        assert!(changes[0].changed_at > 0);
+        assert!(changes[0].changed_at <= changes[1].changed_at);
+        assert!(matches!(
+            repo.get_book_changes(book_id + 1).await,
+            Err(BookRepositoryError::NotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if add_book and get_book work correctly
+    /// for the sake of not starting container multiple times it tests everything in one testcase
+    async fn test_add_book_patch_and_get_it() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let not_existing_book = 2000;
+        let result = repo
+            .update_book(not_existing_book, BookDetailsPatch::default())
+            .await
             .expect("Failed to update");
         // false means nothing to update
         assert!(!result);
@@ -270,6 +941,7 @@ mod postgres_book_repository_tests {
             publisher: "aaad".to_string(),
             description: "ewqeweq".to_string(),
             tags: vec!["tag tag".to_string()],
+            isbn: None,
         };
         let id = repo
             .add_book(book_details.clone())
@@ -298,6 +970,7 @@ mod postgres_book_repository_tests {
             publisher: Some("c".to_string()),
             description: Some("d".to_string()),
             tags: Some(vec!["e".to_string(), "w".to_string()]),
+            isbn: None,
         };
         let patch_result = repo
             .update_book(id, patch_all_fields)
@@ -311,8 +984,989 @@ mod postgres_book_repository_tests {
             publisher: "c".to_string(),
             description: "d".to_string(),
             tags: vec!["e".to_string(), "w".to_string()],
+            isbn: None,
         };
 
         assert_eq!(repo.get_book(id).await.unwrap(), expected_after_patch);
     }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if list_books_by_authors returns union of books of given authors without duplicates
+    /// 1.Adds books split across authors, one of them written by two of the authors
+    /// 2.Lists books by two authors and checks that each matching book is returned once
+    async fn test_list_books_by_authors() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for (title, authors) in [
+            ("book1", vec!["author1"]),
+            ("book2", vec!["author2"]),
+            ("book3", vec!["author1", "author2"]),
+            ("book4", vec!["author3"]),
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: authors.into_iter().map(|a| a.to_string()).collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        let mut books = repo
+            .list_books_by_authors(&["author1".to_string(), "author2".to_string()])
+            .await
+            .expect("Failed to list books by authors");
+        books.sort_by_key(|book| book.book_id);
+        assert_eq!(
+            books,
+            vec![
+                BookTitleAndId {
+                    book_id: book_ids[0],
+                    title: "book1".to_string(),
+                },
+                BookTitleAndId {
+                    book_id: book_ids[1],
+                    title: "book2".to_string(),
+                },
+                BookTitleAndId {
+                    book_id: book_ids[2],
+                    title: "book3".to_string(),
+                },
+            ]
+        );
+
+        let books = repo
+            .list_books_by_authors(&["not existing".to_string()])
+            .await
+            .expect("Failed to list books by authors");
+        assert!(books.is_empty());
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if failed query is logged with the name of the operation
+    /// 1.Drops the table so that the next query fails
+    /// 2.Calls repository method and checks that error with its operation name was logged
+    async fn test_failed_query_logs_operation() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let layer = ErrorOperationsCapturingLayer::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(layer.clone()));
+
+        repo.client.batch_execute("DROP TABLE books").await.unwrap();
+        assert!(repo.list_books(None, None).await.is_err());
+
+        assert_eq!(*layer.operations.lock(), vec!["list_books".to_string()]);
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if books can be found by ISBN, also after the ISBN was changed by a patch
+    /// 1.Adds a book with ISBN and a book without it
+    /// 2.Gets the book by ISBN
+    /// 3.Patches ISBN of the book and checks that only the new ISBN finds it
+    async fn test_get_book_by_isbn() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let book_details = BookDetails {
+            title: "xx".to_string(),
+            authors: vec!["www".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: Some("978-3-16-148410-0".to_string()),
+        };
+        let id = repo
+            .add_book(book_details.clone())
+            .await
+            .expect("Failed to add book");
+        repo.add_book(BookDetails {
+            isbn: None,
+            ..book_details.clone()
+        })
+        .await
+        .expect("Failed to add book");
+
+        assert_eq!(
+            repo.get_book_by_isbn("978-3-16-148410-0").await.unwrap(),
+            BookIdAndDetails {
+                book_id: id,
+                details: book_details,
+            }
+        );
+
+        repo.update_book(
+            id,
+            BookDetailsPatch {
+                isbn: Some("978-0-00-000000-2".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            repo.get_book_by_isbn("978-3-16-148410-0").await,
+            Err(BookRepositoryError::IsbnNotFound(..))
+        ));
+        assert_eq!(
+            repo.get_book_by_isbn("978-0-00-000000-2")
+                .await
+                .unwrap()
+                .book_id,
+            id
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if tag is renamed on every book that has it
+    /// 1.Adds books with the old tag, with both old and new tag and without the old tag
+    /// 2.Renames the tag and checks the number of changed books
+    /// 3.Checks that no book has the old tag and the new tag is not duplicated
+    async fn test_rename_tag() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for tags in [vec!["old", "other"], vec!["new", "old"], vec!["other"]] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: tags.into_iter().map(|tag| tag.to_string()).collect(),
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert_eq!(
+            repo.rename_tag("old", "new")
+                .await
+                .expect("Failed to rename tag"),
+            2
+        );
+
+        let mut tags = vec![];
+        for book_id in book_ids {
+            tags.push(repo.get_book(book_id).await.unwrap().tags);
+        }
+        assert_eq!(
+            tags,
+            vec![
+                vec!["new".to_string(), "other".to_string()],
+                vec!["new".to_string()],
+                vec!["other".to_string()],
+            ]
+        );
+        assert_eq!(repo.rename_tag("old", "new").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if variant spellings of an author are replaced with canonical name
+    /// 1.Adds books written by two spellings of an author (one by both) and a book of other author
+    /// 2.Merges the spellings and checks the number of changed books
+    /// 3.Checks authors of all books and that listing by the canonical name returns merged books only
+    async fn test_merge_authors() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for authors in [
+            vec!["J. Tolkien", "Other"],
+            vec!["Tolkien", "J. Tolkien"],
+            vec!["Other"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: authors
+                        .into_iter()
+                        .map(|author| author.to_string())
+                        .collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert_eq!(
+            repo.merge_authors(
+                &["J. Tolkien".to_string(), "Tolkien".to_string()],
+                "J.R.R. Tolkien"
+            )
+            .await
+            .expect("Failed to merge authors"),
+            2
+        );
+
+        let mut authors = vec![];
+        for book_id in &book_ids {
+            authors.push(repo.get_book(*book_id).await.unwrap().authors);
+        }
+        assert_eq!(
+            authors,
+            vec![
+                vec!["J.R.R. Tolkien".to_string(), "Other".to_string()],
+                vec!["J.R.R. Tolkien".to_string()],
+                vec!["Other".to_string()],
+            ]
+        );
+
+        let mut merged_book_ids: Vec<_> = repo
+            .list_books_by_authors(&["J.R.R. Tolkien".to_string()])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|book| book.book_id)
+            .collect();
+        merged_book_ids.sort();
+        assert_eq!(merged_book_ids, book_ids[..2].to_vec());
+        assert!(repo
+            .list_books_by_authors(&["Tolkien".to_string()])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if popularity of books can be set and retrieved
+    /// 1.Adds two books and sets popularity of the second one
+    /// 2.Checks that the first one has default popularity and not existing book is skipped
+    /// 3.Checks that setting popularity of not existing book returns false
+    async fn test_set_and_get_books_popularity() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for title in ["book1", "book2"] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert!(repo
+            .set_book_popularity(book_ids[1], 7)
+            .await
+            .expect("Failed to set popularity"));
+
+        let not_existing_book_id = 20000;
+        let popularity = repo
+            .get_books_popularity(&[book_ids[0], book_ids[1], not_existing_book_id])
+            .await
+            .expect("Failed to get popularity");
+        assert_eq!(
+            popularity,
+            [(book_ids[0], 0), (book_ids[1], 7)].into_iter().collect()
+        );
+
+        assert!(!repo
+            .set_book_popularity(not_existing_book_id, 1)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if similar books are ranked by Jaccard similarity of tags
+    /// 1.Adds a book and other books sharing all, two, one and none of its tags
+    /// 2.Checks that books sharing any tag are ranked by similarity and limit is applied
+    /// 3.Checks that similar books of not existing book are not found
+    async fn test_similar_books_by_tags() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for tags in [
+            vec!["a", "b", "c"],
+            vec!["a"],
+            vec!["d"],
+            vec!["a", "b", "d"],
+            vec!["c", "b", "a"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: tags.into_iter().map(|tag| tag.to_string()).collect(),
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        let similar_book_ids = |books: Vec<BookTitleAndId>| -> Vec<_> {
+            books.into_iter().map(|book| book.book_id).collect()
+        };
+        assert_eq!(
+            similar_book_ids(repo.similar_books_by_tags(book_ids[0], 10).await.unwrap()),
+            vec![book_ids[4], book_ids[3], book_ids[1]]
+        );
+        assert_eq!(
+            similar_book_ids(repo.similar_books_by_tags(book_ids[0], 2).await.unwrap()),
+            vec![book_ids[4], book_ids[3]]
+        );
+        assert!(matches!(
+            repo.similar_books_by_tags(20000, 10).await,
+            Err(BookRepositoryError::NotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if authors are listed with number of their books and filtered by case insensitive substring
+    /// 1.Adds books of four authors, one of them wrote two books
+    /// 2.Checks that all authors are listed without query, most books first
+    /// 3.Checks that only authors containing "ann" are listed for the query and limit is applied
+    async fn test_list_authors() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        for authors in [
+            vec!["Anna Smith"],
+            vec!["Anna Smith", "John Annan"],
+            vec!["Bob Brown"],
+            vec!["ANNE Marie"],
+        ] {
+            repo.add_book(BookDetails {
+                title: "title".to_string(),
+                authors: authors
+                    .into_iter()
+                    .map(|author| author.to_string())
+                    .collect(),
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        }
+
+        let author_count = |author: &str, books: u64| AuthorBooksCount {
+            author: author.to_string(),
+            books,
+        };
+        assert_eq!(
+            repo.list_authors(None, 10).await.unwrap(),
+            vec![
+                author_count("Anna Smith", 2),
+                author_count("ANNE Marie", 1),
+                author_count("Bob Brown", 1),
+                author_count("John Annan", 1),
+            ]
+        );
+        assert_eq!(
+            repo.list_authors(Some("ann"), 10).await.unwrap(),
+            vec![
+                author_count("Anna Smith", 2),
+                author_count("ANNE Marie", 1),
+                author_count("John Annan", 1),
+            ]
+        );
+        assert_eq!(
+            repo.list_authors(Some("ann"), 2).await.unwrap(),
+            vec![author_count("Anna Smith", 2), author_count("ANNE Marie", 1)]
+        );
+        assert!(repo.list_authors(Some("%"), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if collaborators of the author are listed with number of co-authored books
+    /// 1.Adds books with multiple authors, one of them listing the same collaborator twice
+    /// 2.Checks collaborators of two authors - expect counts of distinct books, the author itself excluded
+    /// 3.Checks collaborators of unknown author - expect empty list
+    async fn test_list_author_collaborators() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        for authors in [
+            vec!["Anna Smith", "John Annan"],
+            vec!["Anna Smith", "John Annan", "Bob Brown"],
+            vec!["Anna Smith", "Bob Brown", "Bob Brown"],
+            vec!["Anna Smith"],
+            vec!["John Annan", "Carl White"],
+        ] {
+            repo.add_book(BookDetails {
+                title: "title".to_string(),
+                authors: authors
+                    .into_iter()
+                    .map(|author| author.to_string())
+                    .collect(),
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .expect("Failed to add book");
+        }
+
+        let collaborator = |author: &str, co_authored_books: u64| AuthorCollaborator {
+            author: author.to_string(),
+            co_authored_books,
+        };
+        assert_eq!(
+            repo.list_author_collaborators("Anna Smith").await.unwrap(),
+            vec![collaborator("Bob Brown", 2), collaborator("John Annan", 2)]
+        );
+        assert_eq!(
+            repo.list_author_collaborators("John Annan").await.unwrap(),
+            vec![
+                collaborator("Anna Smith", 2),
+                collaborator("Bob Brown", 1),
+                collaborator("Carl White", 1),
+            ]
+        );
+        assert!(repo
+            .list_author_collaborators("Unknown")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if tags of the book are fully replaced, including clearing them
+    /// 1.Adds a book with tags and sets new list of tags - expect only the new tags stored
+    /// 2.Sets empty list of tags - expect the book has no tags and other details are unchanged
+    /// 3.Sets tags of not existing book - expect false
+    async fn test_set_tags() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let book_details = BookDetails {
+            title: "title".to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec!["old".to_string(), "other".to_string()],
+            isbn: None,
+        };
+        let book_id = repo
+            .add_book(book_details.clone())
+            .await
+            .expect("Failed to add book");
+
+        assert!(repo
+            .set_tags(book_id, vec!["new".to_string(), "old".to_string()])
+            .await
+            .expect("Failed to set tags"));
+        assert_eq!(
+            repo.get_book(book_id).await.unwrap().tags,
+            vec!["new".to_string(), "old".to_string()]
+        );
+
+        assert!(repo.set_tags(book_id, vec![]).await.unwrap());
+        assert_eq!(
+            repo.get_book(book_id).await.unwrap(),
+            BookDetails {
+                tags: vec![],
+                ..book_details
+            }
+        );
+
+        assert!(!repo.set_tags(book_id + 1000, vec![]).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if details and holder of the book are read from the database shared with reservations
+    /// 1.Creates reservations table as reservations service does and adds two books, the first one reserved
+    /// 2.Checks that the reserved book has the holder and the other one is not reserved
+    /// 3.Checks that not existing book is not found
+    async fn test_get_book_with_availability() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        repo.client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS reservations (
+                    book_id              INTEGER NOT NULL,
+                    user_id              INTEGER NOT NULL,
+                    branch_id            INTEGER NOT NULL DEFAULT 0,
+                    UNIQUE (branch_id, book_id)
+                )",
+            )
+            .await
+            .unwrap();
+
+        let book_details = |title: &str| BookDetails {
+            title: title.to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let reserved_book_id = repo.add_book(book_details("reserved")).await.unwrap();
+        let free_book_id = repo.add_book(book_details("free")).await.unwrap();
+        repo.client
+            .execute(
+                "INSERT INTO reservations (book_id, user_id) VALUES ($1, $2)",
+                &[&reserved_book_id, &7],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repo.get_book_with_availability(reserved_book_id)
+                .await
+                .unwrap(),
+            BookDetailsWithAvailability {
+                details: book_details("reserved"),
+                reserved: true,
+                holder: Some(7),
+            }
+        );
+        assert_eq!(
+            repo.get_book_with_availability(free_book_id).await.unwrap(),
+            BookDetailsWithAvailability {
+                details: book_details("free"),
+                reserved: false,
+                holder: None,
+            }
+        );
+        assert!(matches!(
+            repo.get_book_with_availability(free_book_id + 1000).await,
+            Err(BookRepositoryError::NotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if availability of the book reserved in several branches is read from the default branch
+    /// 1.Creates reservations table as reservations service does and adds two books
+    /// 2.Reserves the first book in the default branch and in branch 1, the second book only in branch 1
+    /// 3.Checks that the first book has the default branch holder and the second one is not reserved
+    async fn test_get_book_with_availability_in_default_branch() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        repo.client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS reservations (
+                    book_id              INTEGER NOT NULL,
+                    user_id              INTEGER NOT NULL,
+                    branch_id            INTEGER NOT NULL DEFAULT 0,
+                    UNIQUE (branch_id, book_id)
+                )",
+            )
+            .await
+            .unwrap();
+
+        let book_details = |title: &str| BookDetails {
+            title: title.to_string(),
+            authors: vec!["author".to_string()],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let first_book_id = repo.add_book(book_details("first")).await.unwrap();
+        let second_book_id = repo.add_book(book_details("second")).await.unwrap();
+        for (book_id, user_id, branch_id) in [
+            (first_book_id, 8, 1),
+            (first_book_id, 7, 0),
+            (second_book_id, 8, 1),
+        ] {
+            repo.client
+                .execute(
+                    "INSERT INTO reservations (book_id, user_id, branch_id) VALUES ($1, $2, $3)",
+                    &[&book_id, &user_id, &branch_id],
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            repo.get_book_with_availability(first_book_id)
+                .await
+                .unwrap(),
+            BookDetailsWithAvailability {
+                details: book_details("first"),
+                reserved: true,
+                holder: Some(7),
+            }
+        );
+        assert_eq!(
+            repo.get_book_with_availability(second_book_id)
+                .await
+                .unwrap(),
+            BookDetailsWithAvailability {
+                details: book_details("second"),
+                reserved: false,
+                holder: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if list_books_by_author_count returns only books with at most given number of authors
+    /// 1.Adds books with 0, 1, 2 and 3 authors
+    /// 2.Lists books by author count 0, 1 and 2 and checks that only books with at most that many authors are returned
+    async fn test_list_books_by_author_count() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+
+        let mut book_ids = vec![];
+        for authors in [
+            vec!["author1", "author2"],
+            vec![],
+            vec!["author1"],
+            vec!["a", "b", "c"],
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("book with {} authors", authors.len()),
+                    authors: authors.into_iter().map(|a| a.to_string()).collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        for (max_authors, expected_books) in [
+            (0, vec![book_ids[1]]),
+            (1, vec![book_ids[1], book_ids[2]]),
+            (2, vec![book_ids[0], book_ids[1], book_ids[2]]),
+        ] {
+            let books = repo
+                .list_books_by_author_count(max_authors)
+                .await
+                .expect("Failed to list books by author count");
+            assert_eq!(
+                books.iter().map(|book| book.book_id).collect::<Vec<_>>(),
+                expected_books
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if deleted book is removed from the repository
+    /// 1.Adds a book and deletes it - expects true
+    /// 2.Gets the deleted book - expects NotFound
+    /// 3.Deletes the book again - expects false
+    async fn test_delete_book() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let book_id = repo
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec!["author".to_string()],
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: Some("isbn".to_string()),
+            })
+            .await
+            .expect("Failed to add book");
+
+        assert!(repo.delete_book(book_id).await.unwrap());
+        assert!(matches!(
+            repo.get_book(book_id).await,
+            Err(BookRepositoryError::NotFound(id)) if id == book_id
+        ));
+        assert!(matches!(
+            repo.get_book_by_isbn("isbn").await,
+            Err(BookRepositoryError::IsbnNotFound(_))
+        ));
+        assert!(!repo.delete_book(book_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if books can still be found after the books table is reindexed
+    /// 1.Adds books with ISBN
+    /// 2.Reindexes - expects primary key and ISBN index rebuilt
+    /// 3.Gets books by id and ISBN - expects them found
+    async fn test_reindex() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut book_ids = vec![];
+        for isbn in ["isbn1", "isbn2"] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: isbn.to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: Some(isbn.to_string()),
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        assert_eq!(
+            repo.reindex().await.unwrap(),
+            ReindexSummary {
+                reindexed: vec!["books_isbn_idx".to_string(), "books_pkey".to_string()],
+            }
+        );
+        for (book_id, isbn) in book_ids.into_iter().zip(["isbn1", "isbn2"]) {
+            assert_eq!(repo.get_book(book_id).await.unwrap().title, isbn);
+            assert_eq!(repo.get_book_by_isbn(isbn).await.unwrap().book_id, book_id);
+        }
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if books are listed in pages ordered by id
+    /// 1.Adds 5 books
+    /// 2.Lists pages of 2 books - expects consecutive books ordered by id, the same ones on every call
+    /// 3.Lists books with offset past the end - expects no books
+    async fn test_list_books_paged() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut book_ids = vec![];
+        for i in 0..5 {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("title{}", i),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+        book_ids.sort();
+
+        for _ in 0..3 {
+            let mut listed_ids = vec![];
+            for offset in [0, 2, 4] {
+                listed_ids.extend(
+                    repo.list_books(Some(2), Some(offset))
+                        .await
+                        .expect("Failed to list books")
+                        .into_iter()
+                        .map(|book| book.book_id),
+                );
+            }
+            assert_eq!(listed_ids, book_ids);
+        }
+        assert_eq!(
+            repo.list_books(Some(2), None)
+                .await
+                .expect("Failed to list books")
+                .into_iter()
+                .map(|book| book.book_id)
+                .collect::<Vec<_>>(),
+            book_ids[..2]
+        );
+
+        assert!(repo
+            .list_books(Some(2), Some(5))
+            .await
+            .expect("Failed to list books")
+            .is_empty());
+        assert!(repo
+            .list_books(None, Some(10))
+            .await
+            .expect("Failed to list books")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if the same patch is applied to all given books at once
+    /// 1.Adds three books and a fourth one that is not patched
+    /// 2.Patches publisher of the three books and a not existing book in one call
+    ///   - expects the three books updated and the not existing one reported as not found
+    /// 3.Gets all books - expects new publisher of the three books with the patch in their changes
+    ///   and the fourth book unchanged
+    async fn test_update_books() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut book_ids = vec![];
+        for i in 0..4 {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: format!("title{}", i),
+                    authors: vec![],
+                    publisher: "old publisher".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+        let not_existing_book_id = book_ids[3] + 100;
+        let patch = BookDetailsPatch {
+            publisher: Some("new publisher".to_string()),
+            ..Default::default()
+        };
+
+        let updated = repo
+            .update_books(
+                &[book_ids[0], book_ids[1], book_ids[2], not_existing_book_id],
+                patch.clone(),
+            )
+            .await
+            .expect("Failed to update books");
+        assert_eq!(
+            updated,
+            HashMap::from([
+                (book_ids[0], true),
+                (book_ids[1], true),
+                (book_ids[2], true),
+                (not_existing_book_id, false),
+            ])
+        );
+
+        for (i, book_id) in book_ids.iter().enumerate() {
+            let details = repo.get_book(*book_id).await.unwrap();
+            let changes = repo.get_book_changes(*book_id).await.unwrap();
+            if i < 3 {
+                assert_eq!(details.publisher, "new publisher");
+                assert_eq!(details.title, format!("title{}", i));
+                assert_eq!(
+                    changes
+                        .into_iter()
+                        .map(|change| change.patch)
+                        .collect::<Vec<_>>(),
+                    vec![patch.clone()]
+                );
+            } else {
+                assert_eq!(details.publisher, "old publisher");
+                assert!(changes.is_empty());
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if books are found by case insensitive part of the title
+    /// 1.Adds books with different titles
+    /// 2.Searches by parts of titles in different case - expects books containing them ordered by id
+    /// 3.Searches with wildcard character - expects it matched literally
+    /// 4.Searches with empty query - expects all books
+    async fn test_search_books() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut book_ids = vec![];
+        for title in [
+            "The Hobbit",
+            "Harry Potter",
+            "the lord of the rings",
+            "50% off",
+        ] {
+            book_ids.push(
+                repo.add_book(BookDetails {
+                    title: title.to_string(),
+                    authors: vec![],
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .expect("Failed to add book"),
+            );
+        }
+
+        for (query, expected_books) in [
+            ("hob", vec![book_ids[0]]),
+            ("THE", vec![book_ids[0], book_ids[2]]),
+            ("r", vec![book_ids[1], book_ids[2]]),
+            ("%", vec![book_ids[3]]),
+            ("not existing", vec![]),
+            ("", book_ids.clone()),
+        ] {
+            let books = repo
+                .search_books(query)
+                .await
+                .expect("Failed to search books");
+            assert_eq!(
+                books.iter().map(|book| book.book_id).collect::<Vec<_>>(),
+                expected_books,
+                "query {}",
+                query
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if metadata records time of adding the book and of its last update
+    /// 1. Adds a book - expect the same creation and update times
+    /// 2. Resets the times to 0 and patches the book - expect only update time changed
+    /// 3. Resets the times to 0 and patches it together with other books - expect only update time changed
+    /// 4. Gets not existing book - expect not found
+    async fn test_get_book_with_metadata() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let details = BookDetails {
+            title: "title".to_string(),
+            authors: vec![],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let book_id = repo.add_book(details.clone()).await.unwrap();
+
+        let book = repo.get_book_with_metadata(book_id).await.unwrap();
+        assert_eq!(book.details, details);
+        assert!(book.metadata.created_at.unwrap() > 0);
+        assert_eq!(book.metadata.created_at, book.metadata.updated_at);
+
+        let patch = BookDetailsPatch {
+            title: Some("new title".to_string()),
+            ..Default::default()
+        };
+        repo.client
+            .execute(
+                "UPDATE books SET created_at = 0, updated_at = 0 WHERE id = $1",
+                &[&book_id],
+            )
+            .await
+            .unwrap();
+        assert!(repo.update_book(book_id, patch.clone()).await.unwrap());
+        let book = repo.get_book_with_metadata(book_id).await.unwrap();
+        assert_eq!(book.details.title, "new title");
+        assert_eq!(book.metadata.created_at, Some(0));
+        assert!(book.metadata.updated_at.unwrap() > 0);
+
+        repo.client
+            .execute(
+                "UPDATE books SET created_at = 0, updated_at = 0 WHERE id = $1",
+                &[&book_id],
+            )
+            .await
+            .unwrap();
+        repo.update_books(&[book_id, book_id + 1], patch)
+            .await
+            .unwrap();
+        let metadata = repo.get_book_with_metadata(book_id).await.unwrap().metadata;
+        assert_eq!(metadata.created_at, Some(0));
+        assert!(metadata.updated_at.unwrap() > 0);
+
+        assert!(matches!(
+            repo.get_book_with_metadata(book_id + 1).await,
+            Err(BookRepositoryError::NotFound(_))
+        ));
+    }
 }
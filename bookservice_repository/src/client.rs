@@ -1,13 +1,27 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 use anyhow::{bail, Context};
 use reqwest::header::LOCATION;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_tracing::TracingMiddleware;
 
-use crate::api::{BookDetails, BookDetailsPatch, BookId, BookTitleAndId};
+use bookservice_types::api_version::ApiVersionRecorder;
+
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, AuthorCountQuery, AuthorsMerge, AuthorsQuery, BookChange,
+    BookDetails, BookDetailsPatch, BookDetailsWithAvailability, BookDetailsWithMetadata, BookId,
+    BookIdAndDetails, BookPopularity, BookTitleAndId, BooksPageQuery, BulkBookDetailsPatch,
+    ReindexSummary, SearchBooksQuery, SimilarBooksQuery, TagRename,
+};
 
 pub struct BookServiceRepositoryClient {
     url: String,
     client: ClientWithMiddleware,
+    api_version: ApiVersionRecorder,
+    /// Books returned by the last list_books_cached call together with the time they were fetched at
+    list_books_cache: Mutex<Option<(Instant, Vec<BookTitleAndId>)>>,
 }
 
 impl BookServiceRepositoryClient {
@@ -15,17 +29,26 @@ impl BookServiceRepositoryClient {
         let reqwest_client = reqwest::Client::builder()
             .build()
             .context("Failed to build reqwest client")?;
+        let api_version = ApiVersionRecorder::default();
         let client = ClientBuilder::new(reqwest_client)
             // Insert the tracing middleware
             .with(TracingMiddleware::default())
+            .with(api_version.clone())
             .build();
 
         Ok(Self {
             url: url.to_string(),
             client,
+            api_version,
+            list_books_cache: Default::default(),
         })
     }
 
+    /// Returns api version of the server from the last response, None if no response was received yet
+    pub fn server_api_version(&self) -> Option<String> {
+        self.api_version.server_api_version()
+    }
+
     /// Calls POST /api/book endpoint
     /// Returns book_id of added book in response
     pub async fn add_book(&self, book_details: BookDetails) -> anyhow::Result<BookId> {
@@ -75,6 +98,91 @@ impl BookServiceRepositoryClient {
         }
     }
 
+    /// Calls GET /api/book/{book_id} endpoint
+    /// Returns details of the book with times of adding and last update of the book
+    /// or None if book was not in the repository
+    pub async fn get_book_with_metadata(
+        &self,
+        book_id: BookId,
+    ) -> anyhow::Result<Option<BookDetailsWithMetadata>> {
+        let response = self
+            .client
+            .get(format!("{}/api/book/{}", self.url, book_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get book with metadata {}", error)
+        }
+    }
+
+    /// Same as `get_book`, named after its result for call sites checking presence of the book
+    pub async fn get_book_or_none(&self, book_id: BookId) -> anyhow::Result<Option<BookDetails>> {
+        self.get_book(book_id).await
+    }
+
+    /// Calls GET /api/book/{book_id} endpoint for each of the given books
+    /// Returns details of the books present in the repository, missing ones are omitted
+    pub async fn get_books(
+        &self,
+        book_ids: &[BookId],
+    ) -> anyhow::Result<HashMap<BookId, BookDetails>> {
+        let mut book_id_to_details = HashMap::new();
+        for book_id in book_ids {
+            if let Some(details) = self.get_book(*book_id).await? {
+                book_id_to_details.insert(*book_id, details);
+            }
+        }
+        Ok(book_id_to_details)
+    }
+
+    /// Calls GET /api/book/isbn/{isbn} endpoint
+    /// Returns book id and details if book with given ISBN was present
+    /// None if there is no such book in the repository
+    /// and error in case of any other failure
+    pub async fn get_book_by_isbn(&self, isbn: &str) -> anyhow::Result<Option<BookIdAndDetails>> {
+        let response = self
+            .client
+            .get(format!("{}/api/book/isbn/{}", self.url, isbn))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get book by ISBN {}", error)
+        }
+    }
+
+    /// Calls GET /api/book/{book_id}/similar endpoint
+    /// Returns books ranked by similarity of tags or None if book was not in the repository
+    pub async fn similar_books(
+        &self,
+        book_id: BookId,
+        limit: Option<u32>,
+    ) -> anyhow::Result<Option<Vec<BookTitleAndId>>> {
+        let response = self
+            .client
+            .get(format!("{}/api/book/{}/similar", self.url, book_id))
+            .query(&SimilarBooksQuery { limit })
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get similar books {}", error)
+        }
+    }
+
     /// Calls PATCH /api/book/{book_id} endpoint
     pub async fn update_book(
         &self,
@@ -95,11 +203,125 @@ impl BookServiceRepositoryClient {
         }
     }
 
+    /// Calls PATCH /api/books endpoint
+    /// Returns for each of the books true if it was updated and false if it was not found
+    pub async fn update_books(
+        &self,
+        book_ids: &[BookId],
+        patch: BookDetailsPatch,
+    ) -> anyhow::Result<HashMap<BookId, bool>> {
+        let response = self
+            .client
+            .patch(format!("{}/api/books", self.url))
+            .json(&BulkBookDetailsPatch {
+                book_ids: book_ids.to_vec(),
+                patch,
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to update books {}", error)
+        }
+    }
+
+    /// Calls GET /api/book/{book_id}/changes endpoint
+    /// Returns patches applied to the book, oldest first, None if book was not found
+    pub async fn get_book_changes(
+        &self,
+        book_id: BookId,
+    ) -> anyhow::Result<Option<Vec<BookChange>>> {
+        let response = self
+            .client
+            .get(format!("{}/api/book/{}/changes", self.url, book_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get book changes {}", error)
+        }
+    }
+
+    /// Calls GET /api/book/{book_id}/availability endpoint
+    /// Returns None if book was not found, fails if the repository does not share database with reservations
+    pub async fn get_book_with_availability(
+        &self,
+        book_id: BookId,
+    ) -> anyhow::Result<Option<BookDetailsWithAvailability>> {
+        let response = self
+            .client
+            .get(format!("{}/api/book/{}/availability", self.url, book_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error = response.text().await.unwrap_or_default();
+            bail!("Failed to get book with availability {}", error)
+        }
+    }
+
+    /// Calls DELETE /api/book/{book_id} endpoint
+    /// Returns false if book was not in the repository
+    pub async fn delete_book(&self, book_id: BookId) -> anyhow::Result<bool> {
+        let response = self
+            .client
+            .delete(format!("{}/api/book/{}", self.url, book_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(false)
+        } else if response.status().is_success() {
+            Ok(true)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to delete book {}", error)
+        }
+    }
+
+    /// Calls PUT /api/book/{book_id}/tags endpoint
+    /// Returns false if book was not in the repository
+    pub async fn set_tags(&self, book_id: BookId, tags: &[String]) -> anyhow::Result<bool> {
+        let response = self
+            .client
+            .put(format!("{}/api/book/{}/tags", self.url, book_id))
+            .json(tags)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(false)
+        } else if response.status().is_success() {
+            Ok(true)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to set book tags {}", error)
+        }
+    }
+
     /// Calls GET /api/books endpoint
     pub async fn list_books(&self) -> anyhow::Result<Vec<BookTitleAndId>> {
+        self.list_books_paged(None, None).await
+    }
+
+    /// Calls GET /api/books?limit=&offset= endpoint
+    /// Returns at most `limit` books ordered by id, skipping first `offset` books
+    pub async fn list_books_paged(
+        &self,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> anyhow::Result<Vec<BookTitleAndId>> {
         let response = self
             .client
             .get(format!("{}/api/books", self.url))
+            .query(&BooksPageQuery { limit, offset })
             .send()
             .await?;
         if response.status().is_success() {
@@ -109,4 +331,533 @@ impl BookServiceRepositoryClient {
             bail!("Failed to list books {}", error)
         }
     }
+
+    /// Calls GET /api/books endpoint, unless books were already fetched by this method less than `ttl` ago
+    /// in which case the previously fetched books are returned
+    pub async fn list_books_cached(&self, ttl: Duration) -> anyhow::Result<Vec<BookTitleAndId>> {
+        if let Some((fetched_at, books)) = self
+            .list_books_cache
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_ref()
+        {
+            if fetched_at.elapsed() < ttl {
+                return Ok(books.clone());
+            }
+        }
+
+        let books = self.list_books().await?;
+        *self
+            .list_books_cache
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some((Instant::now(), books.clone()));
+        Ok(books)
+    }
+
+    /// Calls GET /api/books?authors= endpoint
+    /// Returns books written by any of given authors
+    pub async fn list_books_by_authors(
+        &self,
+        authors: &[String],
+    ) -> anyhow::Result<Vec<BookTitleAndId>> {
+        let response = self
+            .client
+            .get(format!("{}/api/books", self.url))
+            .query(&[("authors", authors.join(","))])
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to list books by authors {}", error)
+        }
+    }
+
+    /// Calls GET /api/books/search?q= endpoint
+    /// Returns books with titles containing `query` (case insensitive) ordered by id
+    pub async fn search_books(&self, query: &str) -> anyhow::Result<Vec<BookTitleAndId>> {
+        let response = self
+            .client
+            .get(format!("{}/api/books/search", self.url))
+            .query(&SearchBooksQuery {
+                q: Some(query.to_string()),
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to search books {}", error)
+        }
+    }
+
+    /// Calls GET /api/books/by_author_count?max= endpoint
+    /// Returns books with at most `max` authors (by default books without authors) ordered by id
+    pub async fn list_books_by_author_count(
+        &self,
+        max: Option<u32>,
+    ) -> anyhow::Result<Vec<BookTitleAndId>> {
+        let response = self
+            .client
+            .get(format!("{}/api/books/by_author_count", self.url))
+            .query(&AuthorCountQuery { max })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to list books by author count {}", error)
+        }
+    }
+
+    /// Calls POST /api/admin/tags/rename endpoint
+    /// Returns number of books that had the tag renamed
+    pub async fn rename_tag(&self, from: &str, to: &str) -> anyhow::Result<u64> {
+        let response = self
+            .client
+            .post(format!("{}/api/admin/tags/rename", self.url))
+            .json(&TagRename {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to rename tag {}", error)
+        }
+    }
+
+    /// Calls POST /api/admin/reindex endpoint
+    /// Returns names of rebuilt indexes of the books table
+    pub async fn reindex(&self) -> anyhow::Result<ReindexSummary> {
+        let response = self
+            .client
+            .post(format!("{}/api/admin/reindex", self.url))
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to reindex {}", error)
+        }
+    }
+
+    /// Calls GET /api/authors endpoint
+    /// Returns authors containing `query` (all authors if not given) with number of their books
+    pub async fn list_authors(
+        &self,
+        query: Option<&str>,
+        limit: Option<u32>,
+    ) -> anyhow::Result<Vec<AuthorBooksCount>> {
+        let response = self
+            .client
+            .get(format!("{}/api/authors", self.url))
+            .query(&AuthorsQuery {
+                q: query.map(str::to_string),
+                limit,
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to list authors {}", error)
+        }
+    }
+
+    /// Calls GET /api/author/{author}/collaborators endpoint
+    /// Returns other authors that co-wrote books with the given author with number of these books
+    pub async fn list_author_collaborators(
+        &self,
+        author: &str,
+    ) -> anyhow::Result<Vec<AuthorCollaborator>> {
+        // Author is pushed as path segment so that it gets percent encoded
+        let mut url = reqwest::Url::parse(&format!("{}/api/author", self.url))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid url {}", self.url))?
+            .push(author)
+            .push("collaborators");
+        let response = self.client.get(url).send().await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to list collaborators of author {}", error)
+        }
+    }
+
+    /// Calls POST /api/admin/authors/merge endpoint
+    /// Returns number of books that had the authors replaced with the canonical name
+    pub async fn merge_authors(&self, from: &[String], to: &str) -> anyhow::Result<u64> {
+        let response = self
+            .client
+            .post(format!("{}/api/admin/authors/merge", self.url))
+            .json(&AuthorsMerge {
+                from: from.to_vec(),
+                to: to.to_string(),
+            })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to merge authors {}", error)
+        }
+    }
+
+    /// Calls POST /api/admin/book/{book_id}/popularity endpoint
+    /// Returns false if book was not in the repository
+    pub async fn set_book_popularity(
+        &self,
+        book_id: BookId,
+        popularity: i64,
+    ) -> anyhow::Result<bool> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/api/admin/book/{}/popularity",
+                self.url, book_id
+            ))
+            .json(&BookPopularity { popularity })
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(false)
+        } else if response.status().is_success() {
+            Ok(true)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to set book popularity {}", error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod client_tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use bookservice_types::api_version::{api_version_headers, API_VERSION, API_VERSION_HEADER};
+    use paperclip::actix::OpenApiExt;
+
+    use super::*;
+    use crate::app_config::config_app;
+    use crate::books_repository::{BookRepository, InMemoryBookRepository};
+    use crate::tags_validator::TagsValidator;
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts mock server counting list books requests
+    /// 2. Lists books twice within ttl - expect only one request to the server
+    /// 3. Lists books with zero ttl - expect the cache to be refreshed with another request
+    async fn test_list_books_cached() {
+        let list_books_requests = Arc::new(AtomicUsize::new(0));
+        let server_requests = list_books_requests.clone();
+        let server = HttpServer::new(move || {
+            let server_requests = server_requests.clone();
+            App::new().route(
+                "/api/books",
+                web::get().to(move || {
+                    server_requests.fetch_add(1, Ordering::SeqCst);
+                    async {
+                        HttpResponse::Ok().json(vec![BookTitleAndId {
+                            book_id: 1,
+                            title: "title".to_string(),
+                        }])
+                    }
+                }),
+            )
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind mock server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        let ttl = Duration::from_secs(60);
+        let first = client.list_books_cached(ttl).await.unwrap();
+        let second = client.list_books_cached(ttl).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 1);
+        assert_eq!(list_books_requests.load(Ordering::SeqCst), 1);
+
+        client.list_books_cached(Duration::ZERO).await.unwrap();
+        assert_eq!(list_books_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts mock server with two books, responding with 404 for any other book
+    /// 2. Gets both books and a missing one in batch - expect only the two present books to be mapped
+    /// 3. Gets the missing book alone - expect None
+    async fn test_get_books_omits_missing() {
+        let server = HttpServer::new(|| {
+            App::new().route(
+                "/api/book/{book_id}",
+                web::get().to(|book_id: web::Path<BookId>| async move {
+                    let book_id = book_id.into_inner();
+                    if book_id <= 2 {
+                        HttpResponse::Ok().json(BookDetails {
+                            title: format!("title{}", book_id),
+                            authors: vec![],
+                            publisher: "".to_string(),
+                            description: "".to_string(),
+                            tags: vec![],
+                            isbn: None,
+                        })
+                    } else {
+                        HttpResponse::NotFound().finish()
+                    }
+                }),
+            )
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind mock server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        let books = client.get_books(&[1, 3, 2]).await.unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[&1].title, "title1");
+        assert_eq!(books[&2].title, "title2");
+        assert!(client.get_book_or_none(3).await.unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository server and adds a book with tags
+    /// 2. Sets new tags of the book - expect them to replace the old ones
+    /// 3. Sets empty tags - expect the book has no tags
+    /// 4. Sets tags of not existing book - expect false
+    async fn test_set_tags() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        let book_id = client
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec![],
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec!["old".to_string()],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        let new_tags = vec!["new".to_string(), "other".to_string()];
+        assert!(client.set_tags(book_id, &new_tags).await.unwrap());
+        assert_eq!(
+            client.get_book(book_id).await.unwrap().unwrap().tags,
+            new_tags
+        );
+
+        assert!(client.set_tags(book_id, &[]).await.unwrap());
+        assert!(client
+            .get_book(book_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .tags
+            .is_empty());
+
+        assert!(!client.set_tags(book_id + 1, &[]).await.unwrap());
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository server and adds a book
+    /// 2. Deletes the book - expect true and the book is not found afterwards
+    /// 3. Deletes the book again - expect false
+    async fn test_delete_book() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        let book_id = client
+            .add_book(BookDetails {
+                title: "title".to_string(),
+                authors: vec![],
+                publisher: "".to_string(),
+                description: "".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(client.delete_book(book_id).await.unwrap());
+        assert_eq!(client.get_book(book_id).await.unwrap(), None);
+        assert!(!client.delete_book(book_id).await.unwrap());
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository server and adds a book
+    /// 2. Gets the book with metadata - expect the added details and the same creation and update times
+    /// 3. Gets not existing book with metadata - expect None
+    async fn test_get_book_with_metadata() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        let details = BookDetails {
+            title: "title".to_string(),
+            authors: vec![],
+            publisher: "".to_string(),
+            description: "".to_string(),
+            tags: vec![],
+            isbn: None,
+        };
+        let book_id = client.add_book(details.clone()).await.unwrap();
+
+        let book = client
+            .get_book_with_metadata(book_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(book.details, details);
+        assert!(book.metadata.created_at.is_some());
+        assert_eq!(book.metadata.created_at, book.metadata.updated_at);
+        assert_eq!(
+            client.get_book_with_metadata(book_id + 1).await.unwrap(),
+            None
+        );
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository server with api version headers
+    /// 2. Gets /api/books - expect X-API-Version header with the current version
+    /// 3. Lists books with the client - expect server api version exposed by the client
+    async fn test_api_version_header() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .wrap(api_version_headers())
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let response = reqwest::get(format!("{}/api/books", url)).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(
+            response.headers().get(API_VERSION_HEADER).unwrap(),
+            API_VERSION
+        );
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        assert_eq!(client.server_api_version(), None);
+        client.list_books().await.unwrap();
+        assert_eq!(client.server_api_version().as_deref(), Some(API_VERSION));
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Starts repository server and adds books with multiple authors
+    /// 2. Lists collaborators of an author with a space in the name - expect counts of co-authored books
+    ///    without the author itself
+    async fn test_list_author_collaborators() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(web::Data::new(books_repository.clone()))
+                .app_data(web::Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let client = BookServiceRepositoryClient::new(&url).unwrap();
+        for authors in [vec!["Anna Smith", "Bob"], vec!["Anna Smith", "Bob", "Carl"]] {
+            client
+                .add_book(BookDetails {
+                    title: "title".to_string(),
+                    authors: authors.into_iter().map(str::to_string).collect(),
+                    publisher: "".to_string(),
+                    description: "".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            client
+                .list_author_collaborators("Anna Smith")
+                .await
+                .unwrap(),
+            vec![
+                AuthorCollaborator {
+                    author: "Bob".to_string(),
+                    co_authored_books: 2,
+                },
+                AuthorCollaborator {
+                    author: "Carl".to_string(),
+                    co_authored_books: 1,
+                },
+            ]
+        );
+    }
 }
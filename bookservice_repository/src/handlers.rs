@@ -1,46 +1,280 @@
-use std::sync::Arc;
+use std::cmp::Reverse;
+use std::collections::HashMap;
 
 use actix_web::{Error, ResponseError};
 use actix_web::body::BoxBody;
+use actix_web::error::ErrorBadRequest;
 use actix_web::http::header::LOCATION;
 use actix_web::HttpResponse;
 use actix_web::web::Data;
 use paperclip::actix::{
     api_v2_operation,
     web::{self, Json},
+    Apiv2Schema,
 };
+use paperclip::v2::models::DefaultSchemaRaw;
+use serde::{Deserialize, Serialize};
 
-use crate::api::{BookDetails, BookDetailsPatch, BookId, BookTitleAndId};
-use crate::books_repository::{BookRepository, BookRepositoryError};
+use bookservice_types::msgpack::{JsonOrMsgpack, Negotiated};
+use bookservice_types::text_validation::{
+    validate_all_no_control_characters, validate_no_control_characters,
+};
+
+use crate::api::{
+    AuthorBooksCount, AuthorCollaborator, AuthorCountQuery, AuthorsMerge, AuthorsQuery, BookChange,
+    BookDetails, BookDetailsPatch, BookDetailsWithAvailability, BookDetailsWithMetadata, BookId,
+    BookIdAndDetails, BookPopularity, BookTitleAndId, BooksSort, BulkBookDetailsPatch,
+    ReindexSummary, SearchBooksQuery, SimilarBooksQuery, TagRename, DEFAULT_AUTHORS_LIMIT,
+    DEFAULT_MAX_AUTHOR_COUNT, DEFAULT_SIMILAR_BOOKS_LIMIT,
+};
+use crate::books_repository::{BookRepository, BookRepositoryError, BookRepositoryHandle};
+use crate::tags_validator::{TagsValidationError, TagsValidator};
 
 #[api_v2_operation]
 pub async fn health() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().finish())
 }
 
+/// OpenAPI spec converted to YAML once at startup
+pub struct YamlSpec(pub String);
+
+#[api_v2_operation(skip)]
+pub async fn get_yaml_spec(spec: Data<YamlSpec>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok()
+        .content_type("application/yaml")
+        .body(spec.0.clone()))
+}
+
 impl ResponseError for BookRepositoryError {
     fn error_response(&self) -> HttpResponse<BoxBody> {
         match self {
             BookRepositoryError::NotFound(book_id) => {
                 HttpResponse::NotFound().body(format!("Book not found {}", book_id))
             }
+            BookRepositoryError::IsbnNotFound(isbn) => {
+                HttpResponse::NotFound().body(format!("Book not found by ISBN {}", isbn))
+            }
+            BookRepositoryError::SharedDbDisabled => {
+                HttpResponse::NotImplemented().body(self.to_string())
+            }
             _ => HttpResponse::InternalServerError().body(self.to_string()),
         }
     }
 }
 
+impl ResponseError for TagsValidationError {
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        HttpResponse::BadRequest().body(self.to_string())
+    }
+}
+
+/// Names of `BookDetails` fields that can be requested by `GET /api/book/{book_id}?fields=`
+const BOOK_DETAILS_FIELDS: [&str; 6] = [
+    "title",
+    "authors",
+    "publisher",
+    "description",
+    "tags",
+    "isbn",
+];
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Unknown book field '{0}'")]
+pub struct UnknownBookFieldError(String);
+
+impl ResponseError for UnknownBookFieldError {
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        HttpResponse::BadRequest().body(self.to_string())
+    }
+}
+
+#[derive(Deserialize, Apiv2Schema)]
+pub struct BookFieldsQuery {
+    /// Comma separated list of returned fields of the book, if not given all fields are returned
+    fields: Option<String>,
+}
+
+/// Book details with metadata, or only requested fields of the details,
+/// documented in api spec as `BookDetailsWithMetadata`
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BookDetailsFields {
+    All(BookDetailsWithMetadata),
+    Selected(serde_json::Map<String, serde_json::Value>),
+}
+
+impl BookDetailsFields {
+    /// Keeps only given fields of the book (missing optional fields are omitted),
+    /// fails if any of them is not a field of `BookDetails`
+    fn select(details: BookDetails, fields: &str) -> Result<Self, Error> {
+        let fields = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(unknown) = fields
+            .iter()
+            .find(|field| !BOOK_DETAILS_FIELDS.contains(field))
+        {
+            return Err(UnknownBookFieldError(unknown.to_string()).into());
+        }
+        let serde_json::Value::Object(mut all_fields) = serde_json::to_value(details)? else {
+            unreachable!("Book details are serialized as an object");
+        };
+        Ok(Self::Selected(
+            fields
+                .into_iter()
+                .filter_map(|field| Some((field.to_string(), all_fields.remove(field)?)))
+                .collect(),
+        ))
+    }
+}
+
+impl paperclip::v2::schema::Apiv2Schema for BookDetailsFields {
+    fn name() -> Option<String> {
+        BookDetailsWithMetadata::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        BookDetailsWithMetadata::raw_schema()
+    }
+}
+
+#[derive(Deserialize, Apiv2Schema)]
+pub struct ListBooksQuery {
+    /// Comma separated list of authors, if given only books of any of these authors are listed
+    authors: Option<String>,
+    /// Order of listed books, by default books are ordered by id
+    sort: Option<BooksSort>,
+    /// Maximal number of listed books, all books if not given
+    limit: Option<i64>,
+    /// Number of skipped books, defaults to 0
+    offset: Option<i64>,
+}
+
+#[api_v2_operation]
+pub async fn get_all_books<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    query: web::Query<ListBooksQuery>,
+) -> Result<Json<Vec<BookTitleAndId>>, Error> {
+    if query.limit.is_some_and(|limit| limit < 0) || query.offset.is_some_and(|offset| offset < 0) {
+        return Err(ErrorBadRequest("limit and offset must not be negative"));
+    }
+    // Only the plain listing is paged by the repository, filtered or sorted books are paged here
+    if query.authors.is_none() && query.sort.is_none() {
+        return Ok(Json(
+            books_repository
+                .list_books(query.limit, query.offset)
+                .await?,
+        ));
+    }
+    let mut books = match &query.authors {
+        Some(authors) => {
+            let authors = authors
+                .split(',')
+                .map(|author| author.trim().to_string())
+                .filter(|author| !author.is_empty())
+                .collect::<Vec<_>>();
+            books_repository.list_books_by_authors(&authors).await?
+        }
+        None => books_repository.list_books(None, None).await?,
+    };
+    if let Some(BooksSort::Popularity) = query.sort {
+        let book_ids: Vec<BookId> = books.iter().map(|book| book.book_id).collect();
+        let popularity = books_repository.get_books_popularity(&book_ids).await?;
+        books.sort_by_key(|book| {
+            (
+                Reverse(popularity.get(&book.book_id).cloned().unwrap_or_default()),
+                book.book_id,
+            )
+        });
+    } else {
+        books.sort_by_key(|book| book.book_id);
+    }
+    Ok(Json(
+        books
+            .into_iter()
+            .skip(query.offset.unwrap_or_default() as usize)
+            .take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect(),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn search_books<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    query: web::Query<SearchBooksQuery>,
+) -> Result<Json<Vec<BookTitleAndId>>, Error> {
+    Ok(Json(
+        books_repository
+            .search_books(query.q.as_deref().unwrap_or_default())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_books_by_author_count<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    query: web::Query<AuthorCountQuery>,
+) -> Result<Json<Vec<BookTitleAndId>>, Error> {
+    Ok(Json(
+        books_repository
+            .list_books_by_author_count(query.max.unwrap_or(DEFAULT_MAX_AUTHOR_COUNT))
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_authors<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    query: web::Query<AuthorsQuery>,
+) -> Result<Json<Vec<AuthorBooksCount>>, Error> {
+    Ok(Json(
+        books_repository
+            .list_authors(
+                query.q.as_deref(),
+                query.limit.unwrap_or(DEFAULT_AUTHORS_LIMIT),
+            )
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_author_collaborators<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    author: web::Path<String>,
+) -> Result<Json<Vec<AuthorCollaborator>>, Error> {
+    Ok(Json(
+        books_repository
+            .list_author_collaborators(&author.into_inner())
+            .await?,
+    ))
+}
+
 #[api_v2_operation]
-pub async fn get_all_books(
-    books_repository: Data<Arc<dyn BookRepository + Send + Sync>>,
+pub async fn get_similar_books<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+    query: web::Query<SimilarBooksQuery>,
 ) -> Result<Json<Vec<BookTitleAndId>>, Error> {
-    Ok(Json(books_repository.list_books().await?))
+    Ok(Json(
+        books_repository
+            .similar_books_by_tags(
+                book_id.into_inner(),
+                query.limit.unwrap_or(DEFAULT_SIMILAR_BOOKS_LIMIT),
+            )
+            .await?,
+    ))
 }
 
 #[api_v2_operation]
-pub async fn add_book(
-    books_repository: Data<Arc<dyn BookRepository + Send + Sync>>,
-    details: web::Json<BookDetails>,
+pub async fn add_book<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    tags_validator: Data<TagsValidator>,
+    details: JsonOrMsgpack<BookDetails>,
 ) -> Result<HttpResponse, Error> {
+    details.validate_text()?;
+    tags_validator.validate(&details.tags)?;
     let book_id = books_repository.add_book(details.into_inner()).await?;
     Ok(HttpResponse::Ok()
         .append_header((LOCATION, format!("/api/book/{}", book_id)))
@@ -48,11 +282,16 @@ pub async fn add_book(
 }
 
 #[api_v2_operation]
-pub async fn update_book(
-    books_repository: Data<Arc<dyn BookRepository + Send + Sync>>,
+pub async fn update_book<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    tags_validator: Data<TagsValidator>,
     book_id: web::Path<BookId>,
     patch: web::Json<BookDetailsPatch>,
 ) -> Result<HttpResponse, Error> {
+    patch.validate_text()?;
+    if let Some(tags) = &patch.tags {
+        tags_validator.validate(tags)?;
+    }
     books_repository
         .update_book(book_id.into_inner(), patch.into_inner())
         .await?;
@@ -60,15 +299,613 @@ pub async fn update_book(
 }
 
 #[api_v2_operation]
-pub async fn get_book(
-    books_repository: Data<Arc<dyn BookRepository + Send + Sync>>,
+pub async fn update_books<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    tags_validator: Data<TagsValidator>,
+    bulk_patch: web::Json<BulkBookDetailsPatch>,
+) -> Result<Json<HashMap<BookId, bool>>, Error> {
+    let BulkBookDetailsPatch { book_ids, patch } = bulk_patch.into_inner();
+    patch.validate_text()?;
+    if let Some(tags) = &patch.tags {
+        tags_validator.validate(tags)?;
+    }
+    Ok(Json(books_repository.update_books(&book_ids, patch).await?))
+}
+
+#[api_v2_operation]
+pub async fn set_book_tags<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    tags_validator: Data<TagsValidator>,
     book_id: web::Path<BookId>,
-) -> Result<web::Json<BookDetails>, Error> {
-    Ok(Json(books_repository.get_book(book_id.into_inner()).await?))
+    tags: web::Json<Vec<String>>,
+) -> Result<HttpResponse, Error> {
+    validate_all_no_control_characters("tags", tags.iter().map(String::as_str))?;
+    tags_validator.validate(&tags)?;
+    let book_id = book_id.into_inner();
+    if books_repository
+        .set_tags(book_id, tags.into_inner())
+        .await?
+    {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Err(BookRepositoryError::NotFound(book_id).into())
+    }
+}
+
+#[api_v2_operation]
+pub async fn delete_book<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+) -> Result<HttpResponse, Error> {
+    let book_id = book_id.into_inner();
+    if books_repository.delete_book(book_id).await? {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Err(BookRepositoryError::NotFound(book_id).into())
+    }
+}
+
+#[api_v2_operation]
+pub async fn get_book<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+    query: web::Query<BookFieldsQuery>,
+) -> Result<Negotiated<BookDetailsFields>, Error> {
+    let book_id = book_id.into_inner();
+    Ok(Negotiated(match &query.fields {
+        Some(fields) => {
+            BookDetailsFields::select(books_repository.get_book(book_id).await?, fields)?
+        }
+        None => BookDetailsFields::All(books_repository.get_book_with_metadata(book_id).await?),
+    }))
+}
+
+#[api_v2_operation]
+pub async fn get_book_changes<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+) -> Result<Json<Vec<BookChange>>, Error> {
+    Ok(Json(
+        books_repository
+            .get_book_changes(book_id.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_book_with_availability<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+) -> Result<web::Json<BookDetailsWithAvailability>, Error> {
+    Ok(Json(
+        books_repository
+            .get_book_with_availability(book_id.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_book_by_isbn<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    isbn: web::Path<String>,
+) -> Result<web::Json<BookIdAndDetails>, Error> {
+    Ok(Json(books_repository.get_book_by_isbn(&isbn).await?))
+}
+
+#[api_v2_operation]
+pub async fn rename_tag<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    tags_validator: Data<TagsValidator>,
+    rename: web::Json<TagRename>,
+) -> Result<web::Json<u64>, Error> {
+    validate_no_control_characters("to", &rename.to)?;
+    tags_validator.validate(std::slice::from_ref(&rename.to))?;
+    Ok(Json(
+        books_repository
+            .rename_tag(&rename.from, &rename.to)
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn reindex<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+) -> Result<Json<ReindexSummary>, Error> {
+    Ok(Json(books_repository.reindex().await?))
+}
+
+#[api_v2_operation]
+pub async fn merge_authors<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    merge: web::Json<AuthorsMerge>,
+) -> Result<web::Json<u64>, Error> {
+    Ok(Json(
+        books_repository
+            .merge_authors(&merge.from, &merge.to)
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn set_book_popularity<R: BookRepositoryHandle>(
+    books_repository: Data<R>,
+    book_id: web::Path<BookId>,
+    popularity: web::Json<BookPopularity>,
+) -> Result<HttpResponse, Error> {
+    let book_id = book_id.into_inner();
+    if books_repository
+        .set_book_popularity(book_id, popularity.popularity)
+        .await?
+    {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Err(BookRepositoryError::NotFound(book_id).into())
+    }
 }
 
 #[cfg(test)]
 mod handler_tests {
-    // TODO: Add client for api
-    // TODO: Add tests for handler
+    use std::sync::Arc;
+
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use paperclip::actix::OpenApiExt;
+
+    use crate::api::{BookDetails, BookDetailsPatch, BookPopularity, BookTitleAndId};
+    use crate::app_config::{config_app, config_yaml_spec};
+    use crate::books_repository::{BookRepository, InMemoryBookRepository};
+    use crate::tags_validator::TagsValidator;
+
+    fn book_with_tags(tags: Vec<String>) -> BookDetails {
+        BookDetails {
+            title: "Title".to_string(),
+            authors: vec!["Author".to_string()],
+            publisher: "Publisher".to_string(),
+            description: "Description".to_string(),
+            tags,
+            isbn: None,
+        }
+    }
+
+    #[actix_web::test]
+    /// Tests if the OpenAPI spec is served as YAML and contains the book endpoints
+    async fn test_get_yaml_spec() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(config_app)
+                .with_raw_json_spec(|app, spec| {
+                    app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+                })
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/apispec/v2.yaml")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+
+        let body = test::read_body(response).await;
+        let spec: serde_yaml::Value =
+            serde_yaml::from_slice(&body).expect("Failed to parse yaml spec");
+        let paths = spec["paths"].as_mapping().expect("No paths in spec");
+        assert!(paths.contains_key("/api/books"));
+        assert!(paths.contains_key("/api/book/{book_id}"));
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Try to add books with too long tag and with too many tags - expect 400
+    /// 2. Add book with valid tags - expect 200
+    /// 3. Try to patch the book with too many tags - expect 400
+    async fn test_add_and_update_book_tags_validation() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .app_data(Data::new(TagsValidator::new(5, 2)))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .set_json(book_with_tags(vec!["too_long".to_string()]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        let body = test::read_body(response).await;
+        assert_eq!(body, "Tag 'too_long' is longer than 5 characters");
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .set_json(book_with_tags(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+            ]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        let body = test::read_body(response).await;
+        assert_eq!(body, "Book has 3 tags, at most 2 are allowed");
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .set_json(book_with_tags(vec!["a".to_string(), "short".to_string()]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        let location = response
+            .headers()
+            .get("location")
+            .expect("No location header")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let request = test::TestRequest::patch()
+            .uri(&location)
+            .set_json(BookDetailsPatch {
+                tags: Some(vec!["a".to_string(), "b".to_string(), "c".to_string()]),
+                ..Default::default()
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[actix_web::test]
+    /// Test scenario:
+    /// 1. Try to add book with a tab in a tag - expect 400
+    /// 2. Add book with normal text - expect 200
+    /// 3. Try to patch authors of the book with a newline - expect 400 and unchanged authors
+    async fn test_control_characters_rejected() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .set_json(book_with_tags(vec!["space\topera".to_string()]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        let body = test::read_body(response).await;
+        assert_eq!(body, "Field 'tags' contains control characters");
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .set_json(book_with_tags(vec!["space opera".to_string()]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 200);
+        let location = response
+            .headers()
+            .get("location")
+            .expect("No location header")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let request = test::TestRequest::patch()
+            .uri(&location)
+            .set_json(BookDetailsPatch {
+                authors: Some(vec!["Author\nINFO injected".to_string()]),
+                ..Default::default()
+            })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+
+        let request = test::TestRequest::get().uri(&location).to_request();
+        let book: BookDetails = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(book.authors, vec!["Author".to_string()]);
+    }
+
+    #[actix_web::test]
+    /// Tests if books can be listed by comma separated authors
+    /// 1. Adds books of three authors
+    /// 2. Lists books of two of them and checks that only their books are returned
+    async fn test_get_all_books_by_authors() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        for author in ["a", "b", "c"] {
+            books_repository
+                .add_book(BookDetails {
+                    title: author.to_string(),
+                    authors: vec![author.to_string()],
+                    publisher: "Publisher".to_string(),
+                    description: "Description".to_string(),
+                    tags: vec![],
+                    isbn: None,
+                })
+                .await
+                .unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/books?authors=a,c")
+            .to_request();
+        let books: Vec<BookTitleAndId> = test::call_and_read_body_json(&app, request).await;
+        let mut titles: Vec<String> = books.into_iter().map(|book| book.title).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["a".to_string(), "c".to_string()]);
+    }
+
+    #[actix_web::test]
+    /// Tests if books can be listed sorted by popularity
+    /// 1. Adds three books and sets popularity of the first two, the second one being more popular
+    /// 2. Lists books sorted by popularity - expect second, first and then the book without popularity
+    /// 3. Tries to set popularity of not existing book - expect 404
+    async fn test_get_all_books_sorted_by_popularity() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let mut book_ids = vec![];
+        for _ in 0..3 {
+            book_ids.push(
+                books_repository
+                    .add_book(book_with_tags(vec![]))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        for (book_id, popularity) in [(book_ids[0], 3), (book_ids[1], 10)] {
+            let request = test::TestRequest::post()
+                .uri(&format!("/api/admin/book/{}/popularity", book_id))
+                .set_json(BookPopularity { popularity })
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert_eq!(response.status(), 200);
+        }
+
+        let request = test::TestRequest::get()
+            .uri("/api/books?sort=popularity")
+            .to_request();
+        let books: Vec<BookTitleAndId> = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            books
+                .into_iter()
+                .map(|book| book.book_id)
+                .collect::<Vec<_>>(),
+            vec![book_ids[1], book_ids[0], book_ids[2]]
+        );
+
+        let request = test::TestRequest::post()
+            .uri("/api/admin/book/20000/popularity")
+            .set_json(BookPopularity { popularity: 1 })
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    /// Runs GET /api/book/{book_id} requests for all given books through the app configured with given function,
+    /// returns the responses
+    async fn get_book_requests<R: crate::books_repository::BookRepositoryHandle>(
+        books_repository: R,
+        configure: fn(&mut paperclip::actix::web::ServiceConfig),
+        book_ids: &[bookservice_types::BookId],
+    ) -> Vec<BookDetails> {
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(configure)
+                .build(),
+        )
+        .await;
+
+        let mut books = vec![];
+        for book_id in book_ids {
+            let request = test::TestRequest::get()
+                .uri(&format!("/api/book/{}", book_id))
+                .to_request();
+            let book: BookDetails = test::call_and_read_body_json(&app, request).await;
+            books.push(book);
+        }
+        books
+    }
+
+    #[actix_web::test]
+    /// Tests if handlers monomorphized for the repository return the same as with dynamic dispatch
+    /// 1. Fills two in-memory repositories with the same books
+    /// 2. Gets all books through app with `Arc<dyn BookRepository>` and with `Arc<InMemoryBookRepository>`
+    /// 3. Checks that both return the same books
+    async fn test_static_and_dynamic_dispatch_return_same_books() {
+        let dyn_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let static_repository = Arc::new(InMemoryBookRepository::default());
+        let mut book_ids = vec![];
+        for no in 0..20 {
+            let details = book_with_tags(vec![format!("tag{}", no)]);
+            let book_id = dyn_repository.add_book(details.clone()).await.unwrap();
+            assert_eq!(static_repository.add_book(details).await.unwrap(), book_id);
+            book_ids.push(book_id);
+        }
+
+        let dyn_books = get_book_requests(dyn_repository, config_app, &book_ids).await;
+        let static_books = get_book_requests(
+            static_repository,
+            crate::app_config::config_app_for::<Arc<InMemoryBookRepository>>,
+            &book_ids,
+        )
+        .await;
+
+        assert_eq!(dyn_books.len(), book_ids.len());
+        assert_eq!(dyn_books, static_books);
+    }
+
+    #[actix_web::test]
+    /// Tests if book details can be sent and received as MessagePack without changing the json responses
+    /// 1. Adds a book with MessagePack body
+    /// 2. Gets the book accepting MessagePack - expect MessagePack body with the same details
+    /// 3. Gets the book without accept header - expect the json body with the same details
+    async fn test_book_details_msgpack_round_trip() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+        let details = book_with_tags(vec!["tag".to_string()]);
+
+        let request = test::TestRequest::post()
+            .uri("/api/book")
+            .insert_header(("Content-Type", "application/msgpack"))
+            .set_payload(rmp_serde::to_vec_named(&details).unwrap())
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+        let location = response
+            .headers()
+            .get("Location")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let request = test::TestRequest::get()
+            .uri(&location)
+            .insert_header(("Accept", "application/msgpack"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/msgpack"
+        );
+        let body = test::read_body(response).await;
+        assert_eq!(
+            rmp_serde::from_slice::<BookDetails>(&body).unwrap(),
+            details
+        );
+
+        let request = test::TestRequest::get().uri(&location).to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/json"
+        );
+        let body = test::read_body(response).await;
+        assert_eq!(
+            serde_json::from_slice::<BookDetails>(&body).unwrap(),
+            details
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if get book returns only requested fields
+    /// 1. Adds a book and gets it with title and authors fields - expect only these fields
+    /// 2. Gets the book with all fields - expect the same details as without fields
+    /// 3. Gets the book with an unknown field - expect 400
+    async fn test_get_book_fields() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let details = BookDetails {
+            isbn: Some("isbn".to_string()),
+            ..book_with_tags(vec!["tag".to_string()])
+        };
+        let book_id = books_repository.add_book(details.clone()).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/book/{}?fields=title,authors", book_id))
+            .to_request();
+        let fields: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            fields,
+            serde_json::json!({"title": "Title", "authors": ["Author"]})
+        );
+
+        let request = test::TestRequest::get()
+            .uri(&format!(
+                "/api/book/{}?fields=title,authors,publisher,description,tags,isbn",
+                book_id
+            ))
+            .to_request();
+        let fields: BookDetails = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(fields, details);
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/book/{}?fields=title,price", book_id))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            test::read_body(response).await,
+            "Unknown book field 'price'"
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if get book returns details with metadata nested under `metadata` key
+    /// 1. Adds a book and gets it - expect the details fields unchanged and metadata with the same
+    ///    creation and update times
+    async fn test_get_book_metadata() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let details = book_with_tags(vec!["tag".to_string()]);
+        let book_id = books_repository.add_book(details.clone()).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/book/{}", book_id))
+            .to_request();
+        let mut book: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        let metadata = book.as_object_mut().unwrap().remove("metadata").unwrap();
+        assert_eq!(book, serde_json::to_value(&details).unwrap());
+        assert!(metadata["created_at"].as_i64().unwrap() > 0);
+        assert_eq!(metadata["created_at"], metadata["updated_at"]);
+    }
 }
@@ -0,0 +1,159 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+
+/// Middleware emitting an info event with `endpoint`, `method`, `status` and `duration_ms` fields
+/// after every request, `endpoint` is the matched route pattern (or path if no route matched)
+#[derive(Clone, Default)]
+pub struct LatencyLogger;
+
+impl<S, B> Transform<S, ServiceRequest> for LatencyLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = LatencyLoggerMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LatencyLoggerMiddleware { service }))
+    }
+}
+
+pub struct LatencyLoggerMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for LatencyLoggerMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let response = self.service.call(req);
+
+        Box::pin(async move {
+            let result = response.await;
+            let (endpoint, status) = match &result {
+                Ok(response) => (
+                    response.request().match_pattern().unwrap_or(path),
+                    response.status(),
+                ),
+                Err(error) => (path, error.as_response_error().status_code()),
+            };
+            tracing::info!(
+                endpoint = endpoint.as_str(),
+                method = method.as_str(),
+                status = status.as_u16() as u64,
+                duration_ms = started_at.elapsed().as_millis() as u64,
+                "Request completed"
+            );
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod latency_logger_tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use actix_web::web::Data;
+    use actix_web::{test, App};
+    use paperclip::actix::OpenApiExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::app_config::config_app;
+    use crate::books_repository::{BookRepository, InMemoryBookRepository};
+
+    /// Layer capturing fields of every logged info event
+    #[derive(Clone, Default)]
+    struct InfoEventsCapturingLayer {
+        events: Arc<parking_lot::Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for InfoEventsCapturingLayer {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if *event.metadata().level() == tracing::Level::INFO {
+                let mut visitor = FieldsVisitor::default();
+                event.record(&mut visitor);
+                self.events.lock().push(visitor.fields);
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct FieldsVisitor {
+        fields: HashMap<String, String>,
+    }
+
+    impl tracing::field::Visit for FieldsVisitor {
+        fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    #[actix_web::test]
+    /// Tests if request to list books emits latency event with endpoint, method, status and duration
+    async fn test_latency_event_emitted() {
+        let layer = InfoEventsCapturingLayer::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(layer.clone()));
+
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository))
+                .wrap(LatencyLogger)
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get().uri("/api/books").to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+
+        let events = layer.events.lock();
+        let event = events
+            .iter()
+            .find(|event| event.get("endpoint").map(String::as_str) == Some("/api/books"))
+            .expect("No latency event for /api/books");
+        assert_eq!(event["method"], "GET");
+        assert_eq!(event["status"], "200");
+        assert!(event["duration_ms"].parse::<u64>().is_ok());
+    }
+}
@@ -9,3 +9,7 @@ pub mod app_config;
 pub mod books_repository;
 #[cfg(any(feature = "server", test))]
 mod handlers;
+#[cfg(any(feature = "server", test))]
+pub mod latency_logger;
+#[cfg(any(feature = "server", test))]
+pub mod tags_validator;
@@ -40,47 +40,85 @@ fn init_telemetry() {
 #[cfg(feature = "server")]
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
-    use bookservice_repository::app_config::config_app;
-    use bookservice_repository::books_repository::{
-        BookRepository, InMemoryBookRepository, PostgresBooksRepository,
-        PostgresBooksRepositoryConfig,
+    use bookservice_repository::app_config::config_yaml_spec;
+    use bookservice_repository::books_repository::seed_from_file;
+    use bookservice_repository::latency_logger::LatencyLogger;
+    use bookservice_repository::tags_validator::{
+        TagsValidator, DEFAULT_MAX_TAGS_PER_BOOK, DEFAULT_MAX_TAG_LENGTH,
     };
+    use bookservice_types::api_error::not_found;
+    use bookservice_types::api_version::api_version_headers;
     use paperclip::actix::{OpenApiExt, web};
     use std::env;
-    use std::sync::Arc;
     use tracing_actix_web::TracingLogger;
 
     init_telemetry();
     println!("starting HTTP server at http://localhost:8080");
 
-    let use_in_memory_db = env::var("USE_IN_MEMORY_DB")
-        .map(|value| value.to_lowercase() == "true")
-        .unwrap_or(false);
-    let pg_hostname = env::var("DB_HOST").unwrap_or("127.0.0.1".to_string());
-    let pg_username = env::var("DB_USERNAME").unwrap_or("postgres".to_string());
-    let pg_password = env::var("DB_PASSWORD").unwrap_or("postgres".to_string());
+    let max_tag_length = env::var("MAX_TAG_LENGTH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TAG_LENGTH);
+    let max_tags_per_book = env::var("MAX_TAGS_PER_BOOK")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TAGS_PER_BOOK);
 
-    let books_repository: Arc<dyn BookRepository + Send + Sync> = if use_in_memory_db {
-        Arc::new(InMemoryBookRepository::default())
-    } else {
-        Arc::new(
-            PostgresBooksRepository::init(PostgresBooksRepositoryConfig {
-                hostname: pg_hostname,
-                username: pg_username,
-                password: pg_password,
-            })
+    #[cfg(not(feature = "static_dispatch"))]
+    let (books_repository, config_app) = {
+        use bookservice_repository::app_config::config_app;
+        use bookservice_repository::books_repository::{
+            build_book_repository, BookRepositoryConfig,
+        };
+
+        let books_repository = build_book_repository(BookRepositoryConfig::from_env())
             .await
-            .expect("Failed to init postgres"),
+            .expect("Failed to init books repository");
+        (books_repository, config_app)
+    };
+    // Static dispatch build supports only in-memory repository, handlers are monomorphized for it.
+    // Postgres config is rejected, so that the build does not silently drop persisted data
+    #[cfg(feature = "static_dispatch")]
+    let (books_repository, config_app) = {
+        use bookservice_repository::app_config::config_app_for;
+        use bookservice_repository::books_repository::{
+            BookRepositoryConfig, InMemoryBookRepository,
+        };
+        use std::sync::Arc;
+
+        assert!(
+            BookRepositoryConfig::from_env().use_in_memory_db,
+            "Static dispatch build supports only in-memory repository, set USE_IN_MEMORY_DB=true"
+        );
+        (
+            Arc::new(InMemoryBookRepository::default()),
+            config_app_for::<Arc<InMemoryBookRepository>>,
         )
     };
+    if let Ok(seed_file) = env::var("SEED_FILE") {
+        let no_of_books = seed_from_file(books_repository.as_ref(), seed_file.as_ref())
+            .await
+            .expect("Failed to seed books repository");
+        tracing::info!("Seeded {} books from {}", no_of_books, seed_file);
+    }
 
     HttpServer::new(move || {
         App::new()
             .wrap_api()
             .app_data(web::Data::new(books_repository.clone()))
+            .app_data(web::Data::new(TagsValidator::new(
+                max_tag_length,
+                max_tags_per_book,
+            )))
+            .wrap(LatencyLogger)
+            .wrap(api_version_headers())
             .wrap(TracingLogger::default())
             .configure(config_app)
+            .default_service(web::to(not_found))
             .with_json_spec_at("/apispec/v2")
+            .with_raw_json_spec(|app, spec| {
+                app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+            })
             .build()
     })
     .bind(("0.0.0.0", 8080))?
@@ -0,0 +1,53 @@
+/// Default maximum number of characters in a single tag
+pub const DEFAULT_MAX_TAG_LENGTH: usize = 32;
+/// Default maximum number of tags assigned to a single book
+pub const DEFAULT_MAX_TAGS_PER_BOOK: usize = 20;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TagsValidationError {
+    #[error("Tag '{tag}' is longer than {max_tag_length} characters")]
+    TagTooLong { tag: String, max_tag_length: usize },
+
+    #[error("Book has {no_of_tags} tags, at most {max_tags} are allowed")]
+    TooManyTags { no_of_tags: usize, max_tags: usize },
+}
+
+/// Validates that tags of a book are within configured limits
+pub struct TagsValidator {
+    max_tag_length: usize,
+    max_tags: usize,
+}
+
+impl Default for TagsValidator {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TAG_LENGTH, DEFAULT_MAX_TAGS_PER_BOOK)
+    }
+}
+
+impl TagsValidator {
+    pub fn new(max_tag_length: usize, max_tags: usize) -> Self {
+        Self {
+            max_tag_length,
+            max_tags,
+        }
+    }
+
+    pub fn validate(&self, tags: &[String]) -> Result<(), TagsValidationError> {
+        if tags.len() > self.max_tags {
+            return Err(TagsValidationError::TooManyTags {
+                no_of_tags: tags.len(),
+                max_tags: self.max_tags,
+            });
+        }
+        if let Some(tag) = tags
+            .iter()
+            .find(|tag| tag.chars().count() > self.max_tag_length)
+        {
+            return Err(TagsValidationError::TagTooLong {
+                tag: tag.clone(),
+                max_tag_length: self.max_tag_length,
+            });
+        }
+        Ok(())
+    }
+}
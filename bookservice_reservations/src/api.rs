@@ -1,17 +1,479 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use paperclip::actix::Apiv2Schema;
+use paperclip::v2::models::{DataType, DataTypeFormat};
+use paperclip::v2::schema::TypedData;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-pub type UserId = i32;
-pub type BookId = i32;
+use bookservice_types::text_validation::{
+    validate_all_no_control_characters, validate_no_control_characters, ControlCharactersError,
+};
+pub use bookservice_types::{BookId, UserId};
+pub type ReservationId = Uuid;
+/// Library branch in which a copy of the book is reserved
+pub type BranchId = i32;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Branch of reservations made without giving a branch, holds, waitlists and renewals apply only to it
+pub const DEFAULT_BRANCH_ID: BranchId = 0;
+
+/// Number of history records returned by a single history page if no limit is given
+pub const DEFAULT_HISTORY_PAGE_LIMIT: u32 = 100;
+
+/// Number of users returned by most active users ranking if no limit is given
+pub const DEFAULT_MOST_ACTIVE_USERS_LIMIT: u32 = 10;
+
+/// Time for which a book is held if no ttl is given
+pub const DEFAULT_HOLD_TTL_SECONDS: u64 = 300;
+
+/// Time by which renewing a reservation extends its due date if no renewal period is configured (14 days)
+pub const DEFAULT_RENEWAL_PERIOD_SECONDS: u64 = 14 * 24 * 60 * 60;
+
+/// Number of copies of a book without explicitly set number of copies
+pub const DEFAULT_BOOK_COPIES: u32 = 1;
+
+/// Weight of a tag without explicitly given weight
+pub const DEFAULT_TAG_WEIGHT: f32 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Apiv2Schema)]
 pub struct UserDetails {
     pub username: String,
-    pub favourite_tags: Vec<String>,
+    /// Accepts both plain tags (`["scifi"]`) and weighted ones (`[{"tag": "scifi", "weight": 2.0}]`)
+    pub favourite_tags: Vec<TagWeight>,
+}
+
+impl UserDetails {
+    /// Returns error if username or any of favourite tags contains control characters
+    pub fn validate_text(&self) -> Result<(), ControlCharactersError> {
+        validate_no_control_characters("username", &self.username)?;
+        validate_all_no_control_characters(
+            "favourite_tags",
+            self.favourite_tags
+                .iter()
+                .map(|tag_weight| tag_weight.tag.as_str()),
+        )
+    }
+
+    /// Returns details with favourite tags trimmed, lowercased and without empty or duplicated tags
+    pub fn normalized(self) -> Self {
+        let mut favourite_tags: Vec<TagWeight> = vec![];
+        for tag_weight in self.favourite_tags {
+            let tag = tag_weight.tag.trim().to_lowercase();
+            if !tag.is_empty() && !favourite_tags.iter().any(|t| t.tag == tag) {
+                favourite_tags.push(TagWeight {
+                    tag,
+                    weight: tag_weight.weight,
+                });
+            }
+        }
+        Self {
+            favourite_tags,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Apiv2Schema)]
+#[serde(from = "TagWeightRepr")]
+/// Favourite tag of the user, the higher the weight the more user likes books with the tag
+pub struct TagWeight {
+    pub tag: String,
+    pub weight: f32,
+}
+
+impl From<&str> for TagWeight {
+    fn from(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+            weight: DEFAULT_TAG_WEIGHT,
+        }
+    }
+}
+
+/// Accepted input forms of the tag weight, plain tag gets the default weight
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagWeightRepr {
+    Plain(String),
+    Weighted { tag: String, weight: f32 },
+}
+
+impl From<TagWeightRepr> for TagWeight {
+    fn from(repr: TagWeightRepr) -> Self {
+        match repr {
+            TagWeightRepr::Plain(tag) => Self {
+                tag,
+                weight: DEFAULT_TAG_WEIGHT,
+            },
+            TagWeightRepr::Weighted { tag, weight } => Self { tag, weight },
+        }
+    }
+}
+
+/// Point in time as number of seconds since the unix epoch, serialized as a plain number
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct UnixSeconds(pub i64);
+
+impl UnixSeconds {
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+}
+
+impl From<SystemTime> for UnixSeconds {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(since_epoch) => Self(since_epoch.as_secs() as i64),
+            Err(err) => Self(-(err.duration().as_secs() as i64)),
+        }
+    }
+}
+
+impl From<UnixSeconds> for SystemTime {
+    fn from(seconds: UnixSeconds) -> Self {
+        if seconds.0 >= 0 {
+            UNIX_EPOCH + Duration::from_secs(seconds.0 as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(seconds.0.unsigned_abs())
+        }
+    }
+}
+
+impl TypedData for UnixSeconds {
+    fn data_type() -> DataType {
+        DataType::Integer
+    }
+
+    fn format() -> Option<DataTypeFormat> {
+        Some(DataTypeFormat::Int64)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
 pub struct ReservationHistoryRecord {
     pub book_id: BookId,
-    pub unreserved_at: i64,
+    /// Not present in records created before reservation time was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserved_at: Option<UnixSeconds>,
+    pub unreserved_at: UnixSeconds,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Confirmation of an active reservation, `reservation_id` is a stable handle of the reservation
+pub struct ReservationReceipt {
+    pub reservation_id: ReservationId,
+    pub user_id: UserId,
+    pub book_id: BookId,
+    /// Not present in reservations created before reservation time was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserved_at: Option<UnixSeconds>,
+    /// Time until which the book can be kept, present once the reservation was renewed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<UnixSeconds>,
+    /// Number of times the reservation was renewed
+    #[serde(default)]
+    pub renewals: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Apiv2Schema)]
+/// All data stored about the user
+pub struct UserDataExport {
+    pub user_id: UserId,
+    pub details: UserDetails,
+    /// Books currently reserved by the user
+    pub reservations: Vec<BookId>,
+    /// Full history of user reservations
+    pub history: Vec<ReservationHistoryRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Summary of user reading activity
+pub struct UserStats {
+    /// Number of all reservations of the user, both active and in history
+    pub total_reserved_ever: i64,
+    pub currently_reserved: i64,
+    /// Number of different authors of all books ever reserved by the user
+    pub distinct_authors: i64,
+    /// Number of different tags of all books ever reserved by the user
+    pub distinct_tags: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// History record together with the user that reserved the book
+pub struct UserHistoryRecord {
+    pub user_id: UserId,
+    pub book_id: BookId,
+    pub unreserved_at: UnixSeconds,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Filters and pagination of history records of all users, records are ordered by `unreserved_at`
+pub struct HistoryQuery {
+    /// Maximal number of returned records, defaults to 100
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Number of matching records to skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// If given only records of this book are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub book_id: Option<BookId>,
+    /// If given only records of this user are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Time window of history records of the user, both bounds are inclusive
+pub struct HistoryWindowQuery {
+    /// If given only records unreserved at or after this time are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<UnixSeconds>,
+    /// If given only records unreserved at or before this time are returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<UnixSeconds>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+/// Order of listed reservations of the user
+pub enum ReservationsSort {
+    /// Ascending by book id
+    #[default]
+    BookId,
+    /// The earliest reserved first (reservations with unknown time first), ties are ordered by book id
+    ReservedAt,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct BranchQuery {
+    /// Branch in which the book is reserved, defaults to the default branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_id: Option<BranchId>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct ReservationsQuery {
+    /// Order of returned book ids, defaults to `book_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<ReservationsSort>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+/// What happens with history records of the user when user data is deleted
+pub enum HistoryRetention {
+    /// History records are removed
+    Delete,
+    /// History records are kept without the user id, so they still count as book reservations
+    #[default]
+    Anonymize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+/// Outcome of unreserving one of the books in bulk
+pub enum UnreserveOutcome {
+    /// Book was reserved by the user and is available again
+    Unreserved,
+    /// Book was not reserved or was reserved by a different user, nothing changed
+    NotHeld,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Ord, Apiv2Schema,
+)]
+#[serde(rename_all = "snake_case")]
+/// Change of the reservation reported to the webhook and listed in the user activity
+pub enum ReservationAction {
+    Reserved,
+    Unreserved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Event posted to the webhook after a book was reserved or unreserved, also an entry of the user activity
+pub struct ReservationEvent {
+    pub action: ReservationAction,
+    pub user_id: UserId,
+    pub book_id: BookId,
+    pub timestamp: UnixSeconds,
+}
+
+/// Outcome of reserving a book or joining its waitlist if the book is unavailable
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReserveOrWaitOutcome {
+    Reserved,
+    /// The book was already reserved by the user, nothing changed
+    AlreadyReserved,
+    /// Position of the user in the waitlist of the book, starting from 1
+    Waitlisted(u32),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReserveOrWaitAction {
+    Reserved,
+    Waitlisted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct ReserveOrWaitResponse {
+    pub action: ReserveOrWaitAction,
+    /// Position in the waitlist, given only if the user was waitlisted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<u32>,
+}
+
+impl From<ReserveOrWaitOutcome> for ReserveOrWaitResponse {
+    fn from(outcome: ReserveOrWaitOutcome) -> Self {
+        match outcome {
+            ReserveOrWaitOutcome::Reserved | ReserveOrWaitOutcome::AlreadyReserved => Self {
+                action: ReserveOrWaitAction::Reserved,
+                position: None,
+            },
+            ReserveOrWaitOutcome::Waitlisted(position) => Self {
+                action: ReserveOrWaitAction::Waitlisted,
+                position: Some(position),
+            },
+        }
+    }
+}
+
+impl From<ReserveOrWaitResponse> for ReserveOrWaitOutcome {
+    fn from(response: ReserveOrWaitResponse) -> Self {
+        match response.action {
+            ReserveOrWaitAction::Reserved => Self::Reserved,
+            ReserveOrWaitAction::Waitlisted => {
+                Self::Waitlisted(response.position.unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Position of the user in the waitlist of the book, starting from 1
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct WaitlistPosition {
+    pub position: u32,
+}
+
+/// Existence of the book in the repository and its availability for reservation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct BookCheck {
+    pub exists: bool,
+    /// True if the book exists and is not reserved
+    pub available: bool,
+}
+
+/// Result of checking if the user may reserve the book now
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct CanReserveResponse {
+    pub allowed: bool,
+    /// Why the reservation is not allowed, given only if it is not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct DeleteUserDataQuery {
+    /// Defaults to anonymize
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryRetention>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+/// Summary of the removed user data
+pub struct UserDataDeletionSummary {
+    pub user_id: UserId,
+    /// Books that were reserved by the user and are available again
+    pub released_reservations: Vec<BookId>,
+    pub deleted_history_records: u64,
+    pub anonymized_history_records: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct MostActiveUsersQuery {
+    /// Maximal number of returned users, defaults to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Apiv2Schema)]
+pub struct HoldQuery {
+    /// For how long the book is held, defaults to 300 seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+#[cfg(test)]
+mod api_tests {
+    use super::*;
+
+    #[test]
+    /// Tests if favourite tags given as plain strings get default weight and survive a round trip
+    fn test_user_details_plain_tags_round_trip() {
+        let user: UserDetails =
+            serde_json::from_str(r#"{"username": "user", "favourite_tags": ["scifi", "fantasy"]}"#)
+                .unwrap();
+        assert_eq!(user.favourite_tags, vec!["scifi".into(), "fantasy".into()]);
+
+        let serialized = serde_json::to_string(&user).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UserDetails>(&serialized).unwrap(),
+            user
+        );
+    }
+
+    #[test]
+    /// Tests if weighted favourite tags (also mixed with plain ones) survive a round trip
+    fn test_user_details_weighted_tags_round_trip() {
+        let user: UserDetails = serde_json::from_str(
+            r#"{"username": "user", "favourite_tags": [{"tag": "scifi", "weight": 2.5}, "fantasy"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            user.favourite_tags,
+            vec![
+                TagWeight {
+                    tag: "scifi".to_string(),
+                    weight: 2.5
+                },
+                "fantasy".into()
+            ]
+        );
+
+        let serialized = serde_json::to_string(&user).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UserDetails>(&serialized).unwrap(),
+            user
+        );
+    }
+
+    #[test]
+    /// Tests if unix seconds are serialized as a plain number in history records
+    fn test_unix_seconds_json_round_trip() {
+        let record = ReservationHistoryRecord {
+            book_id: 1,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(1700000000),
+        };
+        let serialized = serde_json::to_string(&record).unwrap();
+        assert_eq!(serialized, r#"{"book_id":1,"unreserved_at":1700000000}"#);
+        assert_eq!(
+            serde_json::from_str::<ReservationHistoryRecord>(&serialized).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    /// Tests if unix seconds convert to and from system time (also before the epoch)
+    fn test_unix_seconds_system_time_conversion() {
+        let time = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!(UnixSeconds::from(time), UnixSeconds(1700000000));
+        assert_eq!(SystemTime::from(UnixSeconds(1700000000)), time);
+
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(10);
+        assert_eq!(UnixSeconds::from(before_epoch), UnixSeconds(-10));
+        assert_eq!(SystemTime::from(UnixSeconds(-10)), before_epoch);
+    }
 }
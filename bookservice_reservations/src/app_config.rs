@@ -7,20 +7,123 @@ pub fn config_app(cfg: &mut web::ServiceConfig) {
         .service(
             web::scope("/api")
                 .service(web::resource("/users").route(web::get().to(handlers::get_all_users)))
+                .service(
+                    web::resource("/users/most_active")
+                        .route(web::get().to(handlers::get_most_active_users)),
+                )
+                .service(web::resource("/history").route(web::get().to(handlers::get_history)))
+                .service(
+                    web::resource("/users/reservations/batch")
+                        .route(web::post().to(handlers::get_reservations_for_users)),
+                )
+                .service(
+                    web::resource("/users/details/batch")
+                        .route(web::post().to(handlers::get_users_details)),
+                )
+                .service(
+                    web::resource("/users/history/batch")
+                        .route(web::post().to(handlers::get_history_for_users)),
+                )
+                .service(
+                    web::resource("/users/history/since/batch")
+                        .route(web::post().to(handlers::get_history_since_for_users)),
+                )
+                .service(
+                    web::resource("/admin/user/{user_id}/reservation/{book_id}")
+                        .route(web::post().to(handlers::admin_reserve_book)),
+                )
+                .service(
+                    web::resource("/reservations/count")
+                        .route(web::get().to(handlers::count_reservations)),
+                )
+                .service(
+                    web::resource("/reservation/{reservation_id}")
+                        .route(web::get().to(handlers::get_reservation)),
+                )
+                .service(
+                    web::resource("/books/never_reserved")
+                        .route(web::get().to(handlers::get_never_reserved_books)),
+                )
+                .service(
+                    web::resource("/books/availability")
+                        .route(web::post().to(handlers::get_books_availability)),
+                )
+                .service(
+                    web::resource("/book/{book_id}/avg_hold")
+                        .route(web::get().to(handlers::average_hold_duration)),
+                )
+                .service(
+                    web::resource("/book/{book_id}/copies")
+                        .route(web::put().to(handlers::set_book_copies)),
+                )
+                .service(
+                    web::resource("/book/{book_id}/available_copies")
+                        .route(web::get().to(handlers::get_available_copies)),
+                )
+                .service(web::resource("/books/check").route(web::post().to(handlers::check_books)))
+                .service(
+                    web::resource("/books/holders")
+                        .route(web::post().to(handlers::get_books_holders)),
+                )
                 .service(
                     web::scope("/user")
                         .service(web::resource("").route(web::post().to(handlers::add_user)))
                         .service(
                             web::scope("/{user_id}")
                                 .service(web::resource("").route(web::get().to(handlers::get_user)))
+                                .service(
+                                    web::resource("/export")
+                                        .route(web::get().to(handlers::export_user_data)),
+                                )
+                                .service(
+                                    web::resource("/stats")
+                                        .route(web::get().to(handlers::get_user_stats)),
+                                )
+                                .service(
+                                    web::resource("/data")
+                                        .route(web::delete().to(handlers::delete_user_data)),
+                                )
                                 .service(
                                     web::resource("/reservations")
-                                        .route(web::get().to(handlers::get_all_reservations)),
+                                        .route(web::get().to(handlers::get_all_reservations))
+                                        .route(web::delete().to(handlers::unreserve_books)),
                                 )
                                 .service(
                                     web::resource("/history")
                                         .route(web::get().to(handlers::get_reservations_history)),
                                 )
+                                .service(
+                                    web::resource("/activity")
+                                        .route(web::get().to(handlers::get_user_activity)),
+                                )
+                                .service(
+                                    web::resource("/hold/{book_id}")
+                                        .route(web::post().to(handlers::create_hold)),
+                                )
+                                .service(
+                                    web::resource("/hold/{book_id}/confirm")
+                                        .route(web::post().to(handlers::confirm_hold)),
+                                )
+                                .service(
+                                    web::resource("/can_reserve/{book_id}")
+                                        .route(web::get().to(handlers::can_reserve_book)),
+                                )
+                                .service(
+                                    web::resource("/reserve_or_wait/{book_id}")
+                                        .route(web::post().to(handlers::reserve_or_wait)),
+                                )
+                                .service(
+                                    web::resource("/waitlist/{book_id}/position")
+                                        .route(web::get().to(handlers::get_waitlist_position)),
+                                )
+                                .service(
+                                    web::resource("/reservation/{book_id}/renew")
+                                        .route(web::post().to(handlers::renew_reservation)),
+                                )
+                                .service(
+                                    web::resource("/reservation/isbn/{isbn}")
+                                        .route(web::post().to(handlers::reserve_book_by_isbn)),
+                                )
                                 .service(
                                     web::resource("/reservation/{book_id}")
                                         .route(web::post().to(handlers::reserve_book))
@@ -30,3 +133,10 @@ pub fn config_app(cfg: &mut web::ServiceConfig) {
                 ),
         );
 }
+
+/// Serves the given OpenAPI spec converted to YAML under the given path
+pub fn config_yaml_spec(cfg: &mut web::ServiceConfig, path: &str, spec: serde_json::Value) {
+    let spec_yaml = serde_yaml::to_string(&spec).expect("Failed to convert spec to yaml");
+    cfg.app_data(web::Data::new(handlers::YamlSpec(spec_yaml)))
+        .service(web::resource(path).route(web::get().to(handlers::get_yaml_spec)));
+}
@@ -1,38 +1,198 @@
-use anyhow::Context;
-use reqwest_middleware::ClientBuilder;
+use anyhow::{bail, Context};
+use reqwest::StatusCode;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_tracing::TracingMiddleware;
+use serde::Deserialize;
 
 use crate::api::BookId;
+use crate::circuit_breaker::{CircuitBreaker, OpenCircuitAction};
+
+/// Part of the book listed by the repository that is needed to identify it
+#[derive(Deserialize)]
+struct ListedBook {
+    book_id: BookId,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RepositoryCallError {
+    #[error("Repository unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Repository call failed: {0}")]
+    Other(String),
+}
+
+/// Returned instead of calling the repository while the circuit breaker is open
+#[derive(thiserror::Error, Debug)]
+#[error("Circuit open after consecutive repository failures")]
+pub struct CircuitOpenError;
+
+impl From<anyhow::Error> for RepositoryCallError {
+    /// Connection failures, timeouts and open circuit mean that repository is unavailable,
+    /// everything else is a generic failure
+    fn from(err: anyhow::Error) -> Self {
+        let unavailable = err.chain().any(|cause| {
+            cause.is::<CircuitOpenError>()
+                || cause
+                    .downcast_ref::<reqwest::Error>()
+                    .map(|err| err.is_connect() || err.is_timeout())
+                    .unwrap_or_default()
+        });
+        if unavailable {
+            Self::Unavailable(format!("{:#}", err))
+        } else {
+            Self::Other(format!("{:#}", err))
+        }
+    }
+}
 
 pub struct BookExistanceChecker {
     book_repository_url: String,
+    circuit_breaker: Option<CircuitBreaker>,
 }
 
 impl BookExistanceChecker {
     pub fn new(book_repository_url: String) -> Self {
         Self {
             book_repository_url,
+            circuit_breaker: None,
         }
     }
 
-    pub async fn check_book_existance(&self, book_id: BookId) -> anyhow::Result<bool> {
+    /// Guards book existence checks with the circuit breaker, failed calls and server errors count as failures
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    fn build_client() -> anyhow::Result<ClientWithMiddleware> {
         let reqwest_client = reqwest::Client::builder()
             .build()
             .context("Failed to build reqwest client")?;
-        let client = ClientBuilder::new(reqwest_client)
+        Ok(ClientBuilder::new(reqwest_client)
             // Insert the tracing middleware
             .with(TracingMiddleware::default())
-            .build();
+            .build())
+    }
+
+    pub async fn check_book_existance(&self, book_id: BookId) -> anyhow::Result<bool> {
+        let Some(circuit_breaker) = &self.circuit_breaker else {
+            return self
+                .get_book_response_status(book_id)
+                .await
+                .map(|status| status.is_success());
+        };
+        if !circuit_breaker.allows_call() {
+            return match circuit_breaker.open_action() {
+                OpenCircuitAction::FailFast => Err(CircuitOpenError.into()),
+                OpenCircuitAction::SkipCheck => Ok(true),
+            };
+        }
+
+        let status = self.get_book_response_status(book_id).await;
+        match &status {
+            Ok(status) if !status.is_server_error() => circuit_breaker.record_success(),
+            _ => circuit_breaker.record_failure(),
+        }
+        status.map(|status| status.is_success())
+    }
+
+    async fn get_book_response_status(&self, book_id: BookId) -> anyhow::Result<StatusCode> {
+        let client = Self::build_client()?;
 
         let response = client
-            .get(&format!(
-                "{}/api/book/{}",
-                self.book_repository_url, book_id
-            ))
+            .get(format!("{}/api/book/{}", self.book_repository_url, book_id))
             .send()
             .await
             .context("Failed to get book by id")?;
 
-        Ok(response.status().is_success())
+        Ok(response.status())
+    }
+
+    /// Lists ids of all books in the repository
+    pub async fn list_book_ids(&self) -> anyhow::Result<Vec<BookId>> {
+        let client = Self::build_client()?;
+
+        let response = client
+            .get(format!("{}/api/books", self.book_repository_url))
+            .send()
+            .await
+            .context("Failed to list books")?;
+
+        if response.status().is_success() {
+            let books: Vec<ListedBook> = response.json().await?;
+            Ok(books.into_iter().map(|book| book.book_id).collect())
+        } else {
+            bail!("Failed to list books, status {}", response.status())
+        }
+    }
+}
+
+#[cfg(test)]
+mod book_existance_checker_tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    use super::*;
+
+    #[actix_web::test]
+    /// Tests if the circuit breaker opens after consecutive failures and later recovers
+    /// 1. Starts repository stub responding with 500 and checks the book twice - expects calls to the stub
+    /// 2. Checks the book again - expects the circuit is open, the stub is not called and the check fails fast
+    /// 3. Makes the stub healthy and waits for the open duration - expects the check calls the stub and succeeds
+    ///    and the circuit is closed again
+    async fn test_circuit_breaker_opens_and_recovers() {
+        let healthy = Arc::new(AtomicBool::new(false));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let healthy = healthy.clone();
+            let calls = calls.clone();
+            HttpServer::new(move || {
+                let healthy = healthy.clone();
+                let calls = calls.clone();
+                App::new().route(
+                    "/api/book/{book_id}",
+                    web::get().to(move || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        let healthy = healthy.load(Ordering::SeqCst);
+                        async move {
+                            if healthy {
+                                HttpResponse::Ok().finish()
+                            } else {
+                                HttpResponse::InternalServerError().finish()
+                            }
+                        }
+                    }),
+                )
+            })
+            .bind(("127.0.0.1", 0))
+            .expect("Failed to bind repository stub")
+        };
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let checker = BookExistanceChecker::new(url).with_circuit_breaker(CircuitBreaker::new(
+            2,
+            Duration::from_millis(200),
+            OpenCircuitAction::FailFast,
+        ));
+        for _ in 0..2 {
+            assert!(!checker.check_book_existance(1).await.unwrap());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let error = RepositoryCallError::from(checker.check_book_existance(1).await.unwrap_err());
+        assert!(matches!(error, RepositoryCallError::Unavailable(_)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(checker.check_book_existance(1).await.unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(checker.check_book_existance(1).await.unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
     }
 }
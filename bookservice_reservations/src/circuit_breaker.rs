@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+/// Number of consecutive failures opening the circuit when none is configured, 0 disables the circuit breaker
+pub const DEFAULT_CIRCUIT_FAILURE_THRESHOLD: u32 = 0;
+
+/// Time the circuit stays open when none is configured
+pub const DEFAULT_CIRCUIT_OPEN_SECONDS: u64 = 30;
+
+/// What calls guarded by an open circuit do instead of calling the repository
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OpenCircuitAction {
+    /// Fail immediately as if the repository was unavailable
+    FailFast,
+    /// Skip the check and assume the book exists
+    SkipCheck,
+}
+
+#[derive(Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// Start of the single call allowed by the half-open circuit, a probe that did not record its result
+    /// within the open duration (e.g. its request was dropped) no longer blocks the next one
+    probe_started_at: Option<Instant>,
+}
+
+/// Stops calling an unavailable service after `failure_threshold` consecutive failures.
+/// Calls are rejected for `open_duration`, after which the circuit is half-open -
+/// a single probe call is allowed and the others are rejected until it finishes,
+/// its success closes the circuit and its failure opens it again
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    open_action: OpenCircuitAction,
+    state: parking_lot::Mutex<CircuitState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        failure_threshold: u32,
+        open_duration: Duration,
+        open_action: OpenCircuitAction,
+    ) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            open_action,
+            state: Default::default(),
+        }
+    }
+
+    pub fn open_action(&self) -> OpenCircuitAction {
+        self.open_action
+    }
+
+    /// Returns true if the call may be made (circuit closed or half-open without a probe in flight),
+    /// allowed call of the half-open circuit becomes the probe and has to record its result
+    pub fn allows_call(&self) -> bool {
+        let mut state = self.state.lock();
+        let Some(opened_at) = state.opened_at else {
+            return true;
+        };
+        let probe_in_flight = state
+            .probe_started_at
+            .is_some_and(|probe_started_at| probe_started_at.elapsed() < self.open_duration);
+        if opened_at.elapsed() >= self.open_duration && !probe_in_flight {
+            state.probe_started_at = Some(Instant::now());
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock() = CircuitState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock();
+        state.consecutive_failures += 1;
+        if state.opened_at.is_some() || state.consecutive_failures >= self.failure_threshold {
+            state.opened_at = Some(Instant::now());
+        }
+        state.probe_started_at = None;
+    }
+}
+
+#[cfg(test)]
+mod circuit_breaker_tests {
+    use super::*;
+
+    #[test]
+    /// Tests if the half-open circuit allows only a single probe call at a time
+    /// 1. Opens the circuit with a failure and waits for the open duration - expect only the first call is allowed
+    /// 2. Records failure of the probe - expect calls are rejected until the open duration passes again
+    /// 3. Waits for the open duration without recording result of the probe - expect the next probe is allowed
+    /// 4. Records success of the probe - expect all calls are allowed
+    fn test_half_open_circuit_allows_single_probe() {
+        let circuit_breaker =
+            CircuitBreaker::new(1, Duration::from_millis(50), OpenCircuitAction::FailFast);
+        circuit_breaker.record_failure();
+        assert!(!circuit_breaker.allows_call());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(circuit_breaker.allows_call());
+        assert!(!circuit_breaker.allows_call());
+
+        circuit_breaker.record_failure();
+        assert!(!circuit_breaker.allows_call());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(circuit_breaker.allows_call());
+        assert!(!circuit_breaker.allows_call());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(circuit_breaker.allows_call());
+        assert!(!circuit_breaker.allows_call());
+        circuit_breaker.record_success();
+        assert!(circuit_breaker.allows_call());
+        assert!(circuit_breaker.allows_call());
+    }
+}
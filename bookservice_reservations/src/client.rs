@@ -1,14 +1,25 @@
+use std::collections::HashMap;
+
 use anyhow::{bail, Context};
 use reqwest::header::LOCATION;
 use reqwest::StatusCode;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_tracing::TracingMiddleware;
 
-use crate::api::{BookId, ReservationHistoryRecord, UserDetails, UserId};
+use bookservice_types::api_version::ApiVersionRecorder;
+
+use crate::api::{
+    BookCheck, BookId, CanReserveResponse, DeleteUserDataQuery, HistoryQuery, HistoryRetention,
+    HistoryWindowQuery, HoldQuery, MostActiveUsersQuery, ReservationEvent,
+    ReservationHistoryRecord, ReservationId, ReservationReceipt, ReserveOrWaitOutcome,
+    ReserveOrWaitResponse, UnixSeconds, UnreserveOutcome, UserDataDeletionSummary, UserDataExport,
+    UserDetails, UserHistoryRecord, UserId, UserStats, WaitlistPosition,
+};
 
 pub struct BookServiceReservationsClient {
     url: String,
     client: ClientWithMiddleware,
+    api_version: ApiVersionRecorder,
 }
 
 impl BookServiceReservationsClient {
@@ -16,17 +27,25 @@ impl BookServiceReservationsClient {
         let reqwest_client = reqwest::Client::builder()
             .build()
             .context("Failed to build reqwest client")?;
+        let api_version = ApiVersionRecorder::default();
         let client = ClientBuilder::new(reqwest_client)
             // Insert the tracing middleware
             .with(TracingMiddleware::default())
+            .with(api_version.clone())
             .build();
 
         Ok(Self {
             url: url.to_string(),
             client,
+            api_version,
         })
     }
 
+    /// Returns api version of the server from the last response, None if no response was received yet
+    pub fn server_api_version(&self) -> Option<String> {
+        self.api_version.server_api_version()
+    }
+
     /// Calls POST /api/user endpoint
     /// Returns user_id of added user in response
     pub async fn add_user(&self, user_details: UserDetails) -> anyhow::Result<UserId> {
@@ -76,6 +95,70 @@ impl BookServiceReservationsClient {
         }
     }
 
+    /// Calls GET /api/user/{user_id}/export endpoint
+    /// Returns all data stored about the user or None if user was not found
+    pub async fn export_user_data(
+        &self,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<UserDataExport>> {
+        let response = self
+            .client
+            .get(format!("{}/api/user/{}/export", self.url, user_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to export user data {}", error)
+        }
+    }
+
+    /// Calls GET /api/user/{user_id}/stats endpoint
+    /// Returns summary of user reading activity or None if user was not found
+    pub async fn user_stats(&self, user_id: UserId) -> anyhow::Result<Option<UserStats>> {
+        let response = self
+            .client
+            .get(format!("{}/api/user/{}/stats", self.url, user_id))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get user stats {}", error)
+        }
+    }
+
+    /// Calls DELETE /api/user/{user_id}/data endpoint
+    /// Returns summary of removed data or None if user was not found
+    pub async fn delete_user_data(
+        &self,
+        user_id: UserId,
+        history: HistoryRetention,
+    ) -> anyhow::Result<Option<UserDataDeletionSummary>> {
+        let response = self
+            .client
+            .delete(format!("{}/api/user/{}/data", self.url, user_id))
+            .query(&DeleteUserDataQuery {
+                history: Some(history),
+            })
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to delete user data {}", error)
+        }
+    }
+
     /// Calls GET /api/users endpoint
     pub async fn list_users(&self) -> anyhow::Result<Vec<UserId>> {
         let response = self
@@ -91,20 +174,300 @@ impl BookServiceReservationsClient {
         }
     }
 
+    /// Calls GET /api/users/most_active endpoint
+    /// Returns user ids ranked by total number of reservations (active and history)
+    pub async fn most_active_users(&self, limit: Option<u32>) -> anyhow::Result<Vec<UserId>> {
+        let response = self
+            .client
+            .get(format!("{}/api/users/most_active", self.url))
+            .query(&MostActiveUsersQuery { limit })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get most active users {}", error)
+        }
+    }
+
     /// Calls POST /api/user/{user_id}/reservation/{book_id} endpoint
-    /// Returns true if successful and false if failed to reserve
-    pub async fn reserve_book(&self, book_id: BookId, user_id: UserId) -> anyhow::Result<bool> {
+    /// Returns receipt of the reservation if successful and None if failed to reserve
+    pub async fn reserve_book(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<ReservationReceipt>> {
         let url = format!("{}/api/user/{}/reservation/{}", self.url, user_id, book_id);
         // This "json" part is required, as it adds some headers needed for nginx to process correctly
         let response = self.client.post(url).json("").send().await?;
 
+        if response.status() == StatusCode::FORBIDDEN {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get reserve book {}", error)
+        }
+    }
+
+    /// Calls POST /api/user/{user_id}/reservation/isbn/{isbn} endpoint
+    /// Returns receipt of the reservation of the book with given ISBN if successful,
+    /// None if there is no such book or failed to reserve it
+    pub async fn reserve_book_by_isbn(
+        &self,
+        isbn: &str,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<ReservationReceipt>> {
+        let url = format!(
+            "{}/api/user/{}/reservation/isbn/{}",
+            self.url, user_id, isbn
+        );
+        let response = self.client.post(url).json("").send().await?;
+
+        if response.status() == StatusCode::FORBIDDEN || response.status() == StatusCode::NOT_FOUND
+        {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to reserve book by ISBN {}", error)
+        }
+    }
+
+    /// Calls POST /api/user/{user_id}/reservation/{book_id}/renew endpoint
+    /// Returns None if the user does not hold the book or other users wait for it
+    pub async fn renew(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<ReservationReceipt>> {
+        let url = format!(
+            "{}/api/user/{}/reservation/{}/renew",
+            self.url, user_id, book_id
+        );
+        let response = self.client.post(url).json("").send().await?;
+
+        if response.status() == StatusCode::FORBIDDEN || response.status() == StatusCode::CONFLICT {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to renew reservation {}", error)
+        }
+    }
+
+    /// Calls GET /api/user/{user_id}/can_reserve/{book_id} endpoint
+    /// Returns whether the user may reserve the book now and the reason if not, without reserving it
+    pub async fn can_reserve(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<CanReserveResponse> {
+        let url = format!("{}/api/user/{}/can_reserve/{}", self.url, user_id, book_id);
+        let response = self.client.get(url).send().await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to check if book can be reserved {}", error)
+        }
+    }
+
+    /// Calls POST /api/user/{user_id}/reserve_or_wait/{book_id} endpoint
+    /// Returns whether the book was reserved or the user was added to its waitlist (with the position)
+    pub async fn reserve_or_wait(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<ReserveOrWaitOutcome> {
+        let url = format!(
+            "{}/api/user/{}/reserve_or_wait/{}",
+            self.url, user_id, book_id
+        );
+        // This "json" part is required, as it adds some headers needed for nginx to process correctly
+        let response = self.client.post(url).json("").send().await?;
+
+        if response.status().is_success() {
+            let response: ReserveOrWaitResponse = response.json().await?;
+            Ok(response.into())
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to reserve or wait for book {}", error)
+        }
+    }
+
+    /// Calls GET /api/user/{user_id}/waitlist/{book_id}/position endpoint
+    /// Returns position of the user in the waitlist of the book (starting from 1)
+    /// or None if the user is not waiting for the book
+    pub async fn waitlist_position(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<u32>> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/api/user/{}/waitlist/{}/position",
+                self.url, user_id, book_id
+            ))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            let response: WaitlistPosition = response.json().await?;
+            Ok(Some(response.position))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get waitlist position {}", error)
+        }
+    }
+
+    /// Calls GET /api/reservations/count endpoint
+    /// Returns total number of active reservations of all users
+    pub async fn count_reservations(&self) -> anyhow::Result<u64> {
+        let response = self
+            .client
+            .get(format!("{}/api/reservations/count", self.url))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to count reservations {}", error)
+        }
+    }
+
+    /// Calls PUT /api/book/{book_id}/copies endpoint
+    /// Sets number of copies of the book
+    pub async fn set_book_copies(&self, book_id: BookId, copies: u32) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .put(format!("{}/api/book/{}/copies", self.url, book_id))
+            .json(&copies)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to set book copies {}", error)
+        }
+    }
+
+    /// Calls GET /api/book/{book_id}/available_copies endpoint
+    /// Returns number of copies of the book that are not reserved
+    pub async fn available_copies(&self, book_id: BookId) -> anyhow::Result<u32> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/api/book/{}/available_copies",
+                self.url, book_id
+            ))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get available copies {}", error)
+        }
+    }
+
+    /// Calls POST /api/admin/user/{user_id}/reservation/{book_id} endpoint
+    /// Returns receipt of the reservation made for the user if successful and None if failed to reserve
+    pub async fn admin_reserve_book(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+    ) -> anyhow::Result<Option<ReservationReceipt>> {
+        let url = format!(
+            "{}/api/admin/user/{}/reservation/{}",
+            self.url, user_id, book_id
+        );
+        // This "json" part is required, as it adds some headers needed for nginx to process correctly
+        let response = self.client.post(url).json("").send().await?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to reserve book for user {}", error)
+        }
+    }
+
+    /// Calls GET /api/reservation/{reservation_id} endpoint
+    /// Returns receipt of the active reservation or None if it was not found
+    pub async fn get_reservation(
+        &self,
+        reservation_id: ReservationId,
+    ) -> anyhow::Result<Option<ReservationReceipt>> {
+        let response = self
+            .client
+            .get(format!("{}/api/reservation/{}", self.url, reservation_id))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if response.status().is_success() {
+            Ok(Some(response.json().await?))
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get reservation {}", error)
+        }
+    }
+
+    /// Calls POST /api/user/{user_id}/hold/{book_id} endpoint
+    /// Returns true if successful and false if book is already reserved or held by different user
+    pub async fn create_hold(
+        &self,
+        book_id: BookId,
+        user_id: UserId,
+        ttl_seconds: Option<u64>,
+    ) -> anyhow::Result<bool> {
+        let url = format!("{}/api/user/{}/hold/{}", self.url, user_id, book_id);
+        let response = self
+            .client
+            .post(url)
+            .query(&HoldQuery { ttl_seconds })
+            .json("")
+            .send()
+            .await?;
+
         if response.status() == StatusCode::FORBIDDEN {
             Ok(false)
         } else if response.status().is_success() {
             Ok(true)
         } else {
             let error: String = response.json().await.unwrap_or_default();
-            bail!("Failed to get reserve book {}", error)
+            bail!("Failed to hold book {}", error)
+        }
+    }
+
+    /// Calls POST /api/user/{user_id}/hold/{book_id}/confirm endpoint
+    /// Returns true if hold was converted to a reservation and false if there was no active hold of the user
+    pub async fn confirm_hold(&self, book_id: BookId, user_id: UserId) -> anyhow::Result<bool> {
+        let url = format!("{}/api/user/{}/hold/{}/confirm", self.url, user_id, book_id);
+        let response = self.client.post(url).json("").send().await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            Ok(false)
+        } else if response.status().is_success() {
+            Ok(true)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to confirm hold {}", error)
         }
     }
 
@@ -130,6 +493,28 @@ impl BookServiceReservationsClient {
         }
     }
 
+    /// Calls DELETE /api/user/{user_id}/reservations endpoint
+    /// Returns outcome of unreserving each of the given books
+    pub async fn unreserve_books(
+        &self,
+        user_id: UserId,
+        book_ids: &[BookId],
+    ) -> anyhow::Result<HashMap<BookId, UnreserveOutcome>> {
+        let response = self
+            .client
+            .delete(format!("{}/api/user/{}/reservations", self.url, user_id))
+            .json(book_ids)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to unreserve books {}", error)
+        }
+    }
+
     /// Calls GET /api/user/{user_id}/reservations endpoint
     pub async fn list_reservations(&self, user_id: UserId) -> anyhow::Result<Vec<BookId>> {
         let response = self
@@ -145,6 +530,26 @@ impl BookServiceReservationsClient {
         }
     }
 
+    /// Calls POST /api/users/reservations/batch endpoint
+    /// Returns active reservations of each of given users
+    pub async fn list_reservations_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> anyhow::Result<HashMap<UserId, Vec<BookId>>> {
+        let response = self
+            .client
+            .post(format!("{}/api/users/reservations/batch", self.url))
+            .json(user_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to list reservations for users {}", error)
+        }
+    }
+
     /// Calls GET /api/user/{user_id}/history endpoint
     pub async fn history(&self, user_id: UserId) -> anyhow::Result<Vec<ReservationHistoryRecord>> {
         let response = self
@@ -159,4 +564,182 @@ impl BookServiceReservationsClient {
             bail!("Failed to list books {}", error)
         }
     }
+
+    /// Calls GET /api/user/{user_id}/activity endpoint
+    /// Returns reserve and unreserve events of the user ordered by timestamp
+    pub async fn activity(&self, user_id: UserId) -> anyhow::Result<Vec<ReservationEvent>> {
+        let response = self
+            .client
+            .get(format!("{}/api/user/{}/activity", self.url, user_id))
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get user activity {}", error)
+        }
+    }
+
+    /// Calls GET /api/user/{user_id}/history?from=&to= endpoint
+    /// Returns history records of the user unreserved within the window, both bounds are inclusive
+    pub async fn history_between(
+        &self,
+        user_id: UserId,
+        from: Option<UnixSeconds>,
+        to: Option<UnixSeconds>,
+    ) -> anyhow::Result<Vec<ReservationHistoryRecord>> {
+        let response = self
+            .client
+            .get(format!("{}/api/user/{}/history", self.url, user_id))
+            .query(&HistoryWindowQuery { from, to })
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get history between {}", error)
+        }
+    }
+
+    /// Calls GET /api/history endpoint
+    /// Returns a page of history records of all users matching the query
+    pub async fn history_page(
+        &self,
+        query: &HistoryQuery,
+    ) -> anyhow::Result<Vec<UserHistoryRecord>> {
+        let response = self
+            .client
+            .get(format!("{}/api/history", self.url))
+            .query(query)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get history {}", error)
+        }
+    }
+
+    /// Calls POST /api/users/details/batch endpoint
+    /// Returns details of each of given users, users not in the repository are omitted
+    pub async fn users_details(
+        &self,
+        user_ids: &[UserId],
+    ) -> anyhow::Result<HashMap<UserId, UserDetails>> {
+        let response = self
+            .client
+            .post(format!("{}/api/users/details/batch", self.url))
+            .json(user_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get details of users {}", error)
+        }
+    }
+
+    /// Calls POST /api/users/history/batch endpoint
+    /// Returns reservations history of each of given users
+    pub async fn history_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> anyhow::Result<HashMap<UserId, Vec<ReservationHistoryRecord>>> {
+        let response = self
+            .client
+            .post(format!("{}/api/users/history/batch", self.url))
+            .json(user_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get history for users {}", error)
+        }
+    }
+
+    /// Calls POST /api/users/history/since/batch endpoint
+    /// Returns reservations history of each of given users, only with records unreserved at or after given timestamp
+    pub async fn history_since_for_users(
+        &self,
+        user_id_to_since: &HashMap<UserId, UnixSeconds>,
+    ) -> anyhow::Result<HashMap<UserId, Vec<ReservationHistoryRecord>>> {
+        let response = self
+            .client
+            .post(format!("{}/api/users/history/since/batch", self.url))
+            .json(user_id_to_since)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get history since for users {}", error)
+        }
+    }
+
+    /// Calls POST /api/books/availability endpoint
+    /// Returns availability of each of given books (true if book is not reserved)
+    pub async fn books_availability(
+        &self,
+        book_ids: &[BookId],
+    ) -> anyhow::Result<HashMap<BookId, bool>> {
+        let response = self
+            .client
+            .post(format!("{}/api/books/availability", self.url))
+            .json(book_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get books availability {}", error)
+        }
+    }
+
+    /// Calls POST /api/books/check endpoint
+    /// Returns existence in the repository and availability of each of given books
+    pub async fn check_books(
+        &self,
+        book_ids: &[BookId],
+    ) -> anyhow::Result<HashMap<BookId, BookCheck>> {
+        let response = self
+            .client
+            .post(format!("{}/api/books/check", self.url))
+            .json(book_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to check books {}", error)
+        }
+    }
+
+    /// Calls POST /api/books/holders endpoint
+    /// Returns user holding each of given books that are reserved (free books are omitted)
+    pub async fn books_holders(
+        &self,
+        book_ids: &[BookId],
+    ) -> anyhow::Result<HashMap<BookId, UserId>> {
+        let response = self
+            .client
+            .post(format!("{}/api/books/holders", self.url))
+            .json(book_ids)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let error: String = response.json().await.unwrap_or_default();
+            bail!("Failed to get books holders {}", error)
+        }
+    }
 }
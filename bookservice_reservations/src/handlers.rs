@@ -1,16 +1,30 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix_web::{Error, HttpResponse, ResponseError};
 use actix_web::body::BoxBody;
-use actix_web::http::header::LOCATION;
+use actix_web::http::header::{LOCATION, RETRY_AFTER};
+use bookservice_repository::client::BookServiceRepositoryClient;
 use paperclip::actix::{
     api_v2_operation,
     web::{self, Data},
 };
 
-use crate::api::{BookId, ReservationHistoryRecord, UserDetails, UserId};
-use crate::book_existance_checker::BookExistanceChecker;
+use crate::api::{
+    BookCheck, BookId, BranchId, BranchQuery, CanReserveResponse, DeleteUserDataQuery,
+    HistoryQuery, HistoryWindowQuery, HoldQuery, MostActiveUsersQuery, ReservationAction,
+    ReservationEvent, ReservationHistoryRecord, ReservationId, ReservationReceipt,
+    ReservationsQuery, ReservationsSort, ReserveOrWaitOutcome, ReserveOrWaitResponse, UnixSeconds,
+    UnreserveOutcome, UserDataDeletionSummary, UserDataExport, UserDetails, UserHistoryRecord,
+    UserId, UserStats, WaitlistPosition, DEFAULT_BRANCH_ID, DEFAULT_HOLD_TTL_SECONDS,
+    DEFAULT_MOST_ACTIVE_USERS_LIMIT,
+};
+use crate::book_existance_checker::{BookExistanceChecker, RepositoryCallError};
+use crate::renewal_period::RenewalPeriod;
+use crate::reservation_cooldown::ReservationCooldown;
 use crate::reservations_repository::{ReservationsRepository, ReservationsRepositoryError};
+use crate::webhook_dispatcher::WebhookDispatcher;
 
 impl ResponseError for ReservationsRepositoryError {
     fn error_response(&self) -> HttpResponse<BoxBody> {
@@ -21,17 +35,58 @@ impl ResponseError for ReservationsRepositoryError {
             ReservationsRepositoryError::BookAlreadyReserved(book_id) => {
                 HttpResponse::Forbidden().body(format!("Book already reserved {}", book_id))
             }
+            ReservationsRepositoryError::BookAlreadyReservedBy(book_id, holder) => {
+                HttpResponse::Forbidden().body(format!(
+                    "Book already reserved {} by user {}",
+                    book_id, holder
+                ))
+            }
+            ReservationsRepositoryError::BookOnHold(book_id) => {
+                HttpResponse::Forbidden().body(format!("Book held by different user {}", book_id))
+            }
+            ReservationsRepositoryError::HoldNotFound(book_id) => {
+                HttpResponse::NotFound().body(format!("Active hold not found {}", book_id))
+            }
+            ReservationsRepositoryError::ReservationNotFound(reservation_id) => {
+                HttpResponse::NotFound().body(format!("Reservation not found {}", reservation_id))
+            }
             ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(book_id) => {
                 HttpResponse::Forbidden().body(format!(
                     "Book not reserved or reserved {} by different user",
                     book_id
                 ))
             }
+            ReservationsRepositoryError::BookWaitlisted(book_id) => {
+                HttpResponse::Conflict().body(format!("Other users wait for book {}", book_id))
+            }
             _ => HttpResponse::InternalServerError().body(self.to_string()),
         }
     }
 }
 
+impl ResponseError for RepositoryCallError {
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        match self {
+            RepositoryCallError::Unavailable(_) => {
+                HttpResponse::ServiceUnavailable().body("repository unavailable")
+            }
+            RepositoryCallError::Other(_) => {
+                HttpResponse::InternalServerError().body(self.to_string())
+            }
+        }
+    }
+}
+
+/// OpenAPI spec converted to YAML once at startup
+pub struct YamlSpec(pub String);
+
+#[api_v2_operation(skip)]
+pub async fn get_yaml_spec(spec: Data<YamlSpec>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok()
+        .content_type("application/yaml")
+        .body(spec.0.clone()))
+}
+
 #[api_v2_operation]
 pub async fn health() -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().finish())
@@ -42,8 +97,9 @@ pub async fn add_user(
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
     details: web::Json<UserDetails>,
 ) -> Result<HttpResponse, Error> {
+    details.validate_text()?;
     let user_id = reservations_repository
-        .add_user(details.into_inner())
+        .add_user(details.into_inner().normalized())
         .await?;
     Ok(HttpResponse::Ok()
         .append_header((LOCATION, format!("/api/user/{}", user_id)))
@@ -62,6 +118,85 @@ pub async fn get_user(
     ))
 }
 
+#[api_v2_operation]
+pub async fn export_user_data(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_id: web::Path<UserId>,
+) -> Result<web::Json<UserDataExport>, Error> {
+    let user_id = user_id.into_inner();
+    Ok(web::Json(UserDataExport {
+        user_id,
+        details: reservations_repository.get_user(user_id).await?,
+        reservations: reservations_repository
+            .get_all_reservations(user_id, ReservationsSort::default())
+            .await?,
+        history: reservations_repository
+            .get_reservations_history(user_id)
+            .await?,
+    }))
+}
+
+#[api_v2_operation]
+pub async fn get_user_stats(
+    books_repository_client: Data<BookServiceRepositoryClient>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_id: web::Path<UserId>,
+) -> Result<web::Json<UserStats>, Error> {
+    let user_id = user_id.into_inner();
+    // Responds with not found for unknown user
+    reservations_repository.get_user(user_id).await?;
+    let reservations = reservations_repository
+        .get_all_reservations(user_id, ReservationsSort::default())
+        .await?;
+    let history = reservations_repository
+        .get_reservations_history(user_id)
+        .await?;
+
+    // Authors and tags are stored only in the repository service, so details of the books are fetched from it
+    let book_ids: Vec<BookId> = reservations
+        .iter()
+        .cloned()
+        .chain(history.iter().map(|record| record.book_id))
+        .collect::<HashSet<BookId>>()
+        .into_iter()
+        .collect();
+    let book_id_to_details = books_repository_client
+        .get_books(&book_ids)
+        .await
+        .map_err(RepositoryCallError::from)?;
+    for book_id in book_ids.iter() {
+        if !book_id_to_details.contains_key(book_id) {
+            tracing::warn!("Failed to get details for book {}", book_id);
+        }
+    }
+    let mut authors = HashSet::new();
+    let mut tags = HashSet::new();
+    for details in book_id_to_details.into_values() {
+        authors.extend(details.authors);
+        tags.extend(details.tags);
+    }
+
+    Ok(web::Json(UserStats {
+        total_reserved_ever: (reservations.len() + history.len()) as i64,
+        currently_reserved: reservations.len() as i64,
+        distinct_authors: authors.len() as i64,
+        distinct_tags: tags.len() as i64,
+    }))
+}
+
+#[api_v2_operation]
+pub async fn delete_user_data(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_id: web::Path<UserId>,
+    query: web::Query<DeleteUserDataQuery>,
+) -> Result<web::Json<UserDataDeletionSummary>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .delete_user_data(user_id.into_inner(), query.history.unwrap_or_default())
+            .await?,
+    ))
+}
+
 #[api_v2_operation]
 pub async fn get_all_users(
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
@@ -69,14 +204,152 @@ pub async fn get_all_users(
     Ok(web::Json(reservations_repository.get_all_user_ids().await?))
 }
 
+#[api_v2_operation]
+pub async fn get_most_active_users(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    query: web::Query<MostActiveUsersQuery>,
+) -> Result<web::Json<Vec<UserId>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_most_active_users(query.limit.unwrap_or(DEFAULT_MOST_ACTIVE_USERS_LIMIT))
+            .await?,
+    ))
+}
+
 #[api_v2_operation]
 pub async fn get_all_reservations(
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
     user_id: web::Path<UserId>,
+    query: web::Query<ReservationsQuery>,
+) -> Result<web::Json<Vec<BookId>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_all_reservations(user_id.into_inner(), query.sort.unwrap_or_default())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_reservations_for_users(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_ids: web::Json<Vec<UserId>>,
+) -> Result<web::Json<HashMap<UserId, Vec<BookId>>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_reservations_for_users(&user_ids.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_never_reserved_books(
+    book_existance_checker: Data<BookExistanceChecker>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
 ) -> Result<web::Json<Vec<BookId>>, Error> {
+    let book_ids = book_existance_checker
+        .list_book_ids()
+        .await
+        .map_err(RepositoryCallError::from)?;
     Ok(web::Json(
         reservations_repository
-            .get_all_reservations(user_id.into_inner())
+            .get_never_reserved_books(&book_ids)
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_books_availability(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_ids: web::Json<Vec<BookId>>,
+) -> Result<web::Json<HashMap<BookId, bool>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_books_availability(&book_ids.into_inner())
+            .await?,
+    ))
+}
+
+/// Checks existence of the requested books in the repository
+/// and availability of the existing ones, missing books are never available
+#[api_v2_operation]
+pub async fn check_books(
+    books_repository_client: Data<BookServiceRepositoryClient>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_ids: web::Json<Vec<BookId>>,
+) -> Result<web::Json<HashMap<BookId, BookCheck>>, Error> {
+    let book_ids = book_ids.into_inner();
+    let existing_books = books_repository_client
+        .get_books(&book_ids)
+        .await
+        .map_err(RepositoryCallError::from)?;
+    let availability = reservations_repository
+        .get_books_availability(&book_ids)
+        .await?;
+    Ok(web::Json(
+        book_ids
+            .into_iter()
+            .map(|book_id| {
+                let exists = existing_books.contains_key(&book_id);
+                let available = exists && availability.get(&book_id).cloned().unwrap_or_default();
+                (book_id, BookCheck { exists, available })
+            })
+            .collect(),
+    ))
+}
+
+#[api_v2_operation]
+pub async fn count_reservations(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+) -> Result<web::Json<u64>, Error> {
+    Ok(web::Json(
+        reservations_repository.count_reservations().await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_books_holders(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_ids: web::Json<Vec<BookId>>,
+) -> Result<web::Json<HashMap<BookId, UserId>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_books_holders(&book_ids.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn average_hold_duration(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_id: web::Path<BookId>,
+) -> Result<web::Json<Option<f64>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .average_hold_duration(book_id.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn set_book_copies(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_id: web::Path<BookId>,
+    copies: web::Json<u32>,
+) -> Result<HttpResponse, Error> {
+    reservations_repository
+        .set_book_copies(book_id.into_inner(), copies.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[api_v2_operation]
+pub async fn get_available_copies(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    book_id: web::Path<BookId>,
+) -> Result<web::Json<u32>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .count_available_copies(book_id.into_inner())
             .await?,
     ))
 }
@@ -85,10 +358,69 @@ pub async fn get_all_reservations(
 pub async fn get_reservations_history(
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
     user_id: web::Path<UserId>,
+    query: web::Query<HistoryWindowQuery>,
 ) -> Result<web::Json<Vec<ReservationHistoryRecord>>, Error> {
     Ok(web::Json(
         reservations_repository
-            .get_reservations_history(user_id.into_inner())
+            .get_reservations_history_between(user_id.into_inner(), query.from, query.to)
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_user_activity(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_id: web::Path<UserId>,
+) -> Result<web::Json<Vec<ReservationEvent>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_user_activity(user_id.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_history(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    query: web::Query<HistoryQuery>,
+) -> Result<web::Json<Vec<UserHistoryRecord>>, Error> {
+    Ok(web::Json(
+        reservations_repository.get_history(&query).await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_users_details(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_ids: web::Json<Vec<UserId>>,
+) -> Result<web::Json<HashMap<UserId, UserDetails>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_users(&user_ids.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_history_for_users(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_ids: web::Json<Vec<UserId>>,
+) -> Result<web::Json<HashMap<UserId, Vec<ReservationHistoryRecord>>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_history_for_users(&user_ids.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn get_history_since_for_users(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_id_to_since: web::Json<HashMap<UserId, UnixSeconds>>,
+) -> Result<web::Json<HashMap<UserId, Vec<ReservationHistoryRecord>>>, Error> {
+    Ok(web::Json(
+        reservations_repository
+            .get_history_since_for_users(&user_id_to_since.into_inner())
             .await?,
     ))
 }
@@ -97,18 +429,225 @@ pub async fn get_reservations_history(
 pub async fn reserve_book(
     book_existance_checker: Data<BookExistanceChecker>,
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+    branch: web::Query<BranchQuery>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    if let Some(remaining) = reservation_cooldown.remaining(user_id, book_id) {
+        return Ok(cooldown_response(book_id, remaining));
+    }
+    reserve_existing_book(
+        &book_existance_checker,
+        reservations_repository.as_ref(),
+        webhook_dispatcher.as_ref().map(Data::get_ref),
+        user_id,
+        book_id,
+        branch.branch_id.unwrap_or(DEFAULT_BRANCH_ID),
+    )
+    .await
+}
+
+/// Extends due date of the reservation by the renewal period, rejected if other users wait for the book
+#[api_v2_operation]
+pub async fn renew_reservation(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    renewal_period: Data<RenewalPeriod>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+) -> Result<web::Json<ReservationReceipt>, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    Ok(web::Json(
+        reservations_repository
+            .renew_reservation(user_id, book_id, renewal_period.0)
+            .await?,
+    ))
+}
+
+/// Runs the same checks as reserving the book (cooldown, book existence and availability) without reserving it
+#[api_v2_operation]
+pub async fn can_reserve_book(
+    book_existance_checker: Data<BookExistanceChecker>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+) -> Result<web::Json<CanReserveResponse>, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    let denied = |reason: String| {
+        Ok(web::Json(CanReserveResponse {
+            allowed: false,
+            reason: Some(reason),
+        }))
+    };
+
+    if reservation_cooldown.remaining(user_id, book_id).is_some() {
+        return denied(format!("Book {} was unreserved too recently", book_id));
+    }
+    let book_exists = book_existance_checker
+        .check_book_existance(book_id)
+        .await
+        .map_err(RepositoryCallError::from)?;
+    if !book_exists {
+        return denied(format!("Book not found {}", book_id));
+    }
+    match reservations_repository
+        .check_reservable(user_id, book_id)
+        .await
+    {
+        Ok(()) => Ok(web::Json(CanReserveResponse {
+            allowed: true,
+            reason: None,
+        })),
+        Err(
+            ReservationsRepositoryError::BookAlreadyReserved(_)
+            | ReservationsRepositoryError::BookAlreadyReservedBy(..),
+        ) => denied(format!("Book already reserved {}", book_id)),
+        Err(ReservationsRepositoryError::BookOnHold(_)) => {
+            denied(format!("Book held by different user {}", book_id))
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Responds with 429 and `Retry-After` (in whole seconds, rounded up) if book was unreserved too recently
+fn cooldown_response(book_id: BookId, remaining: Duration) -> HttpResponse {
+    let retry_after_seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, retry_after_seconds))
+        .body(format!("Book {} was unreserved too recently", book_id))
+}
+
+/// Reservation made by an admin on behalf of the user (e.g. by a librarian at the desk),
+/// the reservation cooldown does not apply
+#[api_v2_operation]
+pub async fn admin_reserve_book(
+    book_existance_checker: Data<BookExistanceChecker>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    reserve_existing_book(
+        &book_existance_checker,
+        reservations_repository.as_ref(),
+        webhook_dispatcher.as_ref().map(Data::get_ref),
+        user_id,
+        book_id,
+        DEFAULT_BRANCH_ID,
+    )
+    .await
+}
+
+/// Reserves the book for the user in the branch if the book exists in the repository,
+/// responds with the reservation receipt. Reserved event is dispatched only if a new reservation was created
+async fn reserve_existing_book(
+    book_existance_checker: &BookExistanceChecker,
+    reservations_repository: &Arc<dyn ReservationsRepository>,
+    webhook_dispatcher: Option<&WebhookDispatcher>,
+    user_id: UserId,
+    book_id: BookId,
+    branch_id: BranchId,
+) -> Result<HttpResponse, Error> {
+    let book_exists = book_existance_checker
+        .check_book_existance(book_id)
+        .await
+        .map_err(RepositoryCallError::from)?;
+
+    if book_exists {
+        let (receipt, created) = reservations_repository
+            .reserve_book_in_branch_with_status(user_id, book_id, branch_id)
+            .await?;
+        if let Some(webhook_dispatcher) = webhook_dispatcher.filter(|_| created) {
+            webhook_dispatcher.dispatch(ReservationAction::Reserved, user_id, book_id);
+        }
+        Ok(HttpResponse::Ok().json(receipt))
+    } else {
+        Ok(HttpResponse::NotFound().body(format!("Book not found {}", book_id)))
+    }
+}
+
+/// Reserves the book for the user if it is available, otherwise adds the user to the book waitlist
+#[api_v2_operation]
+pub async fn reserve_or_wait(
+    book_existance_checker: Data<BookExistanceChecker>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
     user_and_book_id: web::Path<(UserId, BookId)>,
 ) -> Result<HttpResponse, Error> {
     let (user_id, book_id) = user_and_book_id.into_inner();
+    if let Some(remaining) = reservation_cooldown.remaining(user_id, book_id) {
+        return Ok(cooldown_response(book_id, remaining));
+    }
 
     let book_exists = book_existance_checker
         .check_book_existance(book_id)
         .await
-        .map_err(|err| ReservationsRepositoryError::Other(err.to_string()))?;
+        .map_err(RepositoryCallError::from)?;
 
     if book_exists {
+        let outcome = reservations_repository
+            .reserve_or_wait(user_id, book_id)
+            .await?;
+        if let Some(webhook_dispatcher) =
+            webhook_dispatcher.filter(|_| outcome == ReserveOrWaitOutcome::Reserved)
+        {
+            webhook_dispatcher.dispatch(ReservationAction::Reserved, user_id, book_id);
+        }
+        Ok(HttpResponse::Ok().json(ReserveOrWaitResponse::from(outcome)))
+    } else {
+        Ok(HttpResponse::NotFound().body(format!("Book not found {}", book_id)))
+    }
+}
+
+#[api_v2_operation]
+pub async fn get_waitlist_position(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    match reservations_repository
+        .get_waitlist_position(user_id, book_id)
+        .await?
+    {
+        Some(position) => Ok(HttpResponse::Ok().json(WaitlistPosition { position })),
+        None => Ok(HttpResponse::NotFound().body(format!(
+            "User {} is not waiting for book {}",
+            user_id, book_id
+        ))),
+    }
+}
+
+#[api_v2_operation]
+pub async fn get_reservation(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_id: web::Path<ReservationId>,
+) -> Result<web::Json<ReservationReceipt>, Error> {
+    Ok(web::Json(
         reservations_repository
-            .reserve_book(user_id, book_id)
+            .get_reservation(reservation_id.into_inner())
+            .await?,
+    ))
+}
+
+#[api_v2_operation]
+pub async fn create_hold(
+    book_existance_checker: Data<BookExistanceChecker>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+    query: web::Query<HoldQuery>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+
+    let book_exists = book_existance_checker
+        .check_book_existance(book_id)
+        .await
+        .map_err(RepositoryCallError::from)?;
+
+    if book_exists {
+        let ttl = Duration::from_secs(query.ttl_seconds.unwrap_or(DEFAULT_HOLD_TTL_SECONDS));
+        reservations_repository
+            .create_hold(user_id, book_id, ttl)
             .await?;
         Ok(HttpResponse::Ok().finish())
     } else {
@@ -116,14 +655,1503 @@ pub async fn reserve_book(
     }
 }
 
+#[api_v2_operation]
+pub async fn confirm_hold(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
+    user_and_book_id: web::Path<(UserId, BookId)>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, book_id) = user_and_book_id.into_inner();
+    let created = reservations_repository
+        .confirm_hold(user_id, book_id)
+        .await?;
+    if let Some(webhook_dispatcher) = webhook_dispatcher.filter(|_| created) {
+        webhook_dispatcher.dispatch(ReservationAction::Reserved, user_id, book_id);
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[api_v2_operation]
+pub async fn reserve_book_by_isbn(
+    books_repository_client: Data<BookServiceRepositoryClient>,
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
+    user_id_and_isbn: web::Path<(UserId, String)>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, isbn) = user_id_and_isbn.into_inner();
+
+    let book = books_repository_client
+        .get_book_by_isbn(&isbn)
+        .await
+        .map_err(RepositoryCallError::from)?;
+
+    if let Some(book) = book {
+        if let Some(remaining) = reservation_cooldown.remaining(user_id, book.book_id) {
+            return Ok(cooldown_response(book.book_id, remaining));
+        }
+        let (receipt, created) = reservations_repository
+            .reserve_book_in_branch_with_status(user_id, book.book_id, DEFAULT_BRANCH_ID)
+            .await?;
+        if let Some(webhook_dispatcher) = webhook_dispatcher.filter(|_| created) {
+            webhook_dispatcher.dispatch(ReservationAction::Reserved, user_id, book.book_id);
+        }
+        Ok(HttpResponse::Ok().json(receipt))
+    } else {
+        Ok(HttpResponse::NotFound().body(format!("Book not found by ISBN {}", isbn)))
+    }
+}
+
 #[api_v2_operation]
 pub async fn unreserve_book(
     reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
     user_and_book_id: web::Path<(UserId, BookId)>,
+    branch: web::Query<BranchQuery>,
 ) -> Result<HttpResponse, Error> {
     let (user_id, book_id) = user_and_book_id.into_inner();
     reservations_repository
-        .unreserve_book(user_id, book_id)
+        .unreserve_book_in_branch(
+            user_id,
+            book_id,
+            branch.branch_id.unwrap_or(DEFAULT_BRANCH_ID),
+        )
         .await?;
+    reservation_cooldown.record_unreserve(user_id, book_id);
+    if let Some(webhook_dispatcher) = webhook_dispatcher {
+        webhook_dispatcher.dispatch(ReservationAction::Unreserved, user_id, book_id);
+    }
     Ok(HttpResponse::Ok().finish())
 }
+
+#[api_v2_operation]
+pub async fn unreserve_books(
+    reservations_repository: Data<Arc<dyn ReservationsRepository>>,
+    reservation_cooldown: Data<ReservationCooldown>,
+    webhook_dispatcher: Option<Data<WebhookDispatcher>>,
+    user_id: web::Path<UserId>,
+    book_ids: web::Json<Vec<BookId>>,
+) -> Result<web::Json<HashMap<BookId, UnreserveOutcome>>, Error> {
+    let user_id = user_id.into_inner();
+    let mut outcomes = HashMap::new();
+    for book_id in book_ids.into_inner() {
+        let outcome = match reservations_repository
+            .unreserve_book(user_id, book_id)
+            .await
+        {
+            Ok(()) => {
+                reservation_cooldown.record_unreserve(user_id, book_id);
+                if let Some(webhook_dispatcher) = &webhook_dispatcher {
+                    webhook_dispatcher.dispatch(ReservationAction::Unreserved, user_id, book_id);
+                }
+                UnreserveOutcome::Unreserved
+            }
+            Err(ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(_)) => {
+                UnreserveOutcome::NotHeld
+            }
+            Err(err) => return Err(err.into()),
+        };
+        outcomes.insert(book_id, outcome);
+    }
+    Ok(web::Json(outcomes))
+}
+
+#[cfg(test)]
+mod handler_tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use actix_web::http::header::RETRY_AFTER;
+    use actix_web::web::Data;
+    use actix_web::{test, App, HttpServer};
+    use bookservice_repository::api::BookDetails;
+    use bookservice_repository::books_repository::{BookRepository, InMemoryBookRepository};
+    use bookservice_repository::client::BookServiceRepositoryClient;
+    use bookservice_repository::tags_validator::TagsValidator;
+    use paperclip::actix::OpenApiExt;
+
+    use crate::api::{
+        BookCheck, BookId, CanReserveResponse, ReservationAction, ReservationEvent,
+        ReservationReceipt, ReservationsSort, ReserveOrWaitAction, ReserveOrWaitResponse,
+        UnreserveOutcome, UserDataExport, UserDetails, UserId, UserStats, WaitlistPosition,
+    };
+    use crate::app_config::{config_app, config_yaml_spec};
+    use crate::book_existance_checker::BookExistanceChecker;
+    use crate::client::BookServiceReservationsClient;
+    use crate::renewal_period::RenewalPeriod;
+    use crate::reservation_cooldown::ReservationCooldown;
+    use crate::reservations_repository::{InMemoryReservationsRepository, ReservationsRepository};
+    use crate::webhook_dispatcher::WebhookDispatcher;
+
+    #[actix_web::test]
+    /// Tests if the OpenAPI spec is served as YAML and contains the user endpoints
+    async fn test_get_yaml_spec() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .configure(config_app)
+                .with_raw_json_spec(|app, spec| {
+                    app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+                })
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/apispec/v2.yaml")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+
+        let body = test::read_body(response).await;
+        let spec: serde_yaml::Value =
+            serde_yaml::from_slice(&body).expect("Failed to parse yaml spec");
+        let paths = spec["paths"].as_mapping().expect("No paths in spec");
+        assert!(paths.contains_key("/api/users"));
+        assert!(paths.contains_key("/api/user/{user_id}/reservation/{book_id}"));
+    }
+
+    #[actix_web::test]
+    /// Tests if favourite tags are normalized when user is added
+    /// 1. Adds user with empty and duplicated (case insensitive) tags
+    /// 2. Gets the user and checks that only one lowercase tag is stored
+    async fn test_add_user_normalizes_favourite_tags() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/user")
+            .set_json(serde_json::json!({
+                "username": "user",
+                "favourite_tags": ["  ", "SciFi", "scifi"],
+            }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+        let location = response
+            .headers()
+            .get("location")
+            .expect("No location header")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let request = test::TestRequest::get().uri(&location).to_request();
+        let user: UserDetails = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(user.favourite_tags, vec!["scifi".into()]);
+    }
+
+    #[actix_web::test]
+    /// Tests if user with a newline in favourite tag is rejected with 400
+    async fn test_add_user_with_control_characters_rejected() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/api/user")
+            .set_json(serde_json::json!({
+                "username": "user",
+                "favourite_tags": ["sci\nfi"],
+            }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+        let body = test::read_body(response).await;
+        assert_eq!(body, "Field 'favourite_tags' contains control characters");
+    }
+
+    #[actix_web::test]
+    /// Tests if book can be reserved by its ISBN
+    /// 1. Starts repository service and adds a book with ISBN to it
+    /// 2. Reserves the book by ISBN and checks that receipt of the resolved book is returned and the book is reserved
+    /// 3. Checks that reserving by unknown ISBN returns not found
+    async fn test_reserve_book_by_isbn() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let books_repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let book_id = books_repository_client
+            .add_book(BookDetails {
+                title: "Title".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec![],
+                isbn: Some("978-3-16-148410-0".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .app_data(Data::new(books_repository_client))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/user/{}/reservation/isbn/978-3-16-148410-0",
+                user_id
+            ))
+            .to_request();
+        let receipt: ReservationReceipt = test::call_and_read_body_json(&app, request).await;
+        assert_eq!((receipt.user_id, receipt.book_id), (user_id, book_id));
+        assert_eq!(
+            reservations_repository
+                .get_all_reservations(user_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/api/user/{}/reservation/isbn/unknown", user_id))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if user can not reserve the same book again within the cooldown after unreserving it
+    /// 1. Starts repository service with a book and reservations service with 300ms cooldown
+    /// 2. Reserves and unreserves the book, then immediately reserves it again - expect 429 with Retry-After
+    /// 3. Checks that other user can reserve the book and admin can reserve it for the user within the cooldown
+    /// 4. Unreserves it for both and waits for the cooldown - expect the user can reserve the book again
+    async fn test_reservation_cooldown() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let book_id = BookServiceRepositoryClient::new(&repository_url)
+            .unwrap()
+            .add_book(BookDetails {
+                title: "Title".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["user", "other_user"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .app_data(Data::new(ReservationCooldown::new(Duration::from_millis(
+                    300,
+                ))))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+        let reservation_uri =
+            |user_id: UserId| format!("/api/user/{}/reservation/{}", user_id, book_id);
+
+        for request in [
+            test::TestRequest::post().uri(&reservation_uri(user_ids[0])),
+            test::TestRequest::delete().uri(&reservation_uri(user_ids[0])),
+        ] {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert!(response.status().is_success());
+        }
+
+        let request = test::TestRequest::post()
+            .uri(&reservation_uri(user_ids[0]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+
+        for request in [
+            test::TestRequest::post().uri(&reservation_uri(user_ids[1])),
+            test::TestRequest::delete().uri(&reservation_uri(user_ids[1])),
+            test::TestRequest::post().uri(&format!(
+                "/api/admin/user/{}/reservation/{}",
+                user_ids[0], book_id
+            )),
+            test::TestRequest::delete().uri(&reservation_uri(user_ids[0])),
+        ] {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert!(response.status().is_success());
+        }
+
+        actix_web::rt::time::sleep(Duration::from_millis(400)).await;
+        let request = test::TestRequest::post()
+            .uri(&reservation_uri(user_ids[0]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_web::test]
+    /// Tests if reservation events are posted to the webhook only when reservations change
+    /// 1. Starts repository service with two books and a mock webhook server collecting received events
+    /// 2. Reserves the first book twice and unreserves it
+    /// 3. Reserves the second book with reserve or wait twice and holds and confirms the hold of the third book
+    /// 4. Waits until the webhook receives the events and checks that repeated reservations posted none
+    async fn test_reservation_events_posted_to_webhook() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let books_repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let mut book_ids = vec![];
+        for title in ["First", "Second", "Third"] {
+            book_ids.push(
+                books_repository_client
+                    .add_book(BookDetails {
+                        title: title.to_string(),
+                        authors: vec!["Author".to_string()],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let received_events: Data<parking_lot::Mutex<Vec<ReservationEvent>>> = Data::default();
+        let webhook_events = received_events.clone();
+        let webhook_server = HttpServer::new(move || {
+            App::new().app_data(webhook_events.clone()).route(
+                "/webhook",
+                actix_web::web::post().to(
+                    |events: Data<parking_lot::Mutex<Vec<ReservationEvent>>>,
+                     event: actix_web::web::Json<ReservationEvent>| async move {
+                        events.lock().push(event.into_inner());
+                        actix_web::HttpResponse::Ok().finish()
+                    },
+                ),
+            )
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind webhook server");
+        let webhook_url = format!("http://{}/webhook", webhook_server.addrs()[0]);
+        actix_web::rt::spawn(webhook_server.run());
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .app_data(Data::new(WebhookDispatcher::new(webhook_url).unwrap()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let reservation_uri = format!("/api/user/{}/reservation/{}", user_id, book_ids[0]);
+        let reserve_or_wait_uri = format!("/api/user/{}/reserve_or_wait/{}", user_id, book_ids[1]);
+        let hold_uri = format!("/api/user/{}/hold/{}", user_id, book_ids[2]);
+        for request in [
+            test::TestRequest::post().uri(&reservation_uri),
+            test::TestRequest::post().uri(&reservation_uri),
+            test::TestRequest::delete().uri(&reservation_uri),
+            test::TestRequest::post().uri(&reserve_or_wait_uri),
+            test::TestRequest::post().uri(&reserve_or_wait_uri),
+            test::TestRequest::post().uri(&hold_uri),
+            test::TestRequest::post().uri(&format!("{}/confirm", hold_uri)),
+        ] {
+            let response = test::call_service(&app, request.to_request()).await;
+            assert!(response.status().is_success());
+        }
+
+        for _ in 0..50 {
+            if received_events.lock().len() >= 4 {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Gives possible events of the repeated reservations time to arrive
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+        let mut events = received_events.lock().clone();
+        events.sort_by_key(|event| (event.action, event.book_id));
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.action, event.user_id, event.book_id))
+                .collect::<Vec<_>>(),
+            vec![
+                (ReservationAction::Reserved, user_id, book_ids[0]),
+                (ReservationAction::Reserved, user_id, book_ids[1]),
+                (ReservationAction::Reserved, user_id, book_ids[2]),
+                (ReservationAction::Unreserved, user_id, book_ids[0]),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if admin can reserve a book on behalf of the user
+    /// 1. Starts repository service and adds a book to it
+    /// 2. Reserves the book for the user via admin endpoint and checks that it is recorded for the user
+    /// 3. Checks that the book can not be reserved for other user and that unknown book is not found
+    async fn test_admin_reserve_book() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let book_id = BookServiceRepositoryClient::new(&repository_url)
+            .unwrap()
+            .add_book(BookDetails {
+                title: "Title".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["patron", "other_patron"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/admin/user/{}/reservation/{}",
+                user_ids[0], book_id
+            ))
+            .to_request();
+        let receipt: ReservationReceipt = test::call_and_read_body_json(&app, request).await;
+        assert_eq!((receipt.user_id, receipt.book_id), (user_ids[0], book_id));
+        assert_eq!(
+            reservations_repository
+                .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/admin/user/{}/reservation/{}",
+                user_ids[1], book_id
+            ))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 403);
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/admin/user/{}/reservation/{}",
+                user_ids[1],
+                book_id + 1
+            ))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if book is reserved when available and user is waitlisted otherwise
+    /// 1. Starts repository service and adds a book to it
+    /// 2. First user reserves the book and second user is waitlisted at position 1
+    async fn test_reserve_or_wait() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let book_id = BookServiceRepositoryClient::new(&repository_url)
+            .unwrap()
+            .add_book(BookDetails {
+                title: "Title".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/user/{}/reserve_or_wait/{}",
+                user_ids[0], book_id
+            ))
+            .to_request();
+        let response: ReserveOrWaitResponse = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            response,
+            ReserveOrWaitResponse {
+                action: ReserveOrWaitAction::Reserved,
+                position: None,
+            }
+        );
+
+        let request = test::TestRequest::post()
+            .uri(&format!(
+                "/api/user/{}/reserve_or_wait/{}",
+                user_ids[1], book_id
+            ))
+            .to_request();
+        let response: ReserveOrWaitResponse = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            response,
+            ReserveOrWaitResponse {
+                action: ReserveOrWaitAction::Waitlisted,
+                position: Some(1),
+            }
+        );
+        assert_eq!(
+            reservations_repository
+                .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if waitlist position endpoint returns the position of waiting user and not found otherwise
+    /// 1. First user reserves the book and second user is waitlisted
+    /// 2. Checks that second user is at position 1 and first user is not found in the waitlist
+    async fn test_get_waitlist_position() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        for user_id in &user_ids {
+            reservations_repository
+                .reserve_or_wait(*user_id, 1)
+                .await
+                .unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/waitlist/1/position", user_ids[1]))
+            .to_request();
+        let response: WaitlistPosition = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response, WaitlistPosition { position: 1 });
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/waitlist/1/position", user_ids[0]))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if reserving a book when repository is unreachable responds with service unavailable
+    /// 1. Creates checker pointing at a port nothing listens on, so every check fails to connect
+    /// 2. Reserves a book and checks that 503 with "repository unavailable" body is returned
+    async fn test_reserve_book_repository_unavailable() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .app_data(Data::new(BookExistanceChecker::new(unreachable_url)))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri(&format!("/api/user/{}/reservation/1", user_id))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 503);
+        assert_eq!(test::read_body(response).await, "repository unavailable");
+    }
+
+    #[actix_web::test]
+    /// Tests if reservation can be looked up by the id from its receipt
+    /// 1. Reserves book for the user and looks up the receipt by its reservation id
+    /// 2. Unreserves the book and checks that the reservation is no longer found
+    async fn test_get_reservation() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        let receipt = reservations_repository
+            .reserve_book(user_id, 1)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/reservation/{}", receipt.reservation_id))
+            .to_request();
+        let found: ReservationReceipt = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(found, receipt);
+
+        reservations_repository
+            .unreserve_book(user_id, 1)
+            .await
+            .unwrap();
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/reservation/{}", receipt.reservation_id))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if bulk unreserve releases only the books held by the user
+    /// 1. Reserves first book for the user and second one for other user
+    /// 2. Unreserves first, second and never reserved third book in bulk for the user
+    /// 3. Checks that only the first book is reported unreserved and the second one stays reserved
+    async fn test_unreserve_books() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["user", "other_user"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        reservations_repository
+            .reserve_book(user_ids[0], 1)
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book(user_ids[1], 2)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::delete()
+            .uri(&format!("/api/user/{}/reservations", user_ids[0]))
+            .set_json(vec![1, 2, 3])
+            .to_request();
+        let outcomes: HashMap<BookId, UnreserveOutcome> =
+            test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            outcomes,
+            HashMap::from([
+                (1, UnreserveOutcome::Unreserved),
+                (2, UnreserveOutcome::NotHeld),
+                (3, UnreserveOutcome::NotHeld),
+            ])
+        );
+        assert!(reservations_repository
+            .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            reservations_repository
+                .get_all_reservations(user_ids[1], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![2]
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if user data export contains details, active reservations and history of the user
+    /// 1. Adds user that reserved one book and reserved and unreserved another one
+    /// 2. Exports user data and checks all three sections
+    /// 3. Checks that exporting data of unknown user returns not found
+    async fn test_export_user_data() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_details = UserDetails {
+            username: "user".to_string(),
+            favourite_tags: vec!["scifi".into()],
+        };
+        let user_id = reservations_repository
+            .add_user(user_details.clone())
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book(user_id, 1)
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book(user_id, 2)
+            .await
+            .unwrap();
+        reservations_repository
+            .unreserve_book(user_id, 2)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/export", user_id))
+            .to_request();
+        let export: UserDataExport = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(export.user_id, user_id);
+        assert_eq!(export.details, user_details);
+        assert_eq!(export.reservations, vec![1]);
+        assert_eq!(export.history.len(), 1);
+        assert_eq!(export.history[0].book_id, 2);
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/export", user_id + 1))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if user stats are calculated from reservations, history and details of the books
+    /// 1. Starts repository service and adds three books, two of them share an author and a tag
+    /// 2. User reserves and unreserves the first book, then reserves the first and the second book again
+    /// 3. Checks each stat (the third book is not reserved, so its author and tag are not counted)
+    /// 4. Checks that stats of unknown user are not found
+    async fn test_get_user_stats() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let books_repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let mut book_ids = vec![];
+        for (authors, tags) in [
+            (vec!["Author1", "Author2"], vec!["tag1", "tag2"]),
+            (vec!["Author2"], vec!["tag2"]),
+            (vec!["Author3"], vec!["tag3"]),
+        ] {
+            book_ids.push(
+                books_repository_client
+                    .add_book(BookDetails {
+                        title: "Title".to_string(),
+                        authors: authors.into_iter().map(|a| a.to_string()).collect(),
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: tags.into_iter().map(|t| t.to_string()).collect(),
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book(user_id, book_ids[0])
+            .await
+            .unwrap();
+        reservations_repository
+            .unreserve_book(user_id, book_ids[0])
+            .await
+            .unwrap();
+        for book_id in &book_ids[..2] {
+            reservations_repository
+                .reserve_book(user_id, *book_id)
+                .await
+                .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository))
+                .app_data(Data::new(books_repository_client))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/stats", user_id))
+            .to_request();
+        let stats: UserStats = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            stats,
+            UserStats {
+                total_reserved_ever: 3,
+                currently_reserved: 2,
+                distinct_authors: 2,
+                distinct_tags: 2,
+            }
+        );
+
+        let request = test::TestRequest::get()
+            .uri("/api/user/20000/stats")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[actix_web::test]
+    /// Tests if reservation policy is checked without reserving the book
+    /// 1. Starts repository service with four books, second user reserves one of them and holds another one
+    /// 2. Checks that first user may reserve the free book (and it stays not reserved)
+    /// 3. Checks denial reasons for the reserved, held, not existing and recently unreserved books
+    async fn test_can_reserve_book() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let mut book_ids = vec![];
+        for _ in 0..4 {
+            book_ids.push(
+                repository_client
+                    .add_book(BookDetails {
+                        title: "Title".to_string(),
+                        authors: vec!["Author".to_string()],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        reservations_repository
+            .reserve_book(user_ids[1], book_ids[1])
+            .await
+            .unwrap();
+        reservations_repository
+            .create_hold(user_ids[1], book_ids[2], Duration::from_secs(60))
+            .await
+            .unwrap();
+        let reservation_cooldown = Data::new(ReservationCooldown::new(Duration::from_secs(60)));
+        reservation_cooldown.record_unreserve(user_ids[0], book_ids[3]);
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(reservation_cooldown)
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+
+        let denied = |reason: &str| CanReserveResponse {
+            allowed: false,
+            reason: Some(reason.to_string()),
+        };
+        for (book_id, expected) in [
+            (
+                book_ids[0],
+                CanReserveResponse {
+                    allowed: true,
+                    reason: None,
+                },
+            ),
+            (
+                book_ids[1],
+                denied(&format!("Book already reserved {}", book_ids[1])),
+            ),
+            (
+                book_ids[2],
+                denied(&format!("Book held by different user {}", book_ids[2])),
+            ),
+            (
+                book_ids[3],
+                denied(&format!("Book {} was unreserved too recently", book_ids[3])),
+            ),
+            (100, denied("Book not found 100")),
+        ] {
+            let request = test::TestRequest::get()
+                .uri(&format!(
+                    "/api/user/{}/can_reserve/{}",
+                    user_ids[0], book_id
+                ))
+                .to_request();
+            let response: CanReserveResponse = test::call_and_read_body_json(&app, request).await;
+            assert_eq!(response, expected, "book {}", book_id);
+        }
+        assert!(reservations_repository
+            .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_web::test]
+    /// Tests if reservation is renewed through the client
+    /// 1. Starts reservations service with renewal period of 100 seconds, first user reserves the book
+    /// 2. Renews the book by the first user - expect due date and one renewal in the receipt
+    /// 3. Renews the book by the second user - expect rejection
+    /// 4. Second user waits for the book - expect renewing by the first user is rejected with 409
+    async fn test_renew_reservation() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let receipt = reservations_repository
+            .reserve_book(user_ids[0], 1)
+            .await
+            .unwrap();
+        let server_repository = reservations_repository.clone();
+        let reservations_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(server_repository.clone()))
+                .app_data(Data::new(RenewalPeriod(Duration::from_secs(100))))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind reservations server");
+        let reservations_url = format!("http://{}", reservations_server.addrs()[0]);
+        actix_web::rt::spawn(reservations_server.run());
+        let client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        let renewed = client
+            .renew(1, user_ids[0])
+            .await
+            .unwrap()
+            .expect("Renew rejected");
+        assert_eq!(renewed.reservation_id, receipt.reservation_id);
+        assert_eq!(renewed.renewals, 1);
+        assert!(renewed.due_at.unwrap().0 >= receipt.reserved_at.unwrap().0 + 100);
+        assert_eq!(client.renew(1, user_ids[1]).await.unwrap(), None);
+
+        reservations_repository
+            .reserve_or_wait(user_ids[1], 1)
+            .await
+            .unwrap();
+        let response = reqwest::Client::new()
+            .post(format!(
+                "{}/api/user/{}/reservation/1/renew",
+                reservations_url, user_ids[0]
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+        assert_eq!(client.renew(1, user_ids[0]).await.unwrap(), None);
+        assert_eq!(
+            reservations_repository
+                .get_reservation(receipt.reservation_id)
+                .await
+                .unwrap(),
+            renewed
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if existence and availability of books are checked in one call through the client
+    /// 1. Starts repository service with two books and reservations service, user reserves the first book
+    /// 2. Checks both books and an unknown book
+    /// 3. Expect the reserved book exists but is unavailable, the free one is available, the unknown one does not exist
+    async fn test_check_books() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let repository_client = BookServiceRepositoryClient::new(&repository_url).unwrap();
+        let mut book_ids = vec![];
+        for title in ["Reserved", "Free"] {
+            book_ids.push(
+                repository_client
+                    .add_book(BookDetails {
+                        title: title.to_string(),
+                        authors: vec!["Author".to_string()],
+                        publisher: "Publisher".to_string(),
+                        description: "Description".to_string(),
+                        tags: vec![],
+                        isbn: None,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let unknown_book_id = book_ids.iter().max().unwrap() + 100;
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let user_id = reservations_repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book(user_id, book_ids[0])
+            .await
+            .unwrap();
+        let reservations_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(
+                    BookServiceRepositoryClient::new(&repository_url).unwrap(),
+                ))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind reservations server");
+        let reservations_url = format!("http://{}", reservations_server.addrs()[0]);
+        actix_web::rt::spawn(reservations_server.run());
+        let client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        let checks = client
+            .check_books(&[book_ids[0], book_ids[1], unknown_book_id])
+            .await
+            .unwrap();
+        assert_eq!(
+            checks,
+            HashMap::from([
+                (
+                    book_ids[0],
+                    BookCheck {
+                        exists: true,
+                        available: false
+                    }
+                ),
+                (
+                    book_ids[1],
+                    BookCheck {
+                        exists: true,
+                        available: true
+                    }
+                ),
+                (
+                    unknown_book_id,
+                    BookCheck {
+                        exists: false,
+                        available: false
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    /// Tests if available copies of the book are reported through the client
+    /// 1. Starts reservations service and sets 3 copies of the book through the client
+    /// 2. Reserves the book for two users in different branches
+    /// 3. Expect 1 available copy
+    async fn test_get_available_copies() {
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let repository = reservations_repository.clone();
+        let reservations_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(repository.clone()))
+                .configure(config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind reservations server");
+        let reservations_url = format!("http://{}", reservations_server.addrs()[0]);
+        actix_web::rt::spawn(reservations_server.run());
+        let client = BookServiceReservationsClient::new(&reservations_url).unwrap();
+
+        client.set_book_copies(1, 3).await.unwrap();
+        reservations_repository
+            .reserve_book_in_branch(user_ids[0], 1, 1)
+            .await
+            .unwrap();
+        reservations_repository
+            .reserve_book_in_branch(user_ids[1], 1, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(client.available_copies(1).await.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    /// Tests if the same book is reserved and unreserved independently in each branch
+    /// 1. Starts repository service with a book of 3 copies, first user reserves it in branch 1, second user
+    ///    in branch 2
+    /// 2. Checks that the second user can not reserve it in branch 1
+    /// 3. Checks that unreserving in the default branch fails for the first user and succeeds in branch 1
+    async fn test_reserve_book_in_branches() {
+        let books_repository: Arc<dyn BookRepository + Send + Sync> =
+            Arc::new(InMemoryBookRepository::default());
+        let repository_server = HttpServer::new(move || {
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(books_repository.clone()))
+                .app_data(Data::new(TagsValidator::default()))
+                .configure(bookservice_repository::app_config::config_app)
+                .build()
+        })
+        .bind(("127.0.0.1", 0))
+        .expect("Failed to bind repository server");
+        let repository_url = format!("http://{}", repository_server.addrs()[0]);
+        actix_web::rt::spawn(repository_server.run());
+
+        let book_id = BookServiceRepositoryClient::new(&repository_url)
+            .unwrap()
+            .add_book(BookDetails {
+                title: "Title".to_string(),
+                authors: vec!["Author".to_string()],
+                publisher: "Publisher".to_string(),
+                description: "Description".to_string(),
+                tags: vec![],
+                isbn: None,
+            })
+            .await
+            .unwrap();
+
+        let reservations_repository: Arc<dyn ReservationsRepository> =
+            Arc::new(InMemoryReservationsRepository::default());
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                reservations_repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        reservations_repository
+            .set_book_copies(book_id, 3)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .wrap_api()
+                .app_data(Data::new(reservations_repository.clone()))
+                .app_data(Data::new(BookExistanceChecker::new(repository_url)))
+                .app_data(Data::new(ReservationCooldown::default()))
+                .configure(config_app)
+                .build(),
+        )
+        .await;
+        let reservation_uri = |user_id: UserId, branch_id: Option<i32>| {
+            let uri = format!("/api/user/{}/reservation/{}", user_id, book_id);
+            match branch_id {
+                Some(branch_id) => format!("{}?branch_id={}", uri, branch_id),
+                None => uri,
+            }
+        };
+
+        for (user_id, branch_id) in [(user_ids[0], 1), (user_ids[1], 2)] {
+            let request = test::TestRequest::post()
+                .uri(&reservation_uri(user_id, Some(branch_id)))
+                .to_request();
+            let response = test::call_service(&app, request).await;
+            assert!(response.status().is_success());
+        }
+        let request = test::TestRequest::post()
+            .uri(&reservation_uri(user_ids[1], Some(1)))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 403);
+
+        let request = test::TestRequest::delete()
+            .uri(&reservation_uri(user_ids[0], None))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(!response.status().is_success());
+        let request = test::TestRequest::delete()
+            .uri(&reservation_uri(user_ids[0], Some(1)))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+        assert_eq!(
+            reservations_repository
+                .get_all_reservations(user_ids[1], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+    }
+}
@@ -9,8 +9,20 @@ pub mod app_config;
 #[cfg(any(feature = "server", test))]
 pub mod book_existance_checker;
 
+#[cfg(any(feature = "server", test))]
+pub mod circuit_breaker;
+
 #[cfg(any(feature = "server", test))]
 mod handlers;
 
+#[cfg(any(feature = "server", test))]
+pub mod renewal_period;
+
+#[cfg(any(feature = "server", test))]
+pub mod reservation_cooldown;
+
 #[cfg(any(feature = "server", test))]
 pub mod reservations_repository;
+
+#[cfg(any(feature = "server", test))]
+pub mod webhook_dispatcher;
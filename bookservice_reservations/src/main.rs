@@ -40,53 +40,112 @@ fn init_telemetry() {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     use actix_web::{App, HttpServer};
-    use bookservice_reservations::app_config::config_app;
+    use bookservice_repository::client::BookServiceRepositoryClient;
+    use bookservice_reservations::api::DEFAULT_RENEWAL_PERIOD_SECONDS;
+    use bookservice_reservations::app_config::{config_app, config_yaml_spec};
     use bookservice_reservations::book_existance_checker::BookExistanceChecker;
+    use bookservice_reservations::circuit_breaker::{
+        CircuitBreaker, OpenCircuitAction, DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+        DEFAULT_CIRCUIT_OPEN_SECONDS,
+    };
+    use bookservice_reservations::renewal_period::RenewalPeriod;
+    use bookservice_reservations::reservation_cooldown::{
+        ReservationCooldown, DEFAULT_RESERVATION_COOLDOWN_SECONDS,
+    };
     use bookservice_reservations::reservations_repository::{
-        InMemoryReservationsRepository, PostgresReservationsRepository,
-        PostgresReservationsRepositoryConfig, ReservationsRepository,
+        build_reservations_repository, ReservationsRepositoryConfig,
     };
+    use bookservice_reservations::webhook_dispatcher::WebhookDispatcher;
+    use bookservice_types::api_error::not_found;
+    use bookservice_types::api_version::api_version_headers;
     use paperclip::actix::{OpenApiExt, web};
     use std::env;
-    use std::sync::Arc;
+    use std::time::Duration;
     use tracing_actix_web::TracingLogger;
 
     init_telemetry();
     println!("starting HTTP server at http://localhost:8080");
 
-    let use_in_memory_db = env::var("USE_IN_MEMORY_DB")
-        .map(|value| value.to_lowercase() == "true")
-        .unwrap_or_default();
-    let pg_hostname = env::var("DB_HOST").unwrap_or("127.0.0.1".to_string());
-    let pg_username = env::var("DB_USERNAME").unwrap_or("postgres".to_string());
-    let pg_password = env::var("DB_PASSWORD").unwrap_or("postgres".to_string());
     let bookservice_repository_url =
         env::var("BOOKSERVICE_REPOSITORY_URL").unwrap_or("http://localhost:8080".to_string());
 
-    let books_repository: Arc<dyn ReservationsRepository> = if use_in_memory_db {
-        Arc::new(InMemoryReservationsRepository::default())
-    } else {
-        Arc::new(
-            PostgresReservationsRepository::init(PostgresReservationsRepositoryConfig {
-                hostname: pg_hostname,
-                username: pg_username,
-                password: pg_password,
-            })
-            .await
-            .expect("Failed to init postgres"),
+    let books_repository = build_reservations_repository(ReservationsRepositoryConfig::from_env())
+        .await
+        .expect("Failed to init reservations repository");
+
+    let reservation_cooldown = web::Data::new(ReservationCooldown::new(Duration::from_secs(
+        env::var("RESERVATION_COOLDOWN_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RESERVATION_COOLDOWN_SECONDS),
+    )));
+
+    let renewal_period = web::Data::new(RenewalPeriod(Duration::from_secs(
+        env::var("RENEWAL_PERIOD_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RENEWAL_PERIOD_SECONDS),
+    )));
+
+    let mut book_existance_checker = BookExistanceChecker::new(bookservice_repository_url.clone());
+    let circuit_failure_threshold = env::var("EXISTENCE_CHECK_CIRCUIT_FAILURE_THRESHOLD")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_CIRCUIT_FAILURE_THRESHOLD);
+    if circuit_failure_threshold > 0 {
+        let open_duration = Duration::from_secs(
+            env::var("EXISTENCE_CHECK_CIRCUIT_OPEN_SECONDS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_CIRCUIT_OPEN_SECONDS),
+        );
+        let skip_when_open = env::var("EXISTENCE_CHECK_SKIP_WHEN_OPEN")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(false);
+        let open_action = if skip_when_open {
+            OpenCircuitAction::SkipCheck
+        } else {
+            OpenCircuitAction::FailFast
+        };
+        book_existance_checker = book_existance_checker.with_circuit_breaker(CircuitBreaker::new(
+            circuit_failure_threshold,
+            open_duration,
+            open_action,
+        ));
+    }
+    // Shared by all workers so that the circuit breaker sees all the failures
+    let book_existance_checker = web::Data::new(book_existance_checker);
+
+    let webhook_dispatcher = env::var("WEBHOOK_URL").ok().map(|webhook_url| {
+        web::Data::new(
+            WebhookDispatcher::new(webhook_url).expect("Failed to create webhook dispatcher"),
         )
-    };
+    });
 
     HttpServer::new(move || {
-        App::new()
-            .wrap_api()
-            .app_data(web::Data::new(books_repository.clone()))
-            .app_data(web::Data::new(BookExistanceChecker::new(
-                bookservice_repository_url.clone(),
-            )))
+        let app = App::new().wrap_api();
+        // Reservation events are posted only if the webhook is configured
+        let app = match &webhook_dispatcher {
+            Some(webhook_dispatcher) => app.app_data(webhook_dispatcher.clone()),
+            None => app,
+        };
+        app.app_data(web::Data::new(books_repository.clone()))
+            .app_data(reservation_cooldown.clone())
+            .app_data(renewal_period.clone())
+            .app_data(book_existance_checker.clone())
+            .app_data(web::Data::new(
+                BookServiceRepositoryClient::new(&bookservice_repository_url)
+                    .expect("Failed to create repository client"),
+            ))
+            .wrap(api_version_headers())
             .wrap(TracingLogger::default())
             .configure(config_app)
+            .default_service(web::to(not_found))
             .with_json_spec_at("/apispec/v2")
+            .with_raw_json_spec(|app, spec| {
+                app.configure(|cfg| config_yaml_spec(cfg, "/apispec/v2.yaml", spec))
+            })
             .build()
     })
     .bind(("0.0.0.0", 8080))?
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+use crate::api::DEFAULT_RENEWAL_PERIOD_SECONDS;
+
+/// Time by which renewing a reservation extends its due date
+pub struct RenewalPeriod(pub Duration);
+
+impl Default for RenewalPeriod {
+    fn default() -> Self {
+        Self(Duration::from_secs(DEFAULT_RENEWAL_PERIOD_SECONDS))
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::api::{BookId, UserId};
+
+/// Cooldown used when none is configured, 0 disables it
+pub const DEFAULT_RESERVATION_COOLDOWN_SECONDS: u64 = 0;
+
+/// Rejects reserving a book again by the user shortly after unreserving it,
+/// so that rapid reserve/unreserve churn does not skew recommendations
+#[derive(Default)]
+pub struct ReservationCooldown {
+    cooldown: Duration,
+    last_unreserved_at: parking_lot::Mutex<HashMap<(UserId, BookId), Instant>>,
+}
+
+impl ReservationCooldown {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            last_unreserved_at: Default::default(),
+        }
+    }
+
+    /// Records that the user unreserved the book, nothing is recorded if the cooldown is disabled
+    pub fn record_unreserve(&self, user_id: UserId, book_id: BookId) {
+        if self.cooldown.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut last_unreserved_at = self.last_unreserved_at.lock();
+        last_unreserved_at.retain(|_, unreserved_at| now - *unreserved_at < self.cooldown);
+        last_unreserved_at.insert((user_id, book_id), now);
+    }
+
+    /// Returns time left until the user can reserve the book again, None if it can be reserved now
+    pub fn remaining(&self, user_id: UserId, book_id: BookId) -> Option<Duration> {
+        let unreserved_at = *self.last_unreserved_at.lock().get(&(user_id, book_id))?;
+        self.cooldown
+            .checked_sub(unreserved_at.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
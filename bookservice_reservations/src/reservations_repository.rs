@@ -1,13 +1,69 @@
-pub use in_memory_reservations_repository::InMemoryReservationsRepository;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use in_memory_reservations_repository::{
+    InMemoryReservationsRepository, DEFAULT_MAX_HISTORY_RECORDS_PER_USER,
+};
 pub use postgres_reservations_repository::{
     PostgresReservationsRepository, PostgresReservationsRepositoryConfig,
 };
 
-use crate::api::{BookId, ReservationHistoryRecord, UserDetails, UserId};
+use crate::api::{
+    BookId, BranchId, HistoryQuery, HistoryRetention, ReservationEvent, ReservationHistoryRecord,
+    ReservationId, ReservationReceipt, ReservationsSort, ReserveOrWaitOutcome, UnixSeconds,
+    UserDataDeletionSummary, UserDetails, UserHistoryRecord, UserId,
+};
 
 mod in_memory_reservations_repository;
 mod postgres_reservations_repository;
 
+/// Selects and configures the reservations repository backend
+pub struct ReservationsRepositoryConfig {
+    /// Use in memory repository instead of postgres (data is not persisted)
+    pub use_in_memory_db: bool,
+    /// Used only by in memory repository
+    pub max_history_records_per_user: usize,
+    pub postgres: PostgresReservationsRepositoryConfig,
+}
+
+impl ReservationsRepositoryConfig {
+    /// Reads config from `USE_IN_MEMORY_DB`, `MAX_HISTORY_RECORDS_PER_USER`, `DB_HOST`, `DB_USERNAME`
+    /// and `DB_PASSWORD` env variables
+    pub fn from_env() -> Self {
+        Self {
+            use_in_memory_db: env::var("USE_IN_MEMORY_DB")
+                .map(|value| value.to_lowercase() == "true")
+                .unwrap_or_default(),
+            max_history_records_per_user: env::var("MAX_HISTORY_RECORDS_PER_USER")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_HISTORY_RECORDS_PER_USER),
+            postgres: PostgresReservationsRepositoryConfig {
+                hostname: env::var("DB_HOST").unwrap_or("127.0.0.1".to_string()),
+                username: env::var("DB_USERNAME").unwrap_or("postgres".to_string()),
+                password: env::var("DB_PASSWORD").unwrap_or("postgres".to_string()),
+            },
+        }
+    }
+}
+
+/// Creates the reservations repository backend selected in the config
+pub async fn build_reservations_repository(
+    config: ReservationsRepositoryConfig,
+) -> anyhow::Result<Arc<dyn ReservationsRepository>> {
+    if config.use_in_memory_db {
+        Ok(Arc::new(InMemoryReservationsRepository::new(
+            config.max_history_records_per_user,
+        )))
+    } else {
+        Ok(Arc::new(
+            PostgresReservationsRepository::init(config.postgres).await?,
+        ))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReservationsRepositoryError {
     #[error("User {0} not found")]
@@ -16,9 +72,24 @@ pub enum ReservationsRepositoryError {
     #[error("Book {0} already reserved")]
     BookAlreadyReserved(BookId),
 
+    #[error("Book {0} already reserved by user {1}")]
+    BookAlreadyReservedBy(BookId, UserId),
+
+    #[error("Book {0} is held by different user")]
+    BookOnHold(BookId),
+
+    #[error("No active hold of book {0} by the user")]
+    HoldNotFound(BookId),
+
+    #[error("Reservation {0} not found")]
+    ReservationNotFound(ReservationId),
+
     #[error("Book {0} not reserved or reserved by different user")]
     BookNotReservedOrReservedByDifferentUser(BookId),
 
+    #[error("Other users are waiting for book {0}")]
+    BookWaitlisted(BookId),
+
     #[error("Failed to deserialize book: {0}")]
     DeserializationError(#[from] serde_json::Error),
 
@@ -35,27 +106,243 @@ pub trait ReservationsRepository: Send + Sync {
 
     async fn get_user(&self, id: UserId) -> Result<UserDetails, ReservationsRepositoryError>;
 
+    /// Returns details of each of given users, unknown users are omitted
+    async fn get_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, UserDetails>, ReservationsRepositoryError>;
+
     async fn get_all_user_ids(&self) -> Result<Vec<UserId>, ReservationsRepositoryError>;
 
+    /// Returns up to `limit` users with the highest number of reservations (active and history),
+    /// users with the same number of reservations are ordered by id
+    async fn get_most_active_users(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<UserId>, ReservationsRepositoryError>;
+
+    /// Deletes the user, releases user reservations and holds and deletes or anonymizes user history
+    async fn delete_user_data(
+        &self,
+        user_id: UserId,
+        history_retention: HistoryRetention,
+    ) -> Result<UserDataDeletionSummary, ReservationsRepositoryError>;
+
+    /// Reserves book for the user in the default branch and returns receipt of the reservation,
+    /// reserving a book already reserved by the same user succeeds without any change and returns the existing receipt
     async fn reserve_book(
         &self,
         user_id: UserId,
         book_id: BookId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError>;
+
+    /// Same as `reserve_book` in given branch, the same book can be reserved independently in each branch
+    /// as long as it has a copy not reserved in any branch (holds of the book are taken into account only
+    /// in the default branch)
+    async fn reserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError>;
+
+    /// Same as `reserve_book_in_branch`, additionally returns true if a new reservation was created
+    /// and false if the user already held the book
+    async fn reserve_book_in_branch_with_status(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<(ReservationReceipt, bool), ReservationsRepositoryError>;
+
+    /// Checks if `reserve_book` would succeed without reserving the book,
+    /// returns the error `reserve_book` would fail with otherwise
+    async fn check_reservable(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
     ) -> Result<(), ReservationsRepositoryError>;
 
+    /// Reserves book for the user if it is neither reserved nor held by other user,
+    /// otherwise adds the user to the end of the book waitlist (user already waiting keeps the position)
+    async fn reserve_or_wait(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<ReserveOrWaitOutcome, ReservationsRepositoryError>;
+
+    /// Returns position of the user in the waitlist of the book (starting from 1)
+    /// or None if the user is not waiting for the book
+    async fn get_waitlist_position(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<Option<u32>, ReservationsRepositoryError>;
+
+    /// Retrieves receipt of an active reservation
+    async fn get_reservation(
+        &self,
+        reservation_id: ReservationId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError>;
+
+    /// Unreserves book of the user in the default branch and records it in the history
     async fn unreserve_book(
         &self,
         user_id: UserId,
         book_id: BookId,
     ) -> Result<(), ReservationsRepositoryError>;
 
+    /// Same as `unreserve_book` in given branch
+    async fn unreserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<(), ReservationsRepositoryError>;
+
+    /// Extends due date of the reservation of the user by given period (counted from now if it has no due date yet)
+    /// and records the renewal, fails if the book is not reserved by the user or other users wait for it
+    async fn renew_reservation(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        period: Duration,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError>;
+
+    /// Holds the book for the user for given time, so that no other user can reserve or hold it.
+    /// Holding the book again by the same user extends the hold, expired holds are released automatically
+    async fn create_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        ttl: Duration,
+    ) -> Result<(), ReservationsRepositoryError>;
+
+    /// Converts active hold of the user into a reservation, returns true if a new reservation was created
+    /// and false if the user already held the book
+    async fn confirm_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<bool, ReservationsRepositoryError>;
+
+    /// Returns books reserved by the user in all branches in given order
     async fn get_all_reservations(
         &self,
         user_id: UserId,
+        sort: ReservationsSort,
     ) -> Result<Vec<BookId>, ReservationsRepositoryError>;
 
+    /// Returns active reservations for each of given users (users without reservations map to empty list)
+    async fn get_reservations_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<BookId>>, ReservationsRepositoryError>;
+
+    /// Returns history records of all users matching the query, ordered by unreserved_at, user_id and book_id
+    async fn get_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<Vec<UserHistoryRecord>, ReservationsRepositoryError>;
+
+    /// Returns those of given books that were never reserved (neither active reservation nor history)
+    async fn get_never_reserved_books(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<Vec<BookId>, ReservationsRepositoryError>;
+
+    /// Returns availability of each of given books (true if book is neither reserved nor held)
+    async fn get_books_availability(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, bool>, ReservationsRepositoryError>;
+
+    /// Returns user holding each of given books that are reserved, not reserved books are omitted
+    async fn get_books_holders(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, UserId>, ReservationsRepositoryError>;
+
+    /// Returns total number of active reservations of all users
+    async fn count_reservations(&self) -> Result<u64, ReservationsRepositoryError>;
+
+    /// Returns mean number of seconds the book was reserved for, computed from history records
+    /// with known reservation time, None if there are no such records
+    async fn average_hold_duration(
+        &self,
+        book_id: BookId,
+    ) -> Result<Option<f64>, ReservationsRepositoryError>;
+
+    /// Sets number of copies of the book, books without set number of copies have a single copy.
+    /// Reservations over the new number of copies are kept, new ones are rejected until enough are released
+    async fn set_book_copies(
+        &self,
+        book_id: BookId,
+        copies: u32,
+    ) -> Result<(), ReservationsRepositoryError>;
+
+    /// Returns number of copies of the book minus its active reservations in all branches,
+    /// 0 if there are more reservations than copies
+    async fn count_available_copies(
+        &self,
+        book_id: BookId,
+    ) -> Result<u32, ReservationsRepositoryError>;
+
     async fn get_reservations_history(
         &self,
         user_id: UserId,
     ) -> Result<Vec<ReservationHistoryRecord>, ReservationsRepositoryError>;
+
+    /// Returns reserve and unreserve events of the user (active reservations in all branches and history)
+    /// ordered by timestamp, events with the same timestamp are ordered reserve first and then by book id.
+    /// Reservations with unknown reservation time have no reserve event
+    async fn get_user_activity(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<ReservationEvent>, ReservationsRepositoryError>;
+
+    /// Returns reservations history records of the user unreserved within given time window,
+    /// both bounds are inclusive and missing bound does not limit the records
+    async fn get_reservations_history_between(
+        &self,
+        user_id: UserId,
+        from: Option<UnixSeconds>,
+        to: Option<UnixSeconds>,
+    ) -> Result<Vec<ReservationHistoryRecord>, ReservationsRepositoryError>;
+
+    /// Returns reservations history for each of given users (users without history map to empty list)
+    async fn get_history_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError>;
+
+    /// Returns reservations history records unreserved at or after given timestamp, for each of given users
+    /// (users without such history map to empty list)
+    async fn get_history_since_for_users(
+        &self,
+        user_id_to_since: &HashMap<UserId, UnixSeconds>,
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError>;
+}
+
+#[cfg(test)]
+mod reservations_repository_tests {
+    use super::*;
+
+    #[tokio::test]
+    /// Tests if in memory repository is built when the flag is set, without connecting to postgres
+    async fn test_build_in_memory_reservations_repository() {
+        let repository = build_reservations_repository(ReservationsRepositoryConfig {
+            use_in_memory_db: true,
+            max_history_records_per_user: 1,
+            postgres: PostgresReservationsRepositoryConfig {
+                hostname: "unreachable.invalid".to_string(),
+                username: "postgres".to_string(),
+                password: "postgres".to_string(),
+            },
+        })
+        .await
+        .unwrap();
+
+        assert!(repository.get_all_user_ids().await.unwrap().is_empty());
+    }
 }
@@ -1,21 +1,182 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicI32, Ordering};
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, Instant};
 
-use crate::api::ReservationHistoryRecord;
+use uuid::Uuid;
+
+use crate::api::{
+    BranchId, HistoryQuery, HistoryRetention, ReservationAction, ReservationEvent,
+    ReservationHistoryRecord, ReservationId, ReservationReceipt, ReservationsSort,
+    ReserveOrWaitOutcome, UnixSeconds, UserDataDeletionSummary, UserHistoryRecord,
+    DEFAULT_BOOK_COPIES, DEFAULT_BRANCH_ID, DEFAULT_HISTORY_PAGE_LIMIT,
+};
 use crate::reservations_repository::{
     BookId, ReservationsRepository, ReservationsRepositoryError, UserDetails, UserId,
 };
 
-#[derive(Default)]
+/// Default number of history records kept per user, the oldest ones are dropped when exceeded
+pub const DEFAULT_MAX_HISTORY_RECORDS_PER_USER: usize = 10_000;
+
+struct Reservation {
+    reservation_id: ReservationId,
+    user_id: UserId,
+    reserved_at: UnixSeconds,
+    due_at: Option<UnixSeconds>,
+    /// Time of each renewal of the reservation
+    renewed_at: Vec<UnixSeconds>,
+}
+
+impl Reservation {
+    fn receipt(&self, book_id: BookId) -> ReservationReceipt {
+        ReservationReceipt {
+            reservation_id: self.reservation_id,
+            user_id: self.user_id,
+            book_id,
+            reserved_at: Some(self.reserved_at),
+            due_at: self.due_at,
+            renewals: self.renewed_at.len() as u32,
+        }
+    }
+}
+
+struct Hold {
+    user_id: UserId,
+    expires_at: Instant,
+}
+
+impl Hold {
+    fn is_active(&self) -> bool {
+        self.expires_at > Instant::now()
+    }
+}
+
+fn is_held_by_other_user(holds: &HashMap<BookId, Hold>, book_id: BookId, user_id: UserId) -> bool {
+    holds
+        .get(&book_id)
+        .map(|hold| hold.user_id != user_id && hold.is_active())
+        .unwrap_or_default()
+}
+
+/// Returns number of copies of the book minus its reservations in all branches
+fn available_copies_locked(
+    reservations: &HashMap<(BranchId, BookId), Reservation>,
+    book_copies: &HashMap<BookId, u32>,
+    book_id: BookId,
+) -> u32 {
+    let reserved_copies = reservations
+        .keys()
+        .filter(|(_, reserved_book_id)| *reserved_book_id == book_id)
+        .count() as u32;
+    book_copies
+        .get(&book_id)
+        .copied()
+        .unwrap_or(DEFAULT_BOOK_COPIES)
+        .saturating_sub(reserved_copies)
+}
+
+/// Checks if the book is neither reserved in the branch nor held by other user and has a copy that is not reserved
+/// in any branch, so that the user can reserve it
+fn check_reservable_locked(
+    reservations: &HashMap<(BranchId, BookId), Reservation>,
+    holds: &HashMap<BookId, Hold>,
+    book_copies: &HashMap<BookId, u32>,
+    user_id: UserId,
+    book_id: BookId,
+    branch_id: BranchId,
+) -> Result<(), ReservationsRepositoryError> {
+    match reservations.get(&(branch_id, book_id)) {
+        Some(reservation) if reservation.user_id != user_id => Err(
+            ReservationsRepositoryError::BookAlreadyReservedBy(book_id, reservation.user_id),
+        ),
+        Some(_) => Ok(()),
+        None if branch_id == DEFAULT_BRANCH_ID
+            && is_held_by_other_user(holds, book_id, user_id) =>
+        {
+            Err(ReservationsRepositoryError::BookOnHold(book_id))
+        }
+        None if available_copies_locked(reservations, book_copies, book_id) == 0 => {
+            Err(ReservationsRepositoryError::BookAlreadyReserved(book_id))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Reserves the book for the user with both locks already taken, so that checks and insert are atomic
+fn reserve_locked(
+    reservations: &mut HashMap<(BranchId, BookId), Reservation>,
+    holds: &mut HashMap<BookId, Hold>,
+    book_copies: &HashMap<BookId, u32>,
+    user_id: UserId,
+    book_id: BookId,
+    branch_id: BranchId,
+) -> Result<(ReservationReceipt, bool), ReservationsRepositoryError> {
+    check_reservable_locked(
+        reservations,
+        holds,
+        book_copies,
+        user_id,
+        book_id,
+        branch_id,
+    )?;
+    if let Some(reservation) = reservations.get(&(branch_id, book_id)) {
+        return Ok((reservation.receipt(book_id), false));
+    }
+
+    let reservation = Reservation {
+        reservation_id: Uuid::new_v4(),
+        user_id,
+        reserved_at: UnixSeconds::now(),
+        due_at: None,
+        renewed_at: vec![],
+    };
+    let receipt = reservation.receipt(book_id);
+    reservations.insert((branch_id, book_id), reservation);
+    if branch_id == DEFAULT_BRANCH_ID {
+        holds.remove(&book_id);
+    }
+    Ok((receipt, true))
+}
+
+/// Methods taking more than one lock always take them in order of the fields below
+/// (users, reservations, holds, waitlists, history, anonymized_history, book_copies) to prevent deadlocks
 pub struct InMemoryReservationsRepository {
     users: parking_lot::RwLock<HashMap<UserId, UserDetails>>,
-    reservations: parking_lot::RwLock<HashMap<BookId, UserId>>,
-    history: parking_lot::RwLock<HashMap<UserId, Vec<ReservationHistoryRecord>>>,
+    reservations: parking_lot::RwLock<HashMap<(BranchId, BookId), Reservation>>,
+    holds: parking_lot::RwLock<HashMap<BookId, Hold>>,
+    /// Users waiting for each of the books, in order of joining
+    waitlists: parking_lot::RwLock<HashMap<BookId, Vec<UserId>>>,
+    history: parking_lot::RwLock<HashMap<UserId, VecDeque<ReservationHistoryRecord>>>,
+    /// History records of deleted users
+    anonymized_history: parking_lot::RwLock<Vec<ReservationHistoryRecord>>,
+    /// Number of copies of books with explicitly set number of copies
+    book_copies: parking_lot::RwLock<HashMap<BookId, u32>>,
+    max_history_records_per_user: usize,
     user_sequence_generator: AtomicI32,
 }
 
+impl InMemoryReservationsRepository {
+    pub fn new(max_history_records_per_user: usize) -> Self {
+        Self {
+            users: Default::default(),
+            reservations: Default::default(),
+            holds: Default::default(),
+            waitlists: Default::default(),
+            history: Default::default(),
+            anonymized_history: Default::default(),
+            book_copies: Default::default(),
+            max_history_records_per_user,
+            user_sequence_generator: Default::default(),
+        }
+    }
+}
+
+impl Default for InMemoryReservationsRepository {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_HISTORY_RECORDS_PER_USER)
+    }
+}
+
 #[async_trait::async_trait]
 impl ReservationsRepository for InMemoryReservationsRepository {
     async fn add_user(
@@ -36,23 +197,304 @@ impl ReservationsRepository for InMemoryReservationsRepository {
             .ok_or_else(|| ReservationsRepositoryError::UserNotFound(id))
     }
 
+    async fn get_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, UserDetails>, ReservationsRepositoryError> {
+        let locked_users = self.users.read();
+        Ok(user_ids
+            .iter()
+            .filter_map(|user_id| {
+                locked_users
+                    .get(user_id)
+                    .map(|details| (*user_id, details.clone()))
+            })
+            .collect())
+    }
+
     async fn get_all_user_ids(&self) -> Result<Vec<UserId>, ReservationsRepositoryError> {
         Ok(self.users.read().keys().cloned().collect())
     }
 
+    async fn get_most_active_users(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<UserId>, ReservationsRepositoryError> {
+        let mut user_to_reservations_count: HashMap<UserId, usize> = HashMap::new();
+        for reservation in self.reservations.read().values() {
+            *user_to_reservations_count
+                .entry(reservation.user_id)
+                .or_default() += 1;
+        }
+        for (user_id, records) in self.history.read().iter() {
+            *user_to_reservations_count.entry(*user_id).or_default() += records.len();
+        }
+        let mut users: Vec<(UserId, usize)> = user_to_reservations_count
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        users.sort_by_key(|(user_id, count)| (std::cmp::Reverse(*count), *user_id));
+        Ok(users
+            .into_iter()
+            .take(limit as usize)
+            .map(|(user_id, _)| user_id)
+            .collect())
+    }
+
+    async fn delete_user_data(
+        &self,
+        user_id: UserId,
+        history_retention: HistoryRetention,
+    ) -> Result<UserDataDeletionSummary, ReservationsRepositoryError> {
+        let mut users_lock = self.users.write();
+        let mut reservations_lock = self.reservations.write();
+        let mut holds_lock = self.holds.write();
+        let mut waitlists_lock = self.waitlists.write();
+        let mut history_lock = self.history.write();
+
+        if users_lock.remove(&user_id).is_none() {
+            return Err(ReservationsRepositoryError::UserNotFound(user_id));
+        }
+
+        let released_keys: Vec<(BranchId, BookId)> = reservations_lock
+            .iter()
+            .filter(|(_, reservation)| reservation.user_id == user_id)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &released_keys {
+            reservations_lock.remove(key);
+        }
+        let mut released_reservations: Vec<BookId> =
+            released_keys.iter().map(|(_, book_id)| *book_id).collect();
+        released_reservations.sort();
+        holds_lock.retain(|_, hold| hold.user_id != user_id);
+        for waitlist in waitlists_lock.values_mut() {
+            waitlist.retain(|waiting_user_id| *waiting_user_id != user_id);
+        }
+
+        let history = history_lock.remove(&user_id).unwrap_or_default();
+        let history_records = history.len() as u64;
+        let mut summary = UserDataDeletionSummary {
+            user_id,
+            released_reservations,
+            deleted_history_records: 0,
+            anonymized_history_records: 0,
+        };
+        match history_retention {
+            HistoryRetention::Delete => summary.deleted_history_records = history_records,
+            HistoryRetention::Anonymize => {
+                self.anonymized_history.write().extend(history);
+                summary.anonymized_history_records = history_records;
+            }
+        }
+        Ok(summary)
+    }
+
     async fn reserve_book(
         &self,
         user_id: UserId,
         book_id: BookId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        self.reserve_book_in_branch(user_id, book_id, DEFAULT_BRANCH_ID)
+            .await
+    }
+
+    async fn reserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        Ok(self
+            .reserve_book_in_branch_with_status(user_id, book_id, branch_id)
+            .await?
+            .0)
+    }
+
+    async fn reserve_book_in_branch_with_status(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<(ReservationReceipt, bool), ReservationsRepositoryError> {
+        reserve_locked(
+            &mut self.reservations.write(),
+            &mut self.holds.write(),
+            &self.book_copies.read(),
+            user_id,
+            book_id,
+            branch_id,
+        )
+    }
+
+    async fn check_reservable(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
     ) -> Result<(), ReservationsRepositoryError> {
+        check_reservable_locked(
+            &self.reservations.read(),
+            &self.holds.read(),
+            &self.book_copies.read(),
+            user_id,
+            book_id,
+            DEFAULT_BRANCH_ID,
+        )
+    }
+
+    async fn reserve_or_wait(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<ReserveOrWaitOutcome, ReservationsRepositoryError> {
         let mut reservations_lock = self.reservations.write();
+        let mut holds_lock = self.holds.write();
+        let mut waitlists_lock = self.waitlists.write();
 
-        match reservations_lock.entry(book_id) {
-            Entry::Occupied(_) => Err(ReservationsRepositoryError::BookAlreadyReserved(book_id)),
-            Entry::Vacant(entry) => {
-                entry.insert(user_id);
-                Ok(())
+        match reserve_locked(
+            &mut reservations_lock,
+            &mut holds_lock,
+            &self.book_copies.read(),
+            user_id,
+            book_id,
+            DEFAULT_BRANCH_ID,
+        ) {
+            Ok((_, created)) => {
+                if let Some(waitlist) = waitlists_lock.get_mut(&book_id) {
+                    waitlist.retain(|waiting_user_id| *waiting_user_id != user_id);
+                }
+                Ok(if created {
+                    ReserveOrWaitOutcome::Reserved
+                } else {
+                    ReserveOrWaitOutcome::AlreadyReserved
+                })
+            }
+            Err(
+                ReservationsRepositoryError::BookAlreadyReserved(_)
+                | ReservationsRepositoryError::BookAlreadyReservedBy(..)
+                | ReservationsRepositoryError::BookOnHold(_),
+            ) => {
+                let waitlist = waitlists_lock.entry(book_id).or_default();
+                let index = match waitlist.iter().position(|id| *id == user_id) {
+                    Some(index) => index,
+                    None => {
+                        waitlist.push(user_id);
+                        waitlist.len() - 1
+                    }
+                };
+                Ok(ReserveOrWaitOutcome::Waitlisted(index as u32 + 1))
             }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn get_waitlist_position(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<Option<u32>, ReservationsRepositoryError> {
+        Ok(self.waitlists.read().get(&book_id).and_then(|waitlist| {
+            waitlist
+                .iter()
+                .position(|waiting_user_id| *waiting_user_id == user_id)
+                .map(|index| index as u32 + 1)
+        }))
+    }
+
+    async fn renew_reservation(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        period: Duration,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        let mut reservations_lock = self.reservations.write();
+        let waitlists_lock = self.waitlists.read();
+
+        let reservation = reservations_lock
+            .get_mut(&(DEFAULT_BRANCH_ID, book_id))
+            .filter(|reservation| reservation.user_id == user_id)
+            .ok_or(
+                ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(book_id),
+            )?;
+        let others_waiting = waitlists_lock.get(&book_id).is_some_and(|waitlist| {
+            waitlist
+                .iter()
+                .any(|waiting_user_id| *waiting_user_id != user_id)
+        });
+        if others_waiting {
+            return Err(ReservationsRepositoryError::BookWaitlisted(book_id));
+        }
+
+        let now = UnixSeconds::now();
+        let due_from = reservation.due_at.unwrap_or(now);
+        reservation.due_at = Some(UnixSeconds(due_from.0 + period.as_secs() as i64));
+        reservation.renewed_at.push(now);
+        Ok(reservation.receipt(book_id))
+    }
+
+    async fn get_reservation(
+        &self,
+        reservation_id: ReservationId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        self.reservations
+            .read()
+            .iter()
+            .find(|(_, reservation)| reservation.reservation_id == reservation_id)
+            .map(|((_, book_id), reservation)| reservation.receipt(*book_id))
+            .ok_or(ReservationsRepositoryError::ReservationNotFound(
+                reservation_id,
+            ))
+    }
+
+    async fn create_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        ttl: Duration,
+    ) -> Result<(), ReservationsRepositoryError> {
+        let reservations_lock = self.reservations.read();
+        let mut holds_lock = self.holds.write();
+
+        if reservations_lock.contains_key(&(DEFAULT_BRANCH_ID, book_id)) {
+            return Err(ReservationsRepositoryError::BookAlreadyReserved(book_id));
+        }
+        if is_held_by_other_user(&holds_lock, book_id, user_id) {
+            return Err(ReservationsRepositoryError::BookOnHold(book_id));
+        }
+        holds_lock.insert(
+            book_id,
+            Hold {
+                user_id,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        Ok(())
+    }
+
+    async fn confirm_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<bool, ReservationsRepositoryError> {
+        // Hold is checked under the same locks as the reservation is made, so it can not expire in between
+        let mut reservations_lock = self.reservations.write();
+        let mut holds_lock = self.holds.write();
+        let has_active_hold = holds_lock
+            .get(&book_id)
+            .map(|hold| hold.user_id == user_id && hold.is_active())
+            .unwrap_or_default();
+        if has_active_hold {
+            let (_, created) = reserve_locked(
+                &mut reservations_lock,
+                &mut holds_lock,
+                &self.book_copies.read(),
+                user_id,
+                book_id,
+                DEFAULT_BRANCH_ID,
+            )?;
+            Ok(created)
+        } else {
+            Err(ReservationsRepositoryError::HoldNotFound(book_id))
         }
     }
 
@@ -60,22 +502,33 @@ impl ReservationsRepository for InMemoryReservationsRepository {
         &self,
         user_id: UserId,
         book_id: BookId,
+    ) -> Result<(), ReservationsRepositoryError> {
+        self.unreserve_book_in_branch(user_id, book_id, DEFAULT_BRANCH_ID)
+            .await
+    }
+
+    async fn unreserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
     ) -> Result<(), ReservationsRepositoryError> {
         let mut reservations_lock = self.reservations.write();
 
-        match reservations_lock.entry(book_id) {
+        match reservations_lock.entry((branch_id, book_id)) {
             Entry::Occupied(occupied) => {
-                if occupied.get() == &user_id {
-                    occupied.remove();
-                    self.history.write().entry(user_id).or_default().push(
-                        ReservationHistoryRecord {
-                            book_id,
-                            unreserved_at: std::time::SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs() as i64,
-                        },
-                    );
+                if occupied.get().user_id == user_id {
+                    let reservation = occupied.remove();
+                    let mut history_lock = self.history.write();
+                    let user_history = history_lock.entry(user_id).or_default();
+                    user_history.push_back(ReservationHistoryRecord {
+                        book_id,
+                        reserved_at: Some(reservation.reserved_at),
+                        unreserved_at: UnixSeconds::now(),
+                    });
+                    while user_history.len() > self.max_history_records_per_user {
+                        user_history.pop_front();
+                    }
                     Ok(())
                 } else {
                     Err(
@@ -94,16 +547,173 @@ impl ReservationsRepository for InMemoryReservationsRepository {
     async fn get_all_reservations(
         &self,
         user_id: UserId,
+        sort: ReservationsSort,
     ) -> Result<Vec<BookId>, ReservationsRepositoryError> {
-        Ok(self
+        let mut user_reservations: Vec<(UnixSeconds, BookId)> = self
             .reservations
             .read()
             .iter()
-            .filter(|(_, &uid)| user_id == uid)
-            .map(|(book_id, _)| *book_id)
+            .filter(|(_, reservation)| reservation.user_id == user_id)
+            .map(|((_, book_id), reservation)| (reservation.reserved_at, *book_id))
+            .collect();
+        match sort {
+            ReservationsSort::BookId => user_reservations.sort_by_key(|(_, book_id)| *book_id),
+            ReservationsSort::ReservedAt => user_reservations.sort(),
+        }
+        Ok(user_reservations
+            .into_iter()
+            .map(|(_, book_id)| book_id)
+            .collect())
+    }
+
+    async fn get_reservations_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<BookId>>, ReservationsRepositoryError> {
+        let mut user_to_reservations: HashMap<UserId, Vec<BookId>> =
+            user_ids.iter().map(|user_id| (*user_id, vec![])).collect();
+
+        for ((_, book_id), reservation) in self.reservations.read().iter() {
+            if let Some(reservations) = user_to_reservations.get_mut(&reservation.user_id) {
+                reservations.push(*book_id);
+            }
+        }
+        Ok(user_to_reservations)
+    }
+
+    async fn get_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<Vec<UserHistoryRecord>, ReservationsRepositoryError> {
+        let mut records: Vec<UserHistoryRecord> = self
+            .history
+            .read()
+            .iter()
+            .filter(|(user_id, _)| query.user_id.is_none_or(|id| id == **user_id))
+            .flat_map(|(user_id, records)| {
+                records.iter().map(|record| UserHistoryRecord {
+                    user_id: *user_id,
+                    book_id: record.book_id,
+                    unreserved_at: record.unreserved_at,
+                })
+            })
+            .filter(|record| query.book_id.is_none_or(|id| id == record.book_id))
+            .collect();
+        records.sort_by_key(|record| (record.unreserved_at, record.user_id, record.book_id));
+        Ok(records
+            .into_iter()
+            .skip(query.offset.unwrap_or_default() as usize)
+            .take(query.limit.unwrap_or(DEFAULT_HISTORY_PAGE_LIMIT) as usize)
+            .collect())
+    }
+
+    async fn get_never_reserved_books(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<Vec<BookId>, ReservationsRepositoryError> {
+        let reservations_lock = self.reservations.read();
+        let ever_reserved_books: HashSet<BookId> = self
+            .history
+            .read()
+            .values()
+            .flatten()
+            .chain(self.anonymized_history.read().iter())
+            .map(|record| record.book_id)
+            .chain(reservations_lock.keys().map(|(_, book_id)| *book_id))
+            .collect();
+        Ok(book_ids
+            .iter()
+            .filter(|book_id| !ever_reserved_books.contains(book_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_books_availability(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, bool>, ReservationsRepositoryError> {
+        let reservations_lock = self.reservations.read();
+        let holds_lock = self.holds.read();
+        Ok(book_ids
+            .iter()
+            .map(|book_id| {
+                let is_held = holds_lock
+                    .get(book_id)
+                    .map(Hold::is_active)
+                    .unwrap_or_default();
+                (
+                    *book_id,
+                    !reservations_lock.contains_key(&(DEFAULT_BRANCH_ID, *book_id)) && !is_held,
+                )
+            })
+            .collect())
+    }
+
+    async fn get_books_holders(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, UserId>, ReservationsRepositoryError> {
+        let reservations_lock = self.reservations.read();
+        Ok(book_ids
+            .iter()
+            .filter_map(|book_id| {
+                reservations_lock
+                    .get(&(DEFAULT_BRANCH_ID, *book_id))
+                    .map(|reservation| (*book_id, reservation.user_id))
+            })
             .collect())
     }
 
+    async fn count_reservations(&self) -> Result<u64, ReservationsRepositoryError> {
+        Ok(self.reservations.read().len() as u64)
+    }
+
+    async fn set_book_copies(
+        &self,
+        book_id: BookId,
+        copies: u32,
+    ) -> Result<(), ReservationsRepositoryError> {
+        self.book_copies.write().insert(book_id, copies);
+        Ok(())
+    }
+
+    async fn count_available_copies(
+        &self,
+        book_id: BookId,
+    ) -> Result<u32, ReservationsRepositoryError> {
+        Ok(available_copies_locked(
+            &self.reservations.read(),
+            &self.book_copies.read(),
+            book_id,
+        ))
+    }
+
+    async fn average_hold_duration(
+        &self,
+        book_id: BookId,
+    ) -> Result<Option<f64>, ReservationsRepositoryError> {
+        let history_lock = self.history.read();
+        let anonymized_history_lock = self.anonymized_history.read();
+        let hold_durations: Vec<i64> = history_lock
+            .values()
+            .flatten()
+            .chain(anonymized_history_lock.iter())
+            .filter(|record| record.book_id == book_id)
+            .filter_map(|record| {
+                record
+                    .reserved_at
+                    .map(|reserved_at| record.unreserved_at.0 - reserved_at.0)
+            })
+            .collect();
+        if hold_durations.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(
+                hold_durations.iter().sum::<i64>() as f64 / hold_durations.len() as f64,
+            ))
+        }
+    }
+
     async fn get_reservations_history(
         &self,
         user_id: UserId,
@@ -112,9 +722,116 @@ impl ReservationsRepository for InMemoryReservationsRepository {
             .history
             .read()
             .get(&user_id)
-            .cloned()
+            .map(|records| records.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn get_user_activity(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<ReservationEvent>, ReservationsRepositoryError> {
+        let event = |action, book_id, timestamp| ReservationEvent {
+            action,
+            user_id,
+            book_id,
+            timestamp,
+        };
+        let mut events: Vec<ReservationEvent> = self
+            .reservations
+            .read()
+            .iter()
+            .filter(|(_, reservation)| reservation.user_id == user_id)
+            .map(|((_, book_id), reservation)| {
+                event(
+                    ReservationAction::Reserved,
+                    *book_id,
+                    reservation.reserved_at,
+                )
+            })
+            .collect();
+        if let Some(records) = self.history.read().get(&user_id) {
+            for record in records {
+                if let Some(reserved_at) = record.reserved_at {
+                    events.push(event(
+                        ReservationAction::Reserved,
+                        record.book_id,
+                        reserved_at,
+                    ));
+                }
+                events.push(event(
+                    ReservationAction::Unreserved,
+                    record.book_id,
+                    record.unreserved_at,
+                ));
+            }
+        }
+        events.sort_by_key(|event| (event.timestamp, event.action, event.book_id));
+        Ok(events)
+    }
+
+    async fn get_reservations_history_between(
+        &self,
+        user_id: UserId,
+        from: Option<UnixSeconds>,
+        to: Option<UnixSeconds>,
+    ) -> Result<Vec<ReservationHistoryRecord>, ReservationsRepositoryError> {
+        Ok(self
+            .history
+            .read()
+            .get(&user_id)
+            .map(|records| {
+                records
+                    .iter()
+                    .filter(|record| {
+                        from.is_none_or(|from| record.unreserved_at >= from)
+                            && to.is_none_or(|to| record.unreserved_at <= to)
+                    })
+                    .cloned()
+                    .collect()
+            })
             .unwrap_or_default())
     }
+
+    async fn get_history_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError> {
+        let history_lock = self.history.read();
+        Ok(user_ids
+            .iter()
+            .map(|user_id| {
+                (
+                    *user_id,
+                    history_lock
+                        .get(user_id)
+                        .map(|records| records.iter().cloned().collect())
+                        .unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    async fn get_history_since_for_users(
+        &self,
+        user_id_to_since: &HashMap<UserId, UnixSeconds>,
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError> {
+        let history_lock = self.history.read();
+        Ok(user_id_to_since
+            .iter()
+            .map(|(user_id, since)| {
+                (
+                    *user_id,
+                    history_lock
+                        .get(user_id)
+                        .iter()
+                        .flat_map(|records| records.iter())
+                        .filter(|record| record.unreserved_at >= *since)
+                        .cloned()
+                        .collect(),
+                )
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -140,7 +857,7 @@ mod tests_in_memory_reservations_repository {
 
         let user_details = UserDetails {
             username: "username".to_string(),
-            favourite_tags: vec!["tag1".to_string(), "tag2".to_string()],
+            favourite_tags: vec!["tag1".into(), "tag2".into()],
         };
 
         let user_id = repository.add_user(user_details.clone()).await.unwrap();
@@ -199,7 +916,10 @@ mod tests_in_memory_reservations_repository {
             .unwrap();
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             Vec::<BookId>::default()
         );
 
@@ -214,13 +934,28 @@ mod tests_in_memory_reservations_repository {
         let test_book_id: BookId = 1;
 
         // reserve book for the user
-        repository
+        let receipt = repository
             .reserve_book(user_1_id, test_book_id)
             .await
             .unwrap();
+        assert_eq!(
+            (receipt.user_id, receipt.book_id),
+            (user_1_id, test_book_id)
+        );
+        assert!(receipt.reserved_at.is_some());
+        assert_eq!(
+            repository
+                .get_reservation(receipt.reservation_id)
+                .await
+                .unwrap(),
+            receipt
+        );
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             vec![test_book_id]
         );
         assert_eq!(
@@ -231,11 +966,35 @@ mod tests_in_memory_reservations_repository {
             Vec::<ReservationHistoryRecord>::default()
         );
 
+        // reserving the book again by the same user succeeds without duplicating the reservation
+        assert_eq!(
+            repository
+                .reserve_book(user_1_id, test_book_id)
+                .await
+                .unwrap(),
+            receipt
+        );
+        assert_eq!(
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![test_book_id]
+        );
+        assert_eq!(
+            repository
+                .reserve_book_in_branch_with_status(user_1_id, test_book_id, DEFAULT_BRANCH_ID)
+                .await
+                .unwrap(),
+            (receipt.clone(), false)
+        );
+
         let reserve_conflict = repository.reserve_book(user_2_id, test_book_id).await;
 
         assert!(matches!(
             reserve_conflict,
-            Err(ReservationsRepositoryError::BookAlreadyReserved(..))
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(book_id, holder))
+                if book_id == test_book_id && holder == user_1_id
         ));
 
         // unreserve book for wrong user
@@ -251,10 +1010,17 @@ mod tests_in_memory_reservations_repository {
             .unreserve_book(user_1_id, test_book_id)
             .await
             .unwrap();
+        assert!(matches!(
+            repository.get_reservation(receipt.reservation_id).await,
+            Err(ReservationsRepositoryError::ReservationNotFound(..))
+        ));
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
-            Vec::<BookId>::default()
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            Vec::<BookId>::default()
         );
 
         let history = repository
@@ -277,7 +1043,10 @@ mod tests_in_memory_reservations_repository {
             .await
             .unwrap();
 
-        let mut two_reservations = repository.get_all_reservations(user_1_id).await.unwrap();
+        let mut two_reservations = repository
+            .get_all_reservations(user_1_id, ReservationsSort::BookId)
+            .await
+            .unwrap();
         two_reservations.sort();
         assert_eq!(two_reservations, vec![test_book_id, other_book_id]);
 
@@ -288,7 +1057,10 @@ mod tests_in_memory_reservations_repository {
             .unwrap();
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             vec![other_book_id]
         );
 
@@ -300,4 +1072,1227 @@ mod tests_in_memory_reservations_repository {
         assert_eq!(history[0].book_id, test_book_id);
         assert_eq!(history[1].book_id, test_book_id);
     }
+
+    #[tokio::test]
+    /// Tests if reservations of multiple users are fetched at once
+    /// 1.Creates three users, reserves two books for the first and one for the second
+    /// 2.Fetches reservations for all three users
+    async fn test_get_reservations_for_users() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[0], 2).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+
+        let mut user_to_reservations = repository
+            .get_reservations_for_users(&user_ids)
+            .await
+            .unwrap();
+        user_to_reservations
+            .values_mut()
+            .for_each(|reservations| reservations.sort());
+
+        assert_eq!(
+            user_to_reservations,
+            HashMap::from([
+                (user_ids[0], vec![1, 2]),
+                (user_ids[1], vec![3]),
+                (user_ids[2], vec![]),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if details of multiple users are fetched at once
+    /// 1.Creates two users
+    /// 2.Fetches details of both users and of an unknown user
+    /// 3.Checks that details of both users are returned and the unknown user is omitted
+    async fn test_get_users() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let first_details = UserDetails {
+            username: "user1".to_string(),
+            favourite_tags: vec!["tag1".into()],
+        };
+        let second_details = UserDetails {
+            username: "user2".to_string(),
+            favourite_tags: vec![],
+        };
+        let first_user_id = repository.add_user(first_details.clone()).await.unwrap();
+        let second_user_id = repository.add_user(second_details.clone()).await.unwrap();
+        let unknown_user_id = first_user_id.max(second_user_id) + 1;
+
+        assert_eq!(
+            repository
+                .get_users(&[first_user_id, second_user_id, unknown_user_id])
+                .await
+                .unwrap(),
+            HashMap::from([
+                (first_user_id, first_details),
+                (second_user_id, second_details)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if history of multiple users fetched at once matches the per user history
+    /// 1.Creates three users, reserves and unreserves books for the first two
+    /// 2.Fetches history for all three users and compares it with per user calls
+    async fn test_get_history_for_users() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        for (user_id, book_id) in [(user_ids[0], 1), (user_ids[0], 2), (user_ids[1], 3)] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+
+        let user_to_history = repository.get_history_for_users(&user_ids).await.unwrap();
+
+        assert_eq!(user_to_history.len(), 3);
+        for user_id in user_ids.iter() {
+            assert_eq!(
+                user_to_history[user_id],
+                repository.get_reservations_history(*user_id).await.unwrap()
+            );
+        }
+        assert_eq!(user_to_history[&user_ids[0]].len(), 2);
+        assert_eq!(user_to_history[&user_ids[1]].len(), 1);
+        assert_eq!(user_to_history[&user_ids[2]].len(), 0);
+    }
+    #[tokio::test]
+    /// Tests if history since given timestamp returns only records of each user unreserved at or after it
+    /// 1.Creates three users, reserves and unreserves books for the first two
+    /// 2.Fetches history since -1 for the first and third user and since last record for the second one
+    /// 3.Checks that the first user gets full history, the second one its last record and the third one no records
+    async fn test_get_history_since_for_users() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        for (user_id, book_id) in [(user_ids[0], 1), (user_ids[0], 2), (user_ids[1], 3)] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+        let second_user_last_unreserved_at = repository
+            .get_reservations_history(user_ids[1])
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.unreserved_at)
+            .max()
+            .unwrap();
+
+        let user_to_history = repository
+            .get_history_since_for_users(&HashMap::from([
+                (user_ids[0], UnixSeconds(-1)),
+                (user_ids[1], second_user_last_unreserved_at),
+                (user_ids[2], UnixSeconds(-1)),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(user_to_history.len(), 3);
+        assert_eq!(
+            user_to_history[&user_ids[0]],
+            repository
+                .get_reservations_history(user_ids[0])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            user_to_history[&user_ids[1]]
+                .iter()
+                .map(|r| r.unreserved_at)
+                .collect::<Vec<_>>(),
+            vec![second_user_last_unreserved_at]
+        );
+        assert_eq!(user_to_history[&user_ids[2]].len(), 0);
+    }
+    #[tokio::test]
+    /// Tests if availability of multiple books is fetched at once
+    /// 1.Creates user and reserves two of four books
+    /// 2.Fetches availability of all four books and checks reserved ones are not available
+    async fn test_get_books_availability() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user1".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        repository.reserve_book(user_id, 1).await.unwrap();
+        repository.reserve_book(user_id, 3).await.unwrap();
+
+        let book_to_availability = repository
+            .get_books_availability(&[1, 2, 3, 4])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            book_to_availability,
+            HashMap::from([(1, false), (2, true), (3, false), (4, true)])
+        );
+    }
+    #[tokio::test]
+    /// Tests if only books missing in both active reservations and history are returned as never reserved
+    /// 1.Creates user, reserves first book and reserves and unreserves the second one
+    /// 2.Checks that of three books only the third one was never reserved
+    async fn test_get_never_reserved_books() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user1".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        repository.reserve_book(user_id, 1).await.unwrap();
+        repository.reserve_book(user_id, 2).await.unwrap();
+        repository.unreserve_book(user_id, 2).await.unwrap();
+
+        assert_eq!(
+            repository
+                .get_never_reserved_books(&[1, 2, 3])
+                .await
+                .unwrap(),
+            vec![3]
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if the oldest history records are dropped when history capacity is exceeded
+    /// 1.Creates repository keeping two history records per user
+    /// 2.Reserves and unreserves three books
+    /// 3.Checks that only the two most recent records are returned
+    async fn test_history_capacity_drops_oldest_records() {
+        let repository = InMemoryReservationsRepository::new(2);
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user1".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        for book_id in [1, 2, 3] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+
+        let history = repository.get_reservations_history(user_id).await.unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|record| record.book_id)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+    }
+    #[tokio::test]
+    /// Tests if history of all users can be filtered by user and book and paginated
+    /// 1.Creates two users, each reserves and unreserves two books (one book is shared)
+    /// 2.Checks filtering by user, by book and by both
+    /// 3.Checks that pages with limit and offset together return all records
+    async fn test_get_history() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        for (user_id, book_id) in [
+            (user_ids[0], 1),
+            (user_ids[0], 2),
+            (user_ids[1], 2),
+            (user_ids[1], 3),
+        ] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+
+        let user_and_book_ids = |records: Vec<UserHistoryRecord>| {
+            let mut ids: Vec<(UserId, BookId)> = records
+                .into_iter()
+                .map(|record| (record.user_id, record.book_id))
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        let by_user = repository
+            .get_history(&HistoryQuery {
+                user_id: Some(user_ids[0]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            user_and_book_ids(by_user),
+            vec![(user_ids[0], 1), (user_ids[0], 2)]
+        );
+
+        let by_book = repository
+            .get_history(&HistoryQuery {
+                book_id: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            user_and_book_ids(by_book),
+            vec![(user_ids[0], 2), (user_ids[1], 2)]
+        );
+
+        let by_user_and_book = repository
+            .get_history(&HistoryQuery {
+                book_id: Some(2),
+                user_id: Some(user_ids[1]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(user_and_book_ids(by_user_and_book), vec![(user_ids[1], 2)]);
+
+        let all = repository.get_history(&Default::default()).await.unwrap();
+        assert_eq!(all.len(), 4);
+        let mut pages = vec![];
+        for offset in [0, 3] {
+            pages.extend(
+                repository
+                    .get_history(&HistoryQuery {
+                        limit: Some(3),
+                        offset: Some(offset),
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_eq!(pages, all);
+    }
+    #[tokio::test]
+    /// Tests if held book can be reserved only by the holder, confirming the hold within ttl
+    /// 1.Creates two users, first one holds a book
+    /// 2.Checks that book is unavailable and the second user can neither hold nor reserve it
+    /// 3.Confirms the hold and checks that the book is reserved by the first user
+    async fn test_confirm_hold_within_ttl() {
+        let repository = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let book_id = 1;
+        repository
+            .create_hold(user_ids[0], book_id, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.get_books_availability(&[book_id]).await.unwrap(),
+            HashMap::from([(book_id, false)])
+        );
+        assert!(matches!(
+            repository
+                .create_hold(user_ids[1], book_id, Duration::from_secs(60))
+                .await,
+            Err(ReservationsRepositoryError::BookOnHold(..))
+        ));
+        assert!(matches!(
+            repository.reserve_book(user_ids[1], book_id).await,
+            Err(ReservationsRepositoryError::BookOnHold(..))
+        ));
+        assert!(matches!(
+            repository.confirm_hold(user_ids[1], book_id).await,
+            Err(ReservationsRepositoryError::HoldNotFound(..))
+        ));
+
+        repository.confirm_hold(user_ids[0], book_id).await.unwrap();
+        assert_eq!(
+            repository
+                .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if hold is released automatically after ttl
+    /// 1.Creates two users, first one holds a book for a short time
+    /// 2.Waits until the hold expires
+    /// 3.Checks that the hold can not be confirmed, book is available and the second user can reserve it
+    async fn test_hold_expires() {
+        let repository = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let book_id = 1;
+        repository
+            .create_hold(user_ids[0], book_id, Duration::from_millis(100))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(matches!(
+            repository.confirm_hold(user_ids[0], book_id).await,
+            Err(ReservationsRepositoryError::HoldNotFound(..))
+        ));
+        assert_eq!(
+            repository.get_books_availability(&[book_id]).await.unwrap(),
+            HashMap::from([(book_id, true)])
+        );
+        repository.reserve_book(user_ids[1], book_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    /// Tests if deleting user data removes the user and releases reservations for both history retention modes
+    /// 1.Creates two users, each reserves one book and reserves and unreserves another one
+    /// 2.Deletes first user with history deletion and second one with history anonymization
+    /// 3.Checks returned summaries, that users are gone, books are available and history is removed
+    /// 4.Checks that anonymized history still counts as reserved books and deleting missing user fails
+    async fn test_delete_user_data() {
+        let repository = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        for (user_id, reserved_book_id, unreserved_book_id) in
+            [(user_ids[0], 1, 2), (user_ids[1], 3, 4)]
+        {
+            repository
+                .reserve_book(user_id, reserved_book_id)
+                .await
+                .unwrap();
+            repository
+                .reserve_book(user_id, unreserved_book_id)
+                .await
+                .unwrap();
+            repository
+                .unreserve_book(user_id, unreserved_book_id)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            repository
+                .delete_user_data(user_ids[0], HistoryRetention::Delete)
+                .await
+                .unwrap(),
+            UserDataDeletionSummary {
+                user_id: user_ids[0],
+                released_reservations: vec![1],
+                deleted_history_records: 1,
+                anonymized_history_records: 0,
+            }
+        );
+        assert_eq!(
+            repository
+                .delete_user_data(user_ids[1], HistoryRetention::Anonymize)
+                .await
+                .unwrap(),
+            UserDataDeletionSummary {
+                user_id: user_ids[1],
+                released_reservations: vec![3],
+                deleted_history_records: 0,
+                anonymized_history_records: 1,
+            }
+        );
+
+        assert!(repository.get_all_user_ids().await.unwrap().is_empty());
+        for user_id in &user_ids {
+            assert!(matches!(
+                repository.get_user(*user_id).await,
+                Err(ReservationsRepositoryError::UserNotFound(..))
+            ));
+            assert!(repository
+                .get_reservations_history(*user_id)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+        assert!(repository
+            .get_history(&Default::default())
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            repository.get_books_availability(&[1, 3]).await.unwrap(),
+            HashMap::from([(1, true), (3, true)])
+        );
+        assert_eq!(
+            repository
+                .get_never_reserved_books(&[1, 2, 3, 4])
+                .await
+                .unwrap(),
+            vec![1, 2, 3]
+        );
+
+        assert!(matches!(
+            repository
+                .delete_user_data(user_ids[0], HistoryRetention::Delete)
+                .await,
+            Err(ReservationsRepositoryError::UserNotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    /// Tests if holders are returned only for reserved books
+    /// 1.Creates two users, each reserves one of three books
+    /// 2.Fetches holders of all three books and checks that only reserved ones are present with correct users
+    async fn test_get_books_holders() {
+        let repository = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+
+        assert_eq!(
+            repository.get_books_holders(&[1, 2, 3]).await.unwrap(),
+            HashMap::from([(1, user_ids[0]), (3, user_ids[1])])
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if average hold duration is computed from history records with known reservation time
+    /// 1.Checks that book without history has no average
+    /// 2.Adds two completed reservations of the book lasting 100 and 300 seconds, one without reservation time
+    ///   and one of another book
+    /// 3.Checks that the average is 200 seconds
+    async fn test_average_hold_duration() {
+        let repository = InMemoryReservationsRepository::default();
+        assert_eq!(repository.average_hold_duration(1).await.unwrap(), None);
+        repository.history.write().insert(
+            1,
+            VecDeque::from([
+                ReservationHistoryRecord {
+                    book_id: 1,
+                    reserved_at: Some(UnixSeconds(100)),
+                    unreserved_at: UnixSeconds(200),
+                },
+                ReservationHistoryRecord {
+                    book_id: 1,
+                    reserved_at: Some(UnixSeconds(1000)),
+                    unreserved_at: UnixSeconds(1300),
+                },
+                ReservationHistoryRecord {
+                    book_id: 1,
+                    reserved_at: None,
+                    unreserved_at: UnixSeconds(5000),
+                },
+                ReservationHistoryRecord {
+                    book_id: 2,
+                    reserved_at: Some(UnixSeconds(0)),
+                    unreserved_at: UnixSeconds(5000),
+                },
+            ]),
+        );
+
+        assert_eq!(
+            repository.average_hold_duration(1).await.unwrap(),
+            Some(200.0)
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if users are ranked by total number of active and history reservations
+    /// 1.Creates three users, first one reserves one book, second one reserves one book and has two in history,
+    ///   third one has no reservations
+    /// 2.Checks that the second user ranks first, the user without reservations is omitted and limit is applied
+    async fn test_get_most_active_users() {
+        let repository = InMemoryReservationsRepository::default();
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        for book_id in [2, 3] {
+            repository.reserve_book(user_ids[1], book_id).await.unwrap();
+            repository
+                .unreserve_book(user_ids[1], book_id)
+                .await
+                .unwrap();
+        }
+        repository.reserve_book(user_ids[1], 4).await.unwrap();
+
+        assert_eq!(
+            repository.get_most_active_users(10).await.unwrap(),
+            vec![user_ids[1], user_ids[0]]
+        );
+        assert_eq!(
+            repository.get_most_active_users(1).await.unwrap(),
+            vec![user_ids[1]]
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if total number of reservations counts active reservations of all users
+    /// 1.Checks that there are no reservations at start
+    /// 2.Creates two users reserving three books in total, then one of the books is unreserved
+    /// 3.Checks that two reservations are counted
+    async fn test_count_reservations() {
+        let repository = InMemoryReservationsRepository::default();
+        assert_eq!(repository.count_reservations().await.unwrap(), 0);
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[0], 2).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+        repository.unreserve_book(user_ids[0], 2).await.unwrap();
+
+        assert_eq!(repository.count_reservations().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    /// Tests if available copies are copies of the book minus its reservations in all branches
+    /// and reservations are limited by them
+    /// 1. Checks that a book without set number of copies has a single available copy
+    /// 2. Sets 3 copies of the book and reserves it in two branches - expect 1 available copy
+    /// 3. Reserves it in the third branch - expect no available copies and reserving it in the fourth branch fails
+    /// 4. Checks that a book with a single copy reserved in the default branch can not be reserved in other branch
+    async fn test_count_available_copies() {
+        let repository = InMemoryReservationsRepository::default();
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 1);
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.set_book_copies(1, 3).await.unwrap();
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 1)
+            .await
+            .unwrap();
+        repository.reserve_book(user_ids[1], 2).await.unwrap();
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 1);
+
+        repository
+            .reserve_book_in_branch(user_ids[2], 1, 2)
+            .await
+            .unwrap();
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 0);
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[0], 1, 3).await,
+            Err(ReservationsRepositoryError::BookAlreadyReserved(1))
+        ));
+
+        assert_eq!(repository.count_available_copies(2).await.unwrap(), 0);
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[0], 2, 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReserved(2))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    /// Stress test of concurrent reservations, checks that no lock ordering deadlock happens
+    /// 1. Spawns tasks of many users reserving (directly or through a hold) and unreserving the same few books
+    ///    together with tasks reading reservations and history
+    /// 2. Waits for all tasks with a timeout - expect all of them finish
+    /// 3. Checks that no book stays reserved and every successful reservation is in the history
+    async fn test_concurrent_reserve_and_unreserve() {
+        let repository = std::sync::Arc::new(InMemoryReservationsRepository::default());
+        let book_ids: Vec<BookId> = (1..=4).collect();
+
+        let mut tasks = vec![];
+        for user_no in 0..8 {
+            let user_id = repository
+                .add_user(UserDetails {
+                    username: format!("user{}", user_no),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap();
+            let repository = repository.clone();
+            let book_ids = book_ids.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut reserved = 0;
+                for iteration in 0..200 {
+                    for &book_id in &book_ids {
+                        let result = if (iteration + user_no) % 2 == 0 {
+                            repository.reserve_book(user_id, book_id).await.map(|_| ())
+                        } else {
+                            match repository
+                                .create_hold(user_id, book_id, Duration::from_secs(60))
+                                .await
+                            {
+                                Ok(()) => {
+                                    repository.confirm_hold(user_id, book_id).await.map(|_| ())
+                                }
+                                Err(err) => Err(err),
+                            }
+                        };
+                        if result.is_ok() {
+                            reserved += 1;
+                            repository.unreserve_book(user_id, book_id).await.unwrap();
+                        }
+                        tokio::task::yield_now().await;
+                    }
+                }
+                reserved
+            }));
+        }
+        for _ in 0..2 {
+            let repository = repository.clone();
+            let book_ids = book_ids.clone();
+            tasks.push(tokio::spawn(async move {
+                for _ in 0..200 {
+                    repository.get_most_active_users(10).await.unwrap();
+                    repository
+                        .get_never_reserved_books(&book_ids)
+                        .await
+                        .unwrap();
+                    repository.get_books_availability(&book_ids).await.unwrap();
+                    repository.average_hold_duration(book_ids[0]).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+                0
+            }));
+        }
+
+        let reserved: usize = tokio::time::timeout(Duration::from_secs(60), async {
+            let mut reserved = 0;
+            for task in tasks {
+                reserved += task.await.unwrap();
+            }
+            reserved
+        })
+        .await
+        .expect("Concurrent reservations did not finish, possible deadlock");
+
+        assert!(reserved > 0);
+        assert_eq!(repository.count_reservations().await.unwrap(), 0);
+        assert_eq!(
+            repository
+                .history
+                .read()
+                .values()
+                .map(VecDeque::len)
+                .sum::<usize>(),
+            reserved
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if reservation is checked without reserving the book
+    /// 1. Second user reserves one book and holds another one
+    /// 2. Checks that the second user may reserve both and first user only the free book
+    /// 3. Checks that nothing was reserved by the checks
+    async fn test_check_reservable() {
+        let repo = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+        repo.reserve_book(user_ids[1], 1).await.unwrap();
+        repo.create_hold(user_ids[1], 2, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert!(repo.check_reservable(user_ids[0], 3).await.is_ok());
+        assert!(repo.check_reservable(user_ids[1], 1).await.is_ok());
+        assert!(repo.check_reservable(user_ids[1], 2).await.is_ok());
+        assert!(matches!(
+            repo.check_reservable(user_ids[0], 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(1, _))
+        ));
+        assert!(matches!(
+            repo.check_reservable(user_ids[0], 2).await,
+            Err(ReservationsRepositoryError::BookOnHold(2))
+        ));
+        assert!(repo
+            .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if book is reserved when free and user is added to the waitlist when it is reserved
+    /// 1. First user reserves the book, second and third users are waitlisted at positions 1 and 2
+    /// 2. Second user asking again keeps the position
+    /// 3. After deleting second user data the third user moves to position 1
+    async fn test_reserve_or_wait() {
+        let repo = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[0], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Reserved
+        );
+        assert_eq!(
+            repo.get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[2], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(2)
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+
+        repo.delete_user_data(user_ids[1], HistoryRetention::Delete)
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[2], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if waitlist position of the user is returned and shifts when the head of the waitlist leaves
+    /// 1. First user reserves the book, other users wait - expect positions 1, 2 and 3 and none for the first user
+    /// 2. First user unreserves and second user reserves the book - expect remaining users move up by one
+    async fn test_get_waitlist_position() {
+        let repo = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third", "fourth"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        repo.reserve_or_wait(user_ids[0], 1).await.unwrap();
+        for user_id in &user_ids[1..] {
+            repo.reserve_or_wait(*user_id, 1).await.unwrap();
+        }
+        let mut positions = vec![];
+        for user_id in &user_ids {
+            positions.push(repo.get_waitlist_position(*user_id, 1).await.unwrap());
+        }
+        assert_eq!(positions, vec![None, Some(1), Some(2), Some(3)]);
+        assert_eq!(
+            repo.get_waitlist_position(user_ids[1], 2).await.unwrap(),
+            None
+        );
+
+        repo.unreserve_book(user_ids[0], 1).await.unwrap();
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Reserved
+        );
+        let mut positions = vec![];
+        for user_id in &user_ids {
+            positions.push(repo.get_waitlist_position(*user_id, 1).await.unwrap());
+        }
+        assert_eq!(positions, vec![None, None, Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    /// Tests if renewing extends due date of the reservation and records renewals
+    /// 1. First user reserves the book, second user can not renew it
+    /// 2. First user renews the book twice - expect due date extended by two periods and two renewals
+    /// 3. Second user waits for the book - expect renewing is rejected and the reservation is unchanged
+    async fn test_renew_reservation() {
+        let repo = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+        let period = Duration::from_secs(100);
+
+        let receipt = repo.reserve_book(user_ids[0], 1).await.unwrap();
+        assert_eq!(receipt.due_at, None);
+        assert!(matches!(
+            repo.renew_reservation(user_ids[1], 1, period).await,
+            Err(ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(1))
+        ));
+
+        let before_renew = UnixSeconds::now();
+        let first_renew = repo
+            .renew_reservation(user_ids[0], 1, period)
+            .await
+            .unwrap();
+        assert_eq!(first_renew.reservation_id, receipt.reservation_id);
+        assert_eq!(first_renew.renewals, 1);
+        let first_due_at = first_renew.due_at.unwrap();
+        assert!(first_due_at.0 >= before_renew.0 + 100);
+        let second_renew = repo
+            .renew_reservation(user_ids[0], 1, period)
+            .await
+            .unwrap();
+        assert_eq!(second_renew.due_at, Some(UnixSeconds(first_due_at.0 + 100)));
+        assert_eq!(second_renew.renewals, 2);
+        assert_eq!(
+            repo.get_reservation(receipt.reservation_id).await.unwrap(),
+            second_renew
+        );
+
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+        assert!(matches!(
+            repo.renew_reservation(user_ids[0], 1, period).await,
+            Err(ReservationsRepositoryError::BookWaitlisted(1))
+        ));
+        assert_eq!(
+            repo.get_reservation(receipt.reservation_id).await.unwrap(),
+            second_renew
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if only history records unreserved within the window are returned, bounds are inclusive
+    async fn test_get_reservations_history_between() {
+        let repo = InMemoryReservationsRepository::default();
+        let record = |book_id: BookId, unreserved_at: i64| ReservationHistoryRecord {
+            book_id,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(unreserved_at),
+        };
+        repo.history.write().insert(
+            1,
+            VecDeque::from([record(1, 10), record(2, 20), record(3, 30), record(4, 40)]),
+        );
+        repo.history
+            .write()
+            .insert(2, VecDeque::from([record(5, 20)]));
+
+        assert_eq!(
+            repo.get_reservations_history_between(1, Some(UnixSeconds(20)), Some(UnixSeconds(30)))
+                .await
+                .unwrap(),
+            vec![record(2, 20), record(3, 30)]
+        );
+        assert_eq!(
+            repo.get_reservations_history_between(1, Some(UnixSeconds(30)), None)
+                .await
+                .unwrap(),
+            vec![record(3, 30), record(4, 40)]
+        );
+        assert_eq!(
+            repo.get_reservations_history_between(1, None, Some(UnixSeconds(10)))
+                .await
+                .unwrap(),
+            vec![record(1, 10)]
+        );
+        assert!(repo
+            .get_reservations_history_between(3, None, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    /// Tests if reservations of the user are listed in requested order
+    /// 1. Reserves books 3, 1 and 2 and sets their reservation times so that book 3 is the earliest and book 1 the latest
+    /// 2. Checks that sorting by book id lists 1, 2, 3 and sorting by reservation time lists 3, 2, 1, also on repeated calls
+    async fn test_get_all_reservations_sorted() {
+        let repository = InMemoryReservationsRepository::default();
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        for (book_id, reserved_at) in [(3, 100), (1, 300), (2, 200)] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository
+                .reservations
+                .write()
+                .get_mut(&(DEFAULT_BRANCH_ID, book_id))
+                .unwrap()
+                .reserved_at = UnixSeconds(reserved_at);
+        }
+
+        for _ in 0..3 {
+            assert_eq!(
+                repository
+                    .get_all_reservations(user_id, ReservationsSort::BookId)
+                    .await
+                    .unwrap(),
+                vec![1, 2, 3]
+            );
+            assert_eq!(
+                repository
+                    .get_all_reservations(user_id, ReservationsSort::ReservedAt)
+                    .await
+                    .unwrap(),
+                vec![3, 2, 1]
+            );
+        }
+    }
+
+    #[tokio::test]
+    /// Tests if the same book is reserved independently in each branch
+    /// 1. Sets 3 copies of the book, first user reserves it in branch 1, second user reserves it in branch 2
+    ///    and in the default branch
+    /// 2. Checks that the second user can not reserve it in branch 1
+    /// 3. Unreserves the book of the first user in branch 1 - expect the second user can reserve it there
+    ///    and the book is still not available in the default branch
+    async fn test_reserve_book_in_branches() {
+        let repository = InMemoryReservationsRepository::default();
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.set_book_copies(1, 3).await.unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[0], 1, 1)
+            .await
+            .unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 2)
+            .await
+            .unwrap();
+        repository.reserve_book(user_ids[1], 1).await.unwrap();
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[1], 1, 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(1, holder)) if holder == user_ids[0]
+        ));
+        assert_eq!(repository.count_reservations().await.unwrap(), 3);
+
+        repository
+            .unreserve_book_in_branch(user_ids[0], 1, 1)
+            .await
+            .unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.get_books_availability(&[1]).await.unwrap(),
+            HashMap::from([(1, false)])
+        );
+        assert_eq!(
+            repository
+                .get_reservations_history(user_ids[0])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    /// Tests if user activity merges active reservations and history ordered by timestamp
+    /// 1. Reserves books 1, 2 and 3 and unreserves books 1 and 2
+    /// 2. Sets reservation and unreservation times so that events of different books interleave
+    /// 3. Gets user activity - expects reserve and unreserve events ordered by timestamp
+    async fn test_get_user_activity() {
+        let repository = InMemoryReservationsRepository::default();
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        for book_id in [1, 2, 3] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+        }
+        repository
+            .reservations
+            .write()
+            .get_mut(&(DEFAULT_BRANCH_ID, 3))
+            .unwrap()
+            .reserved_at = UnixSeconds(400);
+        for book_id in [2, 1] {
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+        for (record, (reserved_at, unreserved_at)) in repository
+            .history
+            .write()
+            .get_mut(&user_id)
+            .unwrap()
+            .iter_mut()
+            .zip([(200, 500), (100, 300)])
+        {
+            record.reserved_at = Some(UnixSeconds(reserved_at));
+            record.unreserved_at = UnixSeconds(unreserved_at);
+        }
+
+        assert_eq!(
+            repository.get_user_activity(user_id).await.unwrap(),
+            vec![
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 1,
+                    timestamp: UnixSeconds(100),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 2,
+                    timestamp: UnixSeconds(200),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Unreserved,
+                    user_id,
+                    book_id: 1,
+                    timestamp: UnixSeconds(300),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 3,
+                    timestamp: UnixSeconds(400),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Unreserved,
+                    user_id,
+                    book_id: 2,
+                    timestamp: UnixSeconds(500),
+                },
+            ]
+        );
+    }
 }
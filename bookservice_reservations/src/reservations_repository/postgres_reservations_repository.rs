@@ -1,11 +1,19 @@
-use std::time::UNIX_EPOCH;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use anyhow::Context;
 use serde_json::json;
 use tokio_postgres::{Client, NoTls, Statement};
 use tokio_postgres::error::SqlState;
-
-use crate::api::ReservationHistoryRecord;
+use tokio_postgres::types::ToSql;
+use uuid::Uuid;
+
+use crate::api::{
+    BranchId, HistoryQuery, HistoryRetention, ReservationAction, ReservationEvent,
+    ReservationHistoryRecord, ReservationId, ReservationReceipt, ReservationsSort,
+    ReserveOrWaitOutcome, UnixSeconds, UserDataDeletionSummary, UserHistoryRecord,
+    DEFAULT_BOOK_COPIES, DEFAULT_BRANCH_ID, DEFAULT_HISTORY_PAGE_LIMIT,
+};
 use crate::reservations_repository::{
     BookId, ReservationsRepository, ReservationsRepositoryError, UserDetails, UserId,
 };
@@ -55,7 +63,15 @@ impl PostgresReservationsRepository {
         CREATE TABLE IF NOT EXISTS reservations (
             book_id              INTEGER NOT NULL UNIQUE,
             user_id              INTEGER NOT NULL
-            )
+            );
+        ALTER TABLE reservations ADD COLUMN IF NOT EXISTS reserved_at BIGINT;
+        ALTER TABLE reservations ADD COLUMN IF NOT EXISTS reservation_id UUID;
+        ALTER TABLE reservations ADD COLUMN IF NOT EXISTS due_at BIGINT;
+        ALTER TABLE reservations ADD COLUMN IF NOT EXISTS branch_id INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE reservations DROP CONSTRAINT IF EXISTS reservations_book_id_key;
+        CREATE UNIQUE INDEX IF NOT EXISTS reservations_branch_id_book_id_idx ON reservations (branch_id, book_id);
+        UPDATE reservations SET reservation_id = gen_random_uuid() WHERE reservation_id IS NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS reservations_reservation_id_idx ON reservations (reservation_id);
         ",
             )
             .await
@@ -68,14 +84,132 @@ impl PostgresReservationsRepository {
             book_id              INTEGER NOT NULL,
             user_id              INTEGER NOT NULL,
             unreserved_at        BIGINT
+            );
+        ALTER TABLE history ALTER COLUMN user_id DROP NOT NULL;
+        ALTER TABLE history ADD COLUMN IF NOT EXISTS reserved_at BIGINT;
+        ",
+            )
+            .await
+            .context("Failed to setup history table")?;
+
+        client
+            .batch_execute(
+                "
+        CREATE TABLE IF NOT EXISTS holds (
+            book_id              INTEGER NOT NULL UNIQUE,
+            user_id              INTEGER NOT NULL,
+            expires_at           TIMESTAMPTZ NOT NULL
             )
         ",
             )
             .await
-            .context("Failed to setup reservations table")?;
+            .context("Failed to setup holds table")?;
+
+        client
+            .batch_execute(
+                "
+        CREATE TABLE IF NOT EXISTS waitlist (
+            id                   BIGSERIAL PRIMARY KEY,
+            book_id              INTEGER NOT NULL,
+            user_id              INTEGER NOT NULL,
+            UNIQUE (book_id, user_id)
+            )
+        ",
+            )
+            .await
+            .context("Failed to setup waitlist table")?;
+
+        client
+            .batch_execute(
+                "
+        CREATE TABLE IF NOT EXISTS renewals (
+            reservation_id       UUID NOT NULL,
+            user_id              INTEGER NOT NULL,
+            book_id              INTEGER NOT NULL,
+            renewed_at           BIGINT NOT NULL,
+            due_at               BIGINT NOT NULL
+            )
+        ",
+            )
+            .await
+            .context("Failed to setup renewals table")?;
+
+        client
+            .batch_execute(
+                "
+        CREATE TABLE IF NOT EXISTS book_copies (
+            book_id              INTEGER PRIMARY KEY,
+            copies               BIGINT NOT NULL
+            )
+        ",
+            )
+            .await
+            .context("Failed to setup book copies table")?;
 
         Ok(Self { client })
     }
+
+    /// Returns why the book could not be reserved in the branch, the holder is reported if the book
+    /// is reserved in the branch, otherwise the book has no free copy or is held by other user
+    async fn reservation_conflict(
+        &self,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<ReservationsRepositoryError, ReservationsRepositoryError> {
+        let holder_rows = self
+            .client
+            .query(
+                "SELECT user_id FROM reservations WHERE book_id = $1 AND branch_id = $2",
+                &[&book_id, &branch_id],
+            )
+            .await
+            .map_err(db_failure("reserve_book"))?;
+        if let Some(holder_row) = holder_rows.first() {
+            Ok(ReservationsRepositoryError::BookAlreadyReservedBy(
+                book_id,
+                holder_row.try_get(0)?,
+            ))
+        } else if self.available_copies(book_id, "reserve_book").await? == 0 {
+            Ok(ReservationsRepositoryError::BookAlreadyReserved(book_id))
+        } else {
+            Ok(ReservationsRepositoryError::BookOnHold(book_id))
+        }
+    }
+
+    /// Returns number of copies of the book minus its reservations in all branches
+    async fn available_copies(
+        &self,
+        book_id: BookId,
+        operation: &'static str,
+    ) -> Result<u32, ReservationsRepositoryError> {
+        let row = self
+            .client
+            .query_one(
+                "SELECT GREATEST(\
+                COALESCE((SELECT copies FROM book_copies WHERE book_id = $1), $2) \
+                - (SELECT COUNT(*) FROM reservations WHERE book_id = $1), 0)",
+                &[&book_id, &(DEFAULT_BOOK_COPIES as i64)],
+            )
+            .await
+            .map_err(db_failure(operation))?;
+        let available_copies: i64 = row.try_get(0)?;
+        Ok(available_copies as u32)
+    }
+}
+
+/// Logs failure of database operation, only the operation name and error code are logged
+/// as the query parameters may contain personal data
+fn db_failure(
+    operation: &'static str,
+) -> impl FnOnce(tokio_postgres::Error) -> ReservationsRepositoryError {
+    move |error| {
+        tracing::error!(
+            operation,
+            code = error.code().map(|code| code.code()).unwrap_or_default(),
+            "Database operation failed"
+        );
+        ReservationsRepositoryError::DatabaseFailure(error)
+    }
 }
 
 #[async_trait::async_trait]
@@ -87,9 +221,14 @@ impl ReservationsRepository for PostgresReservationsRepository {
         let stmt: Statement = self
             .client
             .prepare("INSERT INTO users (params) VALUES ($1) RETURNING id")
-            .await?;
+            .await
+            .map_err(db_failure("add_user"))?;
 
-        let rows = self.client.query(&stmt, &[&json!(user_data)]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&json!(user_data)])
+            .await
+            .map_err(db_failure("add_user"))?;
 
         let user_id: UserId = rows
             .first()
@@ -103,9 +242,14 @@ impl ReservationsRepository for PostgresReservationsRepository {
         let stmt: Statement = self
             .client
             .prepare("SELECT params FROM users WHERE id = ($1)")
-            .await?;
+            .await
+            .map_err(db_failure("get_user"))?;
 
-        let rows = self.client.query(&stmt, &[&id]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&id])
+            .await
+            .map_err(db_failure("get_user"))?;
 
         let details: serde_json::Value = rows
             .first()
@@ -115,31 +259,228 @@ impl ReservationsRepository for PostgresReservationsRepository {
         Ok(serde_json::from_value(details)?)
     }
 
+    async fn get_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, UserDetails>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT id, params FROM users WHERE id = ANY($1)")
+            .await
+            .map_err(db_failure("get_users"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_ids])
+            .await
+            .map_err(db_failure("get_users"))?;
+
+        rows.iter()
+            .map(|row| {
+                let details: serde_json::Value = row.try_get(1)?;
+                Ok((row.try_get(0)?, serde_json::from_value(details)?))
+            })
+            .collect()
+    }
+
     async fn get_all_user_ids(&self) -> Result<Vec<UserId>, ReservationsRepositoryError> {
-        let stmt: Statement = self.client.prepare("SELECT id FROM users").await?;
-        let rows = self.client.query(&stmt, &[]).await?;
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT id FROM users")
+            .await
+            .map_err(db_failure("get_all_user_ids"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[])
+            .await
+            .map_err(db_failure("get_all_user_ids"))?;
+        rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
+    }
+
+    async fn get_most_active_users(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<UserId>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT user_id FROM ( \
+                    SELECT user_id FROM history WHERE user_id IS NOT NULL \
+                    UNION ALL SELECT user_id FROM reservations \
+                ) r GROUP BY user_id ORDER BY COUNT(*) DESC, user_id LIMIT $1",
+            )
+            .await
+            .map_err(db_failure("get_most_active_users"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&i64::from(limit)])
+            .await
+            .map_err(db_failure("get_most_active_users"))?;
         rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
     }
 
+    async fn delete_user_data(
+        &self,
+        user_id: UserId,
+        history_retention: HistoryRetention,
+    ) -> Result<UserDataDeletionSummary, ReservationsRepositoryError> {
+        // Single statement runs in its own transaction, so either all data of the user is removed or none
+        let row = self
+            .client
+            .query_one(
+                "WITH deleted_user AS (DELETE FROM users WHERE id = $1 RETURNING id), \
+                released AS (\
+                    DELETE FROM reservations WHERE user_id IN (SELECT id FROM deleted_user) RETURNING book_id\
+                ), \
+                deleted_holds AS (DELETE FROM holds WHERE user_id IN (SELECT id FROM deleted_user)), \
+                deleted_waitlist AS (DELETE FROM waitlist WHERE user_id IN (SELECT id FROM deleted_user)), \
+                deleted_renewals AS (DELETE FROM renewals WHERE user_id IN (SELECT id FROM deleted_user)), \
+                deleted_history AS (\
+                    DELETE FROM history WHERE $2 AND user_id IN (SELECT id FROM deleted_user) RETURNING 1\
+                ), \
+                anonymized_history AS (\
+                    UPDATE history SET user_id = NULL \
+                    WHERE NOT $2 AND user_id IN (SELECT id FROM deleted_user) RETURNING 1\
+                ) \
+                SELECT (SELECT COUNT(*) FROM deleted_user), \
+                COALESCE((SELECT array_agg(book_id ORDER BY book_id) FROM released), '{}'), \
+                (SELECT COUNT(*) FROM deleted_history), \
+                (SELECT COUNT(*) FROM anonymized_history)",
+                &[
+                    &user_id,
+                    &matches!(history_retention, HistoryRetention::Delete),
+                ],
+            )
+            .await
+            .map_err(db_failure("delete_user_data"))?;
+        let deleted_users: i64 = row.try_get(0)?;
+        if deleted_users == 0 {
+            return Err(ReservationsRepositoryError::UserNotFound(user_id));
+        }
+        let deleted_history_records: i64 = row.try_get(2)?;
+        let anonymized_history_records: i64 = row.try_get(3)?;
+        Ok(UserDataDeletionSummary {
+            user_id,
+            released_reservations: row.try_get(1)?,
+            deleted_history_records: deleted_history_records as u64,
+            anonymized_history_records: anonymized_history_records as u64,
+        })
+    }
+
     async fn reserve_book(
         &self,
         user_id: UserId,
         book_id: BookId,
-    ) -> Result<(), ReservationsRepositoryError> {
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        self.reserve_book_in_branch(user_id, book_id, DEFAULT_BRANCH_ID)
+            .await
+    }
+
+    async fn reserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        Ok(self
+            .reserve_book_in_branch_with_status(user_id, book_id, branch_id)
+            .await?
+            .0)
+    }
+
+    async fn reserve_book_in_branch_with_status(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<(ReservationReceipt, bool), ReservationsRepositoryError> {
+        let holder_rows = self
+            .client
+            .query(
+                "SELECT user_id, reservation_id, reserved_at, due_at, \
+                (SELECT COUNT(*) FROM renewals WHERE renewals.reservation_id = reservations.reservation_id) \
+                FROM reservations WHERE book_id = $1 AND branch_id = $2",
+                &[&book_id, &branch_id],
+            )
+            .await
+            .map_err(db_failure("reserve_book"))?;
+        if let Some(holder_row) = holder_rows.first() {
+            let holder: UserId = holder_row.try_get(0)?;
+            return if holder == user_id {
+                let reserved_at: Option<i64> = holder_row.try_get(2)?;
+                let due_at: Option<i64> = holder_row.try_get(3)?;
+                let renewals: i64 = holder_row.try_get(4)?;
+                let receipt = ReservationReceipt {
+                    reservation_id: holder_row.try_get(1)?,
+                    user_id,
+                    book_id,
+                    reserved_at: reserved_at.map(UnixSeconds),
+                    due_at: due_at.map(UnixSeconds),
+                    renewals: renewals as u32,
+                };
+                Ok((receipt, false))
+            } else {
+                Err(ReservationsRepositoryError::BookAlreadyReservedBy(
+                    book_id, holder,
+                ))
+            };
+        }
+
+        // Copies are counted in the insert statement, so only concurrent reservations
+        // of the last copy in different branches can both succeed
         let stmt: Statement = self
             .client
             .prepare(
-                "INSERT INTO reservations (book_id, user_id) VALUES ($1, $2) RETURNING user_id",
+                "INSERT INTO reservations (book_id, user_id, reserved_at, reservation_id, branch_id) \
+                SELECT $1, $2, $3, $4, $5 \
+                WHERE (NOT $6 OR NOT EXISTS (\
+                    SELECT 1 FROM holds WHERE book_id = $1 AND user_id <> $2 AND expires_at > now()\
+                )) AND (SELECT COUNT(*) FROM reservations WHERE book_id = $1) \
+                < COALESCE((SELECT copies FROM book_copies WHERE book_id = $1), $7) \
+                RETURNING user_id",
             )
-            .await?;
+            .await
+            .map_err(db_failure("reserve_book"))?;
 
-        let rows = self.client.query(&stmt, &[&book_id, &user_id]).await;
+        let reservation_id = Uuid::new_v4();
+        let reserved_at = UnixSeconds::now();
+        let holds_apply = branch_id == DEFAULT_BRANCH_ID;
+        let rows = self
+            .client
+            .query(
+                &stmt,
+                &[
+                    &book_id,
+                    &user_id,
+                    &reserved_at.0,
+                    &reservation_id,
+                    &branch_id,
+                    &holds_apply,
+                    &(DEFAULT_BOOK_COPIES as i64),
+                ],
+            )
+            .await;
 
         match rows {
             Ok(rows) if rows.is_empty() => {
-                Err(ReservationsRepositoryError::BookAlreadyReserved(book_id))
+                Err(self.reservation_conflict(book_id, branch_id).await?)
+            }
+            Ok(_) => {
+                if holds_apply {
+                    self.client
+                        .execute("DELETE FROM holds WHERE book_id = $1", &[&book_id])
+                        .await
+                        .map_err(db_failure("reserve_book"))?;
+                }
+                let receipt = ReservationReceipt {
+                    reservation_id,
+                    user_id,
+                    book_id,
+                    reserved_at: Some(reserved_at),
+                    due_at: None,
+                    renewals: 0,
+                };
+                Ok((receipt, true))
             }
-            Ok(_) => Ok(()),
             Err(err)
                 if err
                     .as_db_error()
@@ -147,65 +488,593 @@ impl ReservationsRepository for PostgresReservationsRepository {
                     .map(|db_err| db_err.code() == &SqlState::from_code("23505"))
                     .unwrap_or_default() =>
             {
-                Err(ReservationsRepositoryError::BookAlreadyReserved(book_id))
+                // Book was reserved concurrently
+                Err(self.reservation_conflict(book_id, branch_id).await?)
             }
-            Err(other_err) => Err(other_err.into()),
+            Err(other_err) => Err(db_failure("reserve_book")(other_err)),
         }
     }
 
-    async fn unreserve_book(
+    async fn check_reservable(
         &self,
         user_id: UserId,
         book_id: BookId,
     ) -> Result<(), ReservationsRepositoryError> {
+        let holder_rows = self
+            .client
+            .query(
+                "SELECT user_id FROM reservations WHERE book_id = $1 AND branch_id = $2",
+                &[&book_id, &DEFAULT_BRANCH_ID],
+            )
+            .await
+            .map_err(db_failure("check_reservable"))?;
+        if let Some(holder_row) = holder_rows.first() {
+            let holder: UserId = holder_row.try_get(0)?;
+            return if holder == user_id {
+                Ok(())
+            } else {
+                Err(ReservationsRepositoryError::BookAlreadyReservedBy(
+                    book_id, holder,
+                ))
+            };
+        }
+
+        let hold_rows = self
+            .client
+            .query(
+                "SELECT 1 FROM holds WHERE book_id = $1 AND user_id <> $2 AND expires_at > now()",
+                &[&book_id, &user_id],
+            )
+            .await
+            .map_err(db_failure("check_reservable"))?;
+        if !hold_rows.is_empty() {
+            Err(ReservationsRepositoryError::BookOnHold(book_id))
+        } else if self.available_copies(book_id, "check_reservable").await? == 0 {
+            Err(ReservationsRepositoryError::BookAlreadyReserved(book_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn reserve_or_wait(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<ReserveOrWaitOutcome, ReservationsRepositoryError> {
+        match self
+            .reserve_book_in_branch_with_status(user_id, book_id, DEFAULT_BRANCH_ID)
+            .await
+        {
+            Ok((_, created)) => {
+                self.client
+                    .execute(
+                        "DELETE FROM waitlist WHERE book_id = $1 AND user_id = $2",
+                        &[&book_id, &user_id],
+                    )
+                    .await
+                    .map_err(db_failure("reserve_or_wait"))?;
+                Ok(if created {
+                    ReserveOrWaitOutcome::Reserved
+                } else {
+                    ReserveOrWaitOutcome::AlreadyReserved
+                })
+            }
+            Err(
+                ReservationsRepositoryError::BookAlreadyReserved(_)
+                | ReservationsRepositoryError::BookAlreadyReservedBy(..)
+                | ReservationsRepositoryError::BookOnHold(_),
+            ) => {
+                self.client
+                    .execute(
+                        "INSERT INTO waitlist (book_id, user_id) VALUES ($1, $2) \
+                        ON CONFLICT (book_id, user_id) DO NOTHING",
+                        &[&book_id, &user_id],
+                    )
+                    .await
+                    .map_err(db_failure("reserve_or_wait"))?;
+                let row = self
+                    .client
+                    .query_one(
+                        "SELECT COUNT(*) FROM waitlist WHERE book_id = $1 AND id <= (\
+                            SELECT id FROM waitlist WHERE book_id = $1 AND user_id = $2\
+                        )",
+                        &[&book_id, &user_id],
+                    )
+                    .await
+                    .map_err(db_failure("reserve_or_wait"))?;
+                let position: i64 = row.try_get(0)?;
+                Ok(ReserveOrWaitOutcome::Waitlisted(position as u32))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn get_waitlist_position(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<Option<u32>, ReservationsRepositoryError> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT (SELECT COUNT(*) FROM waitlist WHERE book_id = $1 AND id <= waiting.id) \
+                FROM waitlist AS waiting WHERE book_id = $1 AND user_id = $2",
+                &[&book_id, &user_id],
+            )
+            .await
+            .map_err(db_failure("get_waitlist_position"))?;
+        Ok(row
+            .map(|row| row.try_get::<_, i64>(0))
+            .transpose()?
+            .map(|position| position as u32))
+    }
+
+    async fn create_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        ttl: Duration,
+    ) -> Result<(), ReservationsRepositoryError> {
+        let reservation_rows = self
+            .client
+            .query(
+                "SELECT book_id FROM reservations WHERE book_id = $1 AND branch_id = $2",
+                &[&book_id, &DEFAULT_BRANCH_ID],
+            )
+            .await
+            .map_err(db_failure("create_hold"))?;
+        if !reservation_rows.is_empty() {
+            return Err(ReservationsRepositoryError::BookAlreadyReserved(book_id));
+        }
+
         let stmt: Statement = self
             .client
             .prepare(
-                "DELETE FROM reservations WHERE book_id = $1 AND user_id = $2 RETURNING book_id",
+                "INSERT INTO holds (book_id, user_id, expires_at) \
+                VALUES ($1, $2, now() + make_interval(secs => $3)) \
+                ON CONFLICT (book_id) DO UPDATE \
+                SET user_id = EXCLUDED.user_id, expires_at = EXCLUDED.expires_at \
+                WHERE holds.user_id = EXCLUDED.user_id OR holds.expires_at <= now() \
+                RETURNING book_id",
             )
-            .await?;
+            .await
+            .map_err(db_failure("create_hold"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id, &user_id, &ttl.as_secs_f64()])
+            .await
+            .map_err(db_failure("create_hold"))?;
+
+        if rows.is_empty() {
+            Err(ReservationsRepositoryError::BookOnHold(book_id))
+        } else {
+            Ok(())
+        }
+    }
 
-        let rows = self.client.query(&stmt, &[&book_id, &user_id]).await?;
+    async fn confirm_hold(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<bool, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id FROM holds WHERE book_id = $1 AND user_id = $2 AND expires_at > now()",
+            )
+            .await
+            .map_err(db_failure("confirm_hold"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id, &user_id])
+            .await
+            .map_err(db_failure("confirm_hold"))?;
 
         if rows.is_empty() {
+            Err(ReservationsRepositoryError::HoldNotFound(book_id))
+        } else {
+            let (_, created) = self
+                .reserve_book_in_branch_with_status(user_id, book_id, DEFAULT_BRANCH_ID)
+                .await?;
+            Ok(created)
+        }
+    }
+
+    async fn get_reservation(
+        &self,
+        reservation_id: ReservationId,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT user_id, book_id, reserved_at, due_at, \
+                (SELECT COUNT(*) FROM renewals WHERE renewals.reservation_id = $1) \
+                FROM reservations WHERE reservation_id = $1",
+            )
+            .await
+            .map_err(db_failure("get_reservation"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&reservation_id])
+            .await
+            .map_err(db_failure("get_reservation"))?;
+
+        let row = rows
+            .first()
+            .ok_or(ReservationsRepositoryError::ReservationNotFound(
+                reservation_id,
+            ))?;
+        let reserved_at: Option<i64> = row.try_get(2)?;
+        let due_at: Option<i64> = row.try_get(3)?;
+        let renewals: i64 = row.try_get(4)?;
+        Ok(ReservationReceipt {
+            reservation_id,
+            user_id: row.try_get(0)?,
+            book_id: row.try_get(1)?,
+            reserved_at: reserved_at.map(UnixSeconds),
+            due_at: due_at.map(UnixSeconds),
+            renewals: renewals as u32,
+        })
+    }
+
+    async fn renew_reservation(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        period: Duration,
+    ) -> Result<ReservationReceipt, ReservationsRepositoryError> {
+        // Due date is extended and the renewal recorded in a single statement, so both happen or none
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "WITH renewed AS (\
+                    UPDATE reservations SET due_at = COALESCE(due_at, $3) + $4 \
+                    WHERE book_id = $1 AND user_id = $2 AND branch_id = $5 \
+                    AND NOT EXISTS (SELECT 1 FROM waitlist WHERE book_id = $1 AND user_id <> $2) \
+                    RETURNING reservation_id, reserved_at, due_at\
+                ), recorded AS (\
+                    INSERT INTO renewals (reservation_id, user_id, book_id, renewed_at, due_at) \
+                    SELECT reservation_id, $2, $1, $3, due_at FROM renewed\
+                ) \
+                SELECT reservation_id, reserved_at, due_at, \
+                (SELECT COUNT(*) FROM renewals WHERE renewals.reservation_id = renewed.reservation_id) + 1 \
+                FROM renewed",
+            )
+            .await
+            .map_err(db_failure("renew_reservation"))?;
+        let rows = self
+            .client
+            .query(
+                &stmt,
+                &[
+                    &book_id,
+                    &user_id,
+                    &UnixSeconds::now().0,
+                    &(period.as_secs() as i64),
+                    &DEFAULT_BRANCH_ID,
+                ],
+            )
+            .await
+            .map_err(db_failure("renew_reservation"))?;
+
+        if let Some(row) = rows.first() {
+            let reserved_at: Option<i64> = row.try_get(1)?;
+            let due_at: i64 = row.try_get(2)?;
+            let renewals: i64 = row.try_get(3)?;
+            return Ok(ReservationReceipt {
+                reservation_id: row.try_get(0)?,
+                user_id,
+                book_id,
+                reserved_at: reserved_at.map(UnixSeconds),
+                due_at: Some(UnixSeconds(due_at)),
+                renewals: renewals as u32,
+            });
+        }
+
+        let reserved_rows = self
+            .client
+            .query(
+                "SELECT 1 FROM reservations WHERE book_id = $1 AND user_id = $2 AND branch_id = $3",
+                &[&book_id, &user_id, &DEFAULT_BRANCH_ID],
+            )
+            .await
+            .map_err(db_failure("renew_reservation"))?;
+        if reserved_rows.is_empty() {
             Err(ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(book_id))
         } else {
+            Err(ReservationsRepositoryError::BookWaitlisted(book_id))
+        }
+    }
+
+    async fn unreserve_book(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+    ) -> Result<(), ReservationsRepositoryError> {
+        self.unreserve_book_in_branch(user_id, book_id, DEFAULT_BRANCH_ID)
+            .await
+    }
+
+    async fn unreserve_book_in_branch(
+        &self,
+        user_id: UserId,
+        book_id: BookId,
+        branch_id: BranchId,
+    ) -> Result<(), ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "DELETE FROM reservations WHERE book_id = $1 AND user_id = $2 AND branch_id = $3 \
+                RETURNING reserved_at",
+            )
+            .await
+            .map_err(db_failure("unreserve_book"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id, &user_id, &branch_id])
+            .await
+            .map_err(db_failure("unreserve_book"))?;
+
+        if let Some(row) = rows.first() {
+            let reserved_at: Option<i64> = row.try_get(0)?;
             let stmt: Statement = self
                 .client
                 .prepare(
-                    "INSERT INTO history (book_id, user_id, unreserved_at) VALUES ($1, $2, $3)",
+                    "INSERT INTO history (book_id, user_id, reserved_at, unreserved_at) \
+                    VALUES ($1, $2, $3, $4)",
                 )
-                .await?;
+                .await
+                .map_err(db_failure("unreserve_book"))?;
 
             self.client
                 .execute(
                     &stmt,
-                    &[
-                        &book_id,
-                        &user_id,
-                        &(std::time::SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs() as i64),
-                    ],
+                    &[&book_id, &user_id, &reserved_at, &UnixSeconds::now().0],
                 )
-                .await?;
+                .await
+                .map_err(db_failure("unreserve_book"))?;
 
             Ok(())
+        } else {
+            Err(ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(book_id))
         }
     }
 
     async fn get_all_reservations(
         &self,
         user_id: UserId,
+        sort: ReservationsSort,
     ) -> Result<Vec<BookId>, ReservationsRepositoryError> {
+        let query = match sort {
+            ReservationsSort::BookId => {
+                "SELECT book_id FROM reservations WHERE user_id = $1 ORDER BY book_id"
+            }
+            ReservationsSort::ReservedAt => {
+                "SELECT book_id FROM reservations WHERE user_id = $1 ORDER BY reserved_at NULLS FIRST, book_id"
+            }
+        };
         let stmt: Statement = self
             .client
-            .prepare("SELECT book_id FROM reservations WHERE user_id = $1")
-            .await?;
-        let rows = self.client.query(&stmt, &[&user_id]).await?;
-        rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
-    }
+            .prepare(query)
+            .await
+            .map_err(db_failure("get_all_reservations"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_id])
+            .await
+            .map_err(db_failure("get_all_reservations"))?;
+        rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
+    }
+
+    async fn get_reservations_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<BookId>>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare("SELECT user_id, book_id FROM reservations WHERE user_id = ANY($1)")
+            .await
+            .map_err(db_failure("get_reservations_for_users"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_ids])
+            .await
+            .map_err(db_failure("get_reservations_for_users"))?;
+
+        let mut user_to_reservations: HashMap<UserId, Vec<BookId>> =
+            user_ids.iter().map(|user_id| (*user_id, vec![])).collect();
+        for row in rows.iter() {
+            let user_id: UserId = row.try_get(0)?;
+            let book_id: BookId = row.try_get(1)?;
+            user_to_reservations
+                .entry(user_id)
+                .or_default()
+                .push(book_id);
+        }
+        Ok(user_to_reservations)
+    }
+
+    async fn get_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<Vec<UserHistoryRecord>, ReservationsRepositoryError> {
+        let limit = i64::from(query.limit.unwrap_or(DEFAULT_HISTORY_PAGE_LIMIT));
+        let offset = i64::from(query.offset.unwrap_or_default());
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&limit, &offset];
+        // Anonymized records of deleted users are not returned
+        let mut conditions = vec!["user_id IS NOT NULL".to_string()];
+        if let Some(book_id) = &query.book_id {
+            params.push(book_id);
+            conditions.push(format!("book_id = ${}", params.len()));
+        }
+        if let Some(user_id) = &query.user_id {
+            params.push(user_id);
+            conditions.push(format!("user_id = ${}", params.len()));
+        }
+
+        let stmt: Statement = self
+            .client
+            .prepare(&format!(
+                "SELECT user_id, book_id, unreserved_at FROM history WHERE {} \
+                ORDER BY unreserved_at, user_id, book_id LIMIT $1 OFFSET $2",
+                conditions.join(" AND ")
+            ))
+            .await
+            .map_err(db_failure("get_history"))?;
+        let rows = self
+            .client
+            .query(&stmt, &params)
+            .await
+            .map_err(db_failure("get_history"))?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(UserHistoryRecord {
+                    user_id: row.try_get(0)?,
+                    book_id: row.try_get(1)?,
+                    unreserved_at: UnixSeconds(row.try_get(2)?),
+                })
+            })
+            .collect()
+    }
+
+    async fn get_never_reserved_books(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<Vec<BookId>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id FROM reservations WHERE book_id = ANY($1) \
+                UNION SELECT book_id FROM history WHERE book_id = ANY($1)",
+            )
+            .await
+            .map_err(db_failure("get_never_reserved_books"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_ids])
+            .await
+            .map_err(db_failure("get_never_reserved_books"))?;
+
+        let ever_reserved_books = rows
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<HashSet<BookId>, _>>()?;
+        Ok(book_ids
+            .iter()
+            .filter(|book_id| !ever_reserved_books.contains(book_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_books_availability(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, bool>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id FROM reservations WHERE book_id = ANY($1) AND branch_id = $2 \
+                UNION SELECT book_id FROM holds WHERE book_id = ANY($1) AND expires_at > now()",
+            )
+            .await
+            .map_err(db_failure("get_books_availability"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_ids, &DEFAULT_BRANCH_ID])
+            .await
+            .map_err(db_failure("get_books_availability"))?;
+
+        let mut book_to_availability: HashMap<BookId, bool> =
+            book_ids.iter().map(|book_id| (*book_id, true)).collect();
+        for row in rows.iter() {
+            let book_id: BookId = row.try_get(0)?;
+            book_to_availability.insert(book_id, false);
+        }
+        Ok(book_to_availability)
+    }
+
+    async fn get_books_holders(
+        &self,
+        book_ids: &[BookId],
+    ) -> Result<HashMap<BookId, UserId>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id, user_id FROM reservations WHERE book_id = ANY($1) AND branch_id = $2",
+            )
+            .await
+            .map_err(db_failure("get_books_holders"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_ids, &DEFAULT_BRANCH_ID])
+            .await
+            .map_err(db_failure("get_books_holders"))?;
+
+        rows.iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect()
+    }
+
+    async fn count_reservations(&self) -> Result<u64, ReservationsRepositoryError> {
+        let row = self
+            .client
+            .query_one("SELECT COUNT(*) FROM reservations", &[])
+            .await
+            .map_err(db_failure("count_reservations"))?;
+        let count: i64 = row.try_get(0)?;
+        Ok(count as u64)
+    }
+
+    async fn set_book_copies(
+        &self,
+        book_id: BookId,
+        copies: u32,
+    ) -> Result<(), ReservationsRepositoryError> {
+        self.client
+            .execute(
+                "INSERT INTO book_copies (book_id, copies) VALUES ($1, $2) \
+                ON CONFLICT (book_id) DO UPDATE SET copies = EXCLUDED.copies",
+                &[&book_id, &(copies as i64)],
+            )
+            .await
+            .map_err(db_failure("set_book_copies"))?;
+        Ok(())
+    }
+
+    async fn count_available_copies(
+        &self,
+        book_id: BookId,
+    ) -> Result<u32, ReservationsRepositoryError> {
+        self.available_copies(book_id, "count_available_copies")
+            .await
+    }
+
+    async fn average_hold_duration(
+        &self,
+        book_id: BookId,
+    ) -> Result<Option<f64>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT AVG(unreserved_at - reserved_at)::FLOAT8 FROM history \
+                WHERE book_id = $1 AND reserved_at IS NOT NULL",
+            )
+            .await
+            .map_err(db_failure("average_hold_duration"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&book_id])
+            .await
+            .map_err(db_failure("average_hold_duration"))?;
+
+        Ok(rows
+            .first()
+            .map(|row| row.try_get(0))
+            .transpose()?
+            .flatten())
+    }
 
     async fn get_reservations_history(
         &self,
@@ -213,23 +1082,187 @@ impl ReservationsRepository for PostgresReservationsRepository {
     ) -> Result<Vec<ReservationHistoryRecord>, ReservationsRepositoryError> {
         let stmt: Statement = self
             .client
-            .prepare("SELECT book_id, unreserved_at FROM history WHERE user_id = $1")
-            .await?;
+            .prepare("SELECT book_id, reserved_at, unreserved_at FROM history WHERE user_id = $1")
+            .await
+            .map_err(db_failure("get_reservations_history"))?;
 
-        let rows = self.client.query(&stmt, &[&user_id]).await?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_id])
+            .await
+            .map_err(db_failure("get_reservations_history"))?;
 
         rows.iter()
             .map(|row| {
                 let book_id = row.try_get(0)?;
-                let unreserved_at: i64 = row.try_get(1)?;
+                let reserved_at: Option<i64> = row.try_get(1)?;
+                let unreserved_at = UnixSeconds(row.try_get(2)?);
 
                 Ok(ReservationHistoryRecord {
                     book_id,
+                    reserved_at: reserved_at.map(UnixSeconds),
                     unreserved_at,
                 })
             })
             .collect()
     }
+
+    async fn get_user_activity(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<ReservationEvent>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id, reserved_at, FALSE FROM reservations \
+                WHERE user_id = $1 AND reserved_at IS NOT NULL \
+                UNION ALL SELECT book_id, reserved_at, FALSE FROM history \
+                WHERE user_id = $1 AND reserved_at IS NOT NULL \
+                UNION ALL SELECT book_id, unreserved_at, TRUE FROM history WHERE user_id = $1 \
+                ORDER BY 2, 3, 1",
+            )
+            .await
+            .map_err(db_failure("get_user_activity"))?;
+
+        let rows = self
+            .client
+            .query(&stmt, &[&user_id])
+            .await
+            .map_err(db_failure("get_user_activity"))?;
+
+        rows.iter()
+            .map(|row| {
+                let unreserved: bool = row.try_get(2)?;
+                Ok(ReservationEvent {
+                    action: if unreserved {
+                        ReservationAction::Unreserved
+                    } else {
+                        ReservationAction::Reserved
+                    },
+                    user_id,
+                    book_id: row.try_get(0)?,
+                    timestamp: UnixSeconds(row.try_get(1)?),
+                })
+            })
+            .collect()
+    }
+
+    async fn get_reservations_history_between(
+        &self,
+        user_id: UserId,
+        from: Option<UnixSeconds>,
+        to: Option<UnixSeconds>,
+    ) -> Result<Vec<ReservationHistoryRecord>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT book_id, reserved_at, unreserved_at FROM history WHERE user_id = $1 \
+                AND ($2::BIGINT IS NULL OR unreserved_at >= $2) \
+                AND ($3::BIGINT IS NULL OR unreserved_at <= $3) \
+                ORDER BY unreserved_at, book_id",
+            )
+            .await
+            .map_err(db_failure("get_reservations_history_between"))?;
+
+        let rows = self
+            .client
+            .query(
+                &stmt,
+                &[&user_id, &from.map(|from| from.0), &to.map(|to| to.0)],
+            )
+            .await
+            .map_err(db_failure("get_reservations_history_between"))?;
+
+        rows.iter()
+            .map(|row| {
+                let reserved_at: Option<i64> = row.try_get(1)?;
+                Ok(ReservationHistoryRecord {
+                    book_id: row.try_get(0)?,
+                    reserved_at: reserved_at.map(UnixSeconds),
+                    unreserved_at: UnixSeconds(row.try_get(2)?),
+                })
+            })
+            .collect()
+    }
+
+    async fn get_history_for_users(
+        &self,
+        user_ids: &[UserId],
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError> {
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT user_id, book_id, reserved_at, unreserved_at FROM history \
+                WHERE user_id = ANY($1)",
+            )
+            .await
+            .map_err(db_failure("get_history_for_users"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_ids])
+            .await
+            .map_err(db_failure("get_history_for_users"))?;
+
+        let mut user_to_history: HashMap<UserId, Vec<ReservationHistoryRecord>> =
+            user_ids.iter().map(|user_id| (*user_id, vec![])).collect();
+        for row in rows.iter() {
+            let user_id: UserId = row.try_get(0)?;
+            let book_id: BookId = row.try_get(1)?;
+            let reserved_at: Option<i64> = row.try_get(2)?;
+            let unreserved_at = UnixSeconds(row.try_get(3)?);
+            user_to_history
+                .entry(user_id)
+                .or_default()
+                .push(ReservationHistoryRecord {
+                    book_id,
+                    reserved_at: reserved_at.map(UnixSeconds),
+                    unreserved_at,
+                });
+        }
+        Ok(user_to_history)
+    }
+
+    async fn get_history_since_for_users(
+        &self,
+        user_id_to_since: &HashMap<UserId, UnixSeconds>,
+    ) -> Result<HashMap<UserId, Vec<ReservationHistoryRecord>>, ReservationsRepositoryError> {
+        let (user_ids, since): (Vec<UserId>, Vec<i64>) = user_id_to_since
+            .iter()
+            .map(|(user_id, since)| (*user_id, since.0))
+            .unzip();
+        let stmt: Statement = self
+            .client
+            .prepare(
+                "SELECT h.user_id, h.book_id, h.reserved_at, h.unreserved_at FROM history h \
+                JOIN UNNEST($1::INTEGER[], $2::BIGINT[]) AS c(user_id, since) ON h.user_id = c.user_id \
+                WHERE h.unreserved_at >= c.since",
+            )
+            .await
+            .map_err(db_failure("get_history_since_for_users"))?;
+        let rows = self
+            .client
+            .query(&stmt, &[&user_ids, &since])
+            .await
+            .map_err(db_failure("get_history_since_for_users"))?;
+
+        let mut user_to_history: HashMap<UserId, Vec<ReservationHistoryRecord>> =
+            user_ids.iter().map(|user_id| (*user_id, vec![])).collect();
+        for row in rows.iter() {
+            let user_id: UserId = row.try_get(0)?;
+            let book_id: BookId = row.try_get(1)?;
+            let reserved_at: Option<i64> = row.try_get(2)?;
+            let unreserved_at = UnixSeconds(row.try_get(3)?);
+            user_to_history
+                .entry(user_id)
+                .or_default()
+                .push(ReservationHistoryRecord {
+                    book_id,
+                    reserved_at: reserved_at.map(UnixSeconds),
+                    unreserved_at,
+                });
+        }
+        Ok(user_to_history)
+    }
 }
 
 #[cfg(test)]
@@ -240,6 +1273,8 @@ mod tests_postgres_reservations_repository {
     use testcontainers::runners::AsyncRunner;
 
     use super::*;
+    use std::sync::Arc;
+    use tracing_subscriber::layer::SubscriberExt;
 
     async fn start_postgres_container_and_init_repo(
     ) -> (ContainerAsync<GenericImage>, PostgresReservationsRepository) {
@@ -267,6 +1302,43 @@ mod tests_postgres_reservations_repository {
         panic!("Failed to setup postgres container")
     }
 
+    /// Layer capturing `operation` field of every logged error event
+    #[derive(Clone, Default)]
+    struct ErrorOperationsCapturingLayer {
+        operations: Arc<parking_lot::Mutex<Vec<String>>>,
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ErrorOperationsCapturingLayer {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if *event.metadata().level() == tracing::Level::ERROR {
+                let mut visitor = OperationVisitor::default();
+                event.record(&mut visitor);
+                if let Some(operation) = visitor.operation {
+                    self.operations.lock().push(operation);
+                }
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct OperationVisitor {
+        operation: Option<String>,
+    }
+
+    impl tracing::field::Visit for OperationVisitor {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            if field.name() == "operation" {
+                self.operation = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
     #[tokio::test]
     #[file_serial(key, path => "../.pgtestslock")]
     /// Simple test to cover user management
@@ -287,7 +1359,7 @@ mod tests_postgres_reservations_repository {
 
         let user_details = UserDetails {
             username: "username".to_string(),
-            favourite_tags: vec!["tag1".to_string(), "tag2".to_string()],
+            favourite_tags: vec!["tag1".into(), "tag2".into()],
         };
 
         let user_id = repository.add_user(user_details.clone()).await.unwrap();
@@ -347,7 +1419,10 @@ mod tests_postgres_reservations_repository {
             .unwrap();
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             Vec::<BookId>::default()
         );
 
@@ -362,13 +1437,28 @@ mod tests_postgres_reservations_repository {
         let test_book_id: BookId = 123;
 
         // reserve book for the user
-        repository
+        let receipt = repository
             .reserve_book(user_1_id, test_book_id)
             .await
             .unwrap();
+        assert_eq!(
+            (receipt.user_id, receipt.book_id),
+            (user_1_id, test_book_id)
+        );
+        assert!(receipt.reserved_at.is_some());
+        assert_eq!(
+            repository
+                .get_reservation(receipt.reservation_id)
+                .await
+                .unwrap(),
+            receipt
+        );
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             vec![test_book_id]
         );
         assert_eq!(
@@ -379,11 +1469,35 @@ mod tests_postgres_reservations_repository {
             Vec::<ReservationHistoryRecord>::default()
         );
 
+        // reserving the book again by the same user succeeds without duplicating the reservation
+        assert_eq!(
+            repository
+                .reserve_book(user_1_id, test_book_id)
+                .await
+                .unwrap(),
+            receipt
+        );
+        assert_eq!(
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![test_book_id]
+        );
+        assert_eq!(
+            repository
+                .reserve_book_in_branch_with_status(user_1_id, test_book_id, DEFAULT_BRANCH_ID)
+                .await
+                .unwrap(),
+            (receipt.clone(), false)
+        );
+
         let reserve_conflict = repository.reserve_book(user_2_id, test_book_id).await;
 
         assert!(matches!(
             reserve_conflict,
-            Err(ReservationsRepositoryError::BookAlreadyReserved(..))
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(book_id, holder))
+                if book_id == test_book_id && holder == user_1_id
         ));
 
         // unreserve book for wrong user
@@ -399,9 +1513,16 @@ mod tests_postgres_reservations_repository {
             .unreserve_book(user_1_id, test_book_id)
             .await
             .unwrap();
+        assert!(matches!(
+            repository.get_reservation(receipt.reservation_id).await,
+            Err(ReservationsRepositoryError::ReservationNotFound(..))
+        ));
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             Vec::<BookId>::default()
         );
 
@@ -425,7 +1546,10 @@ mod tests_postgres_reservations_repository {
             .await
             .unwrap();
 
-        let mut two_reservations = repository.get_all_reservations(user_1_id).await.unwrap();
+        let mut two_reservations = repository
+            .get_all_reservations(user_1_id, ReservationsSort::BookId)
+            .await
+            .unwrap();
         two_reservations.sort();
         assert_eq!(two_reservations, vec![test_book_id, other_book_id]);
 
@@ -436,7 +1560,10 @@ mod tests_postgres_reservations_repository {
             .unwrap();
 
         assert_eq!(
-            repository.get_all_reservations(user_1_id).await.unwrap(),
+            repository
+                .get_all_reservations(user_1_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
             vec![other_book_id]
         );
 
@@ -448,4 +1575,1174 @@ mod tests_postgres_reservations_repository {
         assert_eq!(history[0].book_id, test_book_id);
         assert_eq!(history[1].book_id, test_book_id);
     }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if reservations of multiple users are fetched at once
+    /// 1.Creates three users, reserves two books for the first and one for the second
+    /// 2.Fetches reservations for all three users
+    async fn test_get_reservations_for_users() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[0], 2).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+
+        let mut user_to_reservations = repository
+            .get_reservations_for_users(&user_ids)
+            .await
+            .unwrap();
+        user_to_reservations
+            .values_mut()
+            .for_each(|reservations| reservations.sort());
+
+        assert_eq!(
+            user_to_reservations,
+            HashMap::from([
+                (user_ids[0], vec![1, 2]),
+                (user_ids[1], vec![3]),
+                (user_ids[2], vec![]),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if details of multiple users are fetched at once
+    /// 1.Creates two users
+    /// 2.Fetches details of both users and of an unknown user
+    /// 3.Checks that details of both users are returned and the unknown user is omitted
+    async fn test_get_users() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let first_details = UserDetails {
+            username: "user1".to_string(),
+            favourite_tags: vec!["tag1".into()],
+        };
+        let second_details = UserDetails {
+            username: "user2".to_string(),
+            favourite_tags: vec![],
+        };
+        let first_user_id = repository.add_user(first_details.clone()).await.unwrap();
+        let second_user_id = repository.add_user(second_details.clone()).await.unwrap();
+        let unknown_user_id = first_user_id.max(second_user_id) + 1;
+
+        assert_eq!(
+            repository
+                .get_users(&[first_user_id, second_user_id, unknown_user_id])
+                .await
+                .unwrap(),
+            HashMap::from([
+                (first_user_id, first_details),
+                (second_user_id, second_details)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if history of multiple users fetched at once matches the per user history
+    /// 1.Creates three users, reserves and unreserves books for the first two
+    /// 2.Fetches history for all three users and compares it with per user calls
+    async fn test_get_history_for_users() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        for (user_id, book_id) in [(user_ids[0], 1), (user_ids[0], 2), (user_ids[1], 3)] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+
+        let user_to_history = repository.get_history_for_users(&user_ids).await.unwrap();
+
+        assert_eq!(user_to_history.len(), 3);
+        for user_id in user_ids.iter() {
+            assert_eq!(
+                user_to_history[user_id],
+                repository.get_reservations_history(*user_id).await.unwrap()
+            );
+        }
+        assert_eq!(user_to_history[&user_ids[0]].len(), 2);
+        assert_eq!(user_to_history[&user_ids[1]].len(), 1);
+        assert_eq!(user_to_history[&user_ids[2]].len(), 0);
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if history since given timestamp returns only records of each user unreserved at or after it
+    /// 1.Creates three users, reserves and unreserves books for the first two
+    /// 2.Fetches history since -1 for the first and third user and since last record for the second one
+    /// 3.Checks that the first user gets full history, the second one its last record and the third one no records
+    async fn test_get_history_since_for_users() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        for (user_id, book_id) in [(user_ids[0], 1), (user_ids[0], 2), (user_ids[1], 3)] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+        let second_user_last_unreserved_at = repository
+            .get_reservations_history(user_ids[1])
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.unreserved_at)
+            .max()
+            .unwrap();
+
+        let user_to_history = repository
+            .get_history_since_for_users(&HashMap::from([
+                (user_ids[0], UnixSeconds(-1)),
+                (user_ids[1], second_user_last_unreserved_at),
+                (user_ids[2], UnixSeconds(-1)),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(user_to_history.len(), 3);
+        assert_eq!(
+            user_to_history[&user_ids[0]],
+            repository
+                .get_reservations_history(user_ids[0])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            user_to_history[&user_ids[1]]
+                .iter()
+                .map(|r| r.unreserved_at)
+                .collect::<Vec<_>>(),
+            vec![second_user_last_unreserved_at]
+        );
+        assert_eq!(user_to_history[&user_ids[2]].len(), 0);
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if availability of multiple books is fetched at once
+    /// 1.Creates user and reserves two of four books
+    /// 2.Fetches availability of all four books and checks reserved ones are not available
+    async fn test_get_books_availability() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user1".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        repository.reserve_book(user_id, 1).await.unwrap();
+        repository.reserve_book(user_id, 3).await.unwrap();
+
+        let book_to_availability = repository
+            .get_books_availability(&[1, 2, 3, 4])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            book_to_availability,
+            HashMap::from([(1, false), (2, true), (3, false), (4, true)])
+        );
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if failed query is logged with the name of the operation
+    /// 1.Drops the table so that the next query fails
+    /// 2.Calls repository method and checks that error with its operation name was logged
+    async fn test_failed_query_logs_operation() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let layer = ErrorOperationsCapturingLayer::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(layer.clone()));
+
+        repository
+            .client
+            .batch_execute("DROP TABLE users")
+            .await
+            .unwrap();
+        assert!(repository.get_all_user_ids().await.is_err());
+
+        assert_eq!(
+            *layer.operations.lock(),
+            vec!["get_all_user_ids".to_string()]
+        );
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if only books missing in both active reservations and history are returned as never reserved
+    /// 1.Creates user, reserves first book and reserves and unreserves the second one
+    /// 2.Checks that of three books only the third one was never reserved
+    async fn test_get_never_reserved_books() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user1".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        repository.reserve_book(user_id, 1).await.unwrap();
+        repository.reserve_book(user_id, 2).await.unwrap();
+        repository.unreserve_book(user_id, 2).await.unwrap();
+
+        assert_eq!(
+            repository
+                .get_never_reserved_books(&[1, 2, 3])
+                .await
+                .unwrap(),
+            vec![3]
+        );
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if history of all users can be filtered by user and book and paginated
+    /// 1.Creates two users, each reserves and unreserves two books (one book is shared)
+    /// 2.Checks filtering by user, by book and by both
+    /// 3.Checks that pages with limit and offset together return all records
+    async fn test_get_history() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        for (user_id, book_id) in [
+            (user_ids[0], 1),
+            (user_ids[0], 2),
+            (user_ids[1], 2),
+            (user_ids[1], 3),
+        ] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+
+        let user_and_book_ids = |records: Vec<UserHistoryRecord>| {
+            let mut ids: Vec<(UserId, BookId)> = records
+                .into_iter()
+                .map(|record| (record.user_id, record.book_id))
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        let by_user = repository
+            .get_history(&HistoryQuery {
+                user_id: Some(user_ids[0]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            user_and_book_ids(by_user),
+            vec![(user_ids[0], 1), (user_ids[0], 2)]
+        );
+
+        let by_book = repository
+            .get_history(&HistoryQuery {
+                book_id: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            user_and_book_ids(by_book),
+            vec![(user_ids[0], 2), (user_ids[1], 2)]
+        );
+
+        let by_user_and_book = repository
+            .get_history(&HistoryQuery {
+                book_id: Some(2),
+                user_id: Some(user_ids[1]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(user_and_book_ids(by_user_and_book), vec![(user_ids[1], 2)]);
+
+        let all = repository.get_history(&Default::default()).await.unwrap();
+        assert_eq!(all.len(), 4);
+        let mut pages = vec![];
+        for offset in [0, 3] {
+            pages.extend(
+                repository
+                    .get_history(&HistoryQuery {
+                        limit: Some(3),
+                        offset: Some(offset),
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_eq!(pages, all);
+    }
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if held book can be reserved only by the holder, confirming the hold within ttl
+    /// 1.Creates two users, first one holds a book
+    /// 2.Checks that book is unavailable and the second user can neither hold nor reserve it
+    /// 3.Confirms the hold and checks that the book is reserved by the first user
+    async fn test_confirm_hold_within_ttl() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let book_id = 1;
+        repository
+            .create_hold(user_ids[0], book_id, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.get_books_availability(&[book_id]).await.unwrap(),
+            HashMap::from([(book_id, false)])
+        );
+        assert!(matches!(
+            repository
+                .create_hold(user_ids[1], book_id, Duration::from_secs(60))
+                .await,
+            Err(ReservationsRepositoryError::BookOnHold(..))
+        ));
+        assert!(matches!(
+            repository.reserve_book(user_ids[1], book_id).await,
+            Err(ReservationsRepositoryError::BookOnHold(..))
+        ));
+        assert!(matches!(
+            repository.confirm_hold(user_ids[1], book_id).await,
+            Err(ReservationsRepositoryError::HoldNotFound(..))
+        ));
+
+        repository.confirm_hold(user_ids[0], book_id).await.unwrap();
+        assert_eq!(
+            repository
+                .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![book_id]
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if hold is released automatically after ttl
+    /// 1.Creates two users, first one holds a book for a short time
+    /// 2.Waits until the hold expires
+    /// 3.Checks that the hold can not be confirmed, book is available and the second user can reserve it
+    async fn test_hold_expires() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        let book_id = 1;
+        repository
+            .create_hold(user_ids[0], book_id, Duration::from_millis(100))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(matches!(
+            repository.confirm_hold(user_ids[0], book_id).await,
+            Err(ReservationsRepositoryError::HoldNotFound(..))
+        ));
+        assert_eq!(
+            repository.get_books_availability(&[book_id]).await.unwrap(),
+            HashMap::from([(book_id, true)])
+        );
+        repository.reserve_book(user_ids[1], book_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if deleting user data removes the user and releases reservations for both history retention modes
+    /// 1.Creates two users, each reserves one book and reserves and unreserves another one
+    /// 2.Deletes first user with history deletion and second one with history anonymization
+    /// 3.Checks returned summaries, that users are gone, books are available and history is removed
+    /// 4.Checks that anonymized history still counts as reserved books and deleting missing user fails
+    async fn test_delete_user_data() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        for (user_id, reserved_book_id, unreserved_book_id) in
+            [(user_ids[0], 1, 2), (user_ids[1], 3, 4)]
+        {
+            repository
+                .reserve_book(user_id, reserved_book_id)
+                .await
+                .unwrap();
+            repository
+                .reserve_book(user_id, unreserved_book_id)
+                .await
+                .unwrap();
+            repository
+                .unreserve_book(user_id, unreserved_book_id)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            repository
+                .delete_user_data(user_ids[0], HistoryRetention::Delete)
+                .await
+                .unwrap(),
+            UserDataDeletionSummary {
+                user_id: user_ids[0],
+                released_reservations: vec![1],
+                deleted_history_records: 1,
+                anonymized_history_records: 0,
+            }
+        );
+        assert_eq!(
+            repository
+                .delete_user_data(user_ids[1], HistoryRetention::Anonymize)
+                .await
+                .unwrap(),
+            UserDataDeletionSummary {
+                user_id: user_ids[1],
+                released_reservations: vec![3],
+                deleted_history_records: 0,
+                anonymized_history_records: 1,
+            }
+        );
+
+        assert!(repository.get_all_user_ids().await.unwrap().is_empty());
+        for user_id in &user_ids {
+            assert!(matches!(
+                repository.get_user(*user_id).await,
+                Err(ReservationsRepositoryError::UserNotFound(..))
+            ));
+            assert!(repository
+                .get_reservations_history(*user_id)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+        assert!(repository
+            .get_history(&Default::default())
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            repository.get_books_availability(&[1, 3]).await.unwrap(),
+            HashMap::from([(1, true), (3, true)])
+        );
+        assert_eq!(
+            repository
+                .get_never_reserved_books(&[1, 2, 3, 4])
+                .await
+                .unwrap(),
+            vec![1, 2, 3]
+        );
+
+        assert!(matches!(
+            repository
+                .delete_user_data(user_ids[0], HistoryRetention::Delete)
+                .await,
+            Err(ReservationsRepositoryError::UserNotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if holders are returned only for reserved books
+    /// 1.Creates two users, each reserves one of three books
+    /// 2.Fetches holders of all three books and checks that only reserved ones are present with correct users
+    async fn test_get_books_holders() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+
+        assert_eq!(
+            repository.get_books_holders(&[1, 2, 3]).await.unwrap(),
+            HashMap::from([(1, user_ids[0]), (3, user_ids[1])])
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if average hold duration is computed from history records with known reservation time
+    /// 1.Checks that book without history has no average
+    /// 2.Adds two completed reservations of the book lasting 100 and 300 seconds, one without reservation time
+    ///   and one of another book
+    /// 3.Checks that the average is 200 seconds
+    async fn test_average_hold_duration() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        assert_eq!(repository.average_hold_duration(1).await.unwrap(), None);
+        repository
+            .client
+            .batch_execute(
+                "INSERT INTO history (book_id, user_id, reserved_at, unreserved_at) VALUES \
+                (1, 1, 100, 200), (1, 2, 1000, 1300), (1, 1, NULL, 5000), (2, 1, 0, 5000)",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.average_hold_duration(1).await.unwrap(),
+            Some(200.0)
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if users are ranked by total number of active and history reservations
+    /// 1.Creates three users, first one reserves one book, second one reserves one book and has two in history,
+    ///   third one has no reservations
+    /// 2.Checks that the second user ranks first, the user without reservations is omitted and limit is applied
+    async fn test_get_most_active_users() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+
+        let mut user_ids = vec![];
+        for username in ["user1", "user2", "user3"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        for book_id in [2, 3] {
+            repository.reserve_book(user_ids[1], book_id).await.unwrap();
+            repository
+                .unreserve_book(user_ids[1], book_id)
+                .await
+                .unwrap();
+        }
+        repository.reserve_book(user_ids[1], 4).await.unwrap();
+
+        assert_eq!(
+            repository.get_most_active_users(10).await.unwrap(),
+            vec![user_ids[1], user_ids[0]]
+        );
+        assert_eq!(
+            repository.get_most_active_users(1).await.unwrap(),
+            vec![user_ids[1]]
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if total number of reservations counts active reservations of all users
+    /// 1.Checks that there are no reservations at start
+    /// 2.Creates two users reserving three books in total, then one of the books is unreserved
+    /// 3.Checks that two reservations are counted
+    async fn test_count_reservations() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        assert_eq!(repository.count_reservations().await.unwrap(), 0);
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository.reserve_book(user_ids[0], 2).await.unwrap();
+        repository.reserve_book(user_ids[1], 3).await.unwrap();
+        repository.unreserve_book(user_ids[0], 2).await.unwrap();
+
+        assert_eq!(repository.count_reservations().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if available copies are copies of the book minus its reservations in all branches
+    /// and reservations are limited by them
+    /// 1. Checks that a book without set number of copies has a single available copy
+    /// 2. Sets 3 copies of the book and reserves it in two branches - expect 1 available copy
+    /// 3. Reserves it in the third branch - expect no available copies and reserving it in the fourth branch fails
+    /// 4. Checks that a book with a single copy reserved in the default branch can not be reserved in other branch
+    async fn test_count_available_copies() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 1);
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.set_book_copies(1, 3).await.unwrap();
+        repository.reserve_book(user_ids[0], 1).await.unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 1)
+            .await
+            .unwrap();
+        repository.reserve_book(user_ids[1], 2).await.unwrap();
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 1);
+
+        repository
+            .reserve_book_in_branch(user_ids[2], 1, 2)
+            .await
+            .unwrap();
+        assert_eq!(repository.count_available_copies(1).await.unwrap(), 0);
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[0], 1, 3).await,
+            Err(ReservationsRepositoryError::BookAlreadyReserved(1))
+        ));
+
+        assert_eq!(repository.count_available_copies(2).await.unwrap(), 0);
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[0], 2, 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReserved(2))
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if holder of the book is reported when reservations of two users conflict
+    /// 1.Creates two users that concurrently reserve the same book
+    /// 2.Checks that exactly one of them succeeds and the other one is told who holds the book
+    async fn test_reserve_conflict_reports_holder() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["user1", "user2"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let results = tokio::join!(
+            repository.reserve_book(user_ids[0], 1),
+            repository.reserve_book(user_ids[1], 1)
+        );
+        let (holder, conflict) = match results {
+            (Ok(receipt), conflict) | (conflict, Ok(receipt)) => (receipt.user_id, conflict),
+            other => panic!("Expected one successful reservation, got {:?}", other),
+        };
+        assert!(matches!(
+            conflict,
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(1, conflict_holder))
+                if conflict_holder == holder
+        ));
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if reservation is checked without reserving the book
+    /// 1. Second user reserves one book and holds another one
+    /// 2. Checks that the second user may reserve both and first user only the free book
+    /// 3. Checks that nothing was reserved by the checks
+    async fn test_check_reservable() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+        repo.reserve_book(user_ids[1], 1).await.unwrap();
+        repo.create_hold(user_ids[1], 2, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        assert!(repo.check_reservable(user_ids[0], 3).await.is_ok());
+        assert!(repo.check_reservable(user_ids[1], 1).await.is_ok());
+        assert!(repo.check_reservable(user_ids[1], 2).await.is_ok());
+        assert!(matches!(
+            repo.check_reservable(user_ids[0], 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(1, _))
+        ));
+        assert!(matches!(
+            repo.check_reservable(user_ids[0], 2).await,
+            Err(ReservationsRepositoryError::BookOnHold(2))
+        ));
+        assert!(repo
+            .get_all_reservations(user_ids[0], ReservationsSort::BookId)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if book is reserved when free and user is added to the waitlist when it is reserved
+    /// 1. First user reserves the book, second and third users are waitlisted at positions 1 and 2
+    /// 2. Second user asking again keeps the position
+    /// 3. After deleting second user data the third user moves to position 1
+    async fn test_reserve_or_wait() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[0], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Reserved
+        );
+        assert_eq!(
+            repo.get_all_reservations(user_ids[0], ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[2], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(2)
+        );
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+
+        repo.delete_user_data(user_ids[1], HistoryRetention::Delete)
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[2], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if waitlist position of the user is returned and shifts when the head of the waitlist leaves
+    /// 1. First user reserves the book, other users wait - expect positions 1, 2 and 3 and none for the first user
+    /// 2. First user unreserves and second user reserves the book - expect remaining users move up by one
+    async fn test_get_waitlist_position() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["first", "second", "third", "fourth"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+
+        repo.reserve_or_wait(user_ids[0], 1).await.unwrap();
+        for user_id in &user_ids[1..] {
+            repo.reserve_or_wait(*user_id, 1).await.unwrap();
+        }
+        let mut positions = vec![];
+        for user_id in &user_ids {
+            positions.push(repo.get_waitlist_position(*user_id, 1).await.unwrap());
+        }
+        assert_eq!(positions, vec![None, Some(1), Some(2), Some(3)]);
+        assert_eq!(
+            repo.get_waitlist_position(user_ids[1], 2).await.unwrap(),
+            None
+        );
+
+        repo.unreserve_book(user_ids[0], 1).await.unwrap();
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Reserved
+        );
+        let mut positions = vec![];
+        for user_id in &user_ids {
+            positions.push(repo.get_waitlist_position(*user_id, 1).await.unwrap());
+        }
+        assert_eq!(positions, vec![None, None, Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if renewing extends due date of the reservation and records renewals
+    /// 1. First user reserves the book, second user can not renew it
+    /// 2. First user renews the book twice - expect due date extended by two periods and two renewals
+    /// 3. Second user waits for the book - expect renewing is rejected and the reservation is unchanged
+    async fn test_renew_reservation() {
+        let (_container, repo) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repo.add_user(UserDetails {
+                    username: username.to_string(),
+                    favourite_tags: vec![],
+                })
+                .await
+                .unwrap(),
+            );
+        }
+        let period = Duration::from_secs(100);
+
+        let receipt = repo.reserve_book(user_ids[0], 1).await.unwrap();
+        assert_eq!(receipt.due_at, None);
+        assert!(matches!(
+            repo.renew_reservation(user_ids[1], 1, period).await,
+            Err(ReservationsRepositoryError::BookNotReservedOrReservedByDifferentUser(1))
+        ));
+
+        let before_renew = UnixSeconds::now();
+        let first_renew = repo
+            .renew_reservation(user_ids[0], 1, period)
+            .await
+            .unwrap();
+        assert_eq!(first_renew.reservation_id, receipt.reservation_id);
+        assert_eq!(first_renew.renewals, 1);
+        let first_due_at = first_renew.due_at.unwrap();
+        assert!(first_due_at.0 >= before_renew.0 + 100);
+        let second_renew = repo
+            .renew_reservation(user_ids[0], 1, period)
+            .await
+            .unwrap();
+        assert_eq!(second_renew.due_at, Some(UnixSeconds(first_due_at.0 + 100)));
+        assert_eq!(second_renew.renewals, 2);
+        assert_eq!(
+            repo.get_reservation(receipt.reservation_id).await.unwrap(),
+            second_renew
+        );
+
+        assert_eq!(
+            repo.reserve_or_wait(user_ids[1], 1).await.unwrap(),
+            ReserveOrWaitOutcome::Waitlisted(1)
+        );
+        assert!(matches!(
+            repo.renew_reservation(user_ids[0], 1, period).await,
+            Err(ReservationsRepositoryError::BookWaitlisted(1))
+        ));
+        assert_eq!(
+            repo.get_reservation(receipt.reservation_id).await.unwrap(),
+            second_renew
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if only history records unreserved within the window are returned, bounds are inclusive
+    /// 1. Seeds history records of two users unreserved at different times
+    /// 2. Checks windows with both bounds, only lower and only upper bound
+    async fn test_get_reservations_history_between() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        for (user_id, book_id, unreserved_at) in [
+            (1, 1, 10_i64),
+            (1, 2, 20),
+            (1, 3, 30),
+            (1, 4, 40),
+            (2, 5, 20),
+        ] {
+            repository
+                .client
+                .execute(
+                    "INSERT INTO history (book_id, user_id, unreserved_at) VALUES ($1, $2, $3)",
+                    &[&book_id, &user_id, &unreserved_at],
+                )
+                .await
+                .unwrap();
+        }
+        let record = |book_id: BookId, unreserved_at: i64| ReservationHistoryRecord {
+            book_id,
+            reserved_at: None,
+            unreserved_at: UnixSeconds(unreserved_at),
+        };
+
+        assert_eq!(
+            repository
+                .get_reservations_history_between(1, Some(UnixSeconds(20)), Some(UnixSeconds(30)))
+                .await
+                .unwrap(),
+            vec![record(2, 20), record(3, 30)]
+        );
+        assert_eq!(
+            repository
+                .get_reservations_history_between(1, Some(UnixSeconds(30)), None)
+                .await
+                .unwrap(),
+            vec![record(3, 30), record(4, 40)]
+        );
+        assert_eq!(
+            repository
+                .get_reservations_history_between(1, None, Some(UnixSeconds(10)))
+                .await
+                .unwrap(),
+            vec![record(1, 10)]
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if reservations of the user are listed in requested order
+    /// 1. Reserves books 3, 1 and 2 and sets their reservation times so that book 3 is the earliest and book 1 the latest
+    /// 2. Checks that sorting by book id lists 1, 2, 3 and sorting by reservation time lists 3, 2, 1
+    async fn test_get_all_reservations_sorted() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        for book_id in [3, 1, 2] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+        }
+        repository
+            .client
+            .batch_execute(
+                "UPDATE reservations SET reserved_at = CASE book_id WHEN 3 THEN 100 WHEN 1 THEN 300 ELSE 200 END",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository
+                .get_all_reservations(user_id, ReservationsSort::BookId)
+                .await
+                .unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            repository
+                .get_all_reservations(user_id, ReservationsSort::ReservedAt)
+                .await
+                .unwrap(),
+            vec![3, 2, 1]
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if the same book is reserved independently in each branch
+    /// 1. Sets 3 copies of the book, first user reserves it in branch 1, second user reserves it in branch 2
+    ///    and in the default branch
+    /// 2. Checks that the second user can not reserve it in branch 1
+    /// 3. Unreserves the book of the first user in branch 1 - expect the second user can reserve it there
+    ///    and the book is still not available in the default branch
+    async fn test_reserve_book_in_branches() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let mut user_ids = vec![];
+        for username in ["first", "second"] {
+            user_ids.push(
+                repository
+                    .add_user(UserDetails {
+                        username: username.to_string(),
+                        favourite_tags: vec![],
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        repository.set_book_copies(1, 3).await.unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[0], 1, 1)
+            .await
+            .unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 2)
+            .await
+            .unwrap();
+        repository.reserve_book(user_ids[1], 1).await.unwrap();
+        assert!(matches!(
+            repository.reserve_book_in_branch(user_ids[1], 1, 1).await,
+            Err(ReservationsRepositoryError::BookAlreadyReservedBy(1, holder)) if holder == user_ids[0]
+        ));
+        assert_eq!(repository.count_reservations().await.unwrap(), 3);
+
+        repository
+            .unreserve_book_in_branch(user_ids[0], 1, 1)
+            .await
+            .unwrap();
+        repository
+            .reserve_book_in_branch(user_ids[1], 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.get_books_availability(&[1]).await.unwrap(),
+            HashMap::from([(1, false)])
+        );
+        assert_eq!(
+            repository
+                .get_reservations_history(user_ids[0])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    #[file_serial(key, path => "../.pgtestslock")]
+    /// Tests if user activity merges active reservations and history ordered by timestamp
+    /// 1. Reserves books 1, 2 and 3 and unreserves books 1 and 2
+    /// 2. Sets reservation and unreservation times so that events of different books interleave
+    /// 3. Gets user activity - expects reserve and unreserve events ordered by timestamp
+    async fn test_get_user_activity() {
+        let (_container, repository) = start_postgres_container_and_init_repo().await;
+        let user_id = repository
+            .add_user(UserDetails {
+                username: "user".to_string(),
+                favourite_tags: vec![],
+            })
+            .await
+            .unwrap();
+        for book_id in [1, 2, 3] {
+            repository.reserve_book(user_id, book_id).await.unwrap();
+        }
+        for book_id in [1, 2] {
+            repository.unreserve_book(user_id, book_id).await.unwrap();
+        }
+        repository
+            .client
+            .batch_execute(
+                "UPDATE reservations SET reserved_at = 400; \
+                UPDATE history SET reserved_at = book_id * 100, \
+                unreserved_at = CASE book_id WHEN 1 THEN 300 ELSE 500 END",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.get_user_activity(user_id).await.unwrap(),
+            vec![
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 1,
+                    timestamp: UnixSeconds(100),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 2,
+                    timestamp: UnixSeconds(200),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Unreserved,
+                    user_id,
+                    book_id: 1,
+                    timestamp: UnixSeconds(300),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Reserved,
+                    user_id,
+                    book_id: 3,
+                    timestamp: UnixSeconds(400),
+                },
+                ReservationEvent {
+                    action: ReservationAction::Unreserved,
+                    user_id,
+                    book_id: 2,
+                    timestamp: UnixSeconds(500),
+                },
+            ]
+        );
+    }
 }
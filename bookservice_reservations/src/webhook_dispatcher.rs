@@ -0,0 +1,64 @@
+use anyhow::Context;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::policies::ExponentialBackoff;
+use reqwest_retry::RetryTransientMiddleware;
+use reqwest_tracing::TracingMiddleware;
+
+use crate::api::{BookId, ReservationAction, ReservationEvent, UnixSeconds, UserId};
+
+/// Number of retries of a failed webhook call before the event is dropped
+const WEBHOOK_MAX_RETRIES: u32 = 3;
+
+/// Posts reservation events to the configured webhook url without blocking the response,
+/// failures (after retries) are only logged
+pub struct WebhookDispatcher {
+    url: String,
+    client: ClientWithMiddleware,
+}
+
+impl WebhookDispatcher {
+    pub fn new(url: String) -> anyhow::Result<Self> {
+        let reqwest_client = reqwest::Client::builder()
+            .build()
+            .context("Failed to build reqwest client")?;
+        let client = ClientBuilder::new(reqwest_client)
+            // Insert the tracing middleware
+            .with(TracingMiddleware::default())
+            .with(RetryTransientMiddleware::new_with_policy(
+                ExponentialBackoff::builder().build_with_max_retries(WEBHOOK_MAX_RETRIES),
+            ))
+            .build();
+        Ok(Self { url, client })
+    }
+
+    /// Sends the event for the user and the book stamped with current time in the background
+    pub fn dispatch(&self, action: ReservationAction, user_id: UserId, book_id: BookId) {
+        let event = ReservationEvent {
+            action,
+            user_id,
+            book_id,
+            timestamp: UnixSeconds::now(),
+        };
+        let client = self.client.clone();
+        let url = self.url.clone();
+        actix_web::rt::spawn(async move {
+            match client.post(&url).json(&event).send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => tracing::error!(
+                    "Webhook rejected {:?} event of book {} for user {}, status {}",
+                    event.action,
+                    event.book_id,
+                    event.user_id,
+                    response.status()
+                ),
+                Err(err) => tracing::error!(
+                    "Failed to send {:?} event of book {} for user {} to webhook: {:#}",
+                    event.action,
+                    event.book_id,
+                    event.user_id,
+                    err
+                ),
+            }
+        });
+    }
+}
@@ -6,8 +6,9 @@ use tokio::time;
 
 use bookservice_repository::api::BookDetails;
 use bookservice_repository::client::BookServiceRepositoryClient;
-use bookservice_reservations::api::{BookId, UserDetails, UserId};
+use bookservice_reservations::api::UserDetails;
 use bookservice_reservations::client::BookServiceReservationsClient;
+use bookservice_types::{BookId, UserId};
 
 #[tokio::main]
 async fn main() {
@@ -79,7 +80,7 @@ async fn main() {
             .reserve_book(*book_id, *user_id)
             .await
             .expect("Failed to reserve book");
-        assert!(result, "Failed to reserve book  - result false");
+        assert!(result.is_some(), "Failed to reserve book  - result false");
 
         reserved_books.insert(*book_id, *user_id);
     }
@@ -121,6 +122,7 @@ fn generate_books(
             publisher: format!("Publisher {}", no % 20),
             description: "Some long description that is long".to_string(),
             tags: vec![],
+            isbn: None,
         })
         .collect()
 }
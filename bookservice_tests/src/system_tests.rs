@@ -2,8 +2,9 @@ use std::time::UNIX_EPOCH;
 
 use bookservice_repository::api::{BookDetails, BookDetailsPatch};
 use bookservice_repository::client::BookServiceRepositoryClient;
-use bookservice_reservations::api::{BookId, UserDetails};
+use bookservice_reservations::api::{UnixSeconds, UserDetails};
 use bookservice_reservations::client::BookServiceReservationsClient;
+use bookservice_types::BookId;
 
 #[tokio::test]
 /// Simple test for bookservice repository
@@ -23,6 +24,7 @@ async fn bookservice_repository_e2e_test() {
         publisher: "Publisher1".to_string(),
         description: "Description1".to_string(),
         tags: vec!["TAG1".to_string(), "TAG2".to_string()],
+        isbn: None,
     };
 
     let book_id = bookservice_repository_client
@@ -107,7 +109,7 @@ async fn bookservice_reservations_e2e_test() {
     );
     let user_details = UserDetails {
         username: username.clone(),
-        favourite_tags: vec!["tag1".to_string()],
+        favourite_tags: vec!["tag1".into()],
     };
 
     // ADD USER
@@ -142,6 +144,7 @@ async fn bookservice_reservations_e2e_test() {
         publisher: "Publisher1".to_string(),
         description: "Description1".to_string(),
         tags: vec!["TAG1".to_string(), "TAG2".to_string()],
+        isbn: None,
     };
 
     let book_id = bookservice_repository_client
@@ -150,20 +153,41 @@ async fn bookservice_reservations_e2e_test() {
         .expect("Failed to add book");
 
     // RESERVE Book
-    let reserve_response = bookservice_reservations_client
+    let receipt = bookservice_reservations_client
         .reserve_book(book_id, user_id)
         .await
-        .expect("Failed to reserve book");
+        .expect("Failed to reserve book")
+        .expect("Book not reserved");
+
+    assert_eq!((receipt.user_id, receipt.book_id), (user_id, book_id));
 
-    assert!(reserve_response);
+    // GET RESERVATION BY RECEIPT
+    let found_receipt = bookservice_reservations_client
+        .get_reservation(receipt.reservation_id)
+        .await
+        .expect("Failed to get reservation");
+    assert_eq!(found_receipt, Some(receipt.clone()));
 
-    // RESERVE AGAIN - this time should fail as already reserved
-    // TODO: Add second user for this?
+    // RESERVE AGAIN - succeeds as the book is already reserved by the same user
     let reserve_response = bookservice_reservations_client
         .reserve_book(book_id, user_id)
         .await
         .expect("Failed to reserve book");
-    assert!(!reserve_response);
+    assert_eq!(reserve_response, Some(receipt.clone()));
+
+    // RESERVE BY OTHER USER - this time should fail as already reserved
+    let other_user_id = bookservice_reservations_client
+        .add_user(UserDetails {
+            username: format!("{}_other", username),
+            favourite_tags: vec![],
+        })
+        .await
+        .expect("Failed to add user");
+    let reserve_response = bookservice_reservations_client
+        .reserve_book(book_id, other_user_id)
+        .await
+        .expect("Failed to reserve book");
+    assert!(reserve_response.is_none());
 
     // GET ALL RESERVATIONS
     let reservation_ids = bookservice_reservations_client
@@ -181,6 +205,12 @@ async fn bookservice_reservations_e2e_test() {
 
     assert!(unreserve_response);
 
+    let found_receipt = bookservice_reservations_client
+        .get_reservation(receipt.reservation_id)
+        .await
+        .expect("Failed to get reservation");
+    assert!(found_receipt.is_none());
+
     // GET ALL RESERVATIONS to see if it is removed
     let reservation_ids = bookservice_reservations_client
         .list_reservations(user_id)
@@ -196,5 +226,5 @@ async fn bookservice_reservations_e2e_test() {
 
     assert_eq!(history_records.len(), 1);
     assert_eq!(history_records[0].book_id, book_id);
-    assert!(history_records[0].unreserved_at > 0);
+    assert!(history_records[0].unreserved_at > UnixSeconds(0));
 }
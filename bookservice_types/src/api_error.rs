@@ -0,0 +1,54 @@
+//! Error body returned by all services, so that clients can handle errors the same way for every service
+
+use serde::{Deserialize, Serialize};
+
+/// Code of the error returned for requests that do not match any route
+pub const NOT_FOUND_ERROR_CODE: &str = "not_found";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ApiError {
+    /// Machine readable code of the error, e.g. `not_found`
+    pub code: String,
+    pub message: String,
+}
+
+/// Default service of the apps, responds with 404 and [`ApiError`] body to requests not matching any route
+#[cfg(feature = "server")]
+pub async fn not_found(request: actix_web::HttpRequest) -> actix_web::HttpResponse {
+    actix_web::HttpResponse::NotFound().json(ApiError {
+        code: NOT_FOUND_ERROR_CODE.to_string(),
+        message: format!("No route for {} {}", request.method(), request.path()),
+    })
+}
+
+#[cfg(all(test, feature = "server"))]
+mod api_error_tests {
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+
+    #[actix_web::test]
+    /// Tests if request to a path without route responds with 404 and json error body
+    async fn test_not_found_json_body() {
+        let app = test::init_service(
+            App::new()
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .default_service(web::to(not_found)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/api/nonexistent")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 404);
+        let error: ApiError = test::read_body_json(response).await;
+        assert_eq!(
+            error,
+            ApiError {
+                code: "not_found".to_string(),
+                message: "No route for GET /api/nonexistent".to_string(),
+            }
+        );
+    }
+}
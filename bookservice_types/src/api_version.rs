@@ -0,0 +1,64 @@
+//! Version of the api sent by every service in a response header, so that clients can detect incompatible servers
+
+/// Name of the response header carrying the api version
+pub const API_VERSION_HEADER: &str = "X-API-Version";
+/// Current version of the api of all services, bumped on incompatible changes
+pub const API_VERSION: &str = "1";
+
+/// Middleware adding api version header to every response
+#[cfg(feature = "server")]
+pub fn api_version_headers() -> actix_web::middleware::DefaultHeaders {
+    actix_web::middleware::DefaultHeaders::new().add((API_VERSION_HEADER, API_VERSION))
+}
+
+/// Client middleware remembering api version of the server from the last response,
+/// a warning is logged if it is different than the version of the client
+#[cfg(feature = "client")]
+#[derive(Clone, Default)]
+pub struct ApiVersionRecorder {
+    server_api_version: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+}
+
+#[cfg(feature = "client")]
+impl ApiVersionRecorder {
+    /// Returns api version of the server from the last response, None if no response had the header yet
+    pub fn server_api_version(&self) -> Option<String> {
+        self.server_api_version
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+#[cfg(feature = "client")]
+#[async_trait::async_trait]
+impl reqwest_middleware::Middleware for ApiVersionRecorder {
+    async fn handle(
+        &self,
+        req: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: reqwest_middleware::Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let url = req.url().clone();
+        let response = next.run(req, extensions).await?;
+        if let Some(version) = response
+            .headers()
+            .get(API_VERSION_HEADER)
+            .and_then(|version| version.to_str().ok())
+        {
+            if version != API_VERSION {
+                tracing::warn!(
+                    "Server at {} has api version {}, client expects {}",
+                    url,
+                    version,
+                    API_VERSION
+                );
+            }
+            *self
+                .server_api_version
+                .lock()
+                .unwrap_or_else(|err| err.into_inner()) = Some(version.to_string());
+        }
+        Ok(response)
+    }
+}
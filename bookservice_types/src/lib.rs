@@ -0,0 +1,12 @@
+//! Types shared by all bookservice crates, so ids passed between the services always have the same type
+
+pub mod api_error;
+pub mod api_version;
+#[cfg(feature = "server")]
+pub mod msgpack;
+pub mod text_validation;
+
+/// Id of a book, assigned by the repository service
+pub type BookId = i32;
+/// Id of a user, assigned by the reservations service
+pub type UserId = i32;
@@ -0,0 +1,224 @@
+//! MessagePack content negotiation for bandwidth-sensitive clients: requests with `Accept: application/msgpack`
+//! get responses serialized with MessagePack instead of json, and request bodies sent with
+//! `Content-Type: application/msgpack` are deserialized from MessagePack
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+use actix_web::body::BoxBody;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::http::header::{ACCEPT, VARY};
+use actix_web::web::Bytes;
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
+};
+use paperclip::actix::web::Json;
+use paperclip::actix::OperationModifier;
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw};
+use paperclip::v2::schema::Apiv2Schema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Media type of MessagePack requests and responses
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Returns true if `Accept` header of the request lists MessagePack media type
+pub fn accepts_msgpack(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .filter_map(|media_range| media_range.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE))
+}
+
+/// Finishes the response with value serialized with MessagePack if client accepts it, with json otherwise
+/// Adds `Vary: Accept` so that caches keep json and MessagePack responses apart
+pub fn negotiated_response<T: Serialize>(
+    req: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    value: &T,
+) -> HttpResponse {
+    response.insert_header((VARY, "Accept"));
+    if accepts_msgpack(req) {
+        match rmp_serde::to_vec_named(value) {
+            Ok(body) => response.content_type(MSGPACK_CONTENT_TYPE).body(body),
+            Err(error) => HttpResponse::from_error(ErrorInternalServerError(error.to_string())),
+        }
+    } else {
+        response.json(value)
+    }
+}
+
+/// Response serialized with MessagePack if client accepts `application/msgpack`, with json otherwise
+/// Documented in api spec the same way as `Json<T>`
+pub struct Negotiated<T>(pub T);
+
+impl<T: Serialize> Responder for Negotiated<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        negotiated_response(req, HttpResponse::Ok(), &self.0)
+    }
+}
+
+impl<T: Apiv2Schema> Apiv2Schema for Negotiated<T> {
+    fn name() -> Option<String> {
+        T::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        T::raw_schema()
+    }
+}
+
+impl<T: Apiv2Schema> OperationModifier for Negotiated<T> {
+    fn update_response(op: &mut DefaultOperationRaw) {
+        Json::<T>::update_response(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<T>::update_definitions(map)
+    }
+}
+
+/// Request body deserialized from MessagePack if sent with `Content-Type: application/msgpack`, from json otherwise
+/// Documented in api spec the same way as `Json<T>`
+pub struct JsonOrMsgpack<T>(pub T);
+
+impl<T> JsonOrMsgpack<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for JsonOrMsgpack<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for JsonOrMsgpack<T> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if req
+            .content_type()
+            .eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+        {
+            let body = Bytes::from_request(req, payload);
+            Box::pin(async move {
+                let body = body.await?;
+                rmp_serde::from_slice(&body)
+                    .map(JsonOrMsgpack)
+                    .map_err(|error| ErrorBadRequest(error.to_string()))
+            })
+        } else {
+            let json = Json::<T>::from_request(req, payload);
+            Box::pin(async move { Ok(JsonOrMsgpack(json.await?.into_inner())) })
+        }
+    }
+}
+
+impl<T: Apiv2Schema> Apiv2Schema for JsonOrMsgpack<T> {
+    fn name() -> Option<String> {
+        T::name()
+    }
+
+    fn raw_schema() -> DefaultSchemaRaw {
+        T::raw_schema()
+    }
+}
+
+impl<T: Apiv2Schema> OperationModifier for JsonOrMsgpack<T> {
+    fn update_parameter(op: &mut DefaultOperationRaw) {
+        Json::<T>::update_parameter(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<T>::update_definitions(map)
+    }
+}
+
+#[cfg(test)]
+mod msgpack_tests {
+    use actix_web::{test, web, App};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+    struct Item {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        count: Option<u32>,
+    }
+
+    async fn echo(item: JsonOrMsgpack<Item>) -> Negotiated<Item> {
+        Negotiated(item.into_inner())
+    }
+
+    #[actix_web::test]
+    /// Tests if body and response are negotiated independently
+    /// 1. Posts msgpack body accepting msgpack (with quality parameter) - expect msgpack response
+    /// 2. Posts msgpack body without accept header - expect json response, both responses vary on Accept
+    /// 3. Posts json body accepting msgpack - expect msgpack response
+    /// 4. Posts invalid msgpack body - expect 400
+    async fn test_msgpack_negotiation() {
+        let app = test::init_service(App::new().route("/echo", web::post().to(echo))).await;
+        let item = Item {
+            name: "name".to_string(),
+            count: None,
+        };
+
+        let request = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header(("Content-Type", MSGPACK_CONTENT_TYPE))
+            .insert_header(("Accept", "application/json;q=0.5, application/msgpack;q=1"))
+            .set_payload(rmp_serde::to_vec_named(&item).unwrap())
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            MSGPACK_CONTENT_TYPE
+        );
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+        let body = test::read_body(response).await;
+        assert_eq!(rmp_serde::from_slice::<Item>(&body).unwrap(), item);
+
+        let request = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header(("Content-Type", MSGPACK_CONTENT_TYPE))
+            .set_payload(rmp_serde::to_vec_named(&item).unwrap())
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(response.headers().get(VARY).unwrap(), "Accept");
+        assert_eq!(test::read_body(response).await, r#"{"name":"name"}"#);
+
+        let request = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header(("Accept", MSGPACK_CONTENT_TYPE))
+            .set_json(&item)
+            .to_request();
+        let body = test::call_and_read_body(&app, request).await;
+        assert_eq!(rmp_serde::from_slice::<Item>(&body).unwrap(), item);
+
+        let request = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header(("Content-Type", MSGPACK_CONTENT_TYPE))
+            .set_payload("not msgpack")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 400);
+    }
+}
@@ -0,0 +1,59 @@
+//! Validation of text fields sent by clients, control characters (e.g. newlines or tabs) in names and tags
+//! break log lines and UI showing them, so requests with them are rejected
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Field '{field}' contains control characters")]
+pub struct ControlCharactersError {
+    pub field: &'static str,
+}
+
+/// Returns error if the value of the field contains any control character
+pub fn validate_no_control_characters(
+    field: &'static str,
+    value: &str,
+) -> Result<(), ControlCharactersError> {
+    if value.chars().any(char::is_control) {
+        Err(ControlCharactersError { field })
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns error if any of the values of the field contains a control character
+pub fn validate_all_no_control_characters<'a>(
+    field: &'static str,
+    values: impl IntoIterator<Item = &'a str>,
+) -> Result<(), ControlCharactersError> {
+    values
+        .into_iter()
+        .try_for_each(|value| validate_no_control_characters(field, value))
+}
+
+#[cfg(feature = "server")]
+impl actix_web::ResponseError for ControlCharactersError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::BAD_REQUEST
+    }
+}
+
+#[cfg(test)]
+mod text_validation_tests {
+    use super::*;
+
+    #[test]
+    /// Tests if tags with tab or newline are rejected and normal text passes
+    fn test_control_characters_rejected() {
+        assert_eq!(
+            validate_all_no_control_characters("tags", ["scifi", "space\topera"]),
+            Err(ControlCharactersError { field: "tags" })
+        );
+        assert_eq!(
+            validate_no_control_characters("tags", "fantasy\n"),
+            Err(ControlCharactersError { field: "tags" })
+        );
+        assert_eq!(
+            validate_all_no_control_characters("tags", ["scifi", "Żółć & co. (2nd ed.)"]),
+            Ok(())
+        );
+    }
+}